@@ -0,0 +1,107 @@
+use crate::settings::KeywordAnalysis;
+use anyhow::Result;
+use rust_stemmers::{Algorithm, Stemmer};
+
+// Stored when detection is enabled but the text is too short or ambiguous
+pub const UNKNOWN_LANGUAGE: &str = "unknown";
+
+const ENGLISH_STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "has", "have", "he",
+    "her", "his", "i", "in", "is", "it", "its", "me", "my", "not", "of", "on", "or", "our", "she",
+    "so", "that", "the", "their", "them", "then", "there", "these", "they", "this", "to", "was",
+    "we", "were", "what", "when", "which", "who", "will", "with", "you", "your",
+];
+
+const FRENCH_STOPWORDS: &[&str] = &[
+    "au", "aux", "avec", "ce", "ces", "dans", "de", "des", "du", "elle", "en", "et", "il", "je",
+    "la", "le", "les", "leur", "lui", "ma", "mais", "me", "mes", "ne", "nous", "on", "ou", "par",
+    "pas", "pour", "qu", "que", "qui", "sa", "se", "ses", "son", "sur", "ta", "te", "tu", "un",
+    "une", "vous",
+];
+
+// Normalizes text into search terms: lowercased words, optionally with
+// stopwords removed and reduced to their stems
+pub struct TextAnalyzer {
+    stemmer: Option<Stemmer>,
+    stopwords: &'static [&'static str],
+}
+
+impl TextAnalyzer {
+    pub fn new(config: &KeywordAnalysis) -> Result<Self> {
+        let (algorithm, stopwords) = match config.language.to_lowercase().as_str() {
+            "english" | "en" => (Algorithm::English, ENGLISH_STOPWORDS),
+            "french" | "fr" => (Algorithm::French, FRENCH_STOPWORDS),
+            "german" | "de" => (Algorithm::German, &[][..]),
+            "spanish" | "es" => (Algorithm::Spanish, &[][..]),
+            "italian" | "it" => (Algorithm::Italian, &[][..]),
+            "portuguese" | "pt" => (Algorithm::Portuguese, &[][..]),
+            "dutch" | "nl" => (Algorithm::Dutch, &[][..]),
+            other => return Err(anyhow::anyhow!("Unsupported analysis language: {}", other)),
+        };
+
+        Ok(Self {
+            stemmer: config.stemming.then(|| Stemmer::create(algorithm)),
+            stopwords: if config.remove_stopwords { stopwords } else { &[] },
+        })
+    }
+
+    pub fn terms(&self, text: &str) -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(str::to_lowercase)
+            .filter(|w| !self.stopwords.contains(&w.as_str()))
+            .map(|w| match &self.stemmer {
+                Some(stemmer) => stemmer.stem(&w).into_owned(),
+                None => w,
+            })
+            .collect()
+    }
+}
+
+// ISO 639-3 code (e.g. `eng`, `fra`) of the text's language, or
+// `UNKNOWN_LANGUAGE` when the detector is not confident
+pub fn detect_language(text: &str) -> String {
+    match whatlang::detect(text) {
+        Some(info) if info.is_reliable() => info.lang().code().to_string(),
+        _ => UNKNOWN_LANGUAGE.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terms_are_stemmed_without_stopwords() {
+        let analyzer = TextAnalyzer::new(&KeywordAnalysis::default()).unwrap();
+        assert_eq!(analyzer.terms("The runner was running"), ["runner", "run"]);
+
+        let plain = KeywordAnalysis {
+            stemming: false,
+            remove_stopwords: false,
+            ..KeywordAnalysis::default()
+        };
+        let analyzer = TextAnalyzer::new(&plain).unwrap();
+        assert_eq!(analyzer.terms("The runner was running"), ["the", "runner", "was", "running"]);
+
+        let klingon = KeywordAnalysis {
+            language: "klingon".to_string(),
+            ..KeywordAnalysis::default()
+        };
+        assert!(TextAnalyzer::new(&klingon).is_err());
+    }
+
+    #[test]
+    fn languages_are_detected_only_when_clear() {
+        assert_eq!(
+            detect_language("The weather was lovely this morning, so we walked along the river to the old market."),
+            "eng"
+        );
+        assert_eq!(
+            detect_language("Il faisait très beau ce matin, alors nous avons marché le long de la rivière jusqu'au vieux marché."),
+            "fra"
+        );
+        assert_eq!(detect_language("ok"), UNKNOWN_LANGUAGE);
+        assert_eq!(detect_language(""), UNKNOWN_LANGUAGE);
+    }
+}
@@ -0,0 +1,73 @@
+// Reads the Netscape bookmark file format that Chrome, Firefox and Safari
+// export. Folders are `<H3>` headings followed by a `<DL>` list of their
+// contents; bookmarks are `<A HREF>` links, optionally followed by a `<DD>`
+// description.
+use crate::sanitize;
+use anyhow::Result;
+use regex::Regex;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone)]
+pub struct Bookmark {
+    pub title: String,
+    pub url: String,
+    pub description: Option<String>,
+    // Enclosing folders, outermost first
+    pub folders: Vec<String>,
+}
+
+pub fn parse_bookmarks(html: &str) -> Result<Vec<Bookmark>> {
+    static TOKENS: OnceLock<Regex> = OnceLock::new();
+    static HREF: OnceLock<Regex> = OnceLock::new();
+
+    if !html.trim_start().to_ascii_uppercase().starts_with("<!DOCTYPE NETSCAPE-BOOKMARK-FILE-1>") {
+        return Err(anyhow::anyhow!("Not a Netscape bookmark file"));
+    }
+
+    let tokens = TOKENS.get_or_init(|| {
+        Regex::new(r"(?is)<h3\b[^>]*>(.*?)</h3\s*>|<a\b([^>]*)>(.*?)</a\s*>|<dd>([^<]*)|<dl\b[^>]*>|</dl\s*>").unwrap()
+    });
+    let href = HREF.get_or_init(|| Regex::new(r#"(?i)\bhref\s*=\s*"([^"]*)""#).unwrap());
+
+    let mut bookmarks: Vec<Bookmark> = Vec::new();
+    // One entry per open `<DL>`; `None` for lists that are not a folder's,
+    // such as the outermost one
+    let mut open_lists: Vec<Option<String>> = Vec::new();
+    let mut pending_folder: Option<String> = None;
+    // Folders can have a `<DD>` description too; only a link's is kept
+    let mut after_link = false;
+
+    for token in tokens.captures_iter(html) {
+        let url = token.get(2).and_then(|attributes| href.captures(attributes.as_str()));
+        let is_link = url.is_some();
+        if let Some(name) = token.get(1) {
+            pending_folder = Some(text(name.as_str()));
+        } else if token.get(2).is_some() {
+            // Anchors without an href have nothing to import
+            if let Some(url) = url {
+                bookmarks.push(Bookmark {
+                    title: text(&token[3]),
+                    url: sanitize::decode_entities(&url[1]).trim().to_string(),
+                    description: None,
+                    folders: open_lists.iter().flatten().cloned().collect(),
+                });
+            }
+        } else if let Some(description) = token.get(4) {
+            let description = text(description.as_str());
+            if let Some(bookmark) = bookmarks.last_mut().filter(|_| after_link && !description.is_empty()) {
+                bookmark.description = Some(description);
+            }
+        } else if token[0].starts_with("</") {
+            open_lists.pop();
+        } else {
+            open_lists.push(pending_folder.take());
+        }
+        after_link = is_link;
+    }
+
+    Ok(bookmarks)
+}
+
+fn text(html: &str) -> String {
+    sanitize::decode_entities(html).split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,81 @@
+// Exports end with a trailer line holding the SHA-256 of every byte before
+// it, so a damaged file is rejected before any of it is imported
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+const TRAILER_PREFIX: &str = "\nsha256:";
+
+#[derive(Debug, thiserror::Error)]
+#[error("CorruptedFile: the file does not match its checksum")]
+pub struct CorruptedFile;
+
+// Running digest for exports that are written in pieces
+pub struct Checksum(Sha256);
+
+impl Checksum {
+    pub fn new() -> Self {
+        Self(Sha256::new())
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    pub fn trailer(self) -> String {
+        let digest: String = self.0.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        format!("{}{}\n", TRAILER_PREFIX, digest)
+    }
+}
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn append(mut payload: String) -> String {
+    let mut checksum = Checksum::new();
+    checksum.update(payload.as_bytes());
+    payload.push_str(&checksum.trailer());
+    payload
+}
+
+// Returns the payload without its trailer. Exports written before checksums
+// have no trailer and end with the closing brace of the document; anything
+// else that lacks a valid trailer is treated as corrupted.
+pub fn verify(file: &str) -> Result<&str> {
+    let Some(index) = file.rfind(TRAILER_PREFIX) else {
+        if file.trim_end().ends_with('}') {
+            return Ok(file);
+        }
+        return Err(CorruptedFile.into());
+    };
+
+    let payload = &file[..index];
+    let mut checksum = Checksum::new();
+    checksum.update(payload.as_bytes());
+    if checksum.trailer() != file[index..] {
+        return Err(CorruptedFile.into());
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_flipped_byte_is_rejected() {
+        let file = append("{\"data\": [\"one\", \"two\"]}".to_string());
+        assert_eq!(verify(&file).unwrap(), "{\"data\": [\"one\", \"two\"]}");
+
+        let mut bytes = file.into_bytes();
+        bytes[10] ^= 0x01;
+        let damaged = String::from_utf8(bytes).unwrap();
+        assert!(verify(&damaged).unwrap_err().downcast_ref::<CorruptedFile>().is_some());
+
+        // Exports from before checksums still verify; a cut-off file does not
+        assert_eq!(verify("{\"data\": []}\n").unwrap(), "{\"data\": []}\n");
+        assert!(verify("{\"data\": [").is_err());
+    }
+}
@@ -0,0 +1,301 @@
+// Splits memory content into chunks, treating fenced code blocks differently
+// from the surrounding prose
+use crate::settings::ChunkingSettings;
+use std::ops::Range;
+
+// Recorded on every chunk; bump it whenever `chunk_content` would split the
+// same text differently, so chunk_schema_report can find older chunks.
+// Version 2 packs whole sentences with overlap and records real offsets.
+pub const CHUNKER_VERSION: i64 = 2;
+
+const CODE_CHUNK_TARGET_LINES: usize = 20;
+const CODE_CHUNK_MAX_LINES: usize = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChunkKind {
+    Prose,
+    Code,
+}
+
+impl ChunkKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChunkKind::Prose => "prose",
+            ChunkKind::Code => "code",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TextChunk {
+    pub content: String,
+    pub kind: ChunkKind,
+    // Byte range in the chunked text; `content` is exactly that slice.
+    // Consecutive prose chunks may overlap.
+    pub span: Range<usize>,
+}
+
+// Chunks come back in order of their start offset
+pub fn chunk_content(content: &str, settings: &ChunkingSettings) -> Vec<TextChunk> {
+    let mut chunks = Vec::new();
+    let mut prose: Vec<&str> = Vec::new();
+    let mut code: Vec<&str> = Vec::new();
+    let mut fence: Option<&str> = None;
+
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        match fence {
+            None if trimmed.starts_with("```") || trimmed.starts_with("~~~") => {
+                chunk_prose(content, &prose, settings, &mut chunks);
+                prose.clear();
+                fence = Some(&trimmed[..3]);
+                code.push(line);
+            }
+            None => prose.push(line),
+            Some(marker) => {
+                code.push(line);
+                if trimmed.starts_with(marker) {
+                    chunk_code(content, &code, &mut chunks);
+                    code.clear();
+                    fence = None;
+                }
+            }
+        }
+    }
+
+    // An unterminated fence still counts as code
+    chunk_code(content, &code, &mut chunks);
+    chunk_prose(content, &prose, settings, &mut chunks);
+
+    chunks.sort_by_key(|chunk| chunk.span.start);
+    chunks
+}
+
+// `lines` and `current` below are slices of `content`, which is what spans
+// are measured against. Prose is packed a sentence at a time up to
+// `target_chars`; each chunk after the first starts up to `overlap_chars`
+// back into the previous one, at a word boundary.
+fn chunk_prose(content: &str, lines: &[&str], settings: &ChunkingSettings, chunks: &mut Vec<TextChunk>) {
+    // Long sentences are cut short enough to leave room for the overlap
+    let piece_chars = settings.target_chars.saturating_sub(settings.overlap_chars).max(1);
+    let region = span_of(content, lines);
+    let units: Vec<Range<usize>> = sentences(&content[region.clone()])
+        .into_iter()
+        .flat_map(|sentence| split_long(content, offset_by(sentence, region.start), piece_chars))
+        .collect();
+
+    let mut next = 0;
+    let mut start = match units.first() {
+        Some(unit) => unit.start,
+        None => return,
+    };
+    while next < units.len() {
+        let mut end = units[next].end;
+        next += 1;
+        while next < units.len() && content[start..units[next].end].chars().count() <= settings.target_chars {
+            end = units[next].end;
+            next += 1;
+        }
+        chunks.push(TextChunk {
+            content: content[start..end].to_string(),
+            kind: ChunkKind::Prose,
+            span: start..end,
+        });
+
+        if let Some(unit) = units.get(next) {
+            start = overlap_start(content, start..end, unit.end, settings).unwrap_or(unit.start);
+        }
+    }
+}
+
+// Sentences of `text` as trimmed byte ranges. A sentence ends after `.`, `!`
+// or `?` followed by whitespace, or at a blank line.
+fn sentences(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let Some(&(next_i, next_c)) = chars.peek() else {
+            break;
+        };
+        let ends_sentence = matches!(c, '.' | '!' | '?') && next_c.is_whitespace();
+        let blank_line = c == '\n' && text[next_i..].trim_start_matches([' ', '\t', '\r']).starts_with('\n');
+        if ends_sentence || blank_line {
+            push_trimmed(text, start..i + c.len_utf8(), &mut ranges);
+            start = next_i;
+        }
+    }
+    push_trimmed(text, start..text.len(), &mut ranges);
+    ranges
+}
+
+fn push_trimmed(text: &str, range: Range<usize>, ranges: &mut Vec<Range<usize>>) {
+    let slice = &text[range.clone()];
+    let trimmed = slice.trim();
+    if !trimmed.is_empty() {
+        let start = range.start + (slice.len() - slice.trim_start().len());
+        ranges.push(start..start + trimmed.len());
+    }
+}
+
+fn offset_by(range: Range<usize>, by: usize) -> Range<usize> {
+    range.start + by..range.end + by
+}
+
+// A sentence longer than `target_chars` is cut at word boundaries; a single
+// word longer than that stays whole
+fn split_long(content: &str, sentence: Range<usize>, target_chars: usize) -> Vec<Range<usize>> {
+    let text = &content[sentence.clone()];
+    if text.chars().count() <= target_chars {
+        return vec![sentence];
+    }
+
+    let mut pieces = Vec::new();
+    let mut piece: Option<Range<usize>> = None;
+    for word in text.split_whitespace() {
+        let word = offset_by(span_of(text, &[word]), sentence.start);
+        piece = match piece {
+            Some(current) if content[current.start..word.end].chars().count() > target_chars => {
+                pieces.push(current);
+                Some(word)
+            }
+            Some(current) => Some(current.start..word.end),
+            None => Some(word),
+        };
+    }
+    pieces.extend(piece);
+    pieces
+}
+
+// Where the next chunk starts so it repeats the tail of `previous`: the first
+// word within `overlap_chars` of its end that still lets the chunk reach
+// `next_end` within `target_chars`. None when no word qualifies.
+fn overlap_start(content: &str, previous: Range<usize>, next_end: usize, settings: &ChunkingSettings) -> Option<usize> {
+    if settings.overlap_chars == 0 {
+        return None;
+    }
+    let text = &content[previous.clone()];
+    let tail_start = text
+        .char_indices()
+        .rev()
+        .nth(settings.overlap_chars - 1)
+        .map_or(0, |(i, _)| i);
+    text.split_whitespace()
+        .map(|word| previous.start + span_of(text, &[word]).start)
+        .filter(|&start| start >= previous.start + tail_start && start > previous.start)
+        .find(|&start| content[start..next_end].chars().count() <= settings.target_chars)
+}
+
+// Split code on line boundaries, preferring blank lines or unindented lines
+// (typically the start of a new function or item), and keep indentation intact
+fn chunk_code(content: &str, lines: &[&str], chunks: &mut Vec<TextChunk>) {
+    let mut current: Vec<&str> = Vec::new();
+
+    for line in lines {
+        let is_boundary = line.trim().is_empty() || !line.starts_with(char::is_whitespace);
+        if (current.len() >= CODE_CHUNK_TARGET_LINES && is_boundary) || current.len() >= CODE_CHUNK_MAX_LINES {
+            flush_code(content, &mut current, chunks);
+        }
+        current.push(line);
+    }
+
+    flush_code(content, &mut current, chunks);
+}
+
+fn flush_code(content: &str, current: &mut Vec<&str>, chunks: &mut Vec<TextChunk>) {
+    if current.iter().any(|l| !l.trim().is_empty()) {
+        let span = span_of(content, current);
+        chunks.push(TextChunk {
+            content: content[span.clone()].to_string(),
+            kind: ChunkKind::Code,
+            span,
+        });
+    }
+    current.clear();
+}
+
+// From the start of the first part to the end of the last; every part must
+// be a slice of `content`
+fn span_of(content: &str, parts: &[&str]) -> Range<usize> {
+    let offset = |part: &str| part.as_ptr() as usize - content.as_ptr() as usize;
+    match (parts.first(), parts.last()) {
+        (Some(first), Some(last)) => offset(first)..offset(last) + last.len(),
+        _ => 0..0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIXED: &str = "Intro paragraph about the parser.\n\n```rust\nfn parse() {\n    let x = 1;\n}\n```\n\nClosing words after the code.";
+
+    #[test]
+    fn code_blocks_chunk_separately_from_prose() {
+        let chunks = chunk_content(MIXED, &ChunkingSettings::default());
+        let kinds: Vec<ChunkKind> = chunks.iter().map(|c| c.kind).collect();
+        assert_eq!(kinds, [ChunkKind::Prose, ChunkKind::Code, ChunkKind::Prose]);
+
+        assert_eq!(chunks[0].content, "Intro paragraph about the parser.");
+        assert_eq!(chunks[1].content, "```rust\nfn parse() {\n    let x = 1;\n}\n```");
+        assert_eq!(chunks[2].content, "Closing words after the code.");
+        for chunk in &chunks {
+            assert_eq!(&MIXED[chunk.span.clone()], chunk.content);
+        }
+    }
+
+    #[test]
+    fn long_code_splits_on_line_boundaries() {
+        let body: Vec<String> = (0..50).map(|i| format!("fn f{}() {{}}\n    // body {}", i, i)).collect();
+        let code = format!("```\n{}\n```", body.join("\n"));
+        let chunks = chunk_content(&code, &ChunkingSettings::default());
+
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.kind == ChunkKind::Code));
+        for chunk in &chunks {
+            assert!(chunk.content.lines().count() <= CODE_CHUNK_MAX_LINES);
+            // Indented lines never start a chunk
+            assert!(!chunk.content.starts_with(char::is_whitespace));
+        }
+    }
+
+    #[test]
+    fn unterminated_fence_is_code() {
+        let chunks = chunk_content("Some prose.\n```\nlet open = true;", &ChunkingSettings::default());
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].kind, ChunkKind::Code);
+        assert_eq!(chunks[1].content, "```\nlet open = true;");
+    }
+
+    #[test]
+    fn offsets_are_monotonic_and_rebuild_the_source() {
+        let text = "Short one. A somewhat longer second sentence follows here! Does a question work too? \
+            Abbreviations like e.g. may appear. Unicode — café, naïve — must stay on char boundaries.\n\n\
+            A new paragraph starts here. It has two sentences.\n\n```\nlet code = 1;\n```\n\nTrailing prose.";
+        let settings = ChunkingSettings { target_chars: 60, overlap_chars: 15 };
+        let chunks = chunk_content(text, &settings);
+        assert!(chunks.len() > 3);
+
+        let mut rebuilt = String::new();
+        let mut covered = 0;
+        for pair in chunks.windows(2) {
+            assert!(pair[0].span.start < pair[1].span.start);
+            assert!(pair[0].span.end < pair[1].span.end);
+        }
+        for chunk in &chunks {
+            assert!(chunk.span.start < chunk.span.end);
+            assert_eq!(&text[chunk.span.clone()], chunk.content);
+            // Only whitespace is left out between chunks
+            if chunk.span.start > covered {
+                let gap = &text[covered..chunk.span.start];
+                assert!(gap.trim().is_empty(), "{:?}", gap);
+                rebuilt.push_str(gap);
+            }
+            rebuilt.push_str(&text[chunk.span.start.max(covered)..chunk.span.end]);
+            covered = chunk.span.end;
+        }
+        assert!(text[covered..].trim().is_empty());
+        rebuilt.push_str(&text[covered..]);
+        assert_eq!(rebuilt, text);
+    }
+}
@@ -1,8 +1,15 @@
 use serde::{Deserialize, Serialize};
-use tauri::State;
-use crate::vault::VaultManager;
+use tauri::{AppHandle, Emitter, State};
+use crate::vault::{self, VaultManager};
 use crate::memory::MemoryManager;
 use crate::database::Database;
+use crate::settings::MemorySettings;
+use crate::metrics::{self, CacheMetrics, LatencyStats, OperationMetrics};
+use crate::validation::{SourceValidation, ValidationReport};
+use crate::text_import::SplitStrategy;
+use crate::embedding_throttle::ProcessMemory;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use tokio::sync::{Mutex, MutexGuard};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VaultConfig {
@@ -18,6 +25,9 @@ pub struct MemoryEntry {
     pub title: Option<String>,
     pub tags: Vec<String>,
     pub source: Option<String>,
+    // Changed only through pin_memory/unpin_memory; ignored on add and update
+    #[serde(default)]
+    pub is_pinned: bool,
     pub created_at: Option<String>,
     pub updated_at: Option<String>,
 }
@@ -27,213 +37,1905 @@ pub struct QueryRequest {
     pub query: String,
     pub limit: Option<usize>,
     pub include_citations: bool,
+    // Also collapse chunk hits into one entry per memory
+    #[serde(default)]
+    pub group_by_memory: bool,
+    #[serde(default)]
+    pub score_aggregate: ScoreAggregate,
+    // When false only citations are returned and `answer` is left empty
+    #[serde(default = "default_true")]
+    pub answer: bool,
+    // Cap on the synthesized answer in characters, markers and ellipsis
+    // included; citations are never truncated by it
+    #[serde(default)]
+    pub max_answer_chars: Option<usize>,
+    // Chunks joined into the answer, most relevant first and independent of
+    // `limit`; every returned chunk when unset
+    #[serde(default)]
+    pub answer_chunk_count: Option<usize>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreAggregate {
+    #[default]
+    Max,
+    Sum,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryResult {
+    // Inline `[n]` markers refer to `citations[n - 1]`
     pub answer: String,
     pub citations: Vec<Citation>,
     pub confidence: f32,
     pub processing_time_ms: u64,
+    // Empty unless `group_by_memory` was requested
+    #[serde(default)]
+    pub memory_hits: Vec<MemoryHit>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryHit {
+    pub memory_id: String,
+    pub title: Option<String>,
+    pub source: Option<String>,
+    // Snippet of the best-scoring chunk
+    pub snippet: String,
+    pub relevance_score: f32,
+    pub matching_chunks: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Citation {
+    pub id: String,
+    pub title: Option<String>,
+    pub content: String,
+    pub relevance_score: f32,
+    pub source: Option<String>,
+}
+
+// Emitted as `query-stream` by query_memory_streaming; `stream_id` is the
+// caller's, so concurrent queries can be told apart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QueryStreamEvent {
+    Citation {
+        stream_id: String,
+        // 1-based, matching the answer's citation markers
+        rank: usize,
+        citation: Citation,
+    },
+    Complete {
+        stream_id: String,
+        answer: String,
+        confidence: f32,
+        citation_count: usize,
+    },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    pub pretty: bool,
+    // Allowlist of memory fields to include; all fields when omitted
+    pub fields: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportFileResult {
+    pub path: String,
+    pub bytes: u64,
+    pub memory_count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TextImportResult {
+    // Shared `source` of every imported memory
+    pub source: String,
+    pub memory_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DirectoryImportResult {
+    pub root: String,
+    pub files_imported: u64,
+    pub memory_ids: Vec<String>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SkippedBookmark {
+    pub url: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BookmarkImportResult {
+    pub path: String,
+    pub memory_ids: Vec<String>,
+    pub skipped: Vec<SkippedBookmark>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SkippedNote {
+    pub title: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnexImportResult {
+    pub path: String,
+    pub memory_ids: Vec<String>,
+    pub skipped: Vec<SkippedNote>,
+    // Listed in the memory's content; the files themselves are not stored
+    pub attachment_count: u64,
+}
+
+// Self-contained export of a single memory
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryBundle {
+    pub version: u32,
+    pub exported_at: String,
+    pub memory: MemoryEntry,
+    // Memories sharing a tag, referenced by id only
+    pub related_ids: Vec<String>,
+}
+
+// Same filters as search_memories: tags take precedence over the text query
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchCriteria {
+    #[serde(default)]
+    pub query: String,
+    pub tags: Option<Vec<String>>,
+    pub language: Option<String>,
+    #[serde(default)]
+    pub order: SearchOrder,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchOrder {
+    // Most recently updated first
+    #[default]
+    Updated,
+    // Using the `title_collation` setting; untitled memories last
+    TitleAsc,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub id: String,
+    pub name: String,
+    pub criteria: SearchCriteria,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkTagMode {
+    Add,
+    Remove,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourceRefreshResult {
+    pub memory_id: String,
+    // False when the source still matches the stored content; nothing is
+    // written then
+    pub changed: bool,
+    // The memory_versions entry holding the previous content
+    pub version_id: Option<String>,
+    pub chunk_count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryVersion {
+    pub id: String,
+    pub memory_id: String,
+    pub title: Option<String>,
+    pub content: String,
+    pub source: Option<String>,
+    // When this content stopped being the memory's
+    pub replaced_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryDrift {
+    pub query: String,
+    // 1.0 when both models return the same top memories
+    pub jaccard_overlap: f32,
+}
+
+// Adjacent memories in creation order; `None` at either end of the vault
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryNeighbors {
+    pub prev: Option<MemoryEntry>,
+    pub next: Option<MemoryEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PrefixMatch {
+    pub id: String,
+    pub title: Option<String>,
+    pub snippet: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InMemoryMatch {
+    // `None` when the memory's chunks no longer match its content
+    pub chunk_id: Option<String>,
+    // Surrounding text with the match in `**`
+    pub snippet: String,
+    // Start of the match in the memory content, and its length, in characters
+    pub offset: usize,
+    pub length: usize,
+}
+
+// One segment of a hierarchical tag path such as `project/human-api`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagNode {
+    pub name: String,
+    pub path: String,
+    pub memory_count: u64,
+    pub children: Vec<TagNode>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WordCount {
+    pub word: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagCount {
+    pub name: String,
+    pub memory_count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaggedMemories {
+    pub tag: String,
+    // Memories carrying the tag, before paging
+    pub total: u64,
+    pub memories: Vec<MemoryEntry>,
+    pub co_tags: Vec<TagCount>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuplicateCandidate {
+    pub id: String,
+    pub title: Option<String>,
+    pub created_at: String,
+}
+
+// Memories linked by pairwise similarity at or above the threshold
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuplicateCluster {
+    pub memories: Vec<DuplicateCandidate>,
+    pub max_similarity: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagSuggestion {
+    pub tag: String,
+    // Sum of the similarities of the neighbouring memories carrying the tag
+    pub score: f32,
+    pub memory_count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Keyword {
+    pub term: String,
+    // TF-IDF; only comparable between keywords of the same memory
+    pub score: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryHistoryEntry {
+    pub id: String,
+    pub query: String,
+    pub result_count: u64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VaultStatus {
+    pub is_initialized: bool,
+    pub is_unlocked: bool,
+    pub name: Option<String>,
+    pub memory_count: u64,
+    pub last_sync: Option<String>,
+    // Unlocked with unlock_vault_readonly; writes are rejected
+    #[serde(default)]
+    pub read_only: bool,
+    // Idle time left before auto-lock; None while locked or with auto-lock off
+    #[serde(default)]
+    pub auto_lock_remaining_seconds: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryStats {
+    pub total_memories: u64,
+    pub total_chunks: u64,
+    pub total_embeddings: u64,
+    // Memories that hit `max_chunks_per_memory` and have only partial chunks
+    pub truncated_memories: u64,
+    pub storage_size_bytes: u64,
+    pub last_updated: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActivityDay {
+    // YYYY-MM-DD in UTC
+    pub date: String,
+    pub memory_count: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineGranularity {
+    Day,
+    // Weeks start on Monday
+    Week,
+    Month,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphFormat {
+    // Graphviz
+    Dot,
+    // Gephi, yEd
+    Graphml,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimelineMemory {
+    pub id: String,
+    pub title: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimelineBucket {
+    // First day of the bucket, YYYY-MM-DD in UTC
+    pub start: String,
+    pub memory_count: u64,
+    // Newest first
+    pub memories: Vec<TimelineMemory>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VaultMemoryCount {
+    pub vault_id: String,
+    pub memory_count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkVersionCount {
+    pub version: i64,
+    pub chunk_count: u64,
+    pub memory_count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkSchemaReport {
+    pub current_version: i64,
+    // Lowest version first
+    pub versions: Vec<ChunkVersionCount>,
+    // Chunks from more than one chunker version coexist
+    pub mixed: bool,
+    // Memories with chunks from a version other than the current one
+    pub outdated_memories: u64,
+    // True when rebuild_chunks has work to do
+    pub needs_rebuild: bool,
+}
+
+// Aggregates recomputed from the source tables in one snapshot
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatisticsReport {
+    pub vaults: Vec<VaultMemoryCount>,
+    pub total_chunks: u64,
+    // Chunks with a vector for the current embedding model
+    pub embedded_chunks: u64,
+    pub embedding_coverage: f32,
+    pub storage_size_bytes: u64,
+    pub computed_at: String,
+}
+
+// Steps of optimize_vault; all run unless turned off
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OptimizeSteps {
+    pub cleanup_orphans: bool,
+    pub rebuild_fts: bool,
+    pub vacuum: bool,
+    pub recount: bool,
+}
+
+impl Default for OptimizeSteps {
+    fn default() -> Self {
+        Self {
+            cleanup_orphans: true,
+            rebuild_fts: true,
+            vacuum: true,
+            recount: true,
+        }
+    }
+}
+
+// Rows removed because the memory or chunk they belong to is gone
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OrphanCleanup {
+    pub chunks: u64,
+    pub embeddings: u64,
+    pub citations: u64,
+    pub memory_tags: u64,
+    pub versions: u64,
+    pub relations: u64,
+    pub terms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VacuumResult {
+    pub size_before_bytes: u64,
+    pub size_after_bytes: u64,
+}
+
+// One field per step; None when the step was turned off
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OptimizeReport {
+    pub orphans: Option<OrphanCleanup>,
+    pub fts_rows_indexed: Option<u64>,
+    pub vacuum: Option<VacuumResult>,
+    pub statistics: Option<StatisticsReport>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub id: String,
+    pub label: String,
+    pub memory_count: u64,
+    pub chunk_count: u64,
+    pub tag_count: u64,
+    pub content_hash: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    pub from: String,
+    pub to: String,
+    pub added: u64,
+    pub removed: u64,
+    pub changed: u64,
+    pub unchanged: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingResult {
+    pub vector: Vec<f32>,
+    pub model: String,
+    pub dimension: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingSyncEstimate {
+    pub model: String,
+    pub chunks_to_embed: u64,
+    pub estimated_tokens: u64,
+    // Provider calls at the embedder's batch size
+    pub estimated_requests: u64,
+}
+
+// Emitted as `embedding-sync-progress` after each batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingSyncProgress {
+    pub embedded: u64,
+    pub total: u64,
+    // Size of the next batch after adapting to memory use
+    pub batch_size: usize,
+    pub rss_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DimensionReport {
+    pub model: String,
+    pub expected: usize,
+    // Vectors of the active model with another dimension or an unreadable blob
+    pub mismatched_count: u64,
+    pub mismatched_ids: Vec<String>,
+    // Chunks embedded by the follow-up sync, including any that were
+    // already pending
+    pub reembedded: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub algorithm: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CryptoAudit {
+    pub encrypted: bool,
+    pub cipher: String,
+    // Costs used to derive the key that wraps the vault key; None while the
+    // vault is not encrypted
+    pub kdf: Option<KdfParams>,
+    // Ciphertext format written by this version
+    pub crypto_version: i64,
+    // Encrypted memories and chunks still in an older format
+    pub legacy_rows: u64,
+    pub meets_recommendations: bool,
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingExportResult {
+    pub path: String,
+    pub bytes: u64,
+    pub model: String,
+    pub dimensions: usize,
+    pub chunk_count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingImportResult {
+    pub model: String,
+    pub imported: u64,
+    // Entries for chunks this vault does not have
+    pub missing_chunks: u64,
+}
+
+// Bytes that each optimization would reclaim if run now
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StorageSavingsEstimate {
+    pub f16_savings_bytes: u64,
+    pub chunk_dedup_savings_bytes: u64,
+    pub orphan_cleanup_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkDedupResult {
+    // Sets of two or more chunks with the same text
+    pub duplicate_groups: u64,
+    // Chunks that now use another chunk's embeddings
+    pub shared_chunks: u64,
+    pub embeddings_removed: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingInfo {
+    pub id: String,
+    pub chunk_id: String,
+    pub memory_id: String,
+    pub memory_title: Option<String>,
+    pub model_name: String,
+    pub size_bytes: u64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingDebug {
+    pub embedding_id: String,
+    pub model_name: String,
+    // None when the stored blob cannot be decoded
+    pub dimensions: Option<usize>,
+    pub norm: Option<f32>,
+    // Zero, NaN or infinite norm; such vectors match nothing or everything
+    pub degenerate: bool,
+    // Only filled in when requested
+    pub vector: Option<Vec<f32>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkEmbeddingDebug {
+    pub chunk_id: String,
+    pub kind: String,
+    pub start_pos: i64,
+    pub end_pos: i64,
+    // No embedding from the active model, so semantic search skips the chunk
+    pub missing_embedding: bool,
+    pub embeddings: Vec<EmbeddingDebug>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryEmbeddingDebug {
+    pub memory_id: String,
+    pub model: String,
+    pub expected_dimensions: usize,
+    pub chunks: Vec<ChunkEmbeddingDebug>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProviderStatus {
+    pub ok: bool,
+    pub model: String,
+    pub dimension: usize,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FtsConsistencyReport {
+    // Plaintext memories; encrypted ones are never indexed
+    pub memory_rows: u64,
+    pub indexed_rows: u64,
+    // Memories with no entry in the full-text index
+    pub missing_rows: u64,
+    // Index entries whose memory no longer exists
+    pub stale_rows: u64,
+    pub content_mismatch: bool,
+    pub repaired: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagReferenceRepair {
+    // memory_tags rows pointing at a deleted memory
+    pub missing_memory_rows: u64,
+    // memory_tags rows pointing at a deleted tag
+    pub missing_tag_rows: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Recommendation {
+    // Stable identifier, e.g. `pending_embeddings`
+    pub code: String,
+    pub severity: Severity,
+    pub message: String,
+    // Command that addresses it
+    pub command: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SystemInfo {
+    pub version: String,
+    pub platform: String,
+    pub arch: String,
+    pub memory_usage: u64,
+    pub disk_usage: u64,
+}
+
+// Every command other than a status check restarts the auto-lock timer
+async fn acquire<'r, T: Send + Sync + 'static>(state: &State<'r, Mutex<T>>) -> MutexGuard<'r, T> {
+    vault::record_activity();
+    state.inner().lock().await
+}
+
+// Hands a freshly unlocked vault's key to the memory manager, then switches
+// to the saved embedding provider, whose API key may be sealed with it. A
+// saved provider that can no longer be used leaves the local one in place.
+pub(crate) async fn share_vault_key(vault_manager: &VaultManager, memory_state: &Mutex<MemoryManager>) {
+    let mut memory_manager = memory_state.lock().await;
+    memory_manager.set_vault_key(vault_manager.vault_key());
+    if let Err(e) = memory_manager.load_saved_provider().await {
+        eprintln!("Failed to load the embedding provider: {}", e);
+    }
+}
+
+// Basic greet command for testing
+#[tauri::command]
+pub async fn greet(name: &str) -> Result<String, String> {
+    Ok(format!("Hello, {}! You've been greeted from Rust!", name))
+}
+
+// Vault management commands
+#[tauri::command]
+pub async fn create_vault(
+    vault_state: State<'_, Mutex<VaultManager>>,
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    config: VaultConfig,
+    master_password: String,
+) -> Result<VaultStatus, String> {
+    let mut vault_manager = acquire(&vault_state).await;
+    let status = vault_manager
+        .create_vault(config, master_password)
+        .await
+        .map_err(|e| e.to_string())?;
+    share_vault_key(&vault_manager, &memory_state).await;
+    Ok(status)
+}
+
+#[tauri::command]
+pub async fn unlock_vault(
+    vault_state: State<'_, Mutex<VaultManager>>,
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    master_password: String,
+) -> Result<VaultStatus, String> {
+    let mut vault_manager = acquire(&vault_state).await;
+    let status = vault_manager
+        .unlock_vault(master_password)
+        .await
+        .map_err(|e| e.to_string())?;
+    share_vault_key(&vault_manager, &memory_state).await;
+    Ok(status)
+}
+
+#[tauri::command]
+pub async fn unlock_vault_readonly(
+    vault_state: State<'_, Mutex<VaultManager>>,
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    master_password: String,
+) -> Result<VaultStatus, String> {
+    let mut vault_manager = acquire(&vault_state).await;
+    let status = vault_manager
+        .unlock_vault_readonly(master_password)
+        .await
+        .map_err(|e| e.to_string())?;
+    share_vault_key(&vault_manager, &memory_state).await;
+    Ok(status)
+}
+
+#[tauri::command]
+pub async fn set_database_encryption(
+    vault_state: State<'_, Mutex<VaultManager>>,
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    master_password: String,
+    enabled: bool,
+) -> Result<bool, String> {
+    let mut vault_manager = acquire(&vault_state).await;
+    // Held until the new file is in place; its next request opens a new pool
+    let mut memory_manager = memory_state.lock().await;
+    if let Some(pool) = memory_manager.take_pool() {
+        pool.close().await;
+    }
+    vault_manager
+        .set_database_encryption(master_password, enabled)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_vault_status(
+    vault_state: State<'_, Mutex<VaultManager>>,
+) -> Result<VaultStatus, String> {
+    // Status polls don't count as activity, or the vault would never lock
+    let vault_manager = vault_state.lock().await;
+    vault_manager
+        .get_status()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn lock_vault(
+    vault_state: State<'_, Mutex<VaultManager>>,
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<(), String> {
+    let mut vault_manager = vault_state.lock().await;
+    vault_manager.lock();
+    memory_state.lock().await.lock();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_auto_lock_minutes(
+    vault_state: State<'_, Mutex<VaultManager>>,
+    minutes: u32,
+) -> Result<(), String> {
+    let mut vault_manager = acquire(&vault_state).await;
+    vault_manager
+        .set_auto_lock_minutes(minutes)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_vault_settings(
+    vault_state: State<'_, Mutex<VaultManager>>,
+    name: Option<String>,
+    description: Option<String>,
+) -> Result<(), String> {
+    let mut vault_manager = acquire(&vault_state).await;
+    vault_manager
+        .update_settings(name, description)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Memory management commands
+#[tauri::command]
+pub async fn validate_memory(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    entry: MemoryEntry,
+) -> Result<ValidationReport, String> {
+    let memory_manager = acquire(&memory_state).await;
+    Ok(memory_manager.validate_memory(&entry))
+}
+
+#[tauri::command]
+pub async fn validate_source(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    source: String,
+) -> Result<SourceValidation, String> {
+    let memory_manager = acquire(&memory_state).await;
+    Ok(memory_manager.validate_source(&source))
+}
+
+#[tauri::command]
+pub async fn add_memory(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    entry: MemoryEntry,
+) -> Result<String, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    metrics::timed("add_memory", memory_manager.add_memory(entry))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn capture_memory(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    content: String,
+    source_hint: Option<String>,
+) -> Result<String, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .capture_memory(content, source_hint)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn query_memory(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    request: QueryRequest,
+) -> Result<QueryResult, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    metrics::timed("query_memory", memory_manager.query_memory(request))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn query_memory_streaming(
+    app: AppHandle,
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    request: QueryRequest,
+    stream_id: String,
+) -> Result<(), String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    let query = memory_manager.query_memory_streaming(request, stream_id, |event| {
+        let _ = app.emit("query-stream", event);
+    });
+    metrics::timed("query_memory_streaming", query)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn search_memories(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    query: String,
+    limit: Option<usize>,
+    tags: Option<Vec<String>>,
+    language: Option<String>,
+    pinned_first: Option<bool>,
+    order: Option<SearchOrder>,
+) -> Result<Vec<MemoryEntry>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    let criteria = SearchCriteria { query, tags, language, order: order.unwrap_or_default() };
+    let search = memory_manager.search_memories(criteria, limit, pinned_first.unwrap_or(false));
+    metrics::timed("search_memories", search)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn prefix_search(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    prefix: String,
+    limit: Option<usize>,
+) -> Result<Vec<PrefixMatch>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .prefix_search(prefix, limit)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn search_in_memory(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    memory_id: String,
+    query: String,
+) -> Result<Vec<InMemoryMatch>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .search_in_memory(memory_id, query)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn untagged_memories(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Vec<MemoryEntry>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .untagged_memories(limit, offset)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn orphan_memories(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    limit: Option<usize>,
+    include_queried: Option<bool>,
+) -> Result<Vec<MemoryEntry>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .orphan_memories(limit, include_queried.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn memories_by_tag(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    tag: String,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<TaggedMemories, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .memories_by_tag(tag, limit, offset)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn find_semantic_duplicates(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    threshold: Option<f32>,
+) -> Result<Vec<DuplicateCluster>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .find_semantic_duplicates(threshold)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn suggest_tags_for_content(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    content: String,
+    limit: Option<usize>,
+) -> Result<Vec<TagSuggestion>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .suggest_tags_for_content(content, limit)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn extract_keywords(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    memory_id: String,
+    limit: Option<usize>,
+) -> Result<Vec<Keyword>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .extract_keywords(memory_id, limit)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn word_cloud(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    limit: Option<usize>,
+    min_length: Option<usize>,
+) -> Result<Vec<WordCount>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .word_cloud(limit, min_length)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_tags(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<Vec<TagNode>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .list_tags()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn recompute_statistics(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<StatisticsReport, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .recompute_statistics()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn create_snapshot(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    label: String,
+) -> Result<Snapshot, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .create_snapshot(label)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_snapshots(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<Vec<Snapshot>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .list_snapshots()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn diff_snapshots(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    from: String,
+    to: String,
+) -> Result<SnapshotDiff, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .diff_snapshots(from, to)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_memory_stats(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<MemoryStats, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .get_stats()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn vault_fingerprint(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<String, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .vault_fingerprint()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_memory(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    id: String,
+) -> Result<(), String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .delete_memory(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_memory_expiry(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    id: String,
+    expires_at: Option<String>,
+) -> Result<(), String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .set_memory_expiry(id, expires_at)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn sweep_expired_memories(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<u64, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .sweep_expired_memories()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_memory(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    id: String,
+    entry: MemoryEntry,
+) -> Result<(), String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .update_memory(id, entry)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn memory_similarity(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    id_a: String,
+    id_b: String,
+) -> Result<f32, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .memory_similarity(id_a, id_b)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn refresh_from_source(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    id: String,
+) -> Result<SourceRefreshResult, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .refresh_from_source(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_memory_versions(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    id: String,
+) -> Result<Vec<MemoryVersion>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .list_memory_versions(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn embedding_drift(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    sample_queries: Vec<String>,
+    baseline_model: String,
+    k: Option<usize>,
+) -> Result<Vec<QueryDrift>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .embedding_drift(sample_queries, baseline_model, k)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn memory_neighbors(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    id: String,
+) -> Result<MemoryNeighbors, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .memory_neighbors(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn pin_memory(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    id: String,
+) -> Result<(), String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .set_pinned(id, true)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn unpin_memory(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    id: String,
+) -> Result<(), String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .set_pinned(id, false)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn bulk_tag(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    search_criteria: SearchCriteria,
+    tag: String,
+    mode: BulkTagMode,
+) -> Result<u64, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .bulk_tag(search_criteria, tag, mode)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_citations(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    memory_id: String,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Vec<Citation>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .get_citations(memory_id, limit, offset)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_query_history(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    limit: Option<usize>,
+) -> Result<Vec<QueryHistoryEntry>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .list_query_history(limit)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn clear_query_history(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<(), String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .clear_query_history()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Memory settings
+#[tauri::command]
+pub async fn get_memory_settings(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<MemorySettings, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .get_settings()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_memory_settings(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    settings: MemorySettings,
+) -> Result<(), String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .update_settings(settings)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Insights and analytics
+#[tauri::command]
+pub async fn get_insights(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    period: String, // "daily", "weekly", "monthly"
+) -> Result<serde_json::Value, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .get_insights(period)
+        .await
+        .map_err(|e| e.to_string())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Citation {
-    pub id: String,
-    pub title: Option<String>,
-    pub content: String,
-    pub relevance_score: f32,
-    pub source: Option<String>,
+// Data management
+#[tauri::command]
+pub async fn activity_heatmap(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    days: u32,
+) -> Result<Vec<ActivityDay>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .activity_heatmap(days)
+        .await
+        .map_err(|e| e.to_string())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct VaultStatus {
-    pub is_initialized: bool,
-    pub is_unlocked: bool,
-    pub name: Option<String>,
-    pub memory_count: u64,
-    pub last_sync: Option<String>,
+#[tauri::command]
+pub async fn link_memories(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    source_id: String,
+    target_id: String,
+    kind: String,
+) -> Result<(), String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .link_memories(source_id, target_id, kind)
+        .await
+        .map_err(|e| e.to_string())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct MemoryStats {
-    pub total_memories: u64,
-    pub total_chunks: u64,
-    pub total_embeddings: u64,
-    pub storage_size_bytes: u64,
-    pub last_updated: String,
+#[tauri::command]
+pub async fn export_graph(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    format: GraphFormat,
+) -> Result<String, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .export_graph(format)
+        .await
+        .map_err(|e| e.to_string())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SystemInfo {
-    pub version: String,
-    pub platform: String,
-    pub arch: String,
-    pub memory_usage: u64,
-    pub disk_usage: u64,
+#[tauri::command]
+pub async fn timeline(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    granularity: TimelineGranularity,
+) -> Result<Vec<TimelineBucket>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .timeline(granularity)
+        .await
+        .map_err(|e| e.to_string())
 }
 
-// Basic greet command for testing
 #[tauri::command]
-pub async fn greet(name: &str) -> Result<String, String> {
-    Ok(format!("Hello, {}! You've been greeted from Rust!", name))
+pub async fn export_data(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    format: String,
+    options: Option<ExportOptions>,
+) -> Result<String, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .export_data(format, options.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
 }
 
-// Vault management commands
 #[tauri::command]
-pub async fn create_vault(
-    config: VaultConfig,
-    master_password: String,
-) -> Result<VaultStatus, String> {
-    let mut vault_manager = VaultManager::new();
-    vault_manager
-        .create_vault(config, master_password)
+pub async fn export_to_file(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    path: String,
+    format: String,
+    options: Option<ExportOptions>,
+) -> Result<ExportFileResult, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .export_to_file(path, format, options.unwrap_or_default())
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn unlock_vault(master_password: String) -> Result<VaultStatus, String> {
-    let mut vault_manager = VaultManager::new();
-    vault_manager
-        .unlock_vault(master_password)
+pub async fn save_search(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    name: String,
+    criteria: SearchCriteria,
+) -> Result<SavedSearch, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .save_search(name, criteria)
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn get_vault_status() -> Result<VaultStatus, String> {
-    let mut vault_manager = VaultManager::new();
-    vault_manager
-        .get_status()
+pub async fn list_saved_searches(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<Vec<SavedSearch>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .list_saved_searches()
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn update_vault_settings(
-    name: Option<String>,
-    description: Option<String>,
+pub async fn run_saved_search(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    id: String,
+    limit: Option<usize>,
+) -> Result<Vec<MemoryEntry>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .run_saved_search(id, limit)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_saved_search(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    id: String,
 ) -> Result<(), String> {
-    let mut vault_manager = VaultManager::new();
-    vault_manager
-        .update_settings(name, description)
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .delete_saved_search(id)
         .await
         .map_err(|e| e.to_string())
 }
 
-// Memory management commands
 #[tauri::command]
-pub async fn add_memory(entry: MemoryEntry) -> Result<String, String> {
-    let mut memory_manager = MemoryManager::new();
+pub async fn export_saved_search(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    name: String,
+    format: String,
+    options: Option<ExportOptions>,
+) -> Result<String, String> {
+    let mut memory_manager = acquire(&memory_state).await;
     memory_manager
-        .add_memory(entry)
+        .export_saved_search(name, format, options.unwrap_or_default())
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn query_memory(request: QueryRequest) -> Result<QueryResult, String> {
-    let mut memory_manager = MemoryManager::new();
+pub async fn export_memory(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    id: String,
+    format: String,
+    share_password: Option<String>,
+) -> Result<String, String> {
+    let mut memory_manager = acquire(&memory_state).await;
     memory_manager
-        .query_memory(request)
+        .export_memory(id, format, share_password)
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn search_memories(
-    query: String,
+pub async fn import_memory(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    bundle: String,
+    share_password: Option<String>,
+) -> Result<String, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .import_memory(bundle, share_password)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn import_text_file(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    path: String,
+    split_strategy: SplitStrategy,
+) -> Result<TextImportResult, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .import_text_file(path, split_strategy)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn import_directory(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    path: String,
+    glob: Option<String>,
+    split_strategy: SplitStrategy,
+) -> Result<DirectoryImportResult, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .import_directory(path, glob, split_strategy)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn import_bookmarks(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    path: String,
+) -> Result<BookmarkImportResult, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .import_bookmarks(path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn import_enex(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    path: String,
+) -> Result<EnexImportResult, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .import_enex(path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn import_data(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    data: String,
+    format: String,
+) -> Result<(), String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .import_data(data, format)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// System operations
+#[tauri::command]
+pub async fn sync_embeddings(
+    app: AppHandle,
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<u64, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    let mut memory = ProcessMemory::new();
+    let sync = memory_manager.sync_embeddings_with_progress(&mut memory, |progress| {
+        let _ = app.emit("embedding-sync-progress", progress);
+    });
+    metrics::timed("sync_embeddings", sync)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn estimate_embedding_sync(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<EmbeddingSyncEstimate, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .estimate_embedding_sync()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn clear_embeddings(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<u64, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .clear_embeddings()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn export_embeddings(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    path: String,
+) -> Result<EmbeddingExportResult, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .export_embeddings(path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn import_embeddings(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    path: String,
+) -> Result<EmbeddingImportResult, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .import_embeddings(path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn deduplicate_chunks(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<ChunkDedupResult, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .deduplicate_chunks()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn estimate_storage_savings(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<StorageSavingsEstimate, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .estimate_storage_savings()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn memory_embedding_debug(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    id: String,
+    include_vectors: Option<bool>,
+) -> Result<MemoryEmbeddingDebug, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .memory_embedding_debug(id, include_vectors.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn verify_embedding_dimensions(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    reembed: bool,
+) -> Result<DimensionReport, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .verify_embedding_dimensions(reembed)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_large_embeddings(
+    memory_state: State<'_, Mutex<MemoryManager>>,
     limit: Option<usize>,
-    tags: Option<Vec<String>>,
-) -> Result<Vec<MemoryEntry>, String> {
-    let mut memory_manager = MemoryManager::new();
+) -> Result<Vec<EmbeddingInfo>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
     memory_manager
-        .search_memories(query, limit, tags)
+        .list_large_embeddings(limit)
         .await
         .map_err(|e| e.to_string())
 }
 
+// `older_than` is an RFC 3339 timestamp
 #[tauri::command]
-pub async fn get_memory_stats() -> Result<MemoryStats, String> {
-    let mut memory_manager = MemoryManager::new();
+pub async fn prune_embeddings(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    older_than: Option<String>,
+    model: Option<String>,
+) -> Result<u64, String> {
+    let mut memory_manager = acquire(&memory_state).await;
     memory_manager
-        .get_stats()
+        .prune_embeddings(older_than, model)
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn delete_memory(id: String) -> Result<(), String> {
-    let mut memory_manager = MemoryManager::new();
+pub async fn embed_text(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    text: String,
+) -> Result<EmbeddingResult, String> {
+    let mut memory_manager = acquire(&memory_state).await;
     memory_manager
-        .delete_memory(id)
+        .embed_text(text)
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn update_memory(id: String, entry: MemoryEntry) -> Result<(), String> {
-    let mut memory_manager = MemoryManager::new();
+pub async fn test_provider(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<ProviderStatus, String> {
+    let mut memory_manager = acquire(&memory_state).await;
     memory_manager
-        .update_memory(id, entry)
+        .test_provider()
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn get_citations(memory_id: String) -> Result<Vec<Citation>, String> {
-    let mut memory_manager = MemoryManager::new();
+pub async fn rebuild_fts_index(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<u64, String> {
+    let mut memory_manager = acquire(&memory_state).await;
     memory_manager
-        .get_citations(memory_id)
+        .rebuild_fts_index()
         .await
         .map_err(|e| e.to_string())
 }
 
-// Insights and analytics
 #[tauri::command]
-pub async fn get_insights(
-    period: String, // "daily", "weekly", "monthly"
-) -> Result<serde_json::Value, String> {
-    let mut memory_manager = MemoryManager::new();
+pub async fn optimize_vault(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    steps: Option<OptimizeSteps>,
+) -> Result<OptimizeReport, String> {
+    let mut memory_manager = acquire(&memory_state).await;
     memory_manager
-        .get_insights(period)
+        .optimize_vault(steps.unwrap_or_default())
         .await
         .map_err(|e| e.to_string())
 }
 
-// Data management
 #[tauri::command]
-pub async fn export_data(format: String) -> Result<String, String> {
-    let mut memory_manager = MemoryManager::new();
+pub async fn check_fts_consistency(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    repair: bool,
+) -> Result<FtsConsistencyReport, String> {
+    let mut memory_manager = acquire(&memory_state).await;
     memory_manager
-        .export_data(format)
+        .check_fts_consistency(repair)
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn import_data(data: String, format: String) -> Result<(), String> {
-    let mut memory_manager = MemoryManager::new();
+pub async fn chunk_schema_report(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<ChunkSchemaReport, String> {
+    let mut memory_manager = acquire(&memory_state).await;
     memory_manager
-        .import_data(data, format)
+        .chunk_schema_report()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn rebuild_chunks(memory_state: State<'_, Mutex<MemoryManager>>) -> Result<u64, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .rebuild_chunks()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn repair_missing_chunks(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<u64, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .repair_missing_chunks()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn backfill_titles(memory_state: State<'_, Mutex<MemoryManager>>) -> Result<u64, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .backfill_titles()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn repair_tag_references(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<TagReferenceRepair, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .repair_tag_references()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn enable_encryption(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    master_password: String,
+) -> Result<u64, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .enable_encryption(master_password)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// `key` is the base64-encoded 32-byte vault key
+#[tauri::command]
+pub async fn verify_vault_key(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+    key: String,
+) -> Result<bool, String> {
+    let key: [u8; 32] = BASE64
+        .decode(key.trim())
+        .map_err(|e| format!("Invalid key encoding: {}", e))?
+        .try_into()
+        .map_err(|_| "Vault key must be 32 bytes".to_string())?;
+
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .verify_vault_key(key)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_recommendations(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<Vec<Recommendation>, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .get_recommendations()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn checkpoint_wal() -> Result<i64, String> {
+    let db = Database::new().await.map_err(|e| e.to_string())?;
+    db.checkpoint().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn dump_schema() -> Result<String, String> {
+    let db = Database::new().await.map_err(|e| e.to_string())?;
+    db.dump_schema().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_metrics() -> Result<Vec<OperationMetrics>, String> {
+    Ok(metrics::snapshot())
+}
+
+#[tauri::command]
+pub async fn query_latency_stats() -> Result<LatencyStats, String> {
+    Ok(metrics::query_latency_stats())
+}
+
+#[tauri::command]
+pub async fn query_cache_stats() -> Result<CacheMetrics, String> {
+    Ok(metrics::query_cache_stats())
+}
+
+#[tauri::command]
+pub async fn audit_crypto(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<CryptoAudit, String> {
+    let mut memory_manager = acquire(&memory_state).await;
+    memory_manager
+        .audit_crypto()
         .await
         .map_err(|e| e.to_string())
 }
 
-// System operations
 #[tauri::command]
-pub async fn sync_embeddings() -> Result<(), String> {
-    let mut memory_manager = MemoryManager::new();
+pub async fn migrate_crypto_format(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<u64, String> {
+    let mut memory_manager = acquire(&memory_state).await;
     memory_manager
-        .sync_embeddings()
+        .migrate_crypto_format()
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn get_system_info() -> Result<SystemInfo, String> {
-    let mut memory_manager = MemoryManager::new();
+pub async fn get_system_info(
+    memory_state: State<'_, Mutex<MemoryManager>>,
+) -> Result<SystemInfo, String> {
+    let mut memory_manager = acquire(&memory_state).await;
     memory_manager
         .get_system_info()
         .await
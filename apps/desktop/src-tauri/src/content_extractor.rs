@@ -0,0 +1,97 @@
+// Fetches a memory's source URL and reduces the response to plain text.
+// Only http and https URLs are fetched; HTML is stripped to its text, other
+// text types are kept as they are.
+use crate::sanitize;
+use crate::validation;
+use anyhow::Result;
+use regex::Regex;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Pages are mostly markup, so more is read than the text limit allows
+const MAX_RESPONSE_BYTES: usize = 5 * validation::MAX_CONTENT_BYTES;
+
+pub struct ExtractedContent {
+    // The HTML `<title>`, if there was one
+    pub title: Option<String>,
+    pub text: String,
+}
+
+pub struct ContentExtractor {
+    client: reqwest::Client,
+}
+
+impl ContentExtractor {
+    pub fn new() -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .user_agent(concat!("human-api/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self { client })
+    }
+
+    pub fn is_fetchable(source: &str) -> bool {
+        url::Url::parse(source.trim())
+            .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host_str().is_some())
+    }
+
+    pub async fn extract(&self, source: &str) -> Result<ExtractedContent> {
+        if !Self::is_fetchable(source) {
+            return Err(anyhow::anyhow!("'{}' is not an http or https URL", source));
+        }
+
+        let mut response = self
+            .client
+            .get(source.trim())
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to fetch {}: {}", source, e))?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Fetching {} returned {}", source, response.status()));
+        }
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("text/plain")
+            .to_ascii_lowercase();
+        let is_html = content_type.contains("html");
+        if !is_html && !content_type.starts_with("text/") && !content_type.contains("json") {
+            return Err(anyhow::anyhow!("Cannot extract text from {} ({})", source, content_type));
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > MAX_RESPONSE_BYTES {
+                return Err(anyhow::anyhow!("{} is larger than {} bytes", source, MAX_RESPONSE_BYTES));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        let body = String::from_utf8_lossy(&body);
+
+        if is_html {
+            Ok(ExtractedContent { title: html_title(&body), text: sanitize::html_to_text(&strip_head(&body)) })
+        } else {
+            Ok(ExtractedContent { title: None, text: body.trim().to_string() })
+        }
+    }
+}
+
+// The head holds the title and metadata, none of which is page text
+fn strip_head(html: &str) -> String {
+    static HEAD: OnceLock<Regex> = OnceLock::new();
+    let head = HEAD.get_or_init(|| Regex::new(r"(?is)<head\b.*?</head\s*>").unwrap());
+    head.replace(html, "").into_owned()
+}
+
+fn html_title(html: &str) -> Option<String> {
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    let title = TITLE.get_or_init(|| Regex::new(r"(?is)<title\b[^>]*>(.*?)</title\s*>").unwrap());
+
+    let text = sanitize::decode_entities(&title.captures(html)?[1]);
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
@@ -1,10 +1,32 @@
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use argon2::password_hash::{rand_core::OsRng, SaltString, Error as Argon2Error};
+use argon2::password_hash::{rand_core::OsRng, SaltString};
 use aes_gcm::{Aes256Gcm, Key, KeyInit};
-use aes_gcm::aead::{Aead, generic_array::GenericArray};
+use aes_gcm::aead::{Aead, Payload, generic_array::GenericArray};
 use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use rand::Rng;
 
+// Known plaintext stored encrypted under each vault key, so a candidate key
+// can be checked before anything is decrypted or re-keyed with it
+pub const VAULT_CANARY: &[u8] = b"human-api vault canary v1";
+
+// Format written by `encrypt_text`. Version 1 is the bare base64(nonce ||
+// ciphertext); version 2 prefixes the text with `v2:` and authenticates an
+// algorithm tag as associated data. The prefix cannot occur in base64, so
+// `decrypt_text` reads either format.
+pub const CRYPTO_VERSION: i64 = 2;
+pub const V2_PREFIX: &str = "v2:";
+const V2_AAD: &[u8] = b"human-api/v2/aes-256-gcm";
+
+// Cipher used for every encrypted value
+pub const CIPHER: &str = "AES-256-GCM";
+
+// Lowest Argon2id costs considered acceptable for deriving wrapping keys
+// (the OWASP minimum of 19 MiB memory, two passes, one lane)
+pub const MIN_KDF_MEMORY_KIB: u32 = 19 * 1024;
+pub const MIN_KDF_ITERATIONS: u32 = 2;
+pub const MIN_KDF_PARALLELISM: u32 = 1;
+
 pub struct CryptoManager {
     argon2: Argon2<'static>,
 }
@@ -17,16 +39,58 @@ impl CryptoManager {
     }
 
     pub fn hash_password(&self, password: &str) -> Result<String> {
+        Self::hash_with(&self.argon2, password)
+    }
+
+    pub fn verify_password(&self, password: &str, hash: &str) -> Result<bool> {
+        Self::verify_with(&self.argon2, password, hash)
+    }
+
+    // Argon2 is deliberately slow, so run it on the blocking pool instead of
+    // stalling the async worker that is serving other commands
+    pub async fn hash_password_async(&self, password: &str) -> Result<String> {
+        let argon2 = self.argon2.clone();
+        let password = password.to_string();
+        tokio::task::spawn_blocking(move || Self::hash_with(&argon2, &password)).await?
+    }
+
+    pub async fn verify_password_async(&self, password: &str, hash: &str) -> Result<bool> {
+        let argon2 = self.argon2.clone();
+        let password = password.to_string();
+        let hash = hash.to_string();
+        tokio::task::spawn_blocking(move || Self::verify_with(&argon2, &password, &hash)).await?
+    }
+
+    fn hash_with(argon2: &Argon2<'static>, password: &str) -> Result<String> {
         let salt = SaltString::generate(&mut OsRng);
-        let password_hash = self.argon2.hash_password(password.as_bytes(), &salt)
+        let password_hash = argon2.hash_password(password.as_bytes(), &salt)
             .map_err(|e| anyhow::anyhow!("Argon2 error: {}", e))?;
         Ok(password_hash.to_string())
     }
 
-    pub fn verify_password(&self, password: &str, hash: &str) -> Result<bool> {
+    fn verify_with(argon2: &Argon2<'static>, password: &str, hash: &str) -> Result<bool> {
         let parsed_hash = PasswordHash::new(hash)
             .map_err(|e| anyhow::anyhow!("PasswordHash error: {}", e))?;
-        Ok(self.argon2.verify_password(password.as_bytes(), &parsed_hash).is_ok())
+        Ok(argon2.verify_password(password.as_bytes(), &parsed_hash).is_ok())
+    }
+
+    // Derive a 256-bit key from a password, e.g. to wrap a vault or share key
+    pub fn derive_key(&self, password: &str, salt: &[u8]) -> Result<[u8; 32]> {
+        let mut key = [0u8; 32];
+        self.argon2.hash_password_into(password.as_bytes(), salt, &mut key)
+            .map_err(|e| anyhow::anyhow!("Argon2 error: {}", e))?;
+        Ok(key)
+    }
+
+    // Costs used by `derive_key`, recorded alongside each wrapped key
+    pub fn kdf_params(&self) -> &argon2::Params {
+        self.argon2.params()
+    }
+
+    pub fn generate_salt(&self) -> [u8; 16] {
+        let mut salt = [0u8; 16];
+        OsRng.fill(&mut salt);
+        salt
     }
 
     pub fn generate_key(&self) -> [u8; 32] {
@@ -37,7 +101,7 @@ impl CryptoManager {
 
     pub fn encrypt_data(&self, data: &[u8], key: &[u8; 32]) -> Result<Vec<u8>> {
         let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
-        let nonce = self.generate_nonce();
+        let nonce = generate_nonce();
         let ciphertext = cipher.encrypt(&nonce, data)
             .map_err(|e| anyhow::anyhow!("Encryption error: {}", e))?;
         
@@ -61,15 +125,119 @@ impl CryptoManager {
         Ok(plaintext)
     }
 
-    fn generate_nonce(&self) -> GenericArray<u8, aes_gcm::aead::consts::U12> {
-        let mut nonce = [0u8; 12];
-        OsRng.fill(&mut nonce);
-        GenericArray::from_slice(&nonce).clone()
+    // AES-GCM authenticates the ciphertext, so a wrong key fails cleanly
+    // rather than producing garbage
+    pub fn can_decrypt(&self, sample_ciphertext: &[u8], key: &[u8; 32]) -> bool {
+        self.decrypt_data(sample_ciphertext, key).is_ok()
+    }
+
+    // Text columns hold the encoded ciphertext so they stay valid TEXT. For
+    // more than one value under the same key, use a `TextCipher` instead.
+    pub fn encrypt_text(&self, plaintext: &str, key: &[u8; 32]) -> Result<String> {
+        TextCipher::new(key).encrypt_text(plaintext)
+    }
+
+    pub fn decrypt_text(&self, encoded: &str, key: &[u8; 32]) -> Result<String> {
+        TextCipher::new(key).decrypt_text(encoded)
     }
 }
 
+// Text encryption under one key with the AES key schedule computed once.
+// Nonces are per ciphertext, so a single cipher can serve every row of a
+// request.
+pub struct TextCipher {
+    cipher: Aes256Gcm,
+}
+
+impl TextCipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+        }
+    }
+
+    pub fn encrypt_text(&self, plaintext: &str) -> Result<String> {
+        let nonce = generate_nonce();
+        let ciphertext = self.cipher
+            .encrypt(&nonce, Payload { msg: plaintext.as_bytes(), aad: V2_AAD })
+            .map_err(|e| anyhow::anyhow!("Encryption error: {}", e))?;
+
+        let mut encrypted_data = nonce.to_vec();
+        encrypted_data.extend_from_slice(&ciphertext);
+        Ok(format!("{}{}", V2_PREFIX, BASE64.encode(encrypted_data)))
+    }
+
+    pub fn decrypt_text(&self, encoded: &str) -> Result<String> {
+        let (payload, aad) = match encoded.strip_prefix(V2_PREFIX) {
+            Some(payload) => (payload, V2_AAD),
+            None => (encoded, &b""[..]),
+        };
+        let encrypted_data = BASE64.decode(payload)
+            .map_err(|e| anyhow::anyhow!("Invalid ciphertext encoding: {}", e))?;
+        if encrypted_data.len() < 12 {
+            return Err(anyhow::anyhow!("Encrypted data too short"));
+        }
+
+        // Version 1 had no associated data, which AES-GCM treats the same as
+        // an empty one
+        let (nonce_bytes, ciphertext) = encrypted_data.split_at(12);
+        let plaintext = self.cipher
+            .decrypt(GenericArray::from_slice(nonce_bytes), Payload { msg: ciphertext, aad })
+            .map_err(|e| anyhow::anyhow!("Decryption error: {}", e))?;
+        Ok(String::from_utf8(plaintext)?)
+    }
+}
+
+// Whether `encoded` is in the version 2 format that `encrypt_text` writes
+pub fn is_v2(encoded: &str) -> bool {
+    encoded.starts_with(V2_PREFIX)
+}
+
+fn generate_nonce() -> GenericArray<u8, aes_gcm::aead::consts::U12> {
+    let mut nonce = [0u8; 12];
+    OsRng.fill(&mut nonce);
+    *GenericArray::from_slice(&nonce)
+}
+
 impl Default for CryptoManager {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_cipher_decrypts_a_batch_like_many() {
+        let crypto = CryptoManager::new();
+        let key = crypto.generate_key();
+        let cipher = TextCipher::new(&key);
+        let plaintexts: Vec<String> = (0..200).map(|i| format!("memory number {}", i)).collect();
+        let encoded: Vec<String> = plaintexts.iter().map(|p| cipher.encrypt_text(p).unwrap()).collect();
+
+        let per_row: Vec<String> = encoded.iter().map(|e| crypto.decrypt_text(e, &key).unwrap()).collect();
+        let reused = TextCipher::new(&key);
+        let batched: Vec<String> = encoded.iter().map(|e| reused.decrypt_text(e).unwrap()).collect();
+
+        assert_eq!(batched, plaintexts);
+        assert_eq!(per_row, batched);
+    }
+
+    #[tokio::test]
+    async fn hashing_does_not_block_the_runtime() {
+        // A current-thread runtime: hashing inline would finish before the
+        // spawned task was ever polled
+        let (sent, mut received) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move { sent.send(()).unwrap() });
+
+        // A lightweight command gets to run while the hash is awaited
+        let crypto = CryptoManager::new();
+        let hash = crypto.hash_password_async("correct horse").await.unwrap();
+        assert!(received.try_recv().is_ok());
+
+        assert!(crypto.verify_password_async("correct horse", &hash).await.unwrap());
+        assert!(!crypto.verify_password_async("wrong horse", &hash).await.unwrap());
+    }
+}
@@ -1,7 +1,71 @@
-use sqlx::{sqlite::SqlitePool, Row};
+use sqlx::{sqlite::{SqliteConnectOptions, SqlitePool}, Row};
+use crate::crypto::CryptoManager;
+use crate::memory::DEFAULT_VAULT_ID;
+use crate::settings::TitleCollation;
 use anyhow::Result;
+use std::io::Read;
 use std::path::PathBuf;
-use dirs::data_dir;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use zeroize::Zeroize;
+
+// Bumped whenever `init_schema` gains a table, column or index; stored in
+// `PRAGMA user_version` so a schema dump identifies the migration level
+pub const SCHEMA_VERSION: i64 = 13;
+
+// Set by `unlock_vault_readonly`; every pool opened afterwards is read-only
+// and `maintenance::begin_write` refuses to start a write
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, thiserror::Error)]
+#[error("ReadOnly: the vault is open in read-only mode")]
+pub struct ReadOnly;
+
+// Returned by the memory manager between `lock_vault` and the next unlock
+#[derive(Debug, thiserror::Error)]
+#[error("Locked: unlock the vault first")]
+pub struct Locked;
+
+// SQLCipher passphrase for the whole database file, remembered when the vault
+// is unlocked; only used when the file on disk is actually encrypted
+static FILE_KEY: Mutex<Option<String>> = Mutex::new(None);
+
+// Collation registered on every connection: case-insensitive across all of
+// Unicode rather than only ASCII like NOCASE. The bundled SQLite has no ICU,
+// so there is no per-locale tailoring.
+pub const UNICODE_NOCASE: &str = "UNICODE_NOCASE";
+
+// Every plaintext SQLite file starts with this; anything else is SQLCipher
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+pub fn set_read_only(read_only: bool) {
+    READ_ONLY.store(read_only, Ordering::SeqCst);
+}
+
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::SeqCst)
+}
+
+// The passphrase being replaced is wiped first, e.g. when the vault locks
+pub fn set_file_key(key: Option<String>) {
+    let mut file_key = FILE_KEY.lock().unwrap_or_else(|e| e.into_inner());
+    file_key.zeroize();
+    *file_key = key;
+}
+
+pub fn file_key() -> Option<String> {
+    FILE_KEY.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+// False for a missing or empty file, which is created as plain SQLite
+pub fn is_file_encrypted() -> bool {
+    let mut header = [0u8; 16];
+    match std::fs::File::open(database_path()).and_then(|mut file| file.read_exact(&mut header)) {
+        Ok(()) => &header != SQLITE_HEADER,
+        Err(_) => false,
+    }
+}
 
 pub struct Database {
     pool: SqlitePool,
@@ -9,30 +73,50 @@ pub struct Database {
 
 impl Database {
     pub async fn new() -> Result<Self> {
-        // Use a more accessible database location
-        let data_dir = std::env::current_dir()
-            .unwrap_or_else(|_| std::path::PathBuf::from("."))
-            .join("data");
-        
-        std::fs::create_dir_all(&data_dir)
-            .map_err(|e| anyhow::anyhow!("Failed to create data directory {}: {}", data_dir.display(), e))?;
+        let db_path = database_path();
+        if let Some(data_dir) = db_path.parent() {
+            std::fs::create_dir_all(data_dir)
+                .map_err(|e| anyhow::anyhow!("Failed to create data directory {}: {}", data_dir.display(), e))?;
+        }
         
-        let db_path = data_dir.join("memories.db");
         let database_url = format!("sqlite://{}", db_path.display());
         
-        println!("Initializing database at: {}", database_url);
+        eprintln!("Initializing database at: {}", database_url);
         
-        let pool = SqlitePool::connect(&database_url)
+        let read_only = is_read_only();
+        let mut options = SqliteConnectOptions::from_str(&database_url)?
+            .read_only(read_only)
+            .create_if_missing(!read_only)
+            .collation(UNICODE_NOCASE, |a, b| TitleCollation::Unicode.compare(a, b));
+        if is_file_encrypted() {
+            options = with_file_key(options)?;
+        }
+        let pool = SqlitePool::connect_with(options)
             .await
             .map_err(|e| anyhow::anyhow!("Failed to connect to database at {}: {}", database_url, e))?;
         
         let db = Database { pool };
-        db.init_schema().await?;
+        // Migrations write, so a read-only pool uses the schema as it is
+        if !read_only {
+            db.init_schema().await?;
+        }
         
         Ok(db)
     }
 
     async fn init_schema(&self) -> Result<()> {
+        // Lets background compaction reclaim free pages incrementally; only
+        // takes effect on a database that has no tables yet
+        sqlx::query("PRAGMA auto_vacuum = INCREMENTAL")
+            .execute(&self.pool)
+            .await?;
+
+        // Readers no longer block on writers; the WAL is folded back into the
+        // main file on shutdown
+        sqlx::query("PRAGMA journal_mode = WAL")
+            .execute(&self.pool)
+            .await?;
+
         // Create vaults table
         sqlx::query(
             r#"
@@ -41,6 +125,13 @@ impl Database {
                 name TEXT NOT NULL,
                 description TEXT,
                 encryption_enabled BOOLEAN NOT NULL DEFAULT 1,
+                key_salt BLOB,
+                encrypted_key BLOB,
+                key_canary BLOB,
+                password_hash TEXT,
+                kdf_memory_kib INTEGER,
+                kdf_iterations INTEGER,
+                kdf_parallelism INTEGER,
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
                 updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
             )
@@ -58,6 +149,14 @@ impl Database {
                 title TEXT,
                 content TEXT NOT NULL,
                 source TEXT,
+                encrypted INTEGER NOT NULL DEFAULT 0,
+                crypto_version INTEGER NOT NULL DEFAULT 1,
+                is_pinned INTEGER NOT NULL DEFAULT 0,
+                chunks_truncated INTEGER NOT NULL DEFAULT 0,
+                language TEXT,
+                expires_at DATETIME,
+                keywords TEXT,
+                keywords_computed_at DATETIME,
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
                 updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
                 FOREIGN KEY (vault_id) REFERENCES vaults (id)
@@ -74,9 +173,14 @@ impl Database {
                 id TEXT PRIMARY KEY,
                 memory_id TEXT NOT NULL,
                 content TEXT NOT NULL,
+                kind TEXT NOT NULL DEFAULT 'prose',
                 start_pos INTEGER NOT NULL,
                 end_pos INTEGER NOT NULL,
                 embedding BLOB,
+                encrypted INTEGER NOT NULL DEFAULT 0,
+                crypto_version INTEGER NOT NULL DEFAULT 1,
+                embedding_chunk_id TEXT,
+                chunker_version INTEGER NOT NULL DEFAULT 1,
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
                 FOREIGN KEY (memory_id) REFERENCES memories (id)
             )
@@ -85,6 +189,46 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
+        // Columns added after the initial schema
+        self.ensure_column("chunks", "kind", "TEXT NOT NULL DEFAULT 'prose'").await?;
+        self.ensure_column("vaults", "key_salt", "BLOB").await?;
+        self.ensure_column("vaults", "encrypted_key", "BLOB").await?;
+        self.ensure_column("vaults", "key_canary", "BLOB").await?;
+        self.ensure_column("memories", "encrypted", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.ensure_column("chunks", "encrypted", "INTEGER NOT NULL DEFAULT 0").await?;
+        // Rows written before versioned ciphertexts default to the legacy format
+        self.ensure_column("memories", "crypto_version", "INTEGER NOT NULL DEFAULT 1").await?;
+        self.ensure_column("chunks", "crypto_version", "INTEGER NOT NULL DEFAULT 1").await?;
+        self.ensure_column("memories", "chunks_truncated", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.ensure_column("memories", "language", "TEXT").await?;
+        self.ensure_column("memories", "is_pinned", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.ensure_column("vaults", "kdf_memory_kib", "INTEGER").await?;
+        self.ensure_column("vaults", "kdf_iterations", "INTEGER").await?;
+        self.ensure_column("vaults", "kdf_parallelism", "INTEGER").await?;
+        self.ensure_column("memories", "expires_at", "DATETIME").await?;
+        // JSON cache written by extract_keywords
+        self.ensure_column("memories", "keywords", "TEXT").await?;
+        self.ensure_column("memories", "keywords_computed_at", "DATETIME").await?;
+        // Set by deduplicate_chunks on chunks whose text matches another
+        // chunk's; they have no embeddings of their own and share that one's
+        self.ensure_column("chunks", "embedding_chunk_id", "TEXT").await?;
+        // Chunks from before versions were recorded were all split by version 1
+        self.ensure_column("chunks", "chunker_version", "INTEGER NOT NULL DEFAULT 1").await?;
+        // Argon2 PHC string checked by unlock_vault
+        self.ensure_column("vaults", "password_hash", "TEXT").await?;
+        // Keys wrapped before the parameters were recorded used the argon2
+        // crate defaults
+        sqlx::query(
+            "UPDATE vaults SET kdf_memory_kib = ?, kdf_iterations = ?, kdf_parallelism = ?
+             WHERE encrypted_key IS NOT NULL AND kdf_memory_kib IS NULL"
+        )
+        .bind(argon2::Params::DEFAULT_M_COST)
+        .bind(argon2::Params::DEFAULT_T_COST)
+        .bind(argon2::Params::DEFAULT_P_COST)
+        .execute(&self.pool)
+        .await?;
+        self.adopt_legacy_vault().await?;
+
         // Create tags table
         sqlx::query(
             r#"
@@ -147,15 +291,244 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
+        // Create full-text index over memories, kept in sync by triggers.
+        // Encrypted memories are left out: the index reads them through a
+        // view of the plaintext rows, and the triggers skip them.
+        sqlx::query(
+            r#"
+            CREATE VIEW IF NOT EXISTS memories_fts_source AS
+            SELECT rowid AS memory_rowid, title, content FROM memories WHERE encrypted = 0
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let fts_sql: Option<String> = sqlx::query_scalar(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'memories_fts'",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        // An index from before the view read every row of `memories`, so it is
+        // recreated over the view and refilled below
+        let fts_exists = match fts_sql {
+            Some(sql) if !sql.contains("memories_fts_source") => {
+                for trigger in ["memories_fts_insert", "memories_fts_delete", "memories_fts_update"] {
+                    sqlx::query(&format!("DROP TRIGGER IF EXISTS {}", trigger))
+                        .execute(&self.pool)
+                        .await?;
+                }
+                sqlx::query("DROP TABLE memories_fts").execute(&self.pool).await?;
+                false
+            }
+            Some(_) => true,
+            None => false,
+        };
+
+        sqlx::query(
+            r#"
+            CREATE VIRTUAL TABLE IF NOT EXISTS memories_fts USING fts5(
+                title,
+                content,
+                content='memories_fts_source',
+                content_rowid='memory_rowid'
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS memories_fts_insert AFTER INSERT ON memories WHEN new.encrypted = 0 BEGIN
+                INSERT INTO memories_fts (rowid, title, content) VALUES (new.rowid, new.title, new.content);
+            END
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS memories_fts_delete AFTER DELETE ON memories WHEN old.encrypted = 0 BEGIN
+                INSERT INTO memories_fts (memories_fts, rowid, title, content) VALUES ('delete', old.rowid, old.title, old.content);
+            END
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Covers rows turning encrypted, whose entry is removed and not re-added
+        sqlx::query(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS memories_fts_update AFTER UPDATE ON memories BEGIN
+                INSERT INTO memories_fts (memories_fts, rowid, title, content)
+                SELECT 'delete', old.rowid, old.title, old.content WHERE old.encrypted = 0;
+                INSERT INTO memories_fts (rowid, title, content)
+                SELECT new.rowid, new.title, new.content WHERE new.encrypted = 0;
+            END
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // The triggers only see new writes, so memories that predate the
+        // index are loaded once when it is created
+        if !fts_exists {
+            sqlx::query("INSERT INTO memories_fts (memories_fts) VALUES ('rebuild')")
+                .execute(&self.pool)
+                .await?;
+        }
+
+        // Create index of analyzed (stemmed, stopword-free) terms per memory
+        sqlx::query(
+            r#"
+            CREATE VIRTUAL TABLE IF NOT EXISTS memory_terms USING fts5(
+                memory_id UNINDEXED,
+                terms
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create query history table (opt-in via settings)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS query_history (
+                id TEXT PRIMARY KEY,
+                vault_id TEXT NOT NULL,
+                query TEXT NOT NULL,
+                result_count INTEGER NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create per-vault settings table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS settings (
+                vault_id TEXT PRIMARY KEY,
+                data TEXT NOT NULL,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create vault snapshot tables; each snapshot keeps one hash per memory
+        // so two snapshots can be diffed
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS snapshots (
+                id TEXT PRIMARY KEY,
+                vault_id TEXT NOT NULL,
+                label TEXT NOT NULL,
+                memory_count INTEGER NOT NULL,
+                chunk_count INTEGER NOT NULL,
+                tag_count INTEGER NOT NULL,
+                content_hash TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS snapshot_memories (
+                snapshot_id TEXT NOT NULL,
+                memory_id TEXT NOT NULL,
+                content_hash TEXT NOT NULL,
+                PRIMARY KEY (snapshot_id, memory_id),
+                FOREIGN KEY (snapshot_id) REFERENCES snapshots (id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS saved_searches (
+                id TEXT PRIMARY KEY,
+                vault_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                criteria_json TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE (vault_id, name)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Earlier contents of memories, stored as they were (encrypted if the
+        // memory was)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS memory_versions (
+                id TEXT PRIMARY KEY,
+                memory_id TEXT NOT NULL,
+                title TEXT,
+                content TEXT NOT NULL,
+                source TEXT,
+                encrypted INTEGER NOT NULL DEFAULT 0,
+                crypto_version INTEGER NOT NULL DEFAULT 1,
+                replaced_at DATETIME NOT NULL,
+                FOREIGN KEY (memory_id) REFERENCES memories (id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Directed, typed links between memories, e.g. `cites` or `follows`
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS memory_relations (
+                source_id TEXT NOT NULL,
+                target_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                created_at DATETIME NOT NULL,
+                PRIMARY KEY (source_id, target_id, kind),
+                FOREIGN KEY (source_id) REFERENCES memories (id),
+                FOREIGN KEY (target_id) REFERENCES memories (id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
         // Create indexes for better performance
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_memories_vault_id ON memories (vault_id)")
             .execute(&self.pool)
             .await?;
 
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_memories_expires_at ON memories (expires_at) WHERE expires_at IS NOT NULL")
+            .execute(&self.pool)
+            .await?;
+
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_chunks_memory_id ON chunks (memory_id)")
             .execute(&self.pool)
             .await?;
 
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_chunks_embedding_chunk_id ON chunks (embedding_chunk_id) WHERE embedding_chunk_id IS NOT NULL")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_memory_versions_memory_id ON memory_versions (memory_id)")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_memory_relations_target_id ON memory_relations (target_id)")
+            .execute(&self.pool)
+            .await?;
+
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_memory_tags_memory_id ON memory_tags (memory_id)")
             .execute(&self.pool)
             .await?;
@@ -164,15 +537,387 @@ impl Database {
             .execute(&self.pool)
             .await?;
 
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_query_history_vault_id ON query_history (vault_id, created_at)")
+            .execute(&self.pool)
+            .await?;
+
+        // PRAGMA values cannot be bound as parameters
+        sqlx::query(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Vaults used to be created under a random id while memories were always
+    // filed under DEFAULT_VAULT_ID. The newest such vault becomes the
+    // default one, taking any memories filed under its old id along.
+    async fn adopt_legacy_vault(&self) -> Result<()> {
+        let legacy: Option<String> = sqlx::query_scalar(
+            "SELECT id FROM vaults
+             WHERE NOT EXISTS (SELECT 1 FROM vaults WHERE id = ?)
+             ORDER BY created_at DESC, id LIMIT 1"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(legacy) = legacy else {
+            return Ok(());
+        };
+
+        let mut tx = self.pool.begin().await?;
+        // Memories point at the old id until both rows are updated
+        sqlx::query("PRAGMA defer_foreign_keys = ON").execute(&mut *tx).await?;
+        sqlx::query("UPDATE memories SET vault_id = ? WHERE vault_id = ?")
+            .bind(DEFAULT_VAULT_ID)
+            .bind(&legacy)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE vaults SET id = ? WHERE id = ?")
+            .bind(DEFAULT_VAULT_ID)
+            .bind(&legacy)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // `CREATE TABLE IF NOT EXISTS` leaves older databases untouched, so new
+    // columns are added here when missing
+    async fn ensure_column(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        let exists = sqlx::query(&format!("PRAGMA table_info({})", table))
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .any(|row| row.get::<String, _>("name") == column);
+
+        if !exists {
+            sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                .execute(&self.pool)
+                .await?;
+        }
+
         Ok(())
     }
 
     pub async fn get_pool(&self) -> &SqlitePool {
         &self.pool
     }
+
+    pub fn into_pool(self) -> SqlitePool {
+        self.pool
+    }
+
+    // DDL only, never row data, so the output is safe to attach to a bug
+    // report. Internal `sqlite_*` objects and autoindexes are skipped.
+    pub async fn dump_schema(&self) -> Result<String> {
+        let version: i64 = sqlx::query_scalar("PRAGMA user_version")
+            .fetch_one(&self.pool)
+            .await?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT type, name, sql FROM sqlite_master
+            WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+            ORDER BY CASE type WHEN 'table' THEN 0 WHEN 'index' THEN 1 WHEN 'trigger' THEN 2 ELSE 3 END, name
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut dump = format!("-- schema version: {}\n", version);
+        for row in rows {
+            let kind: String = row.get("type");
+            let name: String = row.get("name");
+            let sql: String = row.get("sql");
+            dump.push_str(&format!("\n-- {} {}\n{};\n", kind, name, sql.trim()));
+        }
+
+        Ok(dump)
+    }
+
+    // Folds the WAL into the main file and truncates it; fails rather than
+    // waiting when a reader holds it open
+    pub async fn checkpoint(&self) -> Result<i64> {
+        checkpoint_pool(&self.pool).await
+    }
+}
+
+async fn checkpoint_pool(pool: &SqlitePool) -> Result<i64> {
+    let row = sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").fetch_one(pool).await?;
+    let busy: i64 = row.get(0);
+    let checkpointed: i64 = row.get(2);
+    if busy != 0 {
+        return Err(anyhow::anyhow!("WAL checkpoint was blocked by an open connection"));
+    }
+
+    // -1 when the database is not in WAL mode
+    Ok(checkpointed.max(0))
+}
+
+// sqlx issues `PRAGMA key` before any other pragma on each new connection
+#[cfg(feature = "sqlcipher")]
+fn with_file_key(options: SqliteConnectOptions) -> Result<SqliteConnectOptions> {
+    let key = file_key().ok_or_else(|| anyhow::anyhow!("The database file is encrypted; unlock the vault first"))?;
+    Ok(options.pragma("key", format!("'{}'", key.replace('\'', "''"))))
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+fn with_file_key(_options: SqliteConnectOptions) -> Result<SqliteConnectOptions> {
+    Err(anyhow::anyhow!("The database file is encrypted, but this build does not include SQLCipher support"))
+}
+
+// Rewrites the whole database file with or without SQLCipher encryption via
+// `sqlcipher_export` and swaps it into place. Pools opened before the swap
+// keep the old file, so the caller must hold the write guard and close every
+// other pool first. Returns false when the file was already in the
+// requested state.
+#[cfg(feature = "sqlcipher")]
+pub async fn set_file_encryption(passphrase: &str, enabled: bool) -> Result<bool> {
+    if enabled && passphrase.is_empty() {
+        return Err(anyhow::anyhow!("A passphrase is required to encrypt the database file"));
+    }
+
+    // The passphrase must be the master password; a wrong one leaves the
+    // remembered key as it was
+    let previous_key = file_key();
+    set_file_key(Some(passphrase.to_string()));
+    let opened = async {
+        let db = Database::new().await?;
+        verify_master_password(&db.pool, passphrase).await?;
+        Ok::<_, anyhow::Error>(db)
+    }
+    .await;
+    let db = match opened {
+        Ok(db) => db,
+        Err(e) => {
+            set_file_key(previous_key);
+            return Err(e);
+        }
+    };
+
+    if is_file_encrypted() == enabled {
+        db.pool.close().await;
+        if !enabled {
+            set_file_key(previous_key);
+        }
+        return Ok(false);
+    }
+    db.checkpoint().await?;
+
+    let path = database_path();
+    let mut converted = path.clone().into_os_string();
+    converted.push(".converting");
+    let converted = PathBuf::from(converted);
+    // The pool's connections cannot create files, so ATTACH gets an empty one
+    std::fs::File::create(&converted)?;
+
+    // An empty key attaches a plain SQLite file
+    let mut conn = db.pool.acquire().await?;
+    sqlx::query("ATTACH DATABASE ? AS converted KEY ?")
+        .bind(converted.display().to_string())
+        .bind(if enabled { passphrase } else { "" })
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("SELECT sqlcipher_export('converted')")
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(&format!("PRAGMA converted.user_version = {}", SCHEMA_VERSION))
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DETACH DATABASE converted")
+        .execute(&mut *conn)
+        .await?;
+    drop(conn);
+    db.pool.close().await;
+
+    std::fs::rename(&converted, &path)
+        .map_err(|e| anyhow::anyhow!("Failed to move converted database into {}: {}", path.display(), e))?;
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = path.clone().into_os_string();
+        sidecar.push(suffix);
+        let _ = std::fs::remove_file(sidecar);
+    }
+
+    set_file_key(enabled.then(|| passphrase.to_string()));
+    Ok(true)
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+pub async fn set_file_encryption(_passphrase: &str, _enabled: bool) -> Result<bool> {
+    Err(anyhow::anyhow!("This build does not include SQLCipher support"))
+}
+
+// Fails unless `password` is the vault's master password. Vaults without a
+// stored hash cannot be checked and are refused.
+pub async fn verify_master_password(pool: &SqlitePool, password: &str) -> Result<()> {
+    let password_hash: Option<String> = sqlx::query_scalar("SELECT password_hash FROM vaults WHERE id = ?")
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_optional(pool)
+        .await?
+        .flatten();
+    let password_hash = password_hash.ok_or_else(|| anyhow::anyhow!("The vault has no master password to check"))?;
+    if !CryptoManager::new().verify_password_async(password, &password_hash).await? {
+        return Err(anyhow::anyhow!("Incorrect master password"));
+    }
+    Ok(())
+}
+
+// Use a more accessible database location
+pub fn database_path() -> PathBuf {
+    std::env::current_dir()
+        .unwrap_or_else(|_| PathBuf::from("."))
+        .join("data")
+        .join("memories.db")
+}
+
+// Size of the `-wal` file next to the database; 0 when there is none
+pub fn wal_size_bytes() -> u64 {
+    let mut wal_path = database_path().into_os_string();
+    wal_path.push("-wal");
+    std::fs::metadata(wal_path).map(|m| m.len()).unwrap_or(0)
 }
 
 pub async fn init() -> Result<()> {
     let _db = Database::new().await?;
     Ok(())
 }
+
+// Called once when the app exits with every pool the app still holds: lets
+// in-flight writes finish, checkpoints the WAL through one of the pools and
+// closes them all, so no `-wal` file is left behind
+pub async fn shutdown(pools: Vec<SqlitePool>) -> Result<()> {
+    // A read-only session never wrote anything to flush
+    let checkpointed = match pools.iter().find(|pool| !pool.is_closed()) {
+        Some(pool) if !is_read_only() => {
+            if !crate::maintenance::wait_for_writes(std::time::Duration::from_secs(5)).await {
+                eprintln!("Shutting down with writes still in flight");
+            }
+            checkpoint_pool(pool).await.map(drop)
+        }
+        _ => Ok(()),
+    };
+
+    for pool in pools {
+        pool.close().await;
+    }
+    checkpointed
+}
+
+// SQLite integers are signed 64-bit; convert explicitly instead of `as` so an
+// out-of-range value is an error rather than a silent wrap
+pub fn to_sql_int(value: usize, what: &str) -> Result<i64> {
+    i64::try_from(value).map_err(|_| anyhow::anyhow!("{} is out of range: {}", what, value))
+}
+
+pub fn to_count(value: i64, what: &str) -> Result<u64> {
+    u64::try_from(value).map_err(|_| anyhow::anyhow!("{} cannot be negative: {}", what, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryManager;
+    use crate::test_support::{self, entry, TestVault};
+
+    #[tokio::test]
+    async fn fts_index_created_on_an_existing_database_is_populated() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("preexisting memory about gardening", &[])).await.unwrap();
+
+        // As a database from before the index existed
+        let pool = test_support::database().await.get_pool().await.clone();
+        for trigger in ["memories_fts_insert", "memories_fts_delete", "memories_fts_update"] {
+            sqlx::query(&format!("DROP TRIGGER {}", trigger)).execute(&pool).await.unwrap();
+        }
+        sqlx::query("DROP TABLE memories_fts").execute(&pool).await.unwrap();
+
+        Database::new().await.unwrap();
+        let matches = MemoryManager::new().prefix_search("garden".into(), None).await.unwrap();
+        assert_eq!(matches.len(), 1);
+    }
+
+    #[test]
+    fn out_of_range_integers_are_errors() {
+        assert_eq!(to_sql_int(i64::MAX as usize, "limit").unwrap(), i64::MAX);
+        let error = to_sql_int(usize::MAX, "limit").unwrap_err().to_string();
+        assert_eq!(error, format!("limit is out of range: {}", usize::MAX));
+
+        assert_eq!(to_count(0, "memory count").unwrap(), 0);
+        assert!(to_count(-1, "memory count").unwrap_err().to_string().contains("cannot be negative"));
+    }
+
+    #[tokio::test]
+    async fn boundary_limits_fail_cleanly() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let id = manager.add_memory(entry("a memory to page through", &[])).await.unwrap();
+
+        let error = manager.get_citations(id.clone(), Some(usize::MAX), None).await.unwrap_err().to_string();
+        assert!(error.contains("limit is out of range"), "{}", error);
+        let error = manager.get_citations(id.clone(), None, Some(usize::MAX)).await.unwrap_err().to_string();
+        assert!(error.contains("offset is out of range"), "{}", error);
+        assert!(manager.get_citations(id, Some(i64::MAX as usize), None).await.unwrap().is_empty());
+        assert!(manager.list_large_embeddings(Some(usize::MAX)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn shutdown_checkpoints_and_closes_the_given_pools() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        for i in 0..20 {
+            manager.add_memory(entry(&format!("memory number {} about the harbour", i), &[])).await.unwrap();
+        }
+        assert!(wal_size_bytes() > 0);
+
+        let pool = manager.take_pool().unwrap();
+        let other = test_support::database().await.into_pool();
+        shutdown(vec![pool.clone(), other.clone()]).await.unwrap();
+
+        assert_eq!(wal_size_bytes(), 0);
+        assert!(pool.is_closed());
+        assert!(other.is_closed());
+    }
+
+    #[tokio::test]
+    async fn schema_dump_lists_tables_and_indexes() {
+        let _vault = TestVault::new().await;
+        let dump = test_support::database().await.dump_schema().await.unwrap();
+
+        assert!(dump.starts_with(&format!("-- schema version: {}\n", SCHEMA_VERSION)));
+        for table in ["vaults", "memories", "chunks", "tags", "memory_tags", "citations", "embeddings", "memories_fts"] {
+            assert!(dump.contains(&format!("-- table {}\n", table)), "missing table {}", table);
+        }
+        for index in ["idx_memories_vault_id", "idx_chunks_memory_id", "idx_memory_tags_memory_id"] {
+            assert!(dump.contains(&format!("-- index {}\n", index)), "missing index {}", index);
+        }
+    }
+
+    #[tokio::test]
+    async fn fts_index_over_every_row_is_rebuilt_over_plaintext_rows() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("plaintext memory about tulips", &[])).await.unwrap();
+
+        // As the index was first defined, straight over `memories`
+        let pool = test_support::database().await.get_pool().await.clone();
+        for trigger in ["memories_fts_insert", "memories_fts_delete", "memories_fts_update"] {
+            sqlx::query(&format!("DROP TRIGGER {}", trigger)).execute(&pool).await.unwrap();
+        }
+        sqlx::query("DROP TABLE memories_fts").execute(&pool).await.unwrap();
+        sqlx::query("CREATE VIRTUAL TABLE memories_fts USING fts5(title, content, content='memories', content_rowid='rowid')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        Database::new().await.unwrap();
+        let sql: String = sqlx::query_scalar("SELECT sql FROM sqlite_master WHERE name = 'memories_fts'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(sql.contains("memories_fts_source"));
+        let matches = MemoryManager::new().prefix_search("tulip".into(), None).await.unwrap();
+        assert_eq!(matches.len(), 1);
+    }
+}
@@ -0,0 +1,142 @@
+// Layout of an embeddings index file, which carries chunk vectors without
+// any content so search can be rebuilt on another copy of the vault:
+//
+//   bytes 0..4   magic "hvix"
+//   byte  4      format version (1)
+//   u16          model name length, then the UTF-8 model name
+//   u32          dimension
+//   u32          entry count
+//   per entry:   u16 chunk id length, the UTF-8 chunk id, u32 blob length
+//                and a `vector_codec` blob
+//   32 bytes     SHA-256 of everything before it
+//
+// Integers are little-endian.
+use crate::checksum::CorruptedFile;
+use crate::vector_codec;
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+const MAGIC: &[u8; 4] = b"hvix";
+const VERSION: u8 = 1;
+const DIGEST_LEN: usize = 32;
+
+pub struct IndexEntry {
+    pub chunk_id: String,
+    pub vector: Vec<f32>,
+}
+
+pub struct EmbeddingIndex {
+    pub model: String,
+    pub dimensions: usize,
+    pub entries: Vec<IndexEntry>,
+}
+
+pub fn encode(index: &EmbeddingIndex) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+    put_str(&mut bytes, &index.model)?;
+    bytes.extend_from_slice(&to_u32(index.dimensions, "dimension")?.to_le_bytes());
+    bytes.extend_from_slice(&to_u32(index.entries.len(), "entry count")?.to_le_bytes());
+
+    for entry in &index.entries {
+        if entry.vector.len() != index.dimensions {
+            return Err(anyhow::anyhow!(
+                "Chunk {} has {} dimensions, expected {}",
+                entry.chunk_id,
+                entry.vector.len(),
+                index.dimensions
+            ));
+        }
+        put_str(&mut bytes, &entry.chunk_id)?;
+        let blob = vector_codec::encode(&entry.vector)?;
+        bytes.extend_from_slice(&to_u32(blob.len(), "vector size")?.to_le_bytes());
+        bytes.extend_from_slice(&blob);
+    }
+
+    let digest = Sha256::digest(&bytes);
+    bytes.extend_from_slice(&digest);
+    Ok(bytes)
+}
+
+// Fails with `CorruptedFile` when the trailing digest does not match
+pub fn decode(bytes: &[u8]) -> Result<EmbeddingIndex> {
+    if bytes.len() < MAGIC.len() + 1 + DIGEST_LEN || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(anyhow::anyhow!("Not an embeddings index file"));
+    }
+    let (body, digest) = bytes.split_at(bytes.len() - DIGEST_LEN);
+    if Sha256::digest(body).as_slice() != digest {
+        return Err(CorruptedFile.into());
+    }
+
+    let mut reader = Reader { bytes: body, pos: MAGIC.len() };
+    let version = reader.take(1)?[0];
+    if version != VERSION {
+        return Err(anyhow::anyhow!("Unsupported embeddings index version {}", version));
+    }
+    let model = reader.string()?;
+    let dimensions = reader.u32()? as usize;
+    let count = reader.u32()? as usize;
+
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let chunk_id = reader.string()?;
+        let blob_len = reader.u32()? as usize;
+        let vector = vector_codec::decode(reader.take(blob_len)?)
+            .map_err(|e| anyhow::anyhow!("Bad vector for chunk {}: {}", chunk_id, e))?;
+        if vector.len() != dimensions {
+            return Err(anyhow::anyhow!(
+                "Chunk {} has {} dimensions, expected {}",
+                chunk_id,
+                vector.len(),
+                dimensions
+            ));
+        }
+        entries.push(IndexEntry { chunk_id, vector });
+    }
+    if reader.pos != body.len() {
+        return Err(anyhow::anyhow!("Embeddings index has trailing data"));
+    }
+
+    Ok(EmbeddingIndex { model, dimensions, entries })
+}
+
+fn put_str(bytes: &mut Vec<u8>, value: &str) -> Result<()> {
+    let len = u16::try_from(value.len()).map_err(|_| anyhow::anyhow!("'{}' is too long to store", value))?;
+    bytes.extend_from_slice(&len.to_le_bytes());
+    bytes.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+fn to_u32(value: usize, what: &str) -> Result<u32> {
+    u32::try_from(value).map_err(|_| anyhow::anyhow!("{} {} is too large", what, value))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| anyhow::anyhow!("Embeddings index is truncated"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len_bytes = self.take(2)?;
+        let len = u16::from_le_bytes([len_bytes[0], len_bytes[1]]) as usize;
+        Ok(std::str::from_utf8(self.take(len)?)?.to_string())
+    }
+}
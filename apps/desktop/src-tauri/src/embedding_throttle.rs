@@ -0,0 +1,86 @@
+// Adaptive batch size for sync_embeddings. Memory usage is sampled after each
+// batch: near the limit the next batch is halved and the sync pauses so freed
+// buffers can be returned to the OS; well under it the batch doubles again.
+use crate::settings::EmbeddingSyncSettings;
+use std::time::Duration;
+use sysinfo::{Pid, System};
+
+// Fractions of the memory limit that shrink and grow the batch
+const HIGH_WATER: f64 = 0.8;
+const LOW_WATER: f64 = 0.5;
+
+// Where the throttle reads memory usage from
+pub trait MemorySignal {
+    // Resident set size in bytes; None when it cannot be read
+    fn rss_bytes(&mut self) -> Option<u64>;
+}
+
+pub struct ProcessMemory {
+    system: System,
+    pid: Option<Pid>,
+}
+
+impl ProcessMemory {
+    pub fn new() -> Self {
+        Self {
+            system: System::new(),
+            pid: sysinfo::get_current_pid().ok(),
+        }
+    }
+}
+
+impl Default for ProcessMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemorySignal for ProcessMemory {
+    fn rss_bytes(&mut self) -> Option<u64> {
+        let pid = self.pid?;
+        if !self.system.refresh_process(pid) {
+            return None;
+        }
+        self.system.process(pid).map(|process| process.memory())
+    }
+}
+
+pub struct BatchThrottle {
+    batch_size: usize,
+    min_batch_size: usize,
+    max_batch_size: usize,
+    limit_bytes: u64,
+    pause: Duration,
+}
+
+impl BatchThrottle {
+    // Starts at the largest batch; the first sample corrects it if needed
+    pub fn new(settings: &EmbeddingSyncSettings) -> Self {
+        Self {
+            batch_size: settings.max_batch_size,
+            min_batch_size: settings.min_batch_size,
+            max_batch_size: settings.max_batch_size,
+            limit_bytes: settings.memory_limit_mb.saturating_mul(1024 * 1024),
+            pause: Duration::from_millis(settings.pause_ms),
+        }
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    // Sizes the next batch from the latest sample and returns how long to
+    // wait before starting it. An unreadable sample leaves the size alone.
+    pub fn observe(&mut self, rss_bytes: Option<u64>) -> Option<Duration> {
+        let usage = rss_bytes? as f64 / self.limit_bytes as f64;
+        if usage >= HIGH_WATER {
+            self.batch_size = (self.batch_size / 2).max(self.min_batch_size);
+            Some(self.pause)
+        } else {
+            if usage < LOW_WATER {
+                self.batch_size = self.batch_size.saturating_mul(2).min(self.max_batch_size);
+            }
+            None
+        }
+    }
+}
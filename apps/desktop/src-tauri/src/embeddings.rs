@@ -0,0 +1,190 @@
+// Embedding providers. `HashingEmbedder` is deterministic feature hashing:
+// it needs no model weights, so the same text always maps to the same vector
+// on every machine. With the `local-model` feature and the weights installed,
+// the local backend runs the sentence-transformer in `local_model` instead;
+// the OpenAI backend calls its embeddings API.
+use crate::openai_embeddings::OpenAiEmbedder;
+use crate::settings::{EmbeddingBackend, EmbeddingProviderSettings};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+pub const DEFAULT_DIMENSIONS: usize = 384;
+
+// Chunks embedded per provider request
+const BATCH_SIZE: usize = 64;
+
+const MODEL_NAME: &str = "hashing-v1";
+
+// Vectors are stored per model name, so two providers with the same name must
+// produce the same vectors
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    fn model_name(&self) -> &str;
+
+    fn dimensions(&self) -> usize;
+
+    // Chunks embedded per provider request
+    fn batch_size(&self) -> usize {
+        BATCH_SIZE
+    }
+
+    // Tokens as the model sees them, for sync estimates
+    fn count_tokens(&self, text: &str) -> usize;
+
+    // One vector per text, in order
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+
+    async fn embed_one(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed(&[text.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("{} returned no vector", self.model_name()))
+    }
+}
+
+// The local model when it is built in and its weights load, otherwise hashing
+pub fn default_provider() -> Arc<dyn EmbeddingProvider> {
+    #[cfg(feature = "local-model")]
+    if let Some(model) = crate::local_model::shared() {
+        return Arc::new(model);
+    }
+    Arc::new(HashingEmbedder::default())
+}
+
+// Fails when the settings name a backend that cannot be used, e.g. OpenAI
+// without an API key
+pub fn provider_from_settings(settings: &EmbeddingProviderSettings) -> Result<Arc<dyn EmbeddingProvider>> {
+    match settings.backend {
+        EmbeddingBackend::Local => Ok(default_provider()),
+        EmbeddingBackend::OpenAi => {
+            let api_key = settings
+                .openai_api_key
+                .as_deref()
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .ok_or_else(|| anyhow::anyhow!("The OpenAI embedding backend needs an API key"))?;
+            Ok(Arc::new(OpenAiEmbedder::new(api_key, &settings.openai_model)?))
+        }
+    }
+}
+
+// The local provider that produced vectors stored under `model_name`, if
+// this build can still run it
+pub fn provider_for_model(model_name: &str) -> Option<Arc<dyn EmbeddingProvider>> {
+    #[cfg(feature = "local-model")]
+    if model_name == crate::local_model::MODEL_NAME {
+        return crate::local_model::shared().map(|model| Arc::new(model) as Arc<dyn EmbeddingProvider>);
+    }
+    HashingEmbedder::from_model_name(model_name).map(|embedder| Arc::new(embedder) as Arc<dyn EmbeddingProvider>)
+}
+
+pub struct HashingEmbedder {
+    dimensions: usize,
+    model_name: String,
+}
+
+impl HashingEmbedder {
+    // The default dimension keeps the plain model name that existing vectors
+    // are stored under; any other dimension is a separate model
+    pub fn new(dimensions: usize) -> Self {
+        let model_name = if dimensions == DEFAULT_DIMENSIONS {
+            MODEL_NAME.to_string()
+        } else {
+            format!("{}-{}", MODEL_NAME, dimensions)
+        };
+        Self { dimensions, model_name }
+    }
+
+    // The embedder that produced vectors stored under `model_name`
+    pub fn from_model_name(model_name: &str) -> Option<Self> {
+        if model_name == MODEL_NAME {
+            return Some(Self::default());
+        }
+        let dimensions: usize = model_name.strip_prefix(MODEL_NAME)?.strip_prefix('-')?.parse().ok()?;
+        (dimensions > 0 && dimensions != DEFAULT_DIMENSIONS).then(|| Self::new(dimensions))
+    }
+
+    fn hash_text(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimensions];
+
+        for word in tokens(text).map(str::to_lowercase) {
+            self.add_feature(&mut vector, word.as_bytes(), 1.0);
+
+            // Character trigrams give related word forms some overlap
+            let chars: Vec<char> = word.chars().collect();
+            for trigram in chars.windows(3) {
+                let trigram: String = trigram.iter().collect();
+                self.add_feature(&mut vector, trigram.as_bytes(), 0.5);
+            }
+        }
+
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            for value in &mut vector {
+                *value /= norm;
+            }
+        }
+
+        vector
+    }
+
+    fn add_feature(&self, vector: &mut [f32], feature: &[u8], weight: f32) {
+        let hash = fnv1a(feature);
+        let index = (hash % self.dimensions as u64) as usize;
+        // Use a separate hash bit for the sign to reduce collision bias
+        let sign = if (hash >> 63) == 0 { 1.0 } else { -1.0 };
+        vector[index] += sign * weight;
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for HashingEmbedder {
+    fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    // The words `embed` hashes
+    fn count_tokens(&self, text: &str) -> usize {
+        tokens(text).count()
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| self.hash_text(text)).collect())
+    }
+}
+
+impl Default for HashingEmbedder {
+    fn default() -> Self {
+        Self::new(DEFAULT_DIMENSIONS)
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|v| v * v).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        (dot / (norm_a * norm_b)).clamp(-1.0, 1.0)
+    }
+}
+
+fn tokens(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty())
+}
+
+// Stable across platforms and Rust versions, unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
@@ -0,0 +1,146 @@
+// Reads Evernote's ENEX export: an `<en-export>` of `<note>` elements, each
+// with a title, ENML content (XHTML wrapped in `<en-note>`, usually inside
+// CDATA), created/updated timestamps, tags, note attributes such as the
+// source URL, and `<resource>` attachments holding base64 data.
+use crate::sanitize;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use regex::Regex;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone)]
+pub struct EnexNote {
+    pub title: Option<String>,
+    // Plain text; checklists become `[ ]`/`[x]` and attachments are listed
+    pub content: String,
+    pub tags: Vec<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub source_url: Option<String>,
+    pub attachments: Vec<EnexAttachment>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnexAttachment {
+    pub file_name: Option<String>,
+    pub mime: Option<String>,
+    // Decoded size; the data itself is not kept
+    pub bytes: usize,
+}
+
+pub fn parse_enex(xml: &str) -> Result<Vec<EnexNote>> {
+    static NOTES: OnceLock<Regex> = OnceLock::new();
+    static RESOURCES: OnceLock<Regex> = OnceLock::new();
+
+    if !xml.contains("<en-export") {
+        return Err(anyhow::anyhow!("Not an Evernote export (no <en-export> element)"));
+    }
+
+    let notes = NOTES.get_or_init(|| Regex::new(r"(?s)<note>(.*?)</note>").unwrap());
+    let resources = RESOURCES.get_or_init(|| Regex::new(r"(?s)<resource>(.*?)</resource>").unwrap());
+
+    let mut parsed = Vec::new();
+    for note in notes.captures_iter(xml) {
+        let note = &note[1];
+
+        let attachments: Vec<EnexAttachment> = resources
+            .captures_iter(note)
+            .map(|resource| {
+                let resource = &resource[1];
+                EnexAttachment {
+                    file_name: element(resource, "file-name").filter(|n| !n.is_empty()),
+                    mime: element(resource, "mime").filter(|m| !m.is_empty()),
+                    bytes: element(resource, "data").map_or(0, |data| base64_len(&data)),
+                }
+            })
+            .collect();
+        // Resources come after the content, so strip them before looking
+        // for note-level elements such as `<title>`
+        let note_fields = resources.replace_all(note, "");
+
+        let mut content = element(&note_fields, "content").map(|enml| enml_to_text(&enml)).unwrap_or_default();
+        if !attachments.is_empty() {
+            content.push_str("\n\nAttachments:");
+            for attachment in &attachments {
+                content.push_str(&format!(
+                    "\n- {} ({}, {} bytes)",
+                    attachment.file_name.as_deref().unwrap_or("unnamed"),
+                    attachment.mime.as_deref().unwrap_or("unknown type"),
+                    attachment.bytes
+                ));
+            }
+        }
+
+        parsed.push(EnexNote {
+            title: element(&note_fields, "title").map(|t| single_line(&t)).filter(|t| !t.is_empty()),
+            content: content.trim().to_string(),
+            tags: elements(&note_fields, "tag").into_iter().map(|t| single_line(&t)).filter(|t| !t.is_empty()).collect(),
+            created_at: element(&note_fields, "created").and_then(|d| parse_timestamp(&d)),
+            updated_at: element(&note_fields, "updated").and_then(|d| parse_timestamp(&d)),
+            source_url: element(&note_fields, "source-url").map(|u| u.trim().to_string()).filter(|u| !u.is_empty()),
+            attachments,
+        });
+    }
+
+    Ok(parsed)
+}
+
+// ENML is XHTML plus a few Evernote elements: `<en-todo>` checkboxes,
+// `<en-media>` references to resources and `<en-crypt>` encrypted blocks
+fn enml_to_text(enml: &str) -> String {
+    static TODO: OnceLock<Regex> = OnceLock::new();
+    static MEDIA: OnceLock<Regex> = OnceLock::new();
+    static CRYPT: OnceLock<Regex> = OnceLock::new();
+    static PREAMBLE: OnceLock<Regex> = OnceLock::new();
+
+    let todo = TODO.get_or_init(|| Regex::new(r#"(?is)<en-todo\b([^>]*?)/?>(\s*</en-todo\s*>)?"#).unwrap());
+    let media = MEDIA.get_or_init(|| Regex::new(r"(?is)<en-media\b[^>]*?/?>(\s*</en-media\s*>)?").unwrap());
+    let crypt = CRYPT.get_or_init(|| Regex::new(r"(?is)<en-crypt\b.*?</en-crypt\s*>").unwrap());
+    let preamble = PREAMBLE.get_or_init(|| Regex::new(r"(?is)<\?xml.*?\?>|<!DOCTYPE[^>]*>").unwrap());
+
+    let text = preamble.replace_all(enml, "");
+    let text = crypt.replace_all(&text, "[encrypted content]");
+    let text = media.replace_all(&text, "[attachment]");
+    let text = todo.replace_all(&text, |caps: &regex::Captures| {
+        let checked = caps[1].to_ascii_lowercase().contains("checked=\"true\"");
+        if checked { "[x] " } else { "[ ] " }
+    });
+    sanitize::html_to_text(&text)
+}
+
+fn element(xml: &str, name: &str) -> Option<String> {
+    elements(xml, name).into_iter().next()
+}
+
+// Text of each `<name>` element, CDATA unwrapped and entities decoded
+fn elements(xml: &str, name: &str) -> Vec<String> {
+    let pattern = Regex::new(&format!(r"(?s)<{0}(?:\s[^>]*)?>(.*?)</{0}\s*>", regex::escape(name))).unwrap();
+    pattern
+        .captures_iter(xml)
+        .map(|caps| {
+            let inner = caps[1].trim();
+            match inner.strip_prefix("<![CDATA[").and_then(|rest| rest.strip_suffix("]]>")) {
+                Some(cdata) => cdata.to_string(),
+                None => sanitize::decode_entities(inner),
+            }
+        })
+        .collect()
+}
+
+// ENEX timestamps look like `20130730T205204Z`
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value.trim(), "%Y%m%dT%H%M%SZ")
+        .ok()
+        .map(|naive| naive.and_utc())
+}
+
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Decoded length of base64 text that may be wrapped across lines
+fn base64_len(data: &str) -> usize {
+    let digits = data.bytes().filter(|b| !b.is_ascii_whitespace()).count();
+    let padding = data.trim_end().bytes().rev().take_while(|b| *b == b'=').count();
+    (digits / 4 * 3).saturating_sub(padding)
+}
@@ -0,0 +1,117 @@
+// Finds the files a directory import reads: a recursive walk that skips
+// hidden entries and does not follow symlinks, filtered by a glob over the
+// path relative to the root
+use anyhow::Result;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_GLOB: &str = "*.{md,markdown,txt}";
+
+pub struct Glob {
+    regex: Regex,
+    // Patterns without `/` are matched against the file name alone
+    match_path: bool,
+}
+
+impl Glob {
+    // `*` and `?` stay within one path segment, `**` spans segments and
+    // `{a,b}` matches either alternative
+    pub fn new(pattern: &str) -> Result<Self> {
+        let mut regex = String::from("^");
+        let mut chars = pattern.chars().peekable();
+        let mut in_braces = false;
+
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if chars.peek() == Some(&'/') {
+                        chars.next();
+                        regex.push_str("(?:.*/)?");
+                    } else {
+                        regex.push_str(".*");
+                    }
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                '{' if !in_braces => {
+                    in_braces = true;
+                    regex.push_str("(?:");
+                }
+                ',' if in_braces => regex.push('|'),
+                '}' if in_braces => {
+                    in_braces = false;
+                    regex.push(')');
+                }
+                _ => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        if in_braces {
+            return Err(anyhow::anyhow!("Invalid glob '{}': unclosed '{{'", pattern));
+        }
+        regex.push('$');
+
+        Ok(Self {
+            regex: Regex::new(&regex).map_err(|e| anyhow::anyhow!("Invalid glob '{}': {}", pattern, e))?,
+            match_path: pattern.contains('/'),
+        })
+    }
+
+    pub fn matches(&self, relative: &str) -> bool {
+        if self.match_path {
+            self.regex.is_match(relative)
+        } else {
+            self.regex.is_match(relative.rsplit('/').next().unwrap_or(relative))
+        }
+    }
+}
+
+pub struct WalkedFile {
+    pub path: PathBuf,
+    // Relative to the root, `/`-separated on every platform
+    pub relative: String,
+    pub size: u64,
+}
+
+// Matching files under `root`, sorted by relative path. Fails once more
+// than `max_files` match rather than importing an arbitrary subset.
+pub async fn collect_files(root: &Path, glob: &Glob, max_files: usize) -> Result<Vec<WalkedFile>> {
+    let mut files = Vec::new();
+    let mut pending = vec![(root.to_path_buf(), String::new())];
+
+    while let Some((dir, prefix)) = pending.pop() {
+        let mut entries = tokio::fs::read_dir(&dir)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", dir.display(), e))?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let relative = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+
+            // DirEntry::file_type does not follow symlinks
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                pending.push((entry.path(), relative));
+            } else if file_type.is_file() && glob.matches(&relative) {
+                if files.len() == max_files {
+                    return Err(anyhow::anyhow!(
+                        "More than {} files match in {}",
+                        max_files,
+                        root.display()
+                    ));
+                }
+                files.push(WalkedFile {
+                    path: entry.path(),
+                    relative,
+                    size: entry.metadata().await?.len(),
+                });
+            }
+        }
+    }
+
+    files.sort_by(|a, b| a.relative.cmp(&b.relative));
+    Ok(files)
+}
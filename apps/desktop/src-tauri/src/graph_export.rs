@@ -0,0 +1,99 @@
+// Renders the memory relation graph as Graphviz DOT or GraphML (for Gephi,
+// yEd and friends). Node ids are memory ids; labels are titles.
+use crate::commands::GraphFormat;
+
+pub struct GraphNode {
+    pub id: String,
+    pub label: String,
+}
+
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+    pub kind: String,
+}
+
+pub fn render(format: GraphFormat, nodes: &[GraphNode], edges: &[GraphEdge]) -> String {
+    match format {
+        GraphFormat::Dot => to_dot(nodes, edges),
+        GraphFormat::Graphml => to_graphml(nodes, edges),
+    }
+}
+
+fn to_dot(nodes: &[GraphNode], edges: &[GraphEdge]) -> String {
+    let mut out = String::from("digraph memories {\n");
+    for node in nodes {
+        out.push_str(&format!("  {} [label={}];\n", dot_string(&node.id), dot_string(&node.label)));
+    }
+    for edge in edges {
+        out.push_str(&format!(
+            "  {} -> {} [label={}];\n",
+            dot_string(&edge.source),
+            dot_string(&edge.target),
+            dot_string(&edge.kind)
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn to_graphml(nodes: &[GraphNode], edges: &[GraphEdge]) -> String {
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n",
+        "  <key id=\"kind\" for=\"edge\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+        "  <graph id=\"memories\" edgedefault=\"directed\">\n",
+    ));
+    for node in nodes {
+        out.push_str(&format!(
+            "    <node id=\"{}\"><data key=\"label\">{}</data></node>\n",
+            xml_escape(&node.id),
+            xml_escape(&node.label)
+        ));
+    }
+    for edge in edges {
+        out.push_str(&format!(
+            "    <edge source=\"{}\" target=\"{}\"><data key=\"kind\">{}</data></edge>\n",
+            xml_escape(&edge.source),
+            xml_escape(&edge.target),
+            xml_escape(&edge.kind)
+        ));
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+// Always quoted, so ids and titles never need to be valid DOT identifiers
+fn dot_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => {}
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than tab and newline are not valid XML
+            c if c.is_control() && c != '\t' && c != '\n' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,153 @@
+// all-MiniLM-L6-v2 sentence embeddings, run in-process on the CPU with
+// candle. The weights are not bundled: `config.json`, `tokenizer.json` and
+// `model.safetensors` from the Hugging Face repo go in `model_dir()`.
+// Vectors are the attention-masked mean of the last hidden layer, L2
+// normalized, as sentence-transformers produces them.
+use crate::database;
+use crate::embeddings::EmbeddingProvider;
+use anyhow::Result;
+use async_trait::async_trait;
+use candle_core::{Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config, DTYPE};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+
+pub const MODEL_NAME: &str = "all-MiniLM-L6-v2";
+
+// The model was trained on inputs up to this many tokens; longer chunks are
+// truncated
+const MAX_TOKENS: usize = 256;
+
+const BATCH_SIZE: usize = 32;
+
+// Cheap to clone; clones share the loaded weights
+#[derive(Clone)]
+pub struct LocalEmbedder {
+    model: Arc<MiniLm>,
+}
+
+struct MiniLm {
+    model: BertModel,
+    tokenizer: Tokenizer,
+    dimensions: usize,
+    device: Device,
+}
+
+// `data/models/all-MiniLM-L6-v2`, next to the database
+pub fn model_dir() -> PathBuf {
+    let data_dir = database::database_path()
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("data"));
+    data_dir.join("models").join(MODEL_NAME)
+}
+
+// Loaded on first use and shared by every MemoryManager. None when the
+// weights are not installed or fail to load.
+pub fn shared() -> Option<LocalEmbedder> {
+    static MODEL: OnceLock<Option<LocalEmbedder>> = OnceLock::new();
+    MODEL
+        .get_or_init(|| {
+            let dir = model_dir();
+            if !dir.join("model.safetensors").exists() {
+                return None;
+            }
+            match LocalEmbedder::load(&dir) {
+                Ok(model) => Some(model),
+                Err(e) => {
+                    eprintln!("Failed to load {} from {}: {}", MODEL_NAME, dir.display(), e);
+                    None
+                }
+            }
+        })
+        .clone()
+}
+
+impl LocalEmbedder {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let config: Config = serde_json::from_str(&std::fs::read_to_string(dir.join("config.json"))?)?;
+
+        let mut tokenizer = Tokenizer::from_file(dir.join("tokenizer.json")).map_err(anyhow::Error::msg)?;
+        tokenizer.with_padding(Some(PaddingParams::default()));
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: MAX_TOKENS,
+                ..Default::default()
+            }))
+            .map_err(anyhow::Error::msg)?;
+
+        let device = Device::Cpu;
+        // Safety: the weights file is only read, and is not expected to
+        // change while the app is running
+        let weights = unsafe { VarBuilder::from_mmaped_safetensors(&[dir.join("model.safetensors")], DTYPE, &device)? };
+        let model = BertModel::load(weights, &config)?;
+
+        Ok(Self {
+            model: Arc::new(MiniLm {
+                model,
+                tokenizer,
+                dimensions: config.hidden_size,
+                device,
+            }),
+        })
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for LocalEmbedder {
+    fn model_name(&self) -> &str {
+        MODEL_NAME
+    }
+
+    fn dimensions(&self) -> usize {
+        self.model.dimensions
+    }
+
+    fn batch_size(&self) -> usize {
+        BATCH_SIZE
+    }
+
+    // Word pieces, including the [CLS] and [SEP] markers, up to the
+    // truncation limit
+    fn count_tokens(&self, text: &str) -> usize {
+        self.model.tokenizer.encode(text, true).map_or(0, |encoding| encoding.len())
+    }
+
+    // Inference is CPU-bound, so it runs on the blocking pool
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let model = Arc::clone(&self.model);
+        let texts = texts.to_vec();
+        tokio::task::spawn_blocking(move || model.embed(texts)).await?
+    }
+}
+
+impl MiniLm {
+    fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Padded to the longest text in the batch
+        let encodings = self.tokenizer.encode_batch(texts, true).map_err(anyhow::Error::msg)?;
+        let mut ids = Vec::with_capacity(encodings.len());
+        let mut masks = Vec::with_capacity(encodings.len());
+        for encoding in &encodings {
+            ids.push(Tensor::new(encoding.get_ids(), &self.device)?);
+            masks.push(Tensor::new(encoding.get_attention_mask(), &self.device)?);
+        }
+        let input_ids = Tensor::stack(&ids, 0)?;
+        let attention_mask = Tensor::stack(&masks, 0)?;
+        let token_type_ids = input_ids.zeros_like()?;
+
+        let hidden = self.model.forward(&input_ids, &token_type_ids, Some(&attention_mask))?;
+
+        // Mean over real tokens only, so padding does not dilute short texts
+        let mask = attention_mask.to_dtype(DTYPE)?.unsqueeze(2)?;
+        let summed = hidden.broadcast_mul(&mask)?.sum(1)?;
+        let pooled = summed.broadcast_div(&mask.sum(1)?)?;
+        let norms = pooled.sqr()?.sum_keepdim(1)?.sqrt()?;
+        Ok(pooled.broadcast_div(&norms)?.to_vec2::<f32>()?)
+    }
+}
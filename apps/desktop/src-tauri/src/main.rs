@@ -6,7 +6,37 @@ mod database;
 mod crypto;
 mod vault;
 mod memory;
+mod settings;
+mod embeddings;
+mod analysis;
+mod chunking;
+mod metrics;
+mod query_cache;
+mod validation;
+mod maintenance;
+mod vector_codec;
+mod text_import;
+mod snapshots;
+mod sanitize;
+mod recommendations;
+mod saved_searches;
+mod folder_import;
+mod checksum;
+mod bookmark_import;
+mod embedding_index;
+mod content_extractor;
+mod embedding_throttle;
+mod graph_export;
+mod enex_import;
+mod openai_embeddings;
+#[cfg(feature = "local-model")]
+mod local_model;
+#[cfg(test)]
+mod test_support;
 
+use memory::MemoryManager;
+use vault::VaultManager;
+use tokio::sync::Mutex;
 use tauri::Manager;
 use tauri_plugin_fs::FsExt;
 use tauri_plugin_dialog::DialogExt;
@@ -15,30 +45,116 @@ use tauri_plugin_shell::ShellExt;
 
 #[tokio::main]
 async fn main() {
-
     tauri::Builder::default()
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_shell::init())
+        // Shared by every command, so an unlocked vault and its key outlive
+        // the command that unlocked it
+        .manage(Mutex::new(VaultManager::new()))
+        .manage(Mutex::new(MemoryManager::new_locked()))
         .invoke_handler(tauri::generate_handler![
             commands::greet,
             commands::create_vault,
             commands::unlock_vault,
+            commands::unlock_vault_readonly,
+            commands::set_database_encryption,
+            commands::validate_memory,
+            commands::validate_source,
             commands::add_memory,
+            commands::capture_memory,
             commands::query_memory,
+            commands::query_memory_streaming,
             commands::search_memories,
+            commands::prefix_search,
+            commands::search_in_memory,
+            commands::untagged_memories,
+            commands::orphan_memories,
+            commands::find_semantic_duplicates,
+            commands::word_cloud,
+            commands::list_tags,
+            commands::memories_by_tag,
+            commands::suggest_tags_for_content,
+            commands::extract_keywords,
             commands::get_insights,
+            commands::activity_heatmap,
+            commands::timeline,
+            commands::link_memories,
+            commands::export_graph,
             commands::export_data,
+            commands::export_to_file,
+            commands::import_bookmarks,
+            commands::import_enex,
             commands::import_data,
+            commands::save_search,
+            commands::list_saved_searches,
+            commands::run_saved_search,
+            commands::delete_saved_search,
+            commands::export_saved_search,
+            commands::export_memory,
+            commands::import_memory,
+            commands::import_text_file,
+            commands::import_directory,
             commands::get_vault_status,
+            commands::lock_vault,
+            commands::set_auto_lock_minutes,
             commands::update_vault_settings,
             commands::get_memory_stats,
+            commands::recompute_statistics,
+            commands::create_snapshot,
+            commands::list_snapshots,
+            commands::diff_snapshots,
+            commands::vault_fingerprint,
             commands::delete_memory,
+            commands::set_memory_expiry,
+            commands::sweep_expired_memories,
             commands::update_memory,
+            commands::memory_neighbors,
+            commands::refresh_from_source,
+            commands::list_memory_versions,
+            commands::memory_similarity,
+            commands::embedding_drift,
+            commands::pin_memory,
+            commands::unpin_memory,
+            commands::bulk_tag,
             commands::get_citations,
+            commands::list_query_history,
+            commands::clear_query_history,
+            commands::get_memory_settings,
+            commands::update_memory_settings,
             commands::sync_embeddings,
-            commands::get_system_info
+            commands::estimate_embedding_sync,
+            commands::clear_embeddings,
+            commands::export_embeddings,
+            commands::import_embeddings,
+            commands::deduplicate_chunks,
+            commands::estimate_storage_savings,
+            commands::memory_embedding_debug,
+            commands::verify_embedding_dimensions,
+            commands::list_large_embeddings,
+            commands::prune_embeddings,
+            commands::embed_text,
+            commands::test_provider,
+            commands::rebuild_fts_index,
+            commands::optimize_vault,
+            commands::check_fts_consistency,
+            commands::repair_missing_chunks,
+            commands::chunk_schema_report,
+            commands::rebuild_chunks,
+            commands::backfill_titles,
+            commands::repair_tag_references,
+            commands::enable_encryption,
+            commands::verify_vault_key,
+            commands::audit_crypto,
+            commands::migrate_crypto_format,
+            commands::get_system_info,
+            commands::get_recommendations,
+            commands::checkpoint_wal,
+            commands::dump_schema,
+            commands::get_metrics,
+            commands::query_latency_stats,
+            commands::query_cache_stats
         ])
         .setup(|app| {
             // Initialize database
@@ -47,8 +163,29 @@ async fn main() {
                     eprintln!("Failed to initialize database: {}", e);
                 }
             });
+            tauri::async_runtime::spawn(maintenance::run_scheduler());
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let vault_state = handle.state::<Mutex<VaultManager>>();
+                let memory_state = handle.state::<Mutex<MemoryManager>>();
+                vault::run_auto_lock(vault_state.inner(), memory_state.inner()).await;
+            });
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                let shutdown = async {
+                    let mut pools = Vec::new();
+                    pools.extend(app.state::<Mutex<VaultManager>>().lock().await.take_pool());
+                    pools.extend(app.state::<Mutex<MemoryManager>>().lock().await.take_pool());
+                    pools.extend(maintenance::scheduler_pool());
+                    database::shutdown(pools).await
+                };
+                if let Err(e) = tauri::async_runtime::block_on(shutdown) {
+                    eprintln!("Failed to shut down database cleanly: {}", e);
+                }
+            }
+        });
 }
\ No newline at end of file
@@ -0,0 +1,243 @@
+// Opt-in background compaction. Runs incremental vacuum and FTS optimize once
+// the app has gone quiet, so it never competes with user-facing writes. Each
+// tick also sweeps memories whose expiry has passed.
+use crate::database::{self, Database};
+use crate::memory::{MemoryManager, DEFAULT_VAULT_ID};
+use crate::settings::MemorySettings;
+use crate::vault;
+use anyhow::Result;
+use sqlx::sqlite::SqlitePool;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// How often to re-check the settings while compaction is disabled
+const DISABLED_POLL: Duration = Duration::from_secs(60);
+
+static ACTIVE_WRITES: AtomicUsize = AtomicUsize::new(0);
+static LAST_WRITE_MS: AtomicU64 = AtomicU64::new(0);
+// Moves on when a write starts and again when it ends; cached query results
+// from an earlier generation are stale
+static WRITE_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// The scheduler's own pool, shared so shutdown can close it
+static SCHEDULER_POOL: Mutex<Option<SqlitePool>> = Mutex::new(None);
+
+// Held for the duration of a write; the scheduler waits for all of them to
+// finish and for the idle window to pass before compacting
+pub struct WriteActivity;
+
+impl Drop for WriteActivity {
+    fn drop(&mut self) {
+        LAST_WRITE_MS.store(now_ms(), Ordering::SeqCst);
+        WRITE_GENERATION.fetch_add(1, Ordering::SeqCst);
+        ACTIVE_WRITES.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Fails with `database::ReadOnly` while the vault is unlocked read-only
+pub fn begin_write() -> Result<WriteActivity> {
+    if database::is_read_only() {
+        return Err(database::ReadOnly.into());
+    }
+    ACTIVE_WRITES.fetch_add(1, Ordering::SeqCst);
+    WRITE_GENERATION.fetch_add(1, Ordering::SeqCst);
+    Ok(WriteActivity)
+}
+
+pub fn write_generation() -> u64 {
+    WRITE_GENERATION.load(Ordering::SeqCst)
+}
+
+// Waits until no write is in progress; false if `timeout` elapsed first
+pub async fn wait_for_writes(timeout: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    while ACTIVE_WRITES.load(Ordering::SeqCst) > 0 {
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    true
+}
+
+fn is_idle(idle_for: Duration) -> bool {
+    let quiet_ms = now_ms().saturating_sub(LAST_WRITE_MS.load(Ordering::SeqCst));
+    ACTIVE_WRITES.load(Ordering::SeqCst) == 0 && quiet_ms >= idle_for.as_millis() as u64
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub async fn run_scheduler() {
+    let mut wait = DISABLED_POLL;
+    loop {
+        tokio::time::sleep(wait).await;
+        // Nothing is swept or compacted while the vault is locked
+        if !vault::is_vault_unlocked() {
+            wait = DISABLED_POLL;
+            continue;
+        }
+        let pool = match open_scheduler_pool().await {
+            Ok(pool) => pool,
+            Err(e) => {
+                eprintln!("Background compaction unavailable: {}", e);
+                wait = DISABLED_POLL;
+                continue;
+            }
+        };
+        wait = tick(&pool).await;
+    }
+}
+
+// Sweeps expired memories, then compacts if due; returns how long to wait
+// before the next tick
+async fn tick(pool: &SqlitePool) -> Duration {
+    if !database::is_read_only() {
+        if let Err(e) = MemoryManager::sweep_expired_memories_static(pool).await {
+            eprintln!("Expired memory sweep failed: {}", e);
+        }
+    }
+    match run_due(pool).await {
+        Ok(next) => next,
+        Err(e) => {
+            eprintln!("Background compaction failed: {}", e);
+            DISABLED_POLL
+        }
+    }
+}
+
+// Opened on first use after unlock, so an encrypted file can be read, and
+// again after `close_scheduler_pool`
+async fn open_scheduler_pool() -> Result<SqlitePool> {
+    if let Some(pool) = scheduler_pool().filter(|pool| !pool.is_closed()) {
+        return Ok(pool);
+    }
+    let pool = Database::new().await?.into_pool();
+    *SCHEDULER_POOL.lock().unwrap_or_else(|e| e.into_inner()) = Some(pool.clone());
+    Ok(pool)
+}
+
+pub fn scheduler_pool() -> Option<SqlitePool> {
+    SCHEDULER_POOL.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+// For when the database file is about to be replaced; the next tick opens
+// a pool on the new file
+pub async fn close_scheduler_pool() {
+    let pool = SCHEDULER_POOL.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if let Some(pool) = pool {
+        pool.close().await;
+    }
+}
+
+// One scheduler tick: compacts if enabled and idle, and returns how long to
+// wait before the next tick
+pub async fn run_due(pool: &SqlitePool) -> Result<Duration> {
+    let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?.compaction;
+    if !settings.enabled || database::is_read_only() {
+        return Ok(DISABLED_POLL);
+    }
+
+    let idle_for = Duration::from_secs(settings.idle_seconds);
+    if !is_idle(idle_for) {
+        // Try again once the app may have gone quiet
+        return Ok(idle_for.max(Duration::from_secs(1)));
+    }
+
+    compact(pool).await?;
+    Ok(Duration::from_secs(settings.interval_minutes.saturating_mul(60)))
+}
+
+pub async fn compact(pool: &SqlitePool) -> Result<()> {
+    // Only databases created with auto_vacuum = INCREMENTAL (2) keep a
+    // freelist that can be reclaimed without a full VACUUM
+    let auto_vacuum: i64 = sqlx::query_scalar("PRAGMA auto_vacuum").fetch_one(pool).await?;
+    if auto_vacuum == 2 {
+        sqlx::query("PRAGMA incremental_vacuum").execute(pool).await?;
+    }
+
+    sqlx::query("INSERT INTO memories_fts (memories_fts) VALUES ('optimize')")
+        .execute(pool)
+        .await?;
+    sqlx::query("INSERT INTO memory_terms (memory_terms) VALUES ('optimize')")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, entry, TestVault};
+
+    async fn freelist_count(pool: &SqlitePool) -> i64 {
+        sqlx::query_scalar("PRAGMA freelist_count").fetch_one(pool).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn due_tick_compacts_with_a_short_interval() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let mut ids = Vec::new();
+        for i in 0..20 {
+            ids.push(manager.add_memory(entry(&format!("bulky note {} {}", i, "x".repeat(8000)), &[])).await.unwrap());
+        }
+        for id in ids {
+            manager.delete_memory(id).await.unwrap();
+        }
+        let db = test_support::database().await;
+        let pool = db.get_pool().await;
+        let freed = freelist_count(pool).await;
+        assert!(freed > 0);
+
+        // Disabled by default: nothing is reclaimed
+        assert_eq!(run_due(pool).await.unwrap(), DISABLED_POLL);
+        assert_eq!(freelist_count(pool).await, freed);
+
+        let mut settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await.unwrap();
+        settings.compaction = crate::settings::CompactionSettings {
+            enabled: true,
+            interval_minutes: 1,
+            idle_seconds: 0,
+        };
+        settings.save(pool, DEFAULT_VAULT_ID).await.unwrap();
+
+        assert_eq!(run_due(pool).await.unwrap(), Duration::from_secs(60));
+        assert!(freelist_count(pool).await < freed);
+    }
+
+    #[tokio::test]
+    async fn ticks_compact_once_the_idle_window_has_passed() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let id = manager.add_memory(entry(&format!("bulky note {}", "x".repeat(64_000)), &[])).await.unwrap();
+        manager.delete_memory(id).await.unwrap();
+        let db = test_support::database().await;
+        let pool = db.get_pool().await;
+        let freed = freelist_count(pool).await;
+        assert!(freed > 0);
+
+        let mut settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await.unwrap();
+        settings.compaction = crate::settings::CompactionSettings {
+            enabled: true,
+            interval_minutes: 1,
+            idle_seconds: 1,
+        };
+        settings.save(pool, DEFAULT_VAULT_ID).await.unwrap();
+
+        // Too soon after the delete
+        assert_eq!(tick(pool).await, Duration::from_secs(1));
+        assert_eq!(freelist_count(pool).await, freed);
+
+        // The tick's own sweep finds nothing expired and is not a write
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(tick(pool).await, Duration::from_secs(60));
+        assert!(freelist_count(pool).await < freed);
+    }
+}
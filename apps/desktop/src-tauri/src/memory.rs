@@ -1,43 +1,288 @@
-use crate::database::Database;
-use crate::commands::{MemoryEntry, QueryRequest, QueryResult, Citation, MemoryStats, SystemInfo};
+use crate::database::{self, to_count, to_sql_int, Database};
+use crate::commands::{MemoryEntry, QueryRequest, QueryResult, Citation, MemoryHit, ScoreAggregate, MemoryStats, SystemInfo, QueryHistoryEntry, ExportOptions, EmbeddingResult, MemoryBundle, ProviderStatus, TagNode, FtsConsistencyReport, PrefixMatch, TagCount, TaggedMemories, DuplicateCandidate, DuplicateCluster, TagSuggestion, ExportFileResult, StatisticsReport, VaultMemoryCount, EmbeddingInfo, TextImportResult, Snapshot, SnapshotDiff, MemoryNeighbors, SearchCriteria, SearchOrder, BulkTagMode, EmbeddingSyncEstimate, TagReferenceRepair, Recommendation, DimensionReport, SavedSearch, ActivityDay, CryptoAudit, KdfParams, DirectoryImportResult, SkippedFile, MemoryEmbeddingDebug, ChunkEmbeddingDebug, EmbeddingDebug, WordCount, BookmarkImportResult, SkippedBookmark, EmbeddingExportResult, EmbeddingImportResult, ChunkDedupResult, TimelineGranularity, TimelineBucket, TimelineMemory, SourceRefreshResult, MemoryVersion, QueryDrift, ChunkSchemaReport, ChunkVersionCount, InMemoryMatch, Keyword, EmbeddingSyncProgress, GraphFormat, StorageSavingsEstimate, QueryStreamEvent, EnexImportResult, SkippedNote, OptimizeSteps, OptimizeReport, OrphanCleanup, VacuumResult};
+use crate::crypto::{self, CryptoManager, TextCipher};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use crate::settings::{CitationContentMode, ExpiryPolicy, KeywordAnalysis, MemorySettings};
+use crate::embeddings::{self, EmbeddingProvider};
+use crate::analysis::{self, TextAnalyzer};
+use crate::chunking;
+use crate::maintenance;
+use crate::query_cache;
+use crate::validation::{self, SourceValidation, ValidationReport};
+use crate::vector_codec;
+use crate::text_import::{self, SplitStrategy};
+use crate::snapshots;
+use crate::sanitize;
+use crate::recommendations;
+use crate::saved_searches;
+use crate::folder_import::{self, Glob};
+use crate::checksum::{self, Checksum};
+use crate::bookmark_import;
+use crate::enex_import;
+use crate::content_extractor::ContentExtractor;
+use crate::embedding_index::{self, EmbeddingIndex, IndexEntry};
+use crate::embedding_throttle::{BatchThrottle, MemorySignal, ProcessMemory};
+use crate::graph_export::{self, GraphEdge, GraphNode};
 use anyhow::Result;
 use uuid::Uuid;
-use chrono::Utc;
-use std::collections::HashMap;
+use chrono::{Duration, Utc};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use sqlx::Row;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+// Memories are not yet scoped to a real vault id
+pub(crate) const DEFAULT_VAULT_ID: &str = "default";
+
+// Fields that can be selected for a JSON export
+const EXPORT_FIELDS: &[&str] = &["id", "title", "content", "tags", "source", "created_at", "updated_at"];
+
+// Rows fetched per batch when exporting to a file
+const EXPORT_BATCH_SIZE: usize = 500;
+
+// Version of the single-memory share bundle format
+const BUNDLE_VERSION: u32 = 1;
+
+// Upper bound for ad-hoc `embed_text` requests
+const MAX_EMBED_TEXT_CHARS: usize = 8192;
+
+// Extended result code FTS5 returns when its index disagrees with the
+// content table
+const SQLITE_CORRUPT_VTAB: &str = "267";
+
+// Duplicate detection only compares memories created close together: each
+// memory against the next DUPLICATE_WINDOW in creation order, across at most
+// MAX_DUPLICATE_SCAN of the most recent memories
+const DUPLICATE_WINDOW: usize = 200;
+const MAX_DUPLICATE_SCAN: usize = 5000;
+const DEFAULT_DUPLICATE_THRESHOLD: f32 = 0.9;
+
+// Directory imports stop before reading anything when more files match
+const MAX_IMPORT_FILES: usize = 1000;
+
+// About ten years of daily buckets for the activity heatmap
+const MAX_HEATMAP_DAYS: u32 = 3660;
+
+// The word cloud reads memories in batches, newest first, and stops after
+// MAX_WORD_CLOUD_MEMORIES
+const WORD_CLOUD_BATCH_SIZE: usize = 500;
+const MAX_WORD_CLOUD_MEMORIES: usize = 20_000;
+
+// Searches in encrypted vaults without a term index decrypt memories in
+// batches of this size
+const SCAN_BATCH_SIZE: usize = 500;
+
+// Chunks with no vector for the model bound second; shared-embedding chunks
+// are covered by the chunk they point at
+const PENDING_EMBEDDINGS: &str = "FROM chunks c
+     JOIN memories m ON c.memory_id = m.id
+     WHERE m.vault_id = ?
+       AND c.embedding_chunk_id IS NULL
+       AND NOT EXISTS (
+           SELECT 1 FROM embeddings e WHERE e.chunk_id = c.id AND e.model_name = ?
+       )";
+
+// Keywords kept per memory by extract_keywords, and the shortest considered
+const MAX_CACHED_KEYWORDS: usize = 50;
+const MIN_KEYWORD_CHARS: usize = 3;
+
+// Limits for search_in_memory
+const MAX_FIND_MATCHES: usize = 1000;
+const FIND_CONTEXT_CHARS: usize = 40;
+
+// Memories compared per query by embedding_drift
+const DEFAULT_DRIFT_K: usize = 10;
+
+// query_memory falls back to substring matching while the vault has no
+// vectors for the current model; every match gets this score
+const KEYWORD_MATCH_SCORE: f32 = 0.8;
+
+// Chunks not yet embedded match by substring too, scored below every
+// semantic hit (those all score above zero) so they rank last and leave
+// the confidence to the similarity scores
+const UNEMBEDDED_MATCH_SCORE: f32 = 0.0;
+
+// Search leaves out memories past their expiry even before the sweep has
+// removed them
+const NOT_EXPIRED: &str = "(expires_at IS NULL OR julianday(expires_at) > julianday('now'))";
+
+// Untitled memories are titled from the first words of their first line
+const TITLE_WORDS: usize = 10;
+
+// Tag suggestions are drawn from this many of the most similar tagged memories
+const SUGGESTION_NEIGHBORS: usize = 10;
+
+// What a quick-captured snippet looks like, used to pick default tags
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CaptureKind {
+    Link,
+    Code,
+    Prose,
+}
 
 pub struct MemoryManager {
     db: Option<Database>,
+    embedder: Arc<dyn EmbeddingProvider>,
+    // Unwrapped vault key, present only while an encrypted vault is unlocked
+    vault_key: Option<[u8; 32]>,
+    // Set by `lock` and cleared when an unlock hands over the vault key
+    locked: bool,
+}
+
+impl Default for MemoryManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MemoryManager {
     pub fn new() -> Self {
-        Self { db: None }
+        Self::with_provider(embeddings::default_provider())
+    }
+
+    // The app's shared manager starts out locked, so nothing is read or
+    // written before an unlock hands over the vault key
+    pub fn new_locked() -> Self {
+        Self {
+            locked: true,
+            ..Self::new()
+        }
+    }
+
+    // Vectors are stored under the provider's model name, so switching
+    // providers leaves existing vectors in place for the old model
+    pub fn with_provider(embedder: Arc<dyn EmbeddingProvider>) -> Self {
+        Self {
+            db: None,
+            embedder,
+            vault_key: None,
+            locked: false,
+        }
+    }
+
+    // Switches to the embedding provider chosen in the saved settings. Called
+    // once the vault key is set, since an encrypted vault seals the API key.
+    pub async fn load_saved_provider(&mut self) -> Result<()> {
+        let cipher = self.text_cipher();
+        let pool = self.get_db().await?.get_pool().await;
+        let mut provider_settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?.embedding_provider;
+        provider_settings.openai_api_key = Self::open_api_key_static(cipher.as_ref(), provider_settings.openai_api_key)?;
+        self.embedder = embeddings::provider_from_settings(&provider_settings)?;
+        Ok(())
+    }
+
+    pub fn set_vault_key(&mut self, key: Option<[u8; 32]>) {
+        self.vault_key = key;
+        self.locked = false;
+    }
+
+    // Wipes the vault key and every cached query result derived from it, and
+    // drops the connection; requests fail with `database::Locked` until the
+    // next unlock
+    pub fn lock(&mut self) {
+        self.vault_key.zeroize();
+        self.vault_key = None;
+        self.db = None;
+        self.locked = true;
+        query_cache::clear();
+    }
+
+    // Built once per request and shared by every row it reads or writes
+    fn text_cipher(&self) -> Option<TextCipher> {
+        self.vault_key.as_ref().map(TextCipher::new)
+    }
+
+    // Hands over the manager's pool, e.g. to close it on shutdown; the next
+    // request opens a new one
+    pub fn take_pool(&mut self) -> Option<sqlx::SqlitePool> {
+        self.db.take().map(Database::into_pool)
     }
 
     async fn get_db(&mut self) -> Result<&Database> {
+        if self.locked {
+            return Err(database::Locked.into());
+        }
         if self.db.is_none() {
             self.db = Some(Database::new().await?);
         }
         Ok(self.db.as_ref().unwrap())
     }
 
+    pub fn validate_memory(&self, entry: &MemoryEntry) -> ValidationReport {
+        validation::validate_entry(entry)
+    }
+
+    pub fn validate_source(&self, source: &str) -> SourceValidation {
+        validation::validate_source(source)
+    }
+
     pub async fn add_memory(&mut self, mut entry: MemoryEntry) -> Result<String> {
+        let _write = maintenance::begin_write()?;
+        let cipher = self.text_cipher();
         let db = self.get_db().await?;
         let pool = db.get_pool().await;
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+
+        // Sanitize first so content that is only invisible characters fails
+        // validation as empty
+        Self::sanitize_entry_static(&mut entry, &settings);
+        for tag in &settings.default_tags {
+            if !entry.tags.iter().any(|t| validation::normalize_tag(t) == *tag) {
+                entry.tags.push(tag.clone());
+            }
+        }
+        let report = validation::validate_entry(&entry);
+        if !report.valid {
+            return Err(anyhow::anyhow!("Invalid memory: {}", report.error_message()));
+        }
+        entry.tags = report.normalized_tags;
+        entry.source = report.normalized_source;
+
+        let encrypted = Self::vault_encrypted_static(pool).await?;
+
+        if entry.title.as_deref().is_none_or(|t| t.trim().is_empty()) {
+            entry.title = Self::derive_title_static(&entry.content);
+        }
+
+        // NULL when detection is off, so those memories are never filtered out
+        let language = settings
+            .language_detection
+            .enabled
+            .then(|| analysis::detect_language(&entry.content));
+        if settings.language_detection.auto_tag {
+            if let Some(language) = language.as_deref().filter(|l| *l != analysis::UNKNOWN_LANGUAGE) {
+                let tag = format!("lang/{}", language);
+                if !entry.tags.contains(&tag) {
+                    entry.tags.push(tag);
+                }
+            }
+        }
+        Self::check_tag_limit_static(&settings, entry.tags.len())?;
         
         let memory_id = entry.id.unwrap_or_else(|| Uuid::new_v4().to_string());
         let now = Utc::now();
 
+        // Split fenced code separately from prose. Past the soft limit only the
+        // first chunks are kept and the memory is flagged as truncated; the
+        // full content is still stored on the memory itself.
+        let mut chunks = chunking::chunk_content(&entry.content, &settings.chunking);
+        let chunks_truncated = chunks.len() > settings.max_chunks_per_memory;
+        chunks.truncate(settings.max_chunks_per_memory);
+
         // Insert memory
         sqlx::query(
-            "INSERT INTO memories (id, vault_id, title, content, source, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
+            "INSERT INTO memories (id, vault_id, title, content, source, encrypted, crypto_version, chunks_truncated, language, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(&memory_id)
-        .bind("default") // For now, use a default vault
-        .bind(&entry.title)
-        .bind(&entry.content)
+        .bind(DEFAULT_VAULT_ID) // For now, use a default vault
+        .bind(Self::seal_title_static(cipher.as_ref(), encrypted, entry.title.as_deref())?)
+        .bind(Self::seal_content_static(cipher.as_ref(), encrypted, &entry.content)?)
         .bind(&entry.source)
+        .bind(encrypted)
+        .bind(crypto::CRYPTO_VERSION)
+        .bind(chunks_truncated)
+        .bind(&language)
         .bind(now)
         .bind(now)
         .execute(pool)
@@ -45,7 +290,7 @@ impl MemoryManager {
 
         // Add tags
         for tag_name in &entry.tags {
-            let tag_id = Self::ensure_tag_static(&pool, tag_name).await?;
+            let tag_id = Self::ensure_tag_static(pool, tag_name).await?;
             sqlx::query(
                 "INSERT OR IGNORE INTO memory_tags (memory_id, tag_id) VALUES (?, ?)"
             )
@@ -55,378 +300,7851 @@ impl MemoryManager {
             .await?;
         }
 
-        // Create chunks (simplified - in real implementation, use the chunker from core)
-        let chunks = Self::create_chunks_static(&entry.content)?;
-        for (i, chunk) in chunks.iter().enumerate() {
+        // Create chunks
+        let mut conn = pool.acquire().await?;
+        Self::insert_chunks_static(&mut conn, cipher.as_ref(), encrypted, &memory_id, &chunks, now).await?;
+        drop(conn);
+
+        if Self::indexes_terms_static(&settings, encrypted) {
+            let analyzer = Self::keyword_analyzer_static(&settings)?;
+            Self::index_terms_static(pool, analyzer.as_ref(), &memory_id, entry.title.as_deref(), &entry.content).await?;
+        }
+
+        Ok(memory_id)
+    }
+
+    async fn insert_chunks_static(
+        conn: &mut sqlx::SqliteConnection,
+        cipher: Option<&TextCipher>,
+        encrypted: bool,
+        memory_id: &str,
+        chunks: &[chunking::TextChunk],
+        now: chrono::DateTime<Utc>,
+    ) -> Result<Vec<String>> {
+        let mut chunk_ids = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
             let chunk_id = Uuid::new_v4().to_string();
             sqlx::query(
-                "INSERT INTO chunks (id, memory_id, content, start_pos, end_pos, created_at) VALUES (?, ?, ?, ?, ?, ?)"
+                "INSERT INTO chunks (id, memory_id, content, kind, start_pos, end_pos, encrypted, crypto_version, chunker_version, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
             )
             .bind(&chunk_id)
-            .bind(&memory_id)
-            .bind(chunk)
-            .bind((i * 100) as i32) // Simplified position calculation
-            .bind(((i + 1) * 100) as i32)
+            .bind(memory_id)
+            .bind(Self::seal_content_static(cipher, encrypted, &chunk.content)?)
+            .bind(chunk.kind.as_str())
+            .bind(to_sql_int(chunk.span.start, "chunk position")?)
+            .bind(to_sql_int(chunk.span.end, "chunk position")?)
+            .bind(encrypted)
+            .bind(crypto::CRYPTO_VERSION)
+            .bind(chunking::CHUNKER_VERSION)
             .bind(now)
-            .execute(pool)
+            .execute(&mut *conn)
             .await?;
+            chunk_ids.push(chunk_id);
         }
+        Ok(chunk_ids)
+    }
 
-        Ok(memory_id)
+    // Encoded vectors for `chunks`, one per chunk, embedded as one batch
+    async fn encode_chunk_vectors(&self, chunks: &[chunking::TextChunk]) -> Result<Vec<Vec<u8>>> {
+        let texts: Vec<String> = chunks.iter().map(|chunk| chunk.content.clone()).collect();
+        self.embedder
+            .embed(&texts)
+            .await?
+            .iter()
+            .map(|vector| vector_codec::encode(vector))
+            .collect()
     }
 
-    async fn ensure_tag_static(pool: &sqlx::SqlitePool, tag_name: &str) -> Result<String> {
-        // Check if tag exists
-        let existing = sqlx::query("SELECT id FROM tags WHERE name = ?")
-            .bind(tag_name)
-            .fetch_optional(pool)
+    // Swaps a memory's chunks for `chunks`, storing `vectors` (encoded, one
+    // per chunk) as their `model` embeddings
+    async fn replace_chunks_static(
+        conn: &mut sqlx::SqliteConnection,
+        cipher: Option<&TextCipher>,
+        encrypted: bool,
+        memory_id: &str,
+        chunks: &[chunking::TextChunk],
+        vectors: &[Vec<u8>],
+        model: &str,
+    ) -> Result<Vec<String>> {
+        let now = Utc::now();
+        Self::release_shared_chunks_static(conn, memory_id).await?;
+        sqlx::query("DELETE FROM embeddings WHERE chunk_id IN (SELECT id FROM chunks WHERE memory_id = ?)")
+            .bind(memory_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("DELETE FROM chunks WHERE memory_id = ?")
+            .bind(memory_id)
+            .execute(&mut *conn)
             .await?;
 
-        if let Some(row) = existing {
-            Ok(row.get("id"))
-        } else {
-            // Create new tag
-            let tag_id = Uuid::new_v4().to_string();
-            sqlx::query("INSERT INTO tags (id, name, created_at) VALUES (?, ?, ?)")
-                .bind(&tag_id)
-                .bind(tag_name)
-                .bind(Utc::now())
-                .execute(pool)
-                .await?;
-            Ok(tag_id)
+        let chunk_ids = Self::insert_chunks_static(conn, cipher, encrypted, memory_id, chunks, now).await?;
+        for (chunk_id, blob) in chunk_ids.iter().zip(vectors) {
+            sqlx::query(
+                "INSERT INTO embeddings (id, chunk_id, vector, model_name, created_at) VALUES (?, ?, ?, ?, ?)"
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(chunk_id)
+            .bind(blob)
+            .bind(model)
+            .bind(now)
+            .execute(&mut *conn)
+            .await?;
         }
+        Ok(chunk_ids)
     }
 
-    fn create_chunks_static(content: &str) -> Result<Vec<String>> {
-        // Simplified chunking - in real implementation, use the core chunker
-        let words: Vec<&str> = content.split_whitespace().collect();
-        let chunk_size = 50; // words per chunk
-        let mut chunks = Vec::new();
-        
-        for chunk in words.chunks(chunk_size) {
-            chunks.push(chunk.join(" "));
+    fn sanitize_entry_static(entry: &mut MemoryEntry, settings: &MemorySettings) {
+        entry.content = sanitize::sanitize(&entry.content, &settings.sanitization);
+        if let Some(title) = entry.title.as_mut() {
+            *title = sanitize::sanitize(title, &settings.sanitization);
         }
-        
-        Ok(chunks)
     }
 
-    pub async fn query_memory(&mut self, request: QueryRequest) -> Result<QueryResult> {
-        let db = self.get_db().await?;
-        let pool = db.get_pool().await;
-        
-        // Simplified query - in real implementation, use vector search
-        let limit = request.limit.unwrap_or(10);
-        
-        let rows = sqlx::query(
-            "SELECT m.id, m.title, m.content, m.source, c.content as chunk_content 
-             FROM memories m 
-             JOIN chunks c ON m.id = c.memory_id 
-             WHERE m.content LIKE ? OR c.content LIKE ?
-             ORDER BY m.updated_at DESC 
-             LIMIT ?"
+    // First non-empty line with any heading marker removed, cut to
+    // TITLE_WORDS words
+    fn derive_title_static(content: &str) -> Option<String> {
+        let line = content
+            .lines()
+            .map(|line| line.trim().trim_start_matches('#').trim())
+            .find(|line| !line.is_empty() && !line.starts_with("```"))?;
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let mut title = words[..words.len().min(TITLE_WORDS)].join(" ");
+        if words.len() > TITLE_WORDS {
+            title.push('…');
+        }
+        if title.chars().count() > validation::MAX_TITLE_CHARS {
+            title = title.chars().take(validation::MAX_TITLE_CHARS - 1).collect();
+            title.push('…');
+        }
+        Some(title)
+    }
+
+    fn check_tag_limit_static(settings: &MemorySettings, tag_count: usize) -> Result<()> {
+        if tag_count > settings.max_tags_per_memory {
+            return Err(anyhow::anyhow!(
+                "Too many tags: {} (max_tags_per_memory is {})",
+                tag_count,
+                settings.max_tags_per_memory
+            ));
+        }
+        Ok(())
+    }
+
+    async fn vault_encrypted_static(pool: &sqlx::SqlitePool) -> Result<bool> {
+        let encrypted: Option<bool> = sqlx::query_scalar(
+            "SELECT encrypted_key IS NOT NULL FROM vaults WHERE id = ?"
         )
-        .bind(&format!("%{}%", request.query))
-        .bind(&format!("%{}%", request.query))
-        .bind(limit as i64)
-        .fetch_all(pool)
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_optional(pool)
         .await?;
 
-        let mut citations = Vec::new();
-        let mut answer_parts = Vec::new();
-
-        for row in rows {
-            let memory_id: String = row.get("id");
-            let title: Option<String> = row.get("title");
-            let content: String = row.get("content");
-            let source: Option<String> = row.get("source");
-            let chunk_content: String = row.get("chunk_content");
+        Ok(encrypted.unwrap_or(false))
+    }
 
-            answer_parts.push(chunk_content.clone());
+    fn seal_content_static(cipher: Option<&TextCipher>, encrypted: bool, content: &str) -> Result<String> {
+        if !encrypted {
+            return Ok(content.to_string());
+        }
+        let cipher = cipher.ok_or_else(|| anyhow::anyhow!("Vault is locked"))?;
+        cipher.encrypt_text(content)
+    }
 
-            if request.include_citations {
-                citations.push(Citation {
-                    id: memory_id.clone(),
-                    title,
-                    content: chunk_content,
-                    relevance_score: 0.8, // Simplified scoring
-                    source,
-                });
-            }
+    fn open_content_static(cipher: Option<&TextCipher>, content: String, encrypted: bool) -> Result<String> {
+        if !encrypted {
+            return Ok(content);
         }
+        let cipher = cipher.ok_or_else(|| anyhow::anyhow!("Vault is locked"))?;
+        cipher.decrypt_text(&content)
+    }
 
-        let answer = answer_parts.join("\n\n");
-        let confidence = if citations.is_empty() { 0.0 } else { 0.8 };
+    // The OpenAI API key is sealed like memory content in an encrypted vault.
+    // get_settings hands it back sealed, so update_settings may receive
+    // either form; a sealed key is always in the version 2 format.
+    fn seal_api_key_static(cipher: Option<&TextCipher>, encrypted: bool, key: Option<String>) -> Result<Option<String>> {
+        key.map(|key| Self::seal_content_static(cipher, encrypted, &key)).transpose()
+    }
 
-        Ok(QueryResult {
-            answer,
-            citations,
-            confidence,
-            processing_time_ms: 100, // Simplified timing
+    fn open_api_key_static(cipher: Option<&TextCipher>, key: Option<String>) -> Result<Option<String>> {
+        key.map(|key| {
+            let sealed = crypto::is_v2(&key);
+            Self::open_content_static(cipher, key, sealed)
         })
+        .transpose()
     }
 
-    pub async fn search_memories(
-        &mut self,
-        query: String,
-        limit: Option<usize>,
-        tags: Option<Vec<String>>,
-    ) -> Result<Vec<MemoryEntry>> {
-        let db = self.get_db().await?;
-        let pool = db.get_pool().await;
-        let limit = limit.unwrap_or(20) as i64;
+    // Titles are sealed along with the content of encrypted memories
+    fn seal_title_static(cipher: Option<&TextCipher>, encrypted: bool, title: Option<&str>) -> Result<Option<String>> {
+        title.map(|title| Self::seal_content_static(cipher, encrypted, title)).transpose()
+    }
 
-        let mut memories = Vec::new();
+    // Encrypted memories written before titles were sealed still have a
+    // plaintext title, which migrate_crypto_format seals. Sealed titles are
+    // always in the version 2 format, so those are told apart by that.
+    fn open_title_static(cipher: Option<&TextCipher>, title: Option<String>, encrypted: bool) -> Result<Option<String>> {
+        title
+            .map(|title| {
+                let sealed = encrypted && crypto::is_v2(&title);
+                Self::open_content_static(cipher, title, sealed)
+            })
+            .transpose()
+    }
 
-        if let Some(tag_names) = tags {
-            // Search by tags
-            let placeholders = tag_names.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-            let query_sql = format!(
-                "SELECT DISTINCT m.id, m.title, m.content, m.source, m.created_at, m.updated_at
-                 FROM memories m
-                 JOIN memory_tags mt ON m.id = mt.memory_id
-                 JOIN tags t ON mt.tag_id = t.id
-                 WHERE t.name IN ({})
-                 ORDER BY m.updated_at DESC
-                 LIMIT ?",
-                placeholders
-            );
+    fn keyword_analyzer_static(settings: &MemorySettings) -> Result<Option<TextAnalyzer>> {
+        if settings.keyword_analysis.enabled {
+            Ok(Some(TextAnalyzer::new(&settings.keyword_analysis)?))
+        } else {
+            Ok(None)
+        }
+    }
 
-            let mut query_builder = sqlx::query(&query_sql);
-            for tag_name in &tag_names {
-                query_builder = query_builder.bind(tag_name);
-            }
-            query_builder = query_builder.bind(limit);
+    async fn index_terms_static(
+        pool: &sqlx::SqlitePool,
+        analyzer: Option<&TextAnalyzer>,
+        memory_id: &str,
+        title: Option<&str>,
+        content: &str,
+    ) -> Result<()> {
+        sqlx::query("DELETE FROM memory_terms WHERE memory_id = ?")
+            .bind(memory_id)
+            .execute(pool)
+            .await?;
 
-            let rows = query_builder.fetch_all(pool).await?;
+        if let Some(analyzer) = analyzer {
+            let mut terms = analyzer.terms(title.unwrap_or_default());
+            terms.extend(analyzer.terms(content));
 
-            for row in rows {
-                let memory_id: String = row.get("id");
-                let tags = Self::get_memory_tags_static(pool, &memory_id).await?;
-                
-                memories.push(MemoryEntry {
-                    id: Some(memory_id),
-                    title: row.get("title"),
-                    content: row.get("content"),
-                    source: row.get("source"),
-                    tags,
-                    created_at: Some(row.get::<chrono::DateTime<Utc>, _>("created_at").to_rfc3339()),
-                    updated_at: Some(row.get::<chrono::DateTime<Utc>, _>("updated_at").to_rfc3339()),
-                });
-            }
-        } else {
-            // Search by content
-            let rows = sqlx::query(
-                "SELECT id, title, content, source, created_at, updated_at
-                 FROM memories
-                 WHERE content LIKE ?
-                 ORDER BY updated_at DESC
-                 LIMIT ?"
-            )
-            .bind(&format!("%{}%", query))
-            .bind(limit)
+            sqlx::query("INSERT INTO memory_terms (memory_id, terms) VALUES (?, ?)")
+                .bind(memory_id)
+                .bind(terms.join(" "))
+                .execute(pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // Analyzed terms are plaintext, so encrypted memories are only indexed
+    // when the settings opt in
+    fn indexes_terms_static(settings: &MemorySettings, encrypted: bool) -> bool {
+        !encrypted || settings.index_encrypted_vaults
+    }
+
+    async fn reindex_terms_static(
+        pool: &sqlx::SqlitePool,
+        settings: &MemorySettings,
+        cipher: Option<&TextCipher>,
+    ) -> Result<u64> {
+        let analyzer = Self::keyword_analyzer_static(settings)?;
+
+        if !settings.index_encrypted_vaults {
+            sqlx::query("DELETE FROM memory_terms WHERE memory_id IN (SELECT id FROM memories WHERE vault_id = ? AND encrypted = 1)")
+                .bind(DEFAULT_VAULT_ID)
+                .execute(pool)
+                .await?;
+        }
+
+        let rows = sqlx::query("SELECT id, title, content, encrypted FROM memories WHERE vault_id = ? AND (encrypted = 0 OR ?)")
+            .bind(DEFAULT_VAULT_ID)
+            .bind(settings.index_encrypted_vaults)
             .fetch_all(pool)
             .await?;
 
-            for row in rows {
-                let memory_id: String = row.get("id");
-                let tags = Self::get_memory_tags_static(pool, &memory_id).await?;
-                
-                memories.push(MemoryEntry {
-                    id: Some(memory_id),
-                    title: row.get("title"),
-                    content: row.get("content"),
-                    source: row.get("source"),
-                    tags,
-                    created_at: Some(row.get::<chrono::DateTime<Utc>, _>("created_at").to_rfc3339()),
-                    updated_at: Some(row.get::<chrono::DateTime<Utc>, _>("updated_at").to_rfc3339()),
-                });
-            }
+        for row in &rows {
+            let memory_id: String = row.get("id");
+            let title = Self::open_title_static(cipher, row.get("title"), row.get("encrypted"))?;
+            let content = Self::open_content_static(cipher, row.get("content"), row.get("encrypted"))?;
+            Self::index_terms_static(pool, analyzer.as_ref(), &memory_id, title.as_deref(), &content).await?;
         }
 
-        Ok(memories)
+        Ok(rows.len() as u64)
     }
 
-    async fn get_memory_tags_static(pool: &sqlx::SqlitePool, memory_id: &str) -> Result<Vec<String>> {
-        let rows = sqlx::query(
-            "SELECT t.name FROM tags t
-             JOIN memory_tags mt ON t.id = mt.tag_id
-             WHERE mt.memory_id = ?"
-        )
-        .bind(memory_id)
-        .fetch_all(pool)
-        .await?;
+    pub async fn capture_memory(&mut self, content: String, source_hint: Option<String>) -> Result<String> {
+        let content = content.trim().to_string();
+        if content.is_empty() {
+            return Err(anyhow::anyhow!("Nothing to capture"));
+        }
 
-        Ok(rows.into_iter().map(|row| row.get("name")).collect())
-    }
+        let (tags, source, title) = match Self::detect_capture_kind_static(&content) {
+            CaptureKind::Link => (vec!["link".to_string()], Some(content.clone()), Some(content.clone())),
+            CaptureKind::Code => (vec!["code".to_string()], source_hint, None),
+            CaptureKind::Prose => (Vec::new(), source_hint, None),
+        };
 
-    pub async fn get_stats(&mut self) -> Result<MemoryStats> {
-        let db = self.get_db().await?;
-        let pool = db.get_pool().await;
+        self.add_memory(MemoryEntry {
+            id: None,
+            content,
+            title,
+            tags,
+            source: source.or_else(|| Some("clipboard".to_string())),
+            is_pinned: false,
+            created_at: None,
+            updated_at: None,
+        })
+        .await
+    }
 
-        let memory_count: i64 = sqlx::query("SELECT COUNT(*) FROM memories")
-            .fetch_one(pool)
-            .await?
-            .get(0);
+    fn detect_capture_kind_static(content: &str) -> CaptureKind {
+        let is_url = !content.contains(char::is_whitespace)
+            && (content.starts_with("http://") || content.starts_with("https://"))
+            && content.len() > "https://".len();
+        if is_url {
+            return CaptureKind::Link;
+        }
 
-        let chunk_count: i64 = sqlx::query("SELECT COUNT(*) FROM chunks")
-            .fetch_one(pool)
-            .await?
-            .get(0);
+        if content.contains("```") || content.contains("~~~") {
+            return CaptureKind::Code;
+        }
 
-        let embedding_count: i64 = sqlx::query("SELECT COUNT(*) FROM embeddings")
-            .fetch_one(pool)
-            .await?
-            .get(0);
+        // Unfenced code: most lines end like statements or blocks
+        let lines: Vec<&str> = content.lines().map(str::trim_end).filter(|l| !l.is_empty()).collect();
+        let code_like = lines
+            .iter()
+            .filter(|l| l.ends_with(';') || l.ends_with('{') || l.ends_with('}') || l.ends_with(')'))
+            .count();
+        if lines.len() >= 2 && code_like * 2 > lines.len() {
+            return CaptureKind::Code;
+        }
 
-        // Simplified storage calculation
-        let storage_size = (memory_count * 1000 + chunk_count * 500) as u64;
+        CaptureKind::Prose
+    }
 
-        Ok(MemoryStats {
-            total_memories: memory_count as u64,
-            total_chunks: chunk_count as u64,
-            total_embeddings: embedding_count as u64,
-            storage_size_bytes: storage_size,
-            last_updated: Utc::now().to_rfc3339(),
-        })
+    // LIKE pattern matching every descendant of a tag path
+    fn tag_descendant_pattern_static(tag_name: &str) -> String {
+        let escaped = tag_name
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        format!("{}/%", escaped)
     }
 
-    pub async fn delete_memory(&mut self, id: String) -> Result<()> {
-        let db = self.get_db().await?;
-        let pool = db.get_pool().await;
+    async fn ensure_tag_static(pool: &sqlx::SqlitePool, tag_name: &str) -> Result<String> {
+        let tag_name = &validation::normalize_tag(tag_name);
+        if tag_name.is_empty() {
+            return Err(anyhow::anyhow!("Tag name cannot be empty"));
+        }
 
-        // Delete associated chunks and citations first
-        sqlx::query("DELETE FROM citations WHERE memory_id = ?")
-            .bind(&id)
-            .execute(pool)
-            .await?;
+        // A single upsert so concurrent inserts of the same new tag cannot
+        // race between a lookup and an insert; the no-op update makes
+        // RETURNING yield the existing row's id on conflict
+        let tag_id: String = sqlx::query_scalar(
+            "INSERT INTO tags (id, name, created_at) VALUES (?, ?, ?)
+             ON CONFLICT(name) DO UPDATE SET name = excluded.name
+             RETURNING id"
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(tag_name)
+        .bind(Utc::now())
+        .fetch_one(pool)
+        .await?;
 
-        sqlx::query("DELETE FROM chunks WHERE memory_id = ?")
-            .bind(&id)
-            .execute(pool)
-            .await?;
+        Ok(tag_id)
+    }
 
-        sqlx::query("DELETE FROM memory_tags WHERE memory_id = ?")
-            .bind(&id)
-            .execute(pool)
-            .await?;
+    fn citation_content_static(mode: &CitationContentMode, content: &str, term: &str) -> String {
+        match mode {
+            CitationContentMode::FullChunk => content.to_string(),
+            CitationContentMode::Snippet { window } => Self::create_snippet_static(content, term, *window),
+        }
+    }
 
-        // Delete memory
-        sqlx::query("DELETE FROM memories WHERE id = ?")
-            .bind(&id)
-            .execute(pool)
-            .await?;
+    fn create_snippet_static(content: &str, term: &str, window: usize) -> String {
+        let chars: Vec<char> = content.chars().collect();
+        if chars.len() <= window {
+            return content.to_string();
+        }
 
-        Ok(())
+        // Case-insensitive search for the first occurrence of the term
+        let needle: Vec<char> = term.chars().flat_map(|c| c.to_lowercase()).collect();
+        let position = if needle.is_empty() {
+            None
+        } else {
+            (0..=chars.len().saturating_sub(needle.len())).find(|&i| {
+                chars[i..i + needle.len()]
+                    .iter()
+                    .flat_map(|c| c.to_lowercase())
+                    .eq(needle.iter().copied())
+            })
+        };
+
+        // Center the window on the match, clamped to the content bounds
+        let start = match position {
+            Some(pos) => (pos + needle.len() / 2)
+                .saturating_sub(window / 2)
+                .min(chars.len() - window),
+            None => 0,
+        };
+
+        chars[start..start + window].iter().collect()
     }
 
-    pub async fn update_memory(&mut self, id: String, entry: MemoryEntry) -> Result<()> {
+    pub async fn get_settings(&mut self) -> Result<MemorySettings> {
         let db = self.get_db().await?;
         let pool = db.get_pool().await;
-        let now = Utc::now();
+        MemorySettings::load(pool, DEFAULT_VAULT_ID).await
+    }
 
-        // Update memory
-        sqlx::query(
-            "UPDATE memories SET title = ?, content = ?, source = ?, updated_at = ? WHERE id = ?"
-        )
-        .bind(&entry.title)
-        .bind(&entry.content)
-        .bind(&entry.source)
-        .bind(now)
-        .bind(&id)
-        .execute(pool)
-        .await?;
+    pub async fn update_settings(&mut self, mut settings: MemorySettings) -> Result<()> {
+        if let CitationContentMode::Snippet { window: 0 } = settings.citation_content {
+            return Err(anyhow::anyhow!("Snippet window must be greater than zero"));
+        }
 
-        // Update tags
-        sqlx::query("DELETE FROM memory_tags WHERE memory_id = ?")
-            .bind(&id)
-            .execute(pool)
-            .await?;
+        // Fail early on an unsupported language
+        Self::keyword_analyzer_static(&settings)?;
+        if settings.compaction.interval_minutes == 0 {
+            return Err(anyhow::anyhow!("Compaction interval must be greater than zero"));
+        }
+        if settings.max_chunks_per_memory == 0 {
+            return Err(anyhow::anyhow!("Max chunks per memory must be greater than zero"));
+        }
+        if settings.max_tags_per_memory == 0 {
+            return Err(anyhow::anyhow!("Max tags per memory must be greater than zero"));
+        }
+        if settings.query_cache.capacity == 0 {
+            return Err(anyhow::anyhow!("Query cache capacity must be greater than zero"));
+        }
+        if settings.embedding_sync.min_batch_size == 0 || settings.embedding_sync.min_batch_size > settings.embedding_sync.max_batch_size {
+            return Err(anyhow::anyhow!("Embedding sync batch sizes must satisfy 0 < min <= max"));
+        }
+        if settings.embedding_sync.memory_limit_mb == 0 {
+            return Err(anyhow::anyhow!("Embedding sync memory limit must be greater than zero"));
+        }
+        if settings.chunking.overlap_chars >= settings.chunking.target_chars {
+            return Err(anyhow::anyhow!("Chunk overlap must be smaller than the chunk target size"));
+        }
+        // Fail early on an unusable backend, e.g. OpenAI without a key
+        let cipher = self.text_cipher();
+        let mut provider_settings = settings.embedding_provider.clone();
+        provider_settings.openai_api_key = Self::open_api_key_static(cipher.as_ref(), provider_settings.openai_api_key)?;
+        let provider = embeddings::provider_from_settings(&provider_settings)?;
 
-        for tag_name in &entry.tags {
-            let tag_id = Self::ensure_tag_static(&pool, tag_name).await?;
-            sqlx::query("INSERT INTO memory_tags (memory_id, tag_id) VALUES (?, ?)")
-                .bind(&id)
-                .bind(&tag_id)
-                .execute(pool)
-                .await?;
+        // Stored normalized so add_memory can compare them directly
+        let mut default_tags: Vec<String> = Vec::new();
+        for tag in &settings.default_tags {
+            let normalized = validation::normalize_tag(tag);
+            if normalized.is_empty() {
+                return Err(anyhow::anyhow!("Default tag '{}' is empty", tag));
+            }
+            if !default_tags.contains(&normalized) {
+                default_tags.push(normalized);
+            }
+        }
+        settings.default_tags = default_tags;
+
+        let _write = maintenance::begin_write()?;
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let previous = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+        let encrypted = Self::vault_encrypted_static(pool).await?;
+
+        // Indexing an encrypted vault needs its plaintext, so check the vault
+        // is unlocked before saving anything
+        if settings.index_encrypted_vaults
+            && !previous.index_encrypted_vaults
+            && cipher.is_none()
+            && encrypted
+        {
+            return Err(anyhow::anyhow!("Vault is locked"));
         }
+        settings.embedding_provider.openai_api_key =
+            Self::seal_api_key_static(cipher.as_ref(), encrypted, provider_settings.openai_api_key.clone())?;
+        settings.save(pool, DEFAULT_VAULT_ID).await?;
+
+        if previous.keyword_analysis != settings.keyword_analysis
+            || previous.index_encrypted_vaults != settings.index_encrypted_vaults
+        {
+            Self::reindex_terms_static(pool, &settings, cipher.as_ref()).await?;
+        }
+        // Sealing the same key twice gives different text, so compare keys
+        // once opened
+        let mut previous_provider = previous.embedding_provider;
+        previous_provider.openai_api_key = Self::open_api_key_static(cipher.as_ref(), previous_provider.openai_api_key)?;
+        if previous_provider != provider_settings {
+            self.embedder = provider;
+        }
+
+        Ok(())
+    }
+
+    pub async fn query_memory(&mut self, request: QueryRequest) -> Result<QueryResult> {
+        self.query_memory_observed(request, &mut |_| {}).await
+    }
 
+    // Same query as query_memory, reported as it runs: one event per
+    // citation in relevance order, then a completion event carrying the
+    // answer and final confidence. Citations are always included.
+    pub async fn query_memory_streaming(
+        &mut self,
+        mut request: QueryRequest,
+        stream_id: String,
+        mut on_event: impl FnMut(QueryStreamEvent),
+    ) -> Result<()> {
+        request.include_citations = true;
+        let mut rank = 0;
+        let result = {
+            let mut on_citation = |citation: &Citation| {
+                rank += 1;
+                on_event(QueryStreamEvent::Citation {
+                    stream_id: stream_id.clone(),
+                    rank,
+                    citation: citation.clone(),
+                });
+            };
+            self.query_memory_observed(request, &mut on_citation).await?
+        };
+        on_event(QueryStreamEvent::Complete {
+            stream_id,
+            answer: result.answer,
+            confidence: result.confidence,
+            citation_count: result.citations.len(),
+        });
         Ok(())
     }
 
-    pub async fn get_citations(&mut self, memory_id: String) -> Result<Vec<Citation>> {
+    // `on_citation` sees each citation as soon as it is built; rows arrive in
+    // relevance order, so that is also the order of the final result
+    async fn query_memory_observed(&mut self, request: QueryRequest, on_citation: &mut dyn FnMut(&Citation)) -> Result<QueryResult> {
+        let cipher = self.text_cipher();
+        let embedder = Arc::clone(&self.embedder);
         let db = self.get_db().await?;
         let pool = db.get_pool().await;
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
 
-        let rows = sqlx::query(
-            "SELECT c.id, m.title, c.content, c.relevance_score, m.source
-             FROM citations c
-             JOIN chunks ch ON c.chunk_id = ch.id
+        let cache_key = query_cache::key(DEFAULT_VAULT_ID, cipher.is_some(), &request)?;
+        let generation = maintenance::write_generation();
+        if settings.query_cache.enabled {
+            let ttl = std::time::Duration::from_secs(settings.query_cache.ttl_seconds);
+            if let Some((result, result_count)) = query_cache::get(&cache_key, generation, ttl) {
+                if settings.record_query_history {
+                    Self::record_query_static(pool, &request.query, result_count).await?;
+                }
+                for citation in &result.citations {
+                    on_citation(citation);
+                }
+                return Ok(result);
+            }
+        }
+        
+        let limit = request.limit.unwrap_or(10);
+
+        // Chunks ranked by similarity to the query; a blank query has
+        // nothing to embed
+        let ranked = if request.query.trim().is_empty() {
+            None
+        } else {
+            let query_vector = embedder.embed_one(&request.query).await?;
+            Self::rank_chunks_static(pool, embedder.model_name(), &query_vector).await?
+        };
+
+        let rows = match ranked {
+            Some(mut ranked) => {
+                ranked.truncate(limit);
+                let mut rows = Self::chunk_rows_by_score_static(pool, &ranked).await?;
+                // Chunks added since the last sync have no vector yet, so
+                // they can only match by keyword. Having no similarity to
+                // show, they fill whatever room the semantic hits leave.
+                let room = limit.saturating_sub(rows.len());
+                let unembedded = Self::keyword_rows_static(pool, cipher.as_ref(), &request.query, Some(embedder.model_name()), room).await?;
+                rows.extend(unembedded.into_iter().map(|row| (row, UNEMBEDDED_MATCH_SCORE)));
+                rows
+            }
+            None => Self::keyword_rows_static(pool, cipher.as_ref(), &request.query, None, limit)
+                .await?
+                .into_iter()
+                .map(|row| (row, KEYWORD_MATCH_SCORE))
+                .collect(),
+        };
+        // The best match decides how sure the answer is
+        let confidence = rows.iter().map(|(_, score)| score.clamp(0.0, 1.0)).fold(0.0, f32::max);
+
+        let mut citations = Vec::new();
+        // Chunk text, its citation marker if citations are returned, and
+        // its relevance
+        let mut answer_parts: Vec<(String, Option<usize>, f32)> = Vec::new();
+        let mut memory_hits: Vec<MemoryHit> = Vec::new();
+        // Position in `memory_hits` and score of the best chunk so far
+        let mut hit_index: HashMap<String, (usize, f32)> = HashMap::new();
+
+        let result_count = rows.len();
+        for (row, relevance_score) in rows {
+            let memory_id: String = row.get("id");
+            let title = Self::open_title_static(cipher.as_ref(), row.get("title"), row.get("memory_encrypted"))?;
+            let source: Option<String> = row.get("source");
+            let chunk_content = Self::open_content_static(cipher.as_ref(), row.get("chunk_content"), row.get("encrypted"))?;
+
+            if request.answer {
+                // 1-based position of the citation pushed below for this chunk
+                let marker = request.include_citations.then(|| citations.len() + 1);
+                answer_parts.push((chunk_content.clone(), marker, relevance_score));
+            }
+
+            if request.group_by_memory {
+                let snippet = Self::citation_content_static(&settings.citation_content, &chunk_content, &request.query);
+                match hit_index.get_mut(&memory_id) {
+                    Some((i, best_score)) => {
+                        let hit = &mut memory_hits[*i];
+                        hit.matching_chunks += 1;
+                        // Keep the snippet of the best chunk; earlier chunks win ties
+                        if relevance_score > *best_score {
+                            *best_score = relevance_score;
+                            hit.snippet = snippet;
+                        }
+                        hit.relevance_score = match request.score_aggregate {
+                            ScoreAggregate::Max => hit.relevance_score.max(relevance_score),
+                            ScoreAggregate::Sum => hit.relevance_score + relevance_score,
+                        };
+                    }
+                    None => {
+                        hit_index.insert(memory_id.clone(), (memory_hits.len(), relevance_score));
+                        memory_hits.push(MemoryHit {
+                            memory_id: memory_id.clone(),
+                            title: title.clone(),
+                            source: source.clone(),
+                            snippet,
+                            relevance_score,
+                            matching_chunks: 1,
+                        });
+                    }
+                }
+            }
+
+            if request.include_citations {
+                let citation = Citation {
+                    id: memory_id.clone(),
+                    title,
+                    content: Self::citation_content_static(&settings.citation_content, &chunk_content, &request.query),
+                    relevance_score,
+                    source,
+                };
+                on_citation(&citation);
+                citations.push(citation);
+            }
+        }
+
+        memory_hits.sort_by(|a, b| b.relevance_score.total_cmp(&a.relevance_score));
+
+        if settings.record_query_history {
+            Self::record_query_static(pool, &request.query, result_count).await?;
+        }
+
+        // Stable, so equally relevant chunks keep their row order
+        answer_parts.sort_by(|a, b| b.2.total_cmp(&a.2));
+        answer_parts.truncate(request.answer_chunk_count.unwrap_or(usize::MAX));
+        let answer = Self::compose_answer_static(
+            answer_parts.into_iter().map(|(text, marker, _)| (text, marker)).collect(),
+            request.max_answer_chars,
+        );
+
+        let result = QueryResult {
+            answer,
+            citations,
+            confidence,
+            processing_time_ms: 100, // Simplified timing
+            memory_hits,
+        };
+        if settings.query_cache.enabled {
+            query_cache::insert(cache_key, generation, &result, result_count, settings.query_cache.capacity);
+        }
+        Ok(result)
+    }
+
+    // Chunks whose memory or chunk text contains `query`, ignoring ASCII
+    // case, newest memory first. With `unembedded_for`, only chunks that
+    // have no vector for that model. A blank query matches nothing.
+    async fn keyword_rows_static(
+        pool: &sqlx::SqlitePool,
+        cipher: Option<&TextCipher>,
+        query: &str,
+        unembedded_for: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<sqlx::sqlite::SqliteRow>> {
+        if query.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let escaped = query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        let pattern = format!("%{}%", escaped);
+        let unembedded = if unembedded_for.is_some() {
+            "AND NOT EXISTS (SELECT 1 FROM embeddings e
+                             WHERE e.chunk_id = COALESCE(c.embedding_chunk_id, c.id) AND e.model_name = ?)"
+        } else {
+            ""
+        };
+        // LIKE would only see ciphertext, so sealed rows are all fetched
+        // and matched below once opened
+        let encrypted = Self::vault_encrypted_static(pool).await?;
+        let sql = format!(
+            "SELECT m.id, m.title, m.content AS memory_content, m.encrypted AS memory_encrypted, m.source,
+                    c.content as chunk_content, c.encrypted
+             FROM memories m
+             JOIN chunks c ON m.id = c.memory_id
+             WHERE m.vault_id = ?
+               AND (m.encrypted = 1 OR c.encrypted = 1
+                    OR m.content LIKE ? ESCAPE '\\' OR c.content LIKE ? ESCAPE '\\')
+               AND {} {}
+             ORDER BY m.updated_at DESC, m.id, c.start_pos, c.id
+             LIMIT ?",
+            NOT_EXPIRED, unembedded
+        );
+        let mut rows = sqlx::query(&sql).bind(DEFAULT_VAULT_ID).bind(&pattern).bind(&pattern);
+        if let Some(model) = unembedded_for {
+            rows = rows.bind(model);
+        }
+        let sql_limit = if encrypted { -1 } else { to_sql_int(limit, "limit")? };
+        let rows = rows.bind(sql_limit).fetch_all(pool).await?;
+
+        let needle = query.to_ascii_lowercase();
+        let contains = |text: &str| text.to_ascii_lowercase().contains(&needle);
+        // Whether each sealed memory's own text matches, opened once
+        let mut memory_matches: HashMap<String, bool> = HashMap::new();
+        let mut matched = Vec::new();
+        for row in rows {
+            if matched.len() == limit {
+                break;
+            }
+            let memory_encrypted: bool = row.get("memory_encrypted");
+            let chunk_encrypted: bool = row.get("encrypted");
+            if memory_encrypted || chunk_encrypted {
+                let memory_id: String = row.get("id");
+                let memory_match = match memory_matches.get(&memory_id) {
+                    Some(&found) => found,
+                    None => {
+                        let content = Self::open_content_static(cipher, row.get("memory_content"), memory_encrypted)?;
+                        let found = contains(&content);
+                        memory_matches.insert(memory_id, found);
+                        found
+                    }
+                };
+                if !memory_match
+                    && !contains(&Self::open_content_static(cipher, row.get("chunk_content"), chunk_encrypted)?)
+                {
+                    continue;
+                }
+            }
+            matched.push(row);
+        }
+        Ok(matched)
+    }
+
+    // Unexpired chunks in the vault with a vector similar to `query`, best
+    // first; ties go to the lower chunk id. Chunks pointing away from the
+    // query are not matches. None when no chunk has a `model` vector yet.
+    async fn rank_chunks_static(pool: &sqlx::SqlitePool, model: &str, query: &[f32]) -> Result<Option<Vec<(String, f32)>>> {
+        let rows = sqlx::query(&format!(
+            "SELECT c.id, e.vector
+             FROM chunks c
              JOIN memories m ON c.memory_id = m.id
-             WHERE c.memory_id = ?
-             ORDER BY c.relevance_score DESC"
-        )
-        .bind(&memory_id)
+             JOIN embeddings e ON e.chunk_id = COALESCE(c.embedding_chunk_id, c.id)
+             WHERE m.vault_id = ? AND e.model_name = ? AND {}",
+            NOT_EXPIRED
+        ))
+        .bind(DEFAULT_VAULT_ID)
+        .bind(model)
         .fetch_all(pool)
         .await?;
+        if rows.is_empty() {
+            return Ok(None);
+        }
 
-        let mut citations = Vec::new();
+        let mut ranked = Vec::new();
+        for row in &rows {
+            let chunk_id: String = row.get("id");
+            let vector = vector_codec::decode(&row.get::<Vec<u8>, _>("vector"))
+                .map_err(|e| anyhow::anyhow!("Bad embedding for chunk {}: {}", chunk_id, e))?;
+            let score = embeddings::cosine_similarity(query, &vector);
+            if score > 0.0 {
+                ranked.push((chunk_id, score));
+            }
+        }
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(Some(ranked))
+    }
+
+    // Citation rows for `ranked` chunks, paired with their scores and kept
+    // in the same order
+    async fn chunk_rows_by_score_static(
+        pool: &sqlx::SqlitePool,
+        ranked: &[(String, f32)],
+    ) -> Result<Vec<(sqlx::sqlite::SqliteRow, f32)>> {
+        let mut rows: HashMap<String, sqlx::sqlite::SqliteRow> = HashMap::with_capacity(ranked.len());
+        // Batched to stay under SQLite's bound parameter limit
+        for batch in ranked.chunks(SCAN_BATCH_SIZE) {
+            let placeholders = vec!["?"; batch.len()].join(", ");
+            let sql = format!(
+                "SELECT c.id AS chunk_id, m.id, m.title, m.encrypted AS memory_encrypted, m.source,
+                        c.content as chunk_content, c.encrypted
+                 FROM chunks c
+                 JOIN memories m ON m.id = c.memory_id
+                 WHERE c.id IN ({})",
+                placeholders
+            );
+            let mut query = sqlx::query(&sql);
+            for (chunk_id, _) in batch {
+                query = query.bind(chunk_id);
+            }
+            for row in query.fetch_all(pool).await? {
+                rows.insert(row.get("chunk_id"), row);
+            }
+        }
+
+        Ok(ranked
+            .iter()
+            .filter_map(|(chunk_id, score)| rows.remove(chunk_id).map(|row| (row, *score)))
+            .collect())
+    }
+
+    // Joins answer parts, suffixing each with its `[n]` citation marker. When
+    // capped, truncation happens inside a part's text so a marker is never
+    // cut in half; parts that no longer fit are dropped with their markers.
+    fn compose_answer_static(parts: Vec<(String, Option<usize>)>, max_chars: Option<usize>) -> String {
+        let mut answer = String::new();
+        let mut remaining = max_chars.unwrap_or(usize::MAX);
+
+        for (text, marker) in parts {
+            let separator = if answer.is_empty() { "" } else { "\n\n" };
+            let suffix = marker.map(|n| format!(" [{}]", n)).unwrap_or_default();
+            let needed = separator.len() + text.chars().count() + suffix.chars().count();
+            if needed <= remaining {
+                answer.push_str(separator);
+                answer.push_str(&text);
+                answer.push_str(&suffix);
+                remaining -= needed;
+                continue;
+            }
+
+            // Room for the separator, the ellipsis, the marker and some text
+            let overhead = separator.len() + 1 + suffix.chars().count();
+            if remaining > overhead {
+                answer.push_str(separator);
+                answer.extend(text.chars().take(remaining - overhead));
+                answer.push('…');
+                answer.push_str(&suffix);
+            } else if answer.is_empty() && remaining > 0 {
+                answer.push('…');
+            }
+            break;
+        }
+
+        answer
+    }
+
+    pub async fn search_memories(
+        &mut self,
+        criteria: SearchCriteria,
+        limit: Option<usize>,
+        // Pinned memories ahead of the rest, each group in `criteria.order`
+        pinned_first: bool,
+    ) -> Result<Vec<MemoryEntry>> {
+        self.search_criteria(criteria, limit, pinned_first).await
+    }
+
+    async fn search_criteria(
+        &mut self,
+        criteria: SearchCriteria,
+        limit: Option<usize>,
+        pinned_first: bool,
+    ) -> Result<Vec<MemoryEntry>> {
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+        let limit = to_sql_int(limit.unwrap_or(20), "limit")?;
+
+        let rows = Self::search_rows_static(pool, cipher.as_ref(), &settings, &criteria, pinned_first, limit).await?;
+
+        let mut memories = Vec::new();
         for row in rows {
-            citations.push(Citation {
-                id: row.get("id"),
-                title: row.get("title"),
-                content: row.get("content"),
-                relevance_score: row.get("relevance_score"),
-                source: row.get("source"),
-            });
+            memories.push(Self::memory_from_row_static(pool, &row, cipher.as_ref()).await?);
         }
 
-        Ok(citations)
+        if settings.record_query_history {
+            Self::record_query_static(pool, &criteria.query, memories.len()).await?;
+        }
+
+        Ok(memories)
     }
 
-    pub async fn get_insights(&mut self, period: String) -> Result<serde_json::Value> {
-        // Simplified insights - in real implementation, generate meaningful insights
-        let insights = serde_json::json!({
-            "period": period,
-            "total_memories": 0,
-            "new_memories": 0,
-            "top_tags": [],
-            "memory_trends": [],
-            "generated_at": Utc::now().to_rfc3339()
+    // Builds and runs the search query shared by search_memories and
+    // bulk_tag. Tags take precedence over the text query; a negative limit
+    // returns every match.
+    async fn search_rows_static(
+        pool: &sqlx::SqlitePool,
+        cipher: Option<&TextCipher>,
+        settings: &MemorySettings,
+        criteria: &SearchCriteria,
+        pinned_first: bool,
+        limit: i64,
+    ) -> Result<Vec<sqlx::sqlite::SqliteRow>> {
+        if criteria.order != SearchOrder::TitleAsc || !Self::vault_encrypted_static(pool).await? {
+            return Self::match_rows_static(pool, cipher, settings, criteria, pinned_first, limit).await;
+        }
+
+        // Sealed titles would sort as ciphertext, so every match is ordered
+        // by its decrypted title here instead
+        let rows = Self::match_rows_static(pool, cipher, settings, criteria, pinned_first, -1).await?;
+        let mut titled = rows
+            .into_iter()
+            .map(|row| Ok((Self::open_title_static(cipher, row.get("title"), row.get("encrypted"))?, row)))
+            .collect::<Result<Vec<_>>>()?;
+        let pinned = |row: &sqlx::sqlite::SqliteRow| pinned_first && row.get::<bool, _>("is_pinned");
+        titled.sort_by(|(a_title, a), (b_title, b)| {
+            pinned(b)
+                .cmp(&pinned(a))
+                .then_with(|| match (a_title, b_title) {
+                    (Some(a_title), Some(b_title)) => settings.title_collation.compare(a_title, b_title),
+                    _ => a_title.is_none().cmp(&b_title.is_none()),
+                })
+                .then_with(|| a.get::<String, _>("id").cmp(&b.get::<String, _>("id")))
         });
+        if let Ok(limit) = usize::try_from(limit) {
+            titled.truncate(limit);
+        }
+        Ok(titled.into_iter().map(|(_, row)| row).collect())
+    }
 
-        Ok(insights)
+    async fn match_rows_static(
+        pool: &sqlx::SqlitePool,
+        cipher: Option<&TextCipher>,
+        settings: &MemorySettings,
+        criteria: &SearchCriteria,
+        pinned_first: bool,
+        limit: i64,
+    ) -> Result<Vec<sqlx::sqlite::SqliteRow>> {
+        let language = &criteria.language;
+        let encrypted = Self::vault_encrypted_static(pool).await?;
+        let order_by = Self::search_order_static(settings, criteria.order);
+
+        if let Some(tag_names) = &criteria.tags {
+            // Search by tags; a parent tag also matches its descendants
+            let tag_names: Vec<String> = tag_names.iter().map(|t| validation::normalize_tag(t)).collect();
+            let conditions = tag_names
+                .iter()
+                .map(|_| "(t.name = ? OR t.name LIKE ? ESCAPE '\\')")
+                .collect::<Vec<_>>()
+                .join(" OR ");
+            let query_sql = format!(
+                "SELECT DISTINCT m.id, m.title, m.content, m.source, m.encrypted, m.is_pinned, m.created_at, m.updated_at
+                 FROM memories m
+                 JOIN memory_tags mt ON m.id = mt.memory_id
+                 JOIN tags t ON mt.tag_id = t.id
+                 WHERE ({}) AND (? IS NULL OR m.language = ?) AND {}
+                 ORDER BY CASE WHEN ? THEN m.is_pinned ELSE 0 END DESC, {}
+                 LIMIT ?",
+                conditions, NOT_EXPIRED, order_by
+            );
+
+            let mut query_builder = sqlx::query(&query_sql);
+            for tag_name in &tag_names {
+                query_builder = query_builder
+                    .bind(tag_name)
+                    .bind(Self::tag_descendant_pattern_static(tag_name));
+            }
+            query_builder = query_builder.bind(language).bind(language).bind(pinned_first).bind(limit);
+
+            return Ok(query_builder.fetch_all(pool).await?);
+        }
+
+        if let Some(analyzer) = Self::keyword_analyzer_static(settings)? {
+            // Match analyzed query terms against the analyzed term index
+            let terms = analyzer.terms(&criteria.query);
+            if terms.is_empty() {
+                // Only stopwords; matching on them would return everything
+                return Ok(Vec::new());
+            }
+            if !Self::indexes_terms_static(settings, encrypted) {
+                return Self::scan_decrypted_static(pool, cipher, language, pinned_first, &order_by, limit, |title, content| {
+                    let mut memory_terms: HashSet<String> = analyzer.terms(title.unwrap_or_default()).into_iter().collect();
+                    memory_terms.extend(analyzer.terms(content));
+                    terms.iter().all(|t| memory_terms.contains(t))
+                })
+                .await;
+            }
+            let match_expr = terms
+                .iter()
+                .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            return Ok(sqlx::query(&format!(
+                "SELECT m.id, m.title, m.content, m.source, m.encrypted, m.is_pinned, m.created_at, m.updated_at
+                 FROM memories m
+                 JOIN memory_terms mt ON mt.memory_id = m.id
+                 WHERE memory_terms MATCH ? AND (? IS NULL OR m.language = ?) AND {}
+                 ORDER BY CASE WHEN ? THEN m.is_pinned ELSE 0 END DESC, {}
+                 LIMIT ?",
+                NOT_EXPIRED, order_by
+            ))
+            .bind(match_expr)
+            .bind(language)
+            .bind(language)
+            .bind(pinned_first)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?);
+        }
+
+        // Search by content
+        if encrypted {
+            let query = criteria.query.to_lowercase();
+            return Self::scan_decrypted_static(pool, cipher, language, pinned_first, &order_by, limit, |_, content| {
+                content.to_lowercase().contains(&query)
+            })
+            .await;
+        }
+        Ok(sqlx::query(&format!(
+            "SELECT id, title, content, source, encrypted, is_pinned, created_at, updated_at
+             FROM memories m
+             WHERE content LIKE ? AND (? IS NULL OR language = ?) AND {}
+             ORDER BY CASE WHEN ? THEN is_pinned ELSE 0 END DESC, {}
+             LIMIT ?",
+            NOT_EXPIRED, order_by
+        ))
+        .bind(format!("%{}%", criteria.query))
+        .bind(language)
+        .bind(language)
+        .bind(pinned_first)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?)
     }
 
-    pub async fn export_data(&mut self, format: String) -> Result<String> {
-        // Simplified export - in real implementation, export actual data
-        let export_data = serde_json::json!({
-            "format": format,
-            "exported_at": Utc::now().to_rfc3339(),
-            "data": []
-        });
+    // ORDER BY terms after the pinned-first term, for a `memories m` query
+    fn search_order_static(settings: &MemorySettings, order: SearchOrder) -> String {
+        match order {
+            SearchOrder::Updated => "m.updated_at DESC, m.id".to_string(),
+            SearchOrder::TitleAsc => format!(
+                "m.title IS NULL, m.title COLLATE {}, m.id",
+                settings.title_collation.sql_name()
+            ),
+        }
+    }
+
+    // Decrypt-then-match for encrypted vaults, which have nothing plaintext
+    // to query. Reads the vault in search order and stops once `limit`
+    // memories match.
+    async fn scan_decrypted_static(
+        pool: &sqlx::SqlitePool,
+        cipher: Option<&TextCipher>,
+        language: &Option<String>,
+        pinned_first: bool,
+        order_by: &str,
+        limit: i64,
+        matches: impl Fn(Option<&str>, &str) -> bool,
+    ) -> Result<Vec<sqlx::sqlite::SqliteRow>> {
+        let mut matched = Vec::new();
+        let mut offset = 0;
+
+        loop {
+            let rows = sqlx::query(&format!(
+                "SELECT id, title, content, source, encrypted, is_pinned, created_at, updated_at
+                 FROM memories m
+                 WHERE vault_id = ? AND (? IS NULL OR language = ?) AND {}
+                 ORDER BY CASE WHEN ? THEN is_pinned ELSE 0 END DESC, {}
+                 LIMIT ? OFFSET ?",
+                NOT_EXPIRED, order_by
+            ))
+            .bind(DEFAULT_VAULT_ID)
+            .bind(language)
+            .bind(language)
+            .bind(pinned_first)
+            .bind(to_sql_int(SCAN_BATCH_SIZE, "batch size")?)
+            .bind(to_sql_int(offset, "offset")?)
+            .fetch_all(pool)
+            .await?;
+            let exhausted = rows.len() < SCAN_BATCH_SIZE;
+            offset += rows.len();
+
+            for row in rows {
+                let title = Self::open_title_static(cipher, row.get("title"), row.get("encrypted"))?;
+                let content = Self::open_content_static(cipher, row.get("content"), row.get("encrypted"))?;
+                if matches(title.as_deref(), &content) {
+                    matched.push(row);
+                    if usize::try_from(limit).is_ok_and(|limit| matched.len() >= limit) {
+                        return Ok(matched);
+                    }
+                }
+            }
+            if exhausted {
+                return Ok(matched);
+            }
+        }
+    }
+
+    // Adds or removes one tag on every memory matching the criteria, in a
+    // single transaction. Returns the number of memories whose tags changed.
+    pub async fn bulk_tag(&mut self, criteria: SearchCriteria, tag: String, mode: BulkTagMode) -> Result<u64> {
+        let _write = maintenance::begin_write()?;
+        let tag = validation::normalize_tag(&tag);
+        if tag.is_empty() {
+            return Err(anyhow::anyhow!("Tag name cannot be empty"));
+        }
+
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+
+        let memory_ids: Vec<String> = Self::search_rows_static(pool, cipher.as_ref(), &settings, &criteria, false, -1)
+            .await?
+            .iter()
+            .map(|row| row.get("id"))
+            .collect();
+        if memory_ids.is_empty() {
+            return Ok(0);
+        }
+
+        // The upsert is idempotent, so it can run ahead of the transaction
+        let tag_id = match mode {
+            BulkTagMode::Add => Some(Self::ensure_tag_static(pool, &tag).await?),
+            BulkTagMode::Remove => sqlx::query_scalar("SELECT id FROM tags WHERE name = ?")
+                .bind(&tag)
+                .fetch_optional(pool)
+                .await?,
+        };
+        let Some(tag_id) = tag_id else {
+            return Ok(0);
+        };
+
+        // Checked up front so one memory at the limit leaves every memory as
+        // it was
+        if let BulkTagMode::Add = mode {
+            for memory_id in &memory_ids {
+                let (tag_count, has_tag): (i64, bool) = sqlx::query_as(
+                    "SELECT COUNT(*), COALESCE(SUM(tag_id = ?), 0) FROM memory_tags WHERE memory_id = ?"
+                )
+                .bind(&tag_id)
+                .bind(memory_id)
+                .fetch_one(pool)
+                .await?;
+                if !has_tag {
+                    Self::check_tag_limit_static(&settings, usize::try_from(tag_count)? + 1)
+                        .map_err(|e| anyhow::anyhow!("Memory {}: {}", memory_id, e))?;
+                }
+            }
+        }
+
+        let statement = match mode {
+            BulkTagMode::Add => "INSERT OR IGNORE INTO memory_tags (memory_id, tag_id) VALUES (?, ?)",
+            BulkTagMode::Remove => "DELETE FROM memory_tags WHERE memory_id = ? AND tag_id = ?",
+        };
+
+        let mut tx = pool.begin().await?;
+        let mut affected = 0u64;
+        for memory_id in &memory_ids {
+            affected += sqlx::query(statement)
+                .bind(memory_id)
+                .bind(&tag_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        }
+        tx.commit().await?;
 
-        Ok(export_data.to_string())
+        Ok(affected)
     }
 
-    pub async fn import_data(&mut self, data: String, _format: String) -> Result<()> {
-        // Simplified import - in real implementation, parse and import data
-        let _parsed: serde_json::Value = serde_json::from_str(&data)?;
+    async fn record_query_static(pool: &sqlx::SqlitePool, query: &str, result_count: usize) -> Result<()> {
+        // Searching stays allowed in read-only mode; only the history is lost.
+        // A blank query, e.g. a tag-only search, is contained in every memory
+        // and would hide them all from orphan_memories.
+        if database::is_read_only() || query.trim().is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query(
+            "INSERT INTO query_history (id, vault_id, query, result_count, created_at) VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(DEFAULT_VAULT_ID)
+        .bind(query)
+        .bind(to_sql_int(result_count, "result count")?)
+        .bind(Utc::now())
+        .execute(pool)
+        .await?;
+
         Ok(())
     }
 
-    pub async fn sync_embeddings(&mut self) -> Result<()> {
-        // Simplified sync - in real implementation, generate embeddings for new chunks
+    pub async fn list_query_history(&mut self, limit: Option<usize>) -> Result<Vec<QueryHistoryEntry>> {
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let limit = to_sql_int(limit.unwrap_or(50), "limit")?;
+
+        let rows = sqlx::query(
+            "SELECT id, query, result_count, created_at
+             FROM query_history
+             WHERE vault_id = ?
+             ORDER BY created_at DESC, id
+             LIMIT ?"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        rows
+            .into_iter()
+            .map(|row| {
+                Ok(QueryHistoryEntry {
+                    id: row.get("id"),
+                    query: row.get("query"),
+                    result_count: to_count(row.get("result_count"), "result count")?,
+                    created_at: row.get::<chrono::DateTime<Utc>, _>("created_at").to_rfc3339(),
+                })
+            })
+            .collect()
+    }
+
+    pub async fn clear_query_history(&mut self) -> Result<()> {
+        let _write = maintenance::begin_write()?;
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        sqlx::query("DELETE FROM query_history WHERE vault_id = ?")
+            .bind(DEFAULT_VAULT_ID)
+            .execute(pool)
+            .await?;
+
         Ok(())
     }
 
-    pub async fn get_system_info(&mut self) -> Result<SystemInfo> {
-        Ok(SystemInfo {
-            version: env!("CARGO_PKG_VERSION").to_string(),
-            platform: std::env::consts::OS.to_string(),
-            arch: std::env::consts::ARCH.to_string(),
-            memory_usage: 0, // Simplified
-            disk_usage: 0,   // Simplified
+    // SQLite only enforces foreign keys on connections that turn them on, so
+    // databases written by older builds or external tools can hold junction
+    // rows pointing at nothing. A row missing both ends counts as a missing
+    // memory.
+    pub async fn repair_tag_references(&mut self) -> Result<TagReferenceRepair> {
+        let _write = maintenance::begin_write()?;
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let mut tx = pool.begin().await?;
+
+        let missing_memory_rows = sqlx::query(
+            "DELETE FROM memory_tags WHERE NOT EXISTS (SELECT 1 FROM memories m WHERE m.id = memory_tags.memory_id)"
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        let missing_tag_rows = sqlx::query(
+            "DELETE FROM memory_tags WHERE NOT EXISTS (SELECT 1 FROM tags t WHERE t.id = memory_tags.tag_id)"
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+
+        Ok(TagReferenceRepair {
+            missing_memory_rows,
+            missing_tag_rows,
         })
     }
+
+    // Chunks per chunker version. More than one version, or any version
+    // other than the current one, means rebuild_chunks should be run.
+    pub async fn chunk_schema_report(&mut self) -> Result<ChunkSchemaReport> {
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let rows = sqlx::query(
+            "SELECT c.chunker_version, COUNT(*) AS chunk_count, COUNT(DISTINCT c.memory_id) AS memory_count
+             FROM chunks c
+             JOIN memories m ON c.memory_id = m.id
+             WHERE m.vault_id = ?
+             GROUP BY c.chunker_version
+             ORDER BY c.chunker_version"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_all(pool)
+        .await?;
+        let versions = rows
+            .iter()
+            .map(|row| {
+                Ok(ChunkVersionCount {
+                    version: row.get("chunker_version"),
+                    chunk_count: to_count(row.get("chunk_count"), "chunk count")?,
+                    memory_count: to_count(row.get("memory_count"), "memory count")?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let outdated_memories: i64 = sqlx::query_scalar(
+            "SELECT COUNT(DISTINCT c.memory_id)
+             FROM chunks c
+             JOIN memories m ON c.memory_id = m.id
+             WHERE m.vault_id = ? AND c.chunker_version != ?"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(chunking::CHUNKER_VERSION)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(ChunkSchemaReport {
+            current_version: chunking::CHUNKER_VERSION,
+            mixed: versions.len() > 1,
+            needs_rebuild: outdated_memories > 0,
+            outdated_memories: to_count(outdated_memories, "outdated memory count")?,
+            versions,
+        })
+    }
+
+    // Re-chunks every memory that has chunks from another chunker version
+    // and embeds the new chunks. Returns how many memories were rebuilt.
+    pub async fn rebuild_chunks(&mut self) -> Result<u64> {
+        let _write = maintenance::begin_write()?;
+        let cipher = self.text_cipher();
+        let model = self.embedder.model_name().to_string();
+        let (settings, rows) = {
+            let pool = self.get_db().await?.get_pool().await;
+            let rows = sqlx::query(
+                "SELECT id, content, encrypted FROM memories m
+                 WHERE vault_id = ? AND EXISTS (
+                     SELECT 1 FROM chunks c WHERE c.memory_id = m.id AND c.chunker_version != ?
+                 )
+                 ORDER BY id"
+            )
+            .bind(DEFAULT_VAULT_ID)
+            .bind(chunking::CHUNKER_VERSION)
+            .fetch_all(pool)
+            .await?;
+            (MemorySettings::load(pool, DEFAULT_VAULT_ID).await?, rows)
+        };
+
+        let mut rebuilt = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let memory_id: String = row.get("id");
+            let encrypted: bool = row.get("encrypted");
+            let content = Self::open_content_static(cipher.as_ref(), row.get("content"), encrypted)?;
+
+            let mut chunks = chunking::chunk_content(&content, &settings.chunking);
+            let chunks_truncated = chunks.len() > settings.max_chunks_per_memory;
+            chunks.truncate(settings.max_chunks_per_memory);
+            let vectors = self.encode_chunk_vectors(&chunks).await?;
+            rebuilt.push((memory_id, encrypted, chunks, chunks_truncated, vectors));
+        }
+
+        let pool = self.get_db().await?.get_pool().await;
+        for (memory_id, encrypted, chunks, chunks_truncated, vectors) in &rebuilt {
+            let mut tx = pool.begin().await?;
+            Self::replace_chunks_static(&mut tx, cipher.as_ref(), *encrypted, memory_id, chunks, vectors, &model).await?;
+            sqlx::query("UPDATE memories SET chunks_truncated = ? WHERE id = ?")
+                .bind(chunks_truncated)
+                .bind(memory_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+        }
+
+        Ok(rebuilt.len() as u64)
+    }
+
+    // Chunks memories that have none, e.g. after an add_memory that failed
+    // between inserting the memory and its chunks. Each memory is chunked in
+    // its own transaction with the same limit as add_memory. Returns the
+    // number of memories that gained chunks; memories whose content yields
+    // no chunks are left as they are.
+    pub async fn repair_missing_chunks(&mut self) -> Result<u64> {
+        let _write = maintenance::begin_write()?;
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+
+        let rows = sqlx::query(
+            "SELECT id, content, encrypted FROM memories m
+             WHERE vault_id = ? AND NOT EXISTS (SELECT 1 FROM chunks c WHERE c.memory_id = m.id)
+             ORDER BY id"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_all(pool)
+        .await?;
+
+        let mut repaired = 0u64;
+        for row in &rows {
+            let memory_id: String = row.get("id");
+            let encrypted: bool = row.get("encrypted");
+            let content = Self::open_content_static(cipher.as_ref(), row.get("content"), encrypted)?;
+
+            let mut chunks = chunking::chunk_content(&content, &settings.chunking);
+            if chunks.is_empty() {
+                continue;
+            }
+            let chunks_truncated = chunks.len() > settings.max_chunks_per_memory;
+            chunks.truncate(settings.max_chunks_per_memory);
+
+            let mut tx = pool.begin().await?;
+            Self::insert_chunks_static(&mut tx, cipher.as_ref(), encrypted, &memory_id, &chunks, Utc::now()).await?;
+            sqlx::query("UPDATE memories SET chunks_truncated = ? WHERE id = ?")
+                .bind(chunks_truncated)
+                .bind(&memory_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            repaired += 1;
+        }
+
+        Ok(repaired)
+    }
+
+    // Titles memories added before untitled content was titled automatically,
+    // using the same heuristic as add_memory
+    pub async fn backfill_titles(&mut self) -> Result<u64> {
+        let _write = maintenance::begin_write()?;
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+        let analyzer = Self::keyword_analyzer_static(&settings)?;
+
+        // A sealed title is never blank, so only missing or empty plaintext
+        // titles match
+        let rows = sqlx::query(
+            "SELECT id, content, encrypted FROM memories
+             WHERE vault_id = ? AND (title IS NULL OR TRIM(title) = '')
+             ORDER BY id"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_all(pool)
+        .await?;
+
+        let mut retitled = 0u64;
+        for row in &rows {
+            let memory_id: String = row.get("id");
+            let encrypted: bool = row.get("encrypted");
+            let content = Self::open_content_static(cipher.as_ref(), row.get("content"), encrypted)?;
+            let Some(title) = Self::derive_title_static(&content) else {
+                continue;
+            };
+
+            sqlx::query("UPDATE memories SET title = ? WHERE id = ?")
+                .bind(Self::seal_content_static(cipher.as_ref(), encrypted, &title)?)
+                .bind(&memory_id)
+                .execute(pool)
+                .await?;
+            if Self::indexes_terms_static(&settings, encrypted) {
+                Self::index_terms_static(pool, analyzer.as_ref(), &memory_id, Some(&title), &content).await?;
+            }
+            retitled += 1;
+        }
+
+        Ok(retitled)
+    }
+
+    // In-document find: every case-insensitive occurrence of `query` in one
+    // memory, in content order. Whitespace in the query matches any run of
+    // whitespace, as chunking collapses it.
+    pub async fn search_in_memory(&mut self, memory_id: String, query: String) -> Result<Vec<InMemoryMatch>> {
+        let words: Vec<String> = query.split_whitespace().map(regex::escape).collect();
+        if words.is_empty() {
+            return Err(anyhow::anyhow!("Query cannot be empty"));
+        }
+        let pattern = regex::Regex::new(&format!("(?i){}", words.join(r"\s+")))?;
+
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+        let row = sqlx::query("SELECT content, encrypted FROM memories WHERE id = ? AND vault_id = ?")
+            .bind(&memory_id)
+            .bind(DEFAULT_VAULT_ID)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Memory not found: {}", memory_id))?;
+        let content = Self::open_content_static(cipher.as_ref(), row.get("content"), row.get("encrypted"))?;
+
+        // Chunker version 2 stores byte offsets into the content. Older
+        // chunks have made-up ones, so their content is chunked again and a
+        // stored chunk is matched with a span only while its text is unchanged.
+        let stored = sqlx::query(
+            "SELECT id, content, encrypted, start_pos, end_pos, chunker_version
+             FROM chunks WHERE memory_id = ? ORDER BY start_pos, id"
+        )
+        .bind(&memory_id)
+        .fetch_all(pool)
+        .await?;
+        let mut spans: Vec<(std::ops::Range<usize>, Option<String>)> = Vec::new();
+        if !stored.is_empty() && stored.iter().all(|row| row.get::<i64, _>("chunker_version") >= 2) {
+            for row in &stored {
+                let start = usize::try_from(row.get::<i64, _>("start_pos"))?;
+                let end = usize::try_from(row.get::<i64, _>("end_pos"))?;
+                if content.get(start..end).is_some() {
+                    spans.push((start..end, Some(row.get("id"))));
+                }
+            }
+        } else {
+            let mut chunks = chunking::chunk_content(&content, &settings.chunking);
+            chunks.truncate(settings.max_chunks_per_memory);
+            for (i, chunk) in chunks.into_iter().enumerate() {
+                let mut id = None;
+                if let Some(row) = stored.get(i) {
+                    let text = Self::open_content_static(cipher.as_ref(), row.get("content"), row.get("encrypted"))?;
+                    if text == chunk.content {
+                        id = Some(row.get("id"));
+                    }
+                }
+                spans.push((chunk.span, id));
+            }
+        }
+
+        // Offsets are counted in characters; matches come in order, so the
+        // count carries over from one to the next
+        let mut matches = Vec::new();
+        let mut counted_bytes = 0;
+        let mut counted_chars = 0;
+        for found in pattern.find_iter(&content).take(MAX_FIND_MATCHES) {
+            counted_chars += content[counted_bytes..found.start()].chars().count();
+            counted_bytes = found.start();
+
+            let chunk = spans.iter().find(|(span, _)| span.contains(&found.start()));
+            matches.push(InMemoryMatch {
+                chunk_id: chunk.and_then(|(_, id)| id.clone()),
+                snippet: Self::highlight_snippet_static(&content, found.range()),
+                offset: counted_chars,
+                length: found.as_str().chars().count(),
+            });
+        }
+
+        Ok(matches)
+    }
+
+    // The match wrapped in `**` with up to FIND_CONTEXT_CHARS characters on
+    // either side, on one line
+    fn highlight_snippet_static(content: &str, found: std::ops::Range<usize>) -> String {
+        let before: Vec<char> = content[..found.start].chars().collect();
+        let after: Vec<char> = content[found.end..].chars().collect();
+        let lead = before.len().saturating_sub(FIND_CONTEXT_CHARS);
+        let tail = after.len().min(FIND_CONTEXT_CHARS);
+
+        let mut snippet = String::new();
+        if lead > 0 {
+            snippet.push('…');
+        }
+        snippet.extend(&before[lead..]);
+        snippet.push_str("**");
+        snippet.push_str(&content[found]);
+        snippet.push_str("**");
+        snippet.extend(&after[..tail]);
+        if tail < after.len() {
+            snippet.push('…');
+        }
+        snippet.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    // Lightweight autocomplete: matches word starts via the FTS index and skips
+    // the tag lookups that full search does
+    pub async fn prefix_search(&mut self, prefix: String, limit: Option<usize>) -> Result<Vec<PrefixMatch>> {
+        let words: Vec<&str> = prefix
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect();
+        let Some((last, complete)) = words.split_last() else {
+            return Ok(Vec::new());
+        };
+
+        // Earlier words must match whole tokens; only the last one is a prefix
+        let mut match_expr: Vec<String> = complete.iter().map(|w| format!("\"{}\"", w)).collect();
+        match_expr.push(format!("\"{}\"*", last));
+
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let limit = to_sql_int(limit.unwrap_or(10), "limit")?;
+
+        let rows = sqlx::query(&format!(
+            "SELECT m.id, m.title, m.updated_at,
+                    snippet(memories_fts, 1, '', '', '…', 12) AS snippet
+             FROM memories_fts
+             JOIN memories m ON m.rowid = memories_fts.rowid
+             WHERE memories_fts MATCH ? AND m.vault_id = ? AND m.encrypted = 0 AND {}
+             ORDER BY m.updated_at DESC, m.id
+             LIMIT ?",
+            NOT_EXPIRED
+        ))
+        .bind(match_expr.join(" "))
+        .bind(DEFAULT_VAULT_ID)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| PrefixMatch {
+                id: row.get("id"),
+                title: row.get("title"),
+                snippet: row.get("snippet"),
+                updated_at: row.get::<chrono::DateTime<Utc>, _>("updated_at").to_rfc3339(),
+            })
+            .collect())
+    }
+
+    // Newest first, for triaging an inbox of memories nobody has tagged
+    pub async fn untagged_memories(&mut self, limit: Option<usize>, offset: Option<usize>) -> Result<Vec<MemoryEntry>> {
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let rows = sqlx::query(
+            "SELECT m.id, m.title, m.content, m.source, m.encrypted, m.is_pinned, m.created_at, m.updated_at
+             FROM memories m
+             WHERE m.vault_id = ?
+               AND NOT EXISTS (SELECT 1 FROM memory_tags mt WHERE mt.memory_id = m.id)
+             ORDER BY julianday(m.created_at) DESC, m.created_at DESC, m.id
+             LIMIT ? OFFSET ?"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(to_sql_int(limit.unwrap_or(20), "limit")?)
+        .bind(to_sql_int(offset.unwrap_or(0), "offset")?)
+        .fetch_all(pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| Self::memory_with_tags_static(row, Vec::new(), cipher.as_ref()))
+            .collect()
+    }
+
+    pub async fn orphan_memories(&mut self, limit: Option<usize>, include_queried: bool) -> Result<Vec<MemoryEntry>> {
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let limit = limit.unwrap_or(20);
+
+        // Search is substring based, so a memory was surfaced by a past query
+        // if its content contains that query text. Query text is matched
+        // literally, so `%` and `_` in it are escaped. Encrypted content can
+        // only be compared once decrypted, so those rows are checked below.
+        let history_filter = if include_queried {
+            ""
+        } else {
+            "AND (m.encrypted = 1 OR NOT EXISTS (
+                 SELECT 1 FROM query_history qh
+                 WHERE qh.vault_id = m.vault_id
+                   AND TRIM(qh.query) != ''
+                   AND m.content LIKE '%' || REPLACE(REPLACE(REPLACE(qh.query, '\\', '\\\\'), '%', '\\%'), '_', '\\_') || '%' ESCAPE '\\'
+             ))"
+        };
+        let past_queries: Vec<String> = if include_queried {
+            Vec::new()
+        } else {
+            sqlx::query_scalar("SELECT DISTINCT query FROM query_history WHERE vault_id = ?")
+                .bind(DEFAULT_VAULT_ID)
+                .fetch_all(pool)
+                .await?
+                .into_iter()
+                .filter(|q: &String| !q.trim().is_empty())
+                .map(|q| q.to_lowercase())
+                .collect()
+        };
+        // Only encrypted rows are filtered after the query; otherwise SQL can
+        // stop at the limit. SQLite treats a negative LIMIT as no limit.
+        let sql_limit = if past_queries.is_empty() || !Self::vault_encrypted_static(pool).await? {
+            to_sql_int(limit, "limit")?
+        } else {
+            -1
+        };
+
+        let query_sql = format!(
+            "SELECT m.id, m.title, m.content, m.source, m.encrypted, m.is_pinned, m.created_at, m.updated_at
+             FROM memories m
+             WHERE m.vault_id = ?
+               AND NOT EXISTS (SELECT 1 FROM citations c WHERE c.memory_id = m.id)
+               {}
+             ORDER BY m.created_at ASC, m.id
+             LIMIT ?",
+            history_filter
+        );
+
+        let rows = sqlx::query(&query_sql)
+            .bind(DEFAULT_VAULT_ID)
+            .bind(sql_limit)
+            .fetch_all(pool)
+            .await?;
+
+        let mut memories = Vec::new();
+        for row in rows {
+            if memories.len() >= limit {
+                break;
+            }
+            if row.get::<bool, _>("encrypted") && !past_queries.is_empty() {
+                let content = Self::open_content_static(cipher.as_ref(), row.get("content"), true)?.to_lowercase();
+                if past_queries.iter().any(|q| content.contains(q.as_str())) {
+                    continue;
+                }
+            }
+            memories.push(Self::memory_from_row_static(pool, &row, cipher.as_ref()).await?);
+        }
+
+        Ok(memories)
+    }
+
+    pub async fn find_semantic_duplicates(&mut self, threshold: Option<f32>) -> Result<Vec<DuplicateCluster>> {
+        let threshold = threshold.unwrap_or(DEFAULT_DUPLICATE_THRESHOLD);
+        if !(threshold > 0.0 && threshold <= 1.0) {
+            return Err(anyhow::anyhow!("Threshold must be in (0, 1], got {}", threshold));
+        }
+
+        let cipher = self.text_cipher();
+        let model = self.embedder.model_name().to_string();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let stored = Self::load_memory_vectors_static(pool, &model).await?;
+
+        let mut rows = sqlx::query(
+            "SELECT id, title, content, encrypted, created_at
+             FROM memories
+             WHERE vault_id = ?
+             ORDER BY created_at DESC, id
+             LIMIT ?"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(to_sql_int(MAX_DUPLICATE_SCAN, "scan limit")?)
+        .fetch_all(pool)
+        .await?;
+        rows.reverse();
+
+        let mut vectors = Vec::with_capacity(rows.len());
+        for row in &rows {
+            // Prefer synced embeddings; embed on the fly for memories not synced yet
+            match stored.get(&row.get::<String, _>("id")) {
+                Some(vector) => vectors.push(vector.clone()),
+                None => {
+                    let content = Self::open_content_static(cipher.as_ref(), row.get("content"), row.get("encrypted"))?;
+                    vectors.push(self.embedder.embed_one(&content).await?);
+                }
+            }
+        }
+
+        // Union-find over memories linked by a similar pair
+        let mut parent: Vec<usize> = (0..rows.len()).collect();
+        fn find(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+
+        let mut best: HashMap<usize, f32> = HashMap::new();
+        for i in 0..vectors.len() {
+            for j in (i + 1)..vectors.len().min(i + 1 + DUPLICATE_WINDOW) {
+                let similarity = embeddings::cosine_similarity(&vectors[i], &vectors[j]);
+                if similarity >= threshold {
+                    let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                    let merged = best.remove(&a).unwrap_or(0.0).max(best.remove(&b).unwrap_or(0.0));
+                    parent[b] = a;
+                    best.insert(a, merged.max(similarity));
+                }
+            }
+        }
+
+        let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+        for i in 0..rows.len() {
+            let root = find(&mut parent, i);
+            if best.contains_key(&root) {
+                clusters.entry(root).or_default().push(i);
+            }
+        }
+
+        let mut result: Vec<DuplicateCluster> = clusters
+            .into_iter()
+            .map(|(root, members)| {
+                Ok(DuplicateCluster {
+                    memories: members
+                        .into_iter()
+                        .map(|i| {
+                            Ok(DuplicateCandidate {
+                                id: rows[i].get("id"),
+                                title: Self::open_title_static(cipher.as_ref(), rows[i].get("title"), rows[i].get("encrypted"))?,
+                                created_at: rows[i].get::<chrono::DateTime<Utc>, _>("created_at").to_rfc3339(),
+                            })
+                        })
+                        .collect::<Result<_>>()?,
+                    max_similarity: best[&root],
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        result.sort_by(|a, b| {
+            b.max_similarity
+                .total_cmp(&a.max_similarity)
+                .then_with(|| a.memories[0].id.cmp(&b.memories[0].id))
+        });
+        Ok(result)
+    }
+
+    pub async fn suggest_tags_for_content(&mut self, content: String, limit: Option<usize>) -> Result<Vec<TagSuggestion>> {
+        if content.trim().is_empty() {
+            return Err(anyhow::anyhow!("Content cannot be empty"));
+        }
+        let limit = limit.unwrap_or(5);
+
+        let cipher = self.text_cipher();
+        let model = self.embedder.model_name().to_string();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let stored = Self::load_memory_vectors_static(pool, &model).await?;
+        let tag_rows = sqlx::query(
+            "SELECT mt.memory_id, t.name
+             FROM memory_tags mt
+             JOIN tags t ON t.id = mt.tag_id
+             JOIN memories m ON m.id = mt.memory_id
+             WHERE m.vault_id = ?"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_all(pool)
+        .await?;
+
+        let mut memory_tags: HashMap<String, Vec<String>> = HashMap::new();
+        for row in tag_rows {
+            memory_tags.entry(row.get("memory_id")).or_default().push(row.get("name"));
+        }
+
+        // Similarity of each tagged memory to the new content
+        let mut scored: Vec<(String, f32)> = Vec::new();
+        if memory_tags.keys().any(|id| stored.contains_key(id)) {
+            let query_vector = self.embedder.embed_one(&content).await?;
+            for memory_id in memory_tags.keys() {
+                if let Some(vector) = stored.get(memory_id) {
+                    scored.push((memory_id.clone(), embeddings::cosine_similarity(&query_vector, vector)));
+                }
+            }
+        } else {
+            // No synced embeddings yet; fall back to word overlap (Jaccard)
+            let query_words = Self::overlap_words_static(&content);
+            let rows = sqlx::query(
+                "SELECT m.id, m.content, m.encrypted
+                 FROM memories m
+                 WHERE m.vault_id = ?
+                   AND EXISTS (SELECT 1 FROM memory_tags mt WHERE mt.memory_id = m.id)"
+            )
+            .bind(DEFAULT_VAULT_ID)
+            .fetch_all(pool)
+            .await?;
+            for row in rows {
+                let memory_words = Self::overlap_words_static(&Self::open_content_static(cipher.as_ref(), row.get("content"), row.get("encrypted"))?);
+                scored.push((row.get("id"), Self::jaccard_static(&query_words, &memory_words)));
+            }
+        }
+
+        scored.retain(|(_, score)| *score > 0.0);
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        scored.truncate(SUGGESTION_NEIGHBORS);
+
+        let mut tallies: HashMap<String, (f32, u64)> = HashMap::new();
+        for (memory_id, score) in &scored {
+            for tag in &memory_tags[memory_id] {
+                let tally = tallies.entry(tag.clone()).or_insert((0.0, 0));
+                tally.0 += score;
+                tally.1 += 1;
+            }
+        }
+
+        let mut suggestions: Vec<TagSuggestion> = tallies
+            .into_iter()
+            .map(|(tag, (score, memory_count))| TagSuggestion { tag, score, memory_count })
+            .collect();
+        suggestions.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.tag.cmp(&b.tag)));
+        suggestions.truncate(limit);
+        Ok(suggestions)
+    }
+
+    // The memory's most distinctive words by TF-IDF against the rest of the
+    // vault: stopwords dropped, unstemmed so they read as tags. Results are
+    // cached on the memory until it is next edited; the cache is skipped for
+    // encrypted vaults unless they allow plaintext indexes. Cached scores do
+    // not follow later changes to the rest of the vault.
+    pub async fn extract_keywords(&mut self, memory_id: String, limit: Option<usize>) -> Result<Vec<Keyword>> {
+        let limit = limit.unwrap_or(10).min(MAX_CACHED_KEYWORDS);
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+
+        let row = sqlx::query(
+            "SELECT rowid, content, encrypted,
+                    CASE WHEN julianday(keywords_computed_at) >= julianday(updated_at) THEN keywords END AS cached
+             FROM memories WHERE id = ? AND vault_id = ?"
+        )
+        .bind(&memory_id)
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Memory not found: {}", memory_id))?;
+        if let Some(cached) = row.get::<Option<String>, _>("cached") {
+            let mut keywords: Vec<Keyword> = serde_json::from_str(&cached)?;
+            keywords.truncate(limit);
+            return Ok(keywords);
+        }
+
+        let analyzer = TextAnalyzer::new(&KeywordAnalysis {
+            enabled: true,
+            language: settings.keyword_analysis.language.clone(),
+            stemming: false,
+            remove_stopwords: true,
+        })?;
+        let keyword_terms = |content: &str| -> Vec<String> {
+            analyzer
+                .terms(content)
+                .into_iter()
+                .filter(|term| term.chars().count() >= MIN_KEYWORD_CHARS && !term.chars().all(|c| c.is_numeric()))
+                .collect()
+        };
+
+        let encrypted: bool = row.get("encrypted");
+        let terms = keyword_terms(&Self::open_content_static(cipher.as_ref(), row.get("content"), encrypted)?);
+        let mut term_counts: HashMap<&str, usize> = HashMap::new();
+        for term in &terms {
+            *term_counts.entry(term).or_insert(0) += 1;
+        }
+
+        // Memories containing each of this memory's terms, itself included
+        let mut document_counts: HashMap<&str, usize> = HashMap::new();
+        let mut documents = 0usize;
+        let mut before: Option<i64> = None;
+        loop {
+            let rows = sqlx::query(
+                "SELECT rowid, content, encrypted FROM memories
+                 WHERE vault_id = ? AND (? IS NULL OR rowid < ?)
+                 ORDER BY rowid DESC
+                 LIMIT ?"
+            )
+            .bind(DEFAULT_VAULT_ID)
+            .bind(before)
+            .bind(before)
+            .bind(to_sql_int(WORD_CLOUD_BATCH_SIZE, "batch size")?)
+            .fetch_all(pool)
+            .await?;
+            let Some(last) = rows.last() else { break };
+            before = Some(last.get("rowid"));
+            documents += rows.len();
+
+            for other in &rows {
+                let content = Self::open_content_static(cipher.as_ref(), other.get("content"), other.get("encrypted"))?;
+                let present: HashSet<String> = keyword_terms(&content).into_iter().collect();
+                for term in term_counts.keys() {
+                    if present.contains(*term) {
+                        *document_counts.entry(term).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+
+        // Smoothed so a term in every memory still scores above zero
+        let mut keywords: Vec<Keyword> = term_counts
+            .iter()
+            .map(|(term, count)| {
+                let tf = *count as f32 / terms.len() as f32;
+                let df = document_counts.get(term).copied().unwrap_or(1);
+                let idf = ((documents + 1) as f32 / (df + 1) as f32).ln() + 1.0;
+                Keyword { term: term.to_string(), score: tf * idf }
+            })
+            .collect();
+        keywords.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.term.cmp(&b.term)));
+        keywords.truncate(MAX_CACHED_KEYWORDS);
+
+        if Self::indexes_terms_static(&settings, encrypted) && !database::is_read_only() {
+            sqlx::query("UPDATE memories SET keywords = ?, keywords_computed_at = ? WHERE rowid = ?")
+                .bind(serde_json::to_string(&keywords)?)
+                .bind(Utc::now())
+                .bind(row.get::<i64, _>("rowid"))
+                .execute(pool)
+                .await?;
+        }
+
+        keywords.truncate(limit);
+        Ok(keywords)
+    }
+
+    // Mean of each chunk's best cosine match in the other memory, taken in
+    // both directions so the score is symmetric. Falls back to word and tag
+    // overlap when either memory has no embeddings for the current model.
+    pub async fn memory_similarity(&mut self, id_a: String, id_b: String) -> Result<f32> {
+        let cipher = self.text_cipher();
+        let model = self.embedder.model_name().to_string();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let mut memories = Vec::with_capacity(2);
+        for id in [&id_a, &id_b] {
+            let row = sqlx::query("SELECT title, content, encrypted FROM memories WHERE id = ? AND vault_id = ?")
+                .bind(id)
+                .bind(DEFAULT_VAULT_ID)
+                .fetch_optional(pool)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Memory not found: {}", id))?;
+
+            let vectors: Vec<Vec<f32>> = sqlx::query_scalar(
+                "SELECT e.vector FROM embeddings e
+                 JOIN chunks c ON e.chunk_id = COALESCE(c.embedding_chunk_id, c.id)
+                 WHERE c.memory_id = ? AND e.model_name = ?"
+            )
+            .bind(id)
+            .bind(&model)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|blob: &Vec<u8>| vector_codec::decode(blob))
+            .collect::<Result<_>>()
+            .map_err(|e| anyhow::anyhow!("Bad embedding for memory {}: {}", id, e))?;
+
+            memories.push((row, vectors));
+        }
+        let (row_a, vectors_a) = &memories[0];
+        let (row_b, vectors_b) = &memories[1];
+
+        if !vectors_a.is_empty() && !vectors_b.is_empty() {
+            let best_matches = |from: &[Vec<f32>], to: &[Vec<f32>]| {
+                from.iter()
+                    .map(|a| to.iter().map(|b| embeddings::cosine_similarity(a, b)).fold(f32::MIN, f32::max))
+                    .sum::<f32>()
+                    / from.len() as f32
+            };
+            let score = (best_matches(vectors_a, vectors_b) + best_matches(vectors_b, vectors_a)) / 2.0;
+            return Ok(score.clamp(0.0, 1.0));
+        }
+
+        let mut tokens = Vec::with_capacity(2);
+        for (id, row) in [(&id_a, row_a), (&id_b, row_b)] {
+            let title = Self::open_title_static(cipher.as_ref(), row.get("title"), row.get("encrypted"))?;
+            let content = Self::open_content_static(cipher.as_ref(), row.get("content"), row.get("encrypted"))?;
+            let mut words = Self::overlap_words_static(&format!("{}\n{}", title.unwrap_or_default(), content));
+
+            // Prefixed so a tag only matches the same tag, never a word
+            let tags: Vec<String> = sqlx::query_scalar(
+                "SELECT t.name FROM memory_tags mt JOIN tags t ON t.id = mt.tag_id WHERE mt.memory_id = ?"
+            )
+            .bind(id)
+            .fetch_all(pool)
+            .await?;
+            words.extend(tags.into_iter().map(|tag| format!("tag:{}", tag)));
+            tokens.push(words);
+        }
+
+        Ok(Self::jaccard_static(&tokens[0], &tokens[1]))
+    }
+
+    // How much retrieval changes between the vectors stored for
+    // `baseline_model` and those of the current model: for each sample
+    // query, the Jaccard overlap of the top `k` memories under each. Both
+    // models must have been synced, e.g. midway through a migration before
+    // the old vectors are pruned.
+    pub async fn embedding_drift(
+        &mut self,
+        sample_queries: Vec<String>,
+        baseline_model: String,
+        k: Option<usize>,
+    ) -> Result<Vec<QueryDrift>> {
+        let k = k.unwrap_or(DEFAULT_DRIFT_K);
+        if k == 0 {
+            return Err(anyhow::anyhow!("k must be greater than zero"));
+        }
+        let baseline = embeddings::provider_for_model(&baseline_model)
+            .ok_or_else(|| anyhow::anyhow!("Unknown embedding model: {}", baseline_model))?;
+        let model = self.embedder.model_name().to_string();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let baseline_vectors = Self::chunk_vectors_static(pool, &baseline_model).await?;
+        let current_vectors = Self::chunk_vectors_static(pool, &model).await?;
+        for (vectors, name) in [(&baseline_vectors, &baseline_model), (&current_vectors, &model)] {
+            if vectors.is_empty() {
+                return Err(anyhow::anyhow!("No {} embeddings to compare; run sync_embeddings first", name));
+            }
+        }
+
+        let mut drift = Vec::with_capacity(sample_queries.len());
+        for query in sample_queries {
+            let before = Self::top_memories_static(&baseline_vectors, &baseline.embed_one(&query).await?, k);
+            let after = Self::top_memories_static(&current_vectors, &self.embedder.embed_one(&query).await?, k);
+            // Two empty result lists agree completely
+            let jaccard_overlap = if before.is_empty() && after.is_empty() {
+                1.0
+            } else {
+                Self::jaccard_static(&before, &after)
+            };
+            drift.push(QueryDrift { query, jaccard_overlap });
+        }
+        Ok(drift)
+    }
+
+    // (memory id, chunk vector) for every unexpired memory embedded with `model`
+    async fn chunk_vectors_static(pool: &sqlx::SqlitePool, model: &str) -> Result<Vec<(String, Vec<f32>)>> {
+        let rows = sqlx::query(&format!(
+            "SELECT c.memory_id, e.vector
+             FROM chunks c
+             JOIN memories m ON c.memory_id = m.id
+             JOIN embeddings e ON e.chunk_id = COALESCE(c.embedding_chunk_id, c.id)
+             WHERE m.vault_id = ? AND e.model_name = ? AND {}
+             ORDER BY c.memory_id, c.id",
+            NOT_EXPIRED
+        ))
+        .bind(DEFAULT_VAULT_ID)
+        .bind(model)
+        .fetch_all(pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let memory_id: String = row.get("memory_id");
+                let vector = vector_codec::decode(&row.get::<Vec<u8>, _>("vector"))
+                    .map_err(|e| anyhow::anyhow!("Bad embedding for memory {}: {}", memory_id, e))?;
+                Ok((memory_id, vector))
+            })
+            .collect()
+    }
+
+    // Memories ranked by their best chunk; ties go to the lower id
+    fn top_memories_static(vectors: &[(String, Vec<f32>)], query: &[f32], k: usize) -> HashSet<String> {
+        let mut best: HashMap<&str, f32> = HashMap::new();
+        for (memory_id, vector) in vectors {
+            let score = embeddings::cosine_similarity(query, vector);
+            let entry = best.entry(memory_id).or_insert(f32::MIN);
+            *entry = entry.max(score);
+        }
+
+        let mut ranked: Vec<(&str, f32)> = best.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        ranked.into_iter().take(k).map(|(id, _)| id.to_string()).collect()
+    }
+
+    fn overlap_words_static(text: &str) -> HashSet<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| w.chars().count() >= 3)
+            .map(str::to_lowercase)
+            .collect()
+    }
+
+    fn jaccard_static(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+        let union = a.union(b).count();
+        if union == 0 {
+            return 0.0;
+        }
+        a.intersection(b).count() as f32 / union as f32
+    }
+
+    pub async fn list_tags(&mut self) -> Result<Vec<TagNode>> {
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let rows = sqlx::query(
+            "SELECT t.name, COUNT(mt.memory_id) AS memory_count
+             FROM tags t
+             LEFT JOIN memory_tags mt ON t.id = mt.tag_id
+             GROUP BY t.id
+             ORDER BY t.name"
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut roots: Vec<TagNode> = Vec::new();
+        for row in rows {
+            let name: String = row.get("name");
+            let count: i64 = row.get("memory_count");
+
+            // Walk/create the path of nodes, creating implicit ancestors
+            let mut level = &mut roots;
+            let mut path = String::new();
+            for segment in name.split('/') {
+                if !path.is_empty() {
+                    path.push('/');
+                }
+                path.push_str(segment);
+
+                let index = match level.iter().position(|n| n.name == segment) {
+                    Some(index) => index,
+                    None => {
+                        level.push(TagNode {
+                            name: segment.to_string(),
+                            path: path.clone(),
+                            memory_count: 0,
+                            children: Vec::new(),
+                        });
+                        level.len() - 1
+                    }
+                };
+
+                if path == name {
+                    level[index].memory_count = to_count(count, "tag memory count")?;
+                }
+                level = &mut level[index].children;
+            }
+        }
+
+        Ok(roots)
+    }
+
+    // Paged memories for a tag (including its descendants) plus how often
+    // other tags co-occur on the full set of tagged memories
+    pub async fn memories_by_tag(
+        &mut self,
+        tag: String,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> Result<TaggedMemories> {
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let limit = to_sql_int(limit.unwrap_or(20), "limit")?;
+        let offset = to_sql_int(offset.unwrap_or(0), "offset")?;
+
+        let tag = validation::normalize_tag(&tag);
+        if tag.is_empty() {
+            return Err(anyhow::anyhow!("Tag cannot be empty"));
+        }
+        let descendants = Self::tag_descendant_pattern_static(&tag);
+
+        const TAGGED: &str = "WITH tagged AS (
+                 SELECT DISTINCT mt.memory_id FROM memory_tags mt
+                 JOIN tags t ON t.id = mt.tag_id
+                 JOIN memories m ON m.id = mt.memory_id
+                 WHERE m.vault_id = ? AND (t.name = ? OR t.name LIKE ? ESCAPE '\\')
+             )";
+
+        let total = to_count(
+            sqlx::query(&format!("{} SELECT COUNT(*) FROM tagged", TAGGED))
+                .bind(DEFAULT_VAULT_ID)
+                .bind(&tag)
+                .bind(&descendants)
+                .fetch_one(pool)
+                .await?
+                .get(0),
+            "tagged memory count",
+        )?;
+
+        let rows = sqlx::query(&format!(
+            "{} SELECT m.id, m.title, m.content, m.source, m.encrypted, m.is_pinned, m.created_at, m.updated_at
+             FROM memories m
+             JOIN tagged ON tagged.memory_id = m.id
+             ORDER BY m.updated_at DESC, m.id
+             LIMIT ? OFFSET ?",
+            TAGGED
+        ))
+        .bind(DEFAULT_VAULT_ID)
+        .bind(&tag)
+        .bind(&descendants)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+        // Tags for the whole page in one query
+        let mut page_tags: HashMap<String, Vec<String>> = HashMap::new();
+        if !rows.is_empty() {
+            let placeholders = vec!["?"; rows.len()].join(", ");
+            let tag_sql = format!(
+                "SELECT mt.memory_id, t.name FROM memory_tags mt
+                 JOIN tags t ON t.id = mt.tag_id
+                 WHERE mt.memory_id IN ({})
+                 ORDER BY t.name",
+                placeholders
+            );
+            let mut tag_query = sqlx::query(&tag_sql);
+            for row in &rows {
+                tag_query = tag_query.bind(row.get::<String, _>("id"));
+            }
+            for tag_row in tag_query.fetch_all(pool).await? {
+                page_tags
+                    .entry(tag_row.get("memory_id"))
+                    .or_default()
+                    .push(tag_row.get("name"));
+            }
+        }
+
+        let mut memories = Vec::new();
+        for row in &rows {
+            let tags = page_tags.remove(&row.get::<String, _>("id")).unwrap_or_default();
+            memories.push(Self::memory_with_tags_static(row, tags, cipher.as_ref())?);
+        }
+
+        let co_tag_rows = sqlx::query(&format!(
+            "{} SELECT t.name, COUNT(DISTINCT mt.memory_id) AS memory_count
+             FROM tagged
+             JOIN memory_tags mt ON mt.memory_id = tagged.memory_id
+             JOIN tags t ON t.id = mt.tag_id
+             WHERE t.name != ?
+             GROUP BY t.name
+             ORDER BY memory_count DESC, t.name",
+            TAGGED
+        ))
+        .bind(DEFAULT_VAULT_ID)
+        .bind(&tag)
+        .bind(&descendants)
+        .bind(&tag)
+        .fetch_all(pool)
+        .await?;
+
+        let co_tags = co_tag_rows
+            .into_iter()
+            .map(|row| {
+                Ok(TagCount {
+                    name: row.get("name"),
+                    memory_count: to_count(row.get("memory_count"), "co-tag count")?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(TaggedMemories {
+            tag,
+            total,
+            memories,
+            co_tags,
+        })
+    }
+
+    async fn get_memory_tags_static(pool: &sqlx::SqlitePool, memory_id: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            "SELECT t.name FROM tags t
+             JOIN memory_tags mt ON t.id = mt.tag_id
+             WHERE mt.memory_id = ?
+             ORDER BY t.name"
+        )
+        .bind(memory_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.get("name")).collect())
+    }
+
+    // Most frequent words across memory content, without stopwords for the
+    // configured analysis language, numbers, or words shorter than
+    // `min_length` characters. Ties are broken alphabetically.
+    pub async fn word_cloud(&mut self, limit: Option<usize>, min_length: Option<usize>) -> Result<Vec<WordCount>> {
+        let limit = limit.unwrap_or(50);
+        let min_length = min_length.unwrap_or(3);
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+        let analyzer = TextAnalyzer::new(&KeywordAnalysis {
+            enabled: true,
+            language: settings.keyword_analysis.language.clone(),
+            stemming: false,
+            remove_stopwords: true,
+        })?;
+
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        let mut before: Option<i64> = None;
+        let mut scanned = 0;
+        while scanned < MAX_WORD_CLOUD_MEMORIES {
+            let batch = WORD_CLOUD_BATCH_SIZE.min(MAX_WORD_CLOUD_MEMORIES - scanned);
+            let rows = sqlx::query(
+                "SELECT rowid, content, encrypted FROM memories
+                 WHERE vault_id = ? AND (? IS NULL OR rowid < ?)
+                 ORDER BY rowid DESC
+                 LIMIT ?"
+            )
+            .bind(DEFAULT_VAULT_ID)
+            .bind(before)
+            .bind(before)
+            .bind(to_sql_int(batch, "batch size")?)
+            .fetch_all(pool)
+            .await?;
+
+            let Some(last) = rows.last() else { break };
+            before = Some(last.get("rowid"));
+            scanned += rows.len();
+
+            for row in &rows {
+                let content = Self::open_content_static(cipher.as_ref(), row.get("content"), row.get("encrypted"))?;
+                for word in analyzer.terms(&content) {
+                    if word.chars().count() >= min_length && !word.chars().all(|c| c.is_numeric()) {
+                        *counts.entry(word).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+
+        let mut words: Vec<WordCount> = counts.into_iter().map(|(word, count)| WordCount { word, count }).collect();
+        words.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.word.cmp(&b.word)));
+        words.truncate(limit);
+        Ok(words)
+    }
+
+    pub async fn get_stats(&mut self) -> Result<MemoryStats> {
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let memory_count = to_count(
+            sqlx::query("SELECT COUNT(*) FROM memories")
+                .fetch_one(pool)
+                .await?
+                .get(0),
+            "memory count",
+        )?;
+
+        let chunk_count = to_count(
+            sqlx::query("SELECT COUNT(*) FROM chunks")
+                .fetch_one(pool)
+                .await?
+                .get(0),
+            "chunk count",
+        )?;
+
+        let embedding_count = to_count(
+            sqlx::query("SELECT COUNT(*) FROM embeddings")
+                .fetch_one(pool)
+                .await?
+                .get(0),
+            "embedding count",
+        )?;
+
+        let truncated_memories = to_count(
+            sqlx::query("SELECT COUNT(*) FROM memories WHERE chunks_truncated = 1")
+                .fetch_one(pool)
+                .await?
+                .get(0),
+            "truncated memory count",
+        )?;
+
+        // Simplified storage calculation
+        let storage_size = memory_count
+            .saturating_mul(1000)
+            .saturating_add(chunk_count.saturating_mul(500));
+
+        Ok(MemoryStats {
+            total_memories: memory_count,
+            total_chunks: chunk_count,
+            total_embeddings: embedding_count,
+            truncated_memories,
+            storage_size_bytes: storage_size,
+            last_updated: Utc::now().to_rfc3339(),
+        })
+    }
+
+    // Recalculates every aggregate from the source tables inside one
+    // transaction, so the figures are consistent with each other even while
+    // other commands are writing
+    pub async fn recompute_statistics(&mut self) -> Result<StatisticsReport> {
+        let model = self.embedder.model_name().to_string();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let mut tx = pool.begin().await?;
+
+        let vault_rows = sqlx::query(
+            "SELECT v.id, COUNT(m.id) AS memory_count
+             FROM vaults v
+             LEFT JOIN memories m ON m.vault_id = v.id
+             GROUP BY v.id
+             ORDER BY v.id"
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let vaults = vault_rows
+            .into_iter()
+            .map(|row| {
+                Ok(VaultMemoryCount {
+                    vault_id: row.get("id"),
+                    memory_count: to_count(row.get("memory_count"), "memory count")?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let total_chunks = to_count(
+            sqlx::query("SELECT COUNT(*) FROM chunks").fetch_one(&mut *tx).await?.get(0),
+            "chunk count",
+        )?;
+        let embedded_chunks = to_count(
+            sqlx::query(
+                "SELECT COUNT(*) FROM chunks c
+                 WHERE EXISTS (
+                     SELECT 1 FROM embeddings e
+                     WHERE e.chunk_id = COALESCE(c.embedding_chunk_id, c.id) AND e.model_name = ?
+                 )"
+            )
+            .bind(&model)
+            .fetch_one(&mut *tx)
+            .await?
+            .get(0),
+            "embedded chunk count",
+        )?;
+
+        let page_count: i64 = sqlx::query_scalar("PRAGMA page_count").fetch_one(&mut *tx).await?;
+        let page_size: i64 = sqlx::query_scalar("PRAGMA page_size").fetch_one(&mut *tx).await?;
+        let storage_size_bytes = to_count(page_count, "page count")?.saturating_mul(to_count(page_size, "page size")?);
+
+        tx.commit().await?;
+
+        Ok(StatisticsReport {
+            vaults,
+            total_chunks,
+            embedded_chunks,
+            embedding_coverage: if total_chunks == 0 {
+                0.0
+            } else {
+                embedded_chunks as f32 / total_chunks as f32
+            },
+            storage_size_bytes,
+            computed_at: Utc::now().to_rfc3339(),
+        })
+    }
+
+    pub async fn create_snapshot(&mut self, label: String) -> Result<Snapshot> {
+        let _write = maintenance::begin_write()?;
+        let db = self.get_db().await?;
+        snapshots::create(db.get_pool().await, DEFAULT_VAULT_ID, &label).await
+    }
+
+    pub async fn list_snapshots(&mut self) -> Result<Vec<Snapshot>> {
+        let db = self.get_db().await?;
+        snapshots::list(db.get_pool().await, DEFAULT_VAULT_ID).await
+    }
+
+    pub async fn diff_snapshots(&mut self, from: String, to: String) -> Result<SnapshotDiff> {
+        let db = self.get_db().await?;
+        snapshots::diff(db.get_pool().await, DEFAULT_VAULT_ID, &from, &to).await
+    }
+
+    // One hash over every memory's id, contents and updated_at, in id order,
+    // for checking whether two devices hold the same vault. Contents are
+    // hashed decrypted so re-encrypting a vault leaves its fingerprint as it
+    // was, and timestamps are normalized to RFC 3339.
+    pub async fn vault_fingerprint(&mut self) -> Result<String> {
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let rows = sqlx::query(
+            "SELECT m.id, m.title, m.content, m.source, m.encrypted, m.updated_at,
+                    (SELECT GROUP_CONCAT(name, char(31)) FROM (
+                        SELECT t.name FROM memory_tags mt JOIN tags t ON t.id = mt.tag_id
+                        WHERE mt.memory_id = m.id ORDER BY t.name
+                    )) AS tags
+             FROM memories m
+             WHERE m.vault_id = ?
+             ORDER BY m.id"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_all(pool)
+        .await?;
+
+        let mut vault_hasher = Sha256::new();
+        for row in &rows {
+            let mut hasher = Sha256::new();
+            for field in [
+                Self::open_title_static(cipher.as_ref(), row.get("title"), row.get("encrypted"))?,
+                Some(Self::open_content_static(cipher.as_ref(), row.get("content"), row.get("encrypted"))?),
+                row.get::<Option<String>, _>("source"),
+                row.get::<Option<String>, _>("tags"),
+            ] {
+                // Length-prefix each field so adjacent values cannot run together
+                let field = field.unwrap_or_default();
+                hasher.update((field.len() as u64).to_le_bytes());
+                hasher.update(field.as_bytes());
+            }
+
+            let memory_id: String = row.get("id");
+            let updated_at = row.get::<chrono::DateTime<Utc>, _>("updated_at").to_rfc3339();
+            for field in [memory_id.as_str(), &snapshots::to_hex(&hasher.finalize()), &updated_at] {
+                vault_hasher.update((field.len() as u64).to_le_bytes());
+                vault_hasher.update(field.as_bytes());
+            }
+        }
+
+        Ok(snapshots::to_hex(&vault_hasher.finalize()))
+    }
+
+    pub async fn delete_memory(&mut self, id: String) -> Result<()> {
+        let _write = maintenance::begin_write()?;
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        Self::delete_memory_rows_static(pool, &id).await
+    }
+
+    // Before a memory's chunks are deleted, chunks in other memories that
+    // share their embeddings take them over
+    async fn release_shared_chunks_static(conn: &mut sqlx::SqliteConnection, memory_id: &str) -> Result<()> {
+        let shared: Vec<(String, String)> = sqlx::query_as(
+            "SELECT c.id, MIN(d.id)
+             FROM chunks c
+             JOIN chunks d ON d.embedding_chunk_id = c.id AND d.memory_id != c.memory_id
+             WHERE c.memory_id = ?
+             GROUP BY c.id"
+        )
+        .bind(memory_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        for (chunk_id, heir_id) in &shared {
+            sqlx::query("DELETE FROM embeddings WHERE chunk_id = ?")
+                .bind(heir_id)
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query("UPDATE embeddings SET chunk_id = ? WHERE chunk_id = ?")
+                .bind(heir_id)
+                .bind(chunk_id)
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query("UPDATE chunks SET embedding_chunk_id = CASE WHEN id = ? THEN NULL ELSE ? END WHERE embedding_chunk_id = ?")
+                .bind(heir_id)
+                .bind(heir_id)
+                .bind(chunk_id)
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn delete_memory_rows_static(pool: &sqlx::SqlitePool, id: &str) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+        Self::release_shared_chunks_static(&mut conn, id).await?;
+        drop(conn);
+
+        // Delete associated chunks and citations first
+        sqlx::query("DELETE FROM citations WHERE memory_id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        sqlx::query("DELETE FROM embeddings WHERE chunk_id IN (SELECT id FROM chunks WHERE memory_id = ?)")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        sqlx::query("DELETE FROM chunks WHERE memory_id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        sqlx::query("DELETE FROM memory_tags WHERE memory_id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        sqlx::query("DELETE FROM memory_terms WHERE memory_id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        sqlx::query("DELETE FROM memory_versions WHERE memory_id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        sqlx::query("DELETE FROM memory_relations WHERE source_id = ? OR target_id = ?")
+            .bind(id)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        // Delete memory
+        sqlx::query("DELETE FROM memories WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Sets or clears (`None`) the time after which a memory is left out of
+    // search and removed by the background sweep
+    pub async fn set_memory_expiry(&mut self, id: String, expires_at: Option<String>) -> Result<()> {
+        let _write = maintenance::begin_write()?;
+        let expires_at = expires_at
+            .map(|value| {
+                chrono::DateTime::parse_from_rfc3339(&value)
+                    .map(|time| time.with_timezone(&Utc))
+                    .map_err(|e| anyhow::anyhow!("Invalid expiry '{}': {}", value, e))
+            })
+            .transpose()?;
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let result = sqlx::query("UPDATE memories SET expires_at = ? WHERE id = ? AND vault_id = ?")
+            .bind(expires_at)
+            .bind(&id)
+            .bind(DEFAULT_VAULT_ID)
+            .execute(pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Memory not found: {}", id));
+        }
+
+        Ok(())
+    }
+
+    // Deletes memories whose expiry has passed, unless the vault is set to
+    // keep them. Returns how many were deleted.
+    pub async fn sweep_expired_memories(&mut self) -> Result<u64> {
+        let _write = maintenance::begin_write()?;
+        let db = self.get_db().await?;
+        Self::sweep_expired_memories_static(db.get_pool().await).await
+    }
+
+    // The sweep on a given pool, e.g. the background scheduler's. It only
+    // counts as a write when something has expired, so a sweep that finds
+    // nothing leaves the compaction idle window running.
+    pub async fn sweep_expired_memories_static(pool: &sqlx::SqlitePool) -> Result<u64> {
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+        if settings.expired_memories == ExpiryPolicy::Keep {
+            return Ok(0);
+        }
+
+        let ids: Vec<String> = sqlx::query_scalar(
+            "SELECT id FROM memories
+             WHERE vault_id = ? AND expires_at IS NOT NULL AND julianday(expires_at) <= julianday('now')
+             ORDER BY id"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_all(pool)
+        .await?;
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let _write = maintenance::begin_write()?;
+        for id in &ids {
+            Self::delete_memory_rows_static(pool, id).await?;
+        }
+
+        Ok(ids.len() as u64)
+    }
+
+    pub async fn update_memory(&mut self, id: String, mut entry: MemoryEntry) -> Result<()> {
+        let _write = maintenance::begin_write()?;
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+
+        Self::sanitize_entry_static(&mut entry, &settings);
+        let report = validation::validate_entry(&entry);
+        if !report.valid {
+            return Err(anyhow::anyhow!("Invalid memory: {}", report.error_message()));
+        }
+        entry.tags = report.normalized_tags;
+        entry.source = report.normalized_source;
+        Self::check_tag_limit_static(&settings, entry.tags.len())?;
+
+        let encrypted = Self::vault_encrypted_static(pool).await?;
+        let now = Utc::now();
+
+        // Update memory
+        sqlx::query(
+            "UPDATE memories SET title = ?, content = ?, source = ?, encrypted = ?, crypto_version = ?, updated_at = ? WHERE id = ?"
+        )
+        .bind(Self::seal_title_static(cipher.as_ref(), encrypted, entry.title.as_deref())?)
+        .bind(Self::seal_content_static(cipher.as_ref(), encrypted, &entry.content)?)
+        .bind(&entry.source)
+        .bind(encrypted)
+        .bind(crypto::CRYPTO_VERSION)
+        .bind(now)
+        .bind(&id)
+        .execute(pool)
+        .await?;
+
+        // Update tags
+        sqlx::query("DELETE FROM memory_tags WHERE memory_id = ?")
+            .bind(&id)
+            .execute(pool)
+            .await?;
+
+        for tag_name in &entry.tags {
+            let tag_id = Self::ensure_tag_static(pool, tag_name).await?;
+            sqlx::query("INSERT INTO memory_tags (memory_id, tag_id) VALUES (?, ?)")
+                .bind(&id)
+                .bind(&tag_id)
+                .execute(pool)
+                .await?;
+        }
+
+        if Self::indexes_terms_static(&settings, encrypted) {
+            let analyzer = Self::keyword_analyzer_static(&settings)?;
+            Self::index_terms_static(pool, analyzer.as_ref(), &id, entry.title.as_deref(), &entry.content).await?;
+        } else {
+            sqlx::query("DELETE FROM memory_terms WHERE memory_id = ?")
+                .bind(&id)
+                .execute(pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // Re-fetches a memory whose source is an http(s) URL. When the text
+    // differs from what is stored, the previous content is kept in
+    // memory_versions, the memory takes the new text and its chunks are
+    // rebuilt and embedded. The title is left alone.
+    pub async fn refresh_from_source(&mut self, id: String) -> Result<SourceRefreshResult> {
+        let cipher = self.text_cipher();
+        let (source, current) = {
+            let pool = self.get_db().await?.get_pool().await;
+            let row = sqlx::query("SELECT content, source, encrypted FROM memories WHERE id = ? AND vault_id = ?")
+                .bind(&id)
+                .bind(DEFAULT_VAULT_ID)
+                .fetch_optional(pool)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Memory not found: {}", id))?;
+            let source: Option<String> = row.get("source");
+            let source = source
+                .filter(|source| ContentExtractor::is_fetchable(source))
+                .ok_or_else(|| anyhow::anyhow!("Memory {} has no http or https source to refresh from", id))?;
+            (source, Self::open_content_static(cipher.as_ref(), row.get("content"), row.get("encrypted"))?)
+        };
+
+        // Fetched before the write starts so a slow server never holds it
+        let extracted = ContentExtractor::new()?.extract(&source).await?;
+
+        let _write = maintenance::begin_write()?;
+        let (settings, encrypted) = {
+            let pool = self.get_db().await?.get_pool().await;
+            (MemorySettings::load(pool, DEFAULT_VAULT_ID).await?, Self::vault_encrypted_static(pool).await?)
+        };
+        let content = sanitize::sanitize(&extracted.text, &settings.sanitization);
+        if content.trim().is_empty() {
+            return Err(anyhow::anyhow!("{} has no text to refresh from", source));
+        }
+        if content.len() > validation::MAX_CONTENT_BYTES {
+            return Err(anyhow::anyhow!(
+                "{} has {} bytes of text, more than the {} a memory can hold",
+                source,
+                content.len(),
+                validation::MAX_CONTENT_BYTES
+            ));
+        }
+
+        if content == current {
+            let pool = self.get_db().await?.get_pool().await;
+            let chunk_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM chunks WHERE memory_id = ?")
+                .bind(&id)
+                .fetch_one(pool)
+                .await?;
+            return Ok(SourceRefreshResult {
+                memory_id: id,
+                changed: false,
+                version_id: None,
+                chunk_count: to_count(chunk_count, "chunk count")?,
+            });
+        }
+
+        let mut chunks = chunking::chunk_content(&content, &settings.chunking);
+        let chunks_truncated = chunks.len() > settings.max_chunks_per_memory;
+        chunks.truncate(settings.max_chunks_per_memory);
+        let vectors = self.encode_chunk_vectors(&chunks).await?;
+        let model = self.embedder.model_name().to_string();
+        let version_id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        let pool = self.get_db().await?.get_pool().await;
+        let mut tx = pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO memory_versions (id, memory_id, title, content, source, encrypted, crypto_version, replaced_at)
+             SELECT ?, id, title, content, source, encrypted, crypto_version, ? FROM memories WHERE id = ?"
+        )
+        .bind(&version_id)
+        .bind(now)
+        .bind(&id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "UPDATE memories SET content = ?, encrypted = ?, crypto_version = ?, chunks_truncated = ?, updated_at = ? WHERE id = ?"
+        )
+        .bind(Self::seal_content_static(cipher.as_ref(), encrypted, &content)?)
+        .bind(encrypted)
+        .bind(crypto::CRYPTO_VERSION)
+        .bind(chunks_truncated)
+        .bind(now)
+        .bind(&id)
+        .execute(&mut *tx)
+        .await?;
+
+        let chunk_ids = Self::replace_chunks_static(&mut tx, cipher.as_ref(), encrypted, &id, &chunks, &vectors, &model).await?;
+        tx.commit().await?;
+
+        if Self::indexes_terms_static(&settings, encrypted) {
+            let title: Option<String> = sqlx::query_scalar("SELECT title FROM memories WHERE id = ?")
+                .bind(&id)
+                .fetch_one(pool)
+                .await?;
+            let analyzer = Self::keyword_analyzer_static(&settings)?;
+            Self::index_terms_static(pool, analyzer.as_ref(), &id, title.as_deref(), &content).await?;
+        }
+
+        Ok(SourceRefreshResult {
+            memory_id: id,
+            changed: true,
+            version_id: Some(version_id),
+            chunk_count: chunk_ids.len() as u64,
+        })
+    }
+
+    // Earlier contents of a memory, most recently replaced first
+    pub async fn list_memory_versions(&mut self, id: String) -> Result<Vec<MemoryVersion>> {
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let rows = sqlx::query(
+            "SELECT v.id, v.memory_id, v.title, v.content, v.source, v.encrypted, v.replaced_at
+             FROM memory_versions v
+             JOIN memories m ON v.memory_id = m.id
+             WHERE v.memory_id = ? AND m.vault_id = ?
+             ORDER BY v.replaced_at DESC, v.id"
+        )
+        .bind(&id)
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_all(pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(MemoryVersion {
+                    id: row.get("id"),
+                    memory_id: row.get("memory_id"),
+                    title: Self::open_title_static(cipher.as_ref(), row.get("title"), row.get("encrypted"))?,
+                    content: Self::open_content_static(cipher.as_ref(), row.get("content"), row.get("encrypted"))?,
+                    source: row.get("source"),
+                    replaced_at: row.get::<chrono::DateTime<Utc>, _>("replaced_at").to_rfc3339(),
+                })
+            })
+            .collect()
+    }
+
+    // Most relevant first; `id` breaks ties so pages never overlap
+    pub async fn get_citations(
+        &mut self,
+        memory_id: String,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> Result<Vec<Citation>> {
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+
+        // Citations have no text of their own; the cited content is the chunk's
+        let rows = sqlx::query(
+            "SELECT c.id, m.title, m.encrypted AS memory_encrypted, ch.content AS content, ch.encrypted,
+                    c.relevance_score, m.source
+             FROM citations c
+             JOIN chunks ch ON c.chunk_id = ch.id
+             JOIN memories m ON c.memory_id = m.id
+             WHERE c.memory_id = ?
+             ORDER BY c.relevance_score DESC, c.id
+             LIMIT ? OFFSET ?"
+        )
+        .bind(&memory_id)
+        .bind(to_sql_int(limit.unwrap_or(50), "limit")?)
+        .bind(to_sql_int(offset.unwrap_or(0), "offset")?)
+        .fetch_all(pool)
+        .await?;
+
+        let mut citations = Vec::new();
+        for row in rows {
+            let content = Self::open_content_static(cipher.as_ref(), row.get("content"), row.get("encrypted"))?;
+            citations.push(Citation {
+                id: row.get("id"),
+                title: Self::open_title_static(cipher.as_ref(), row.get("title"), row.get("memory_encrypted"))?,
+                content: Self::citation_content_static(&settings.citation_content, &content, ""),
+                relevance_score: row.get("relevance_score"),
+                source: row.get("source"),
+            });
+        }
+
+        Ok(citations)
+    }
+
+    pub async fn get_insights(&mut self, period: String) -> Result<serde_json::Value> {
+        // Simplified insights - in real implementation, generate meaningful insights
+        let insights = serde_json::json!({
+            "period": period,
+            "total_memories": 0,
+            "new_memories": 0,
+            "top_tags": [],
+            "memory_trends": [],
+            "generated_at": Utc::now().to_rfc3339()
+        });
+
+        Ok(insights)
+    }
+
+    // Memories created per UTC day over the last `days` days, oldest first
+    // and ending today. Days without activity are included with a zero count.
+    pub async fn activity_heatmap(&mut self, days: u32) -> Result<Vec<ActivityDay>> {
+        if days == 0 || days > MAX_HEATMAP_DAYS {
+            return Err(anyhow::anyhow!("Days must be between 1 and {}", MAX_HEATMAP_DAYS));
+        }
+
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let today = Utc::now().date_naive();
+        let first_day = today - Duration::days(i64::from(days) - 1);
+
+        // date() normalizes both RFC3339 and CURRENT_TIMESTAMP values to UTC
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT date(created_at) AS day, COUNT(*)
+             FROM memories
+             WHERE vault_id = ? AND date(created_at) BETWEEN ? AND ?
+             GROUP BY day"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(first_day.to_string())
+        .bind(today.to_string())
+        .fetch_all(pool)
+        .await?;
+        let counts: HashMap<String, i64> = rows.into_iter().collect();
+
+        first_day
+            .iter_days()
+            .take_while(|day| *day <= today)
+            .map(|day| {
+                let date = day.to_string();
+                let memory_count = to_count(counts.get(&date).copied().unwrap_or(0), "memory count")?;
+                Ok(ActivityDay { date, memory_count })
+            })
+            .collect()
+    }
+
+    // Every memory in the vault grouped by the UTC day, week or month it was
+    // created in, newest bucket first. Empty periods are left out.
+    pub async fn timeline(&mut self, granularity: TimelineGranularity) -> Result<Vec<TimelineBucket>> {
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        // Each expression yields the bucket's first day; weeks step back to
+        // the Monday on or before the date
+        let bucket = match granularity {
+            TimelineGranularity::Day => "date(created_at)",
+            TimelineGranularity::Week => "date(created_at, '-6 days', 'weekday 1')",
+            TimelineGranularity::Month => "strftime('%Y-%m-01', created_at)",
+        };
+        let rows = sqlx::query(&format!(
+            "SELECT id, title, encrypted, created_at, {} AS bucket
+             FROM memories
+             WHERE vault_id = ?
+             ORDER BY bucket DESC, julianday(created_at) DESC, created_at DESC, id",
+            bucket
+        ))
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_all(pool)
+        .await?;
+
+        let mut buckets: Vec<TimelineBucket> = Vec::new();
+        for row in rows {
+            let start: String = row.get("bucket");
+            let memory = TimelineMemory {
+                id: row.get("id"),
+                title: Self::open_title_static(cipher.as_ref(), row.get("title"), row.get("encrypted"))?,
+                created_at: row.get::<chrono::DateTime<Utc>, _>("created_at").to_rfc3339(),
+            };
+            match buckets.last_mut() {
+                Some(last) if last.start == start => {
+                    last.memory_count += 1;
+                    last.memories.push(memory);
+                }
+                _ => buckets.push(TimelineBucket { start, memory_count: 1, memories: vec![memory] }),
+            }
+        }
+
+        Ok(buckets)
+    }
+
+    // Adds a directed `kind` link from one memory to another; linking the
+    // same pair with the same kind again is a no-op
+    pub async fn link_memories(&mut self, source_id: String, target_id: String, kind: String) -> Result<()> {
+        let _write = maintenance::begin_write()?;
+        let kind = kind.trim().to_lowercase();
+        if kind.is_empty() {
+            return Err(anyhow::anyhow!("Relation kind cannot be empty"));
+        }
+        if source_id == target_id {
+            return Err(anyhow::anyhow!("A memory cannot be linked to itself"));
+        }
+
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        for id in [&source_id, &target_id] {
+            let exists: Option<i64> = sqlx::query_scalar("SELECT 1 FROM memories WHERE id = ? AND vault_id = ?")
+                .bind(id)
+                .bind(DEFAULT_VAULT_ID)
+                .fetch_optional(pool)
+                .await?;
+            if exists.is_none() {
+                return Err(anyhow::anyhow!("Memory not found: {}", id));
+            }
+        }
+
+        sqlx::query("INSERT OR IGNORE INTO memory_relations (source_id, target_id, kind, created_at) VALUES (?, ?, ?, ?)")
+            .bind(&source_id)
+            .bind(&target_id)
+            .bind(&kind)
+            .bind(Utc::now())
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Memories with at least one relation become nodes, labelled with their
+    // title (or id when untitled); each relation becomes an edge
+    pub async fn export_graph(&mut self, format: GraphFormat) -> Result<String> {
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let edges: Vec<GraphEdge> = sqlx::query(
+            "SELECT r.source_id, r.target_id, r.kind
+             FROM memory_relations r
+             JOIN memories s ON s.id = r.source_id
+             JOIN memories t ON t.id = r.target_id
+             WHERE s.vault_id = ? AND t.vault_id = ?
+             ORDER BY r.source_id, r.target_id, r.kind"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| GraphEdge {
+            source: row.get("source_id"),
+            target: row.get("target_id"),
+            kind: row.get("kind"),
+        })
+        .collect();
+
+        let nodes: Vec<GraphNode> = sqlx::query(
+            "SELECT id, title, encrypted FROM memories
+             WHERE vault_id = ?
+               AND (id IN (SELECT source_id FROM memory_relations) OR id IN (SELECT target_id FROM memory_relations))
+             ORDER BY id"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| {
+            let id: String = row.get("id");
+            let label = Self::open_title_static(cipher.as_ref(), row.get("title"), row.get("encrypted"))?
+                .unwrap_or_else(|| id.clone());
+            Ok(GraphNode { id, label })
+        })
+        .collect::<Result<_>>()?;
+
+        Ok(graph_export::render(format, &nodes, &edges))
+    }
+
+    pub async fn export_data(&mut self, format: String, options: ExportOptions) -> Result<String> {
+        if format != "json" {
+            return Err(anyhow::anyhow!("Unsupported export format: {}", format));
+        }
+        let fields = Self::export_fields_static(&options)?;
+
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let rows = sqlx::query(
+            "SELECT id, title, content, source, encrypted, created_at, updated_at
+             FROM memories
+             WHERE vault_id = ?
+             ORDER BY created_at ASC, id"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_all(pool)
+        .await?;
+
+        let mut data = Vec::new();
+        for row in rows {
+            data.push(Self::export_record_static(pool, &row, &fields, cipher.as_ref()).await?);
+        }
+
+        let export_data = serde_json::json!({
+            "format": format,
+            "exported_at": Utc::now().to_rfc3339(),
+            "data": data
+        });
+
+        let payload = if options.pretty {
+            serde_json::to_string_pretty(&export_data)?
+        } else {
+            serde_json::to_string(&export_data)?
+        };
+        Ok(checksum::append(payload))
+    }
+
+    pub async fn save_search(&mut self, name: String, criteria: SearchCriteria) -> Result<SavedSearch> {
+        let _write = maintenance::begin_write()?;
+        let db = self.get_db().await?;
+        saved_searches::save(db.get_pool().await, DEFAULT_VAULT_ID, &name, criteria).await
+    }
+
+    pub async fn list_saved_searches(&mut self) -> Result<Vec<SavedSearch>> {
+        let db = self.get_db().await?;
+        saved_searches::list(db.get_pool().await, DEFAULT_VAULT_ID).await
+    }
+
+    // Runs the stored criteria exactly as search_memories would
+    pub async fn run_saved_search(&mut self, id: String, limit: Option<usize>) -> Result<Vec<MemoryEntry>> {
+        let saved = {
+            let db = self.get_db().await?;
+            saved_searches::find(db.get_pool().await, DEFAULT_VAULT_ID, &id).await?
+        };
+        self.search_criteria(saved.criteria, limit, false).await
+    }
+
+    pub async fn delete_saved_search(&mut self, id: String) -> Result<()> {
+        let _write = maintenance::begin_write()?;
+        let db = self.get_db().await?;
+        saved_searches::delete(db.get_pool().await, DEFAULT_VAULT_ID, &id).await
+    }
+
+    // Same document as `export_data`, limited to every memory the saved
+    // search matches, in the order the search returns them
+    pub async fn export_saved_search(&mut self, name: String, format: String, options: ExportOptions) -> Result<String> {
+        if format != "json" {
+            return Err(anyhow::anyhow!("Unsupported export format: {}", format));
+        }
+        let fields = Self::export_fields_static(&options)?;
+
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+        let saved = saved_searches::find_by_name(pool, DEFAULT_VAULT_ID, &name).await?;
+
+        let rows = Self::search_rows_static(pool, cipher.as_ref(), &settings, &saved.criteria, false, -1).await?;
+        let mut data = Vec::new();
+        for row in rows {
+            data.push(Self::export_record_static(pool, &row, &fields, cipher.as_ref()).await?);
+        }
+
+        let export_data = serde_json::json!({
+            "format": format,
+            "exported_at": Utc::now().to_rfc3339(),
+            "saved_search": saved.name,
+            "data": data
+        });
+
+        let payload = if options.pretty {
+            serde_json::to_string_pretty(&export_data)?
+        } else {
+            serde_json::to_string(&export_data)?
+        };
+        Ok(checksum::append(payload))
+    }
+
+    // Same document as `export_data`, written to `path` in batches so the
+    // whole vault is never held in memory. The file is written next to the
+    // target and renamed into place once complete.
+    pub async fn export_to_file(&mut self, path: String, format: String, options: ExportOptions) -> Result<ExportFileResult> {
+        use tokio::io::AsyncWriteExt;
+
+        if format != "json" {
+            return Err(anyhow::anyhow!("Unsupported export format: {}", format));
+        }
+        let fields = Self::export_fields_static(&options)?;
+
+        let target = std::path::PathBuf::from(&path);
+        let partial = target.with_extension("partial");
+
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let file = tokio::fs::File::create(&partial)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", partial.display(), e))?;
+        let mut writer = tokio::io::BufWriter::new(file);
+
+        let header = format!(
+            "{{\"format\":{},\"exported_at\":{},\"data\":[",
+            serde_json::to_string(&format)?,
+            serde_json::to_string(&Utc::now().to_rfc3339())?
+        );
+        let mut checksum = Checksum::new();
+        checksum.update(header.as_bytes());
+        writer.write_all(header.as_bytes()).await?;
+
+        // Keyset pagination on (created_at, id), matching the export order
+        let mut after: Option<(chrono::DateTime<Utc>, String)> = None;
+        let mut memory_count: u64 = 0;
+        loop {
+            let rows = sqlx::query(
+                "SELECT id, title, content, source, encrypted, created_at, updated_at
+                 FROM memories
+                 WHERE vault_id = ?
+                   AND (? IS NULL OR (created_at, id) > (?, ?))
+                 ORDER BY created_at ASC, id
+                 LIMIT ?"
+            )
+            .bind(DEFAULT_VAULT_ID)
+            .bind(after.as_ref().map(|(created_at, _)| *created_at))
+            .bind(after.as_ref().map(|(created_at, _)| *created_at))
+            .bind(after.as_ref().map(|(_, id)| id.clone()))
+            .bind(to_sql_int(EXPORT_BATCH_SIZE, "batch size")?)
+            .fetch_all(pool)
+            .await?;
+
+            let Some(last) = rows.last() else { break };
+            after = Some((last.get("created_at"), last.get("id")));
+
+            for row in &rows {
+                let record = Self::export_record_static(pool, row, &fields, cipher.as_ref()).await?;
+                let mut bytes = Vec::new();
+                if memory_count > 0 {
+                    bytes.push(b',');
+                }
+                if options.pretty {
+                    bytes.push(b'\n');
+                    bytes.extend(serde_json::to_vec_pretty(&record)?);
+                } else {
+                    bytes.extend(serde_json::to_vec(&record)?);
+                }
+                checksum.update(&bytes);
+                writer.write_all(&bytes).await?;
+                memory_count += 1;
+            }
+        }
+
+        let footer: &[u8] = if options.pretty { b"\n]}\n" } else { b"]}" };
+        checksum.update(footer);
+        writer.write_all(footer).await?;
+        writer.write_all(checksum.trailer().as_bytes()).await?;
+        writer.flush().await?;
+        drop(writer);
+
+        tokio::fs::rename(&partial, &target)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to move export into {}: {}", target.display(), e))?;
+        let bytes = tokio::fs::metadata(&target).await?.len();
+
+        Ok(ExportFileResult {
+            path,
+            bytes,
+            memory_count,
+        })
+    }
+
+    fn export_fields_static(options: &ExportOptions) -> Result<Vec<String>> {
+        match &options.fields {
+            Some(fields) => {
+                if let Some(unknown) = fields.iter().find(|f| !EXPORT_FIELDS.contains(&f.as_str())) {
+                    return Err(anyhow::anyhow!(
+                        "Unknown export field '{}', expected one of: {}",
+                        unknown,
+                        EXPORT_FIELDS.join(", ")
+                    ));
+                }
+                Ok(fields.clone())
+            }
+            None => Ok(EXPORT_FIELDS.iter().map(|f| f.to_string()).collect()),
+        }
+    }
+
+    async fn export_record_static(
+        pool: &sqlx::SqlitePool,
+        row: &sqlx::sqlite::SqliteRow,
+        fields: &[String],
+        cipher: Option<&TextCipher>,
+    ) -> Result<serde_json::Value> {
+        let memory_id: String = row.get("id");
+        let mut record = serde_json::Map::new();
+
+        for field in fields {
+            let value = match field.as_str() {
+                "id" => serde_json::json!(memory_id),
+                "title" => serde_json::json!(Self::open_title_static(cipher, row.get("title"), row.get("encrypted"))?),
+                "content" => serde_json::json!(Self::open_content_static(
+                    cipher,
+                    row.get("content"),
+                    row.get("encrypted"),
+                )?),
+                "source" => serde_json::json!(row.get::<Option<String>, _>("source")),
+                "tags" => serde_json::json!(Self::get_memory_tags_static(pool, &memory_id).await?),
+                "created_at" => serde_json::json!(row.get::<chrono::DateTime<Utc>, _>("created_at").to_rfc3339()),
+                "updated_at" => serde_json::json!(row.get::<chrono::DateTime<Utc>, _>("updated_at").to_rfc3339()),
+                _ => unreachable!("export fields are validated above"),
+            };
+            record.insert(field.clone(), value);
+        }
+
+        Ok(serde_json::Value::Object(record))
+    }
+
+    // Rows carry both RFC 3339 and SQLite `CURRENT_TIMESTAMP` values, so
+    // order by julianday() first; the raw text keeps sub-millisecond order
+    // within one format and the id breaks any remaining tie
+    pub async fn memory_neighbors(&mut self, id: String) -> Result<MemoryNeighbors> {
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let created_at: String = sqlx::query_scalar(
+            "SELECT created_at FROM memories WHERE id = ? AND vault_id = ?"
+        )
+        .bind(&id)
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Memory not found: {}", id))?;
+
+        let prev = sqlx::query(
+            "SELECT id, title, content, source, encrypted, is_pinned, created_at, updated_at
+             FROM memories
+             WHERE vault_id = ? AND (julianday(created_at), created_at, id) < (julianday(?), ?, ?)
+             ORDER BY julianday(created_at) DESC, created_at DESC, id DESC
+             LIMIT 1"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(&created_at)
+        .bind(&created_at)
+        .bind(&id)
+        .fetch_optional(pool)
+        .await?;
+
+        let next = sqlx::query(
+            "SELECT id, title, content, source, encrypted, is_pinned, created_at, updated_at
+             FROM memories
+             WHERE vault_id = ? AND (julianday(created_at), created_at, id) > (julianday(?), ?, ?)
+             ORDER BY julianday(created_at), created_at, id
+             LIMIT 1"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(&created_at)
+        .bind(&created_at)
+        .bind(&id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(MemoryNeighbors {
+            prev: match prev {
+                Some(row) => Some(Self::memory_from_row_static(pool, &row, cipher.as_ref()).await?),
+                None => None,
+            },
+            next: match next {
+                Some(row) => Some(Self::memory_from_row_static(pool, &row, cipher.as_ref()).await?),
+                None => None,
+            },
+        })
+    }
+
+    // Pinning does not touch `updated_at`, so it never reorders recency
+    pub async fn set_pinned(&mut self, id: String, pinned: bool) -> Result<()> {
+        let _write = maintenance::begin_write()?;
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let result = sqlx::query("UPDATE memories SET is_pinned = ? WHERE id = ? AND vault_id = ?")
+            .bind(pinned)
+            .bind(&id)
+            .bind(DEFAULT_VAULT_ID)
+            .execute(pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Memory not found: {}", id));
+        }
+
+        Ok(())
+    }
+
+    async fn get_memory_static(
+        pool: &sqlx::SqlitePool,
+        id: &str,
+        cipher: Option<&TextCipher>,
+    ) -> Result<Option<MemoryEntry>> {
+        let row = sqlx::query(
+            "SELECT id, title, content, source, encrypted, is_pinned, created_at, updated_at FROM memories WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(Some(Self::memory_from_row_static(pool, &row, cipher).await?)),
+            None => Ok(None),
+        }
+    }
+
+    // Expects the columns id, title, content, source, encrypted, is_pinned,
+    // created_at and updated_at
+    async fn memory_from_row_static(
+        pool: &sqlx::SqlitePool,
+        row: &sqlx::sqlite::SqliteRow,
+        cipher: Option<&TextCipher>,
+    ) -> Result<MemoryEntry> {
+        let memory_id: String = row.get("id");
+        let tags = Self::get_memory_tags_static(pool, &memory_id).await?;
+        Self::memory_with_tags_static(row, tags, cipher)
+    }
+
+    fn memory_with_tags_static(
+        row: &sqlx::sqlite::SqliteRow,
+        tags: Vec<String>,
+        cipher: Option<&TextCipher>,
+    ) -> Result<MemoryEntry> {
+        Ok(MemoryEntry {
+            id: Some(row.get("id")),
+            title: Self::open_title_static(cipher, row.get("title"), row.get("encrypted"))?,
+            content: Self::open_content_static(cipher, row.get("content"), row.get("encrypted"))?,
+            source: row.get("source"),
+            tags,
+            is_pinned: row.get("is_pinned"),
+            created_at: Some(row.get::<chrono::DateTime<Utc>, _>("created_at").to_rfc3339()),
+            updated_at: Some(row.get::<chrono::DateTime<Utc>, _>("updated_at").to_rfc3339()),
+        })
+    }
+
+    pub async fn export_memory(
+        &mut self,
+        id: String,
+        format: String,
+        share_password: Option<String>,
+    ) -> Result<String> {
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let memory = Self::get_memory_static(pool, &id, cipher.as_ref())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Memory not found: {}", id))?;
+
+        let related_ids: Vec<String> = sqlx::query(
+            "SELECT DISTINCT mt2.memory_id
+             FROM memory_tags mt1
+             JOIN memory_tags mt2 ON mt1.tag_id = mt2.tag_id
+             WHERE mt1.memory_id = ? AND mt2.memory_id != ?
+             ORDER BY mt2.memory_id"
+        )
+        .bind(&id)
+        .bind(&id)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|row| row.get("memory_id"))
+        .collect();
+
+        let bundle = MemoryBundle {
+            version: BUNDLE_VERSION,
+            exported_at: Utc::now().to_rfc3339(),
+            memory,
+            related_ids,
+        };
+
+        let output = match format.as_str() {
+            "json" => serde_json::to_string_pretty(&bundle)?,
+            "markdown" => Self::bundle_to_markdown_static(&bundle),
+            other => return Err(anyhow::anyhow!("Unsupported bundle format: {}", other)),
+        };
+
+        match share_password {
+            Some(password) => {
+                let crypto = CryptoManager::new();
+                let salt = crypto.generate_salt();
+                let key = crypto.derive_key(&password, &salt)?;
+                let ciphertext = crypto.encrypt_data(output.as_bytes(), &key)?;
+
+                Ok(serde_json::to_string_pretty(&serde_json::json!({
+                    "version": BUNDLE_VERSION,
+                    "encrypted": true,
+                    "format": format,
+                    "salt": BASE64.encode(salt),
+                    "payload": BASE64.encode(ciphertext),
+                }))?)
+            }
+            None => Ok(output),
+        }
+    }
+
+    fn bundle_to_markdown_static(bundle: &MemoryBundle) -> String {
+        let memory = &bundle.memory;
+        let mut out = format!("# {}\n\n", memory.title.as_deref().unwrap_or("Untitled memory"));
+
+        out.push_str(&format!("- id: {}\n", memory.id.as_deref().unwrap_or_default()));
+        if !memory.tags.is_empty() {
+            out.push_str(&format!("- tags: {}\n", memory.tags.join(", ")));
+        }
+        if let Some(source) = &memory.source {
+            out.push_str(&format!("- source: {}\n", source));
+        }
+        if let Some(created_at) = &memory.created_at {
+            out.push_str(&format!("- created: {}\n", created_at));
+        }
+        if !bundle.related_ids.is_empty() {
+            out.push_str(&format!("- related: {}\n", bundle.related_ids.join(", ")));
+        }
+
+        out.push_str(&format!("\n{}\n", memory.content));
+        out
+    }
+
+    pub async fn import_memory(&mut self, bundle: String, share_password: Option<String>) -> Result<String> {
+        let value: serde_json::Value = serde_json::from_str(&bundle)?;
+
+        let json = if value.get("encrypted").and_then(|v| v.as_bool()).unwrap_or(false) {
+            let password = share_password
+                .ok_or_else(|| anyhow::anyhow!("This bundle is encrypted; a share password is required"))?;
+            let field = |name: &str| -> Result<Vec<u8>> {
+                let encoded = value
+                    .get(name)
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| anyhow::anyhow!("Encrypted bundle is missing '{}'", name))?;
+                Ok(BASE64.decode(encoded)?)
+            };
+
+            let crypto = CryptoManager::new();
+            let key = crypto.derive_key(&password, &field("salt")?)?;
+            let plaintext = crypto
+                .decrypt_data(&field("payload")?, &key)
+                .map_err(|_| anyhow::anyhow!("Wrong share password or corrupted bundle"))?;
+            String::from_utf8(plaintext)?
+        } else {
+            bundle
+        };
+
+        let bundle: MemoryBundle = serde_json::from_str(&json)
+            .map_err(|e| anyhow::anyhow!("Only JSON bundles can be imported: {}", e))?;
+        if bundle.version > BUNDLE_VERSION {
+            return Err(anyhow::anyhow!("Unsupported bundle version: {}", bundle.version));
+        }
+
+        let mut memory = bundle.memory;
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        // Keep the original id unless it would collide with an existing memory
+        if let Some(id) = &memory.id {
+            if Self::get_memory_static(pool, id, cipher.as_ref()).await?.is_some() {
+                memory.id = None;
+            }
+        }
+
+        self.add_memory(memory).await
+    }
+
+    // Imports each segment of a text file as its own memory, all sharing the
+    // file path as their source
+    pub async fn import_text_file(&mut self, path: String, split_strategy: SplitStrategy) -> Result<TextImportResult> {
+        let bytes = tokio::fs::read(&path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+        let text = text_import::decode_text(&bytes);
+        let segments = text_import::split_text(&text, &split_strategy)?;
+
+        let mut memory_ids = Vec::with_capacity(segments.len());
+        for segment in segments {
+            let title = segment
+                .title
+                .map(|t| t.chars().take(validation::MAX_TITLE_CHARS).collect::<String>());
+            let memory_id = self
+                .add_memory(MemoryEntry {
+                    id: None,
+                    title,
+                    content: segment.content,
+                    source: Some(path.clone()),
+                    tags: Vec::new(),
+                    is_pinned: false,
+                    created_at: None,
+                    updated_at: None,
+                })
+                .await?;
+            memory_ids.push(memory_id);
+        }
+
+        Ok(TextImportResult {
+            source: path,
+            memory_ids,
+        })
+    }
+
+    // Imports every file under `path` that matches `glob`. Markdown notes
+    // become one memory each, titled and tagged from their frontmatter;
+    // other files are split with `split_strategy`. The source of each memory
+    // is the file's path relative to `path`. Binary, empty and oversized
+    // files are skipped and reported.
+    pub async fn import_directory(
+        &mut self,
+        path: String,
+        glob: Option<String>,
+        split_strategy: SplitStrategy,
+    ) -> Result<DirectoryImportResult> {
+        let glob = Glob::new(glob.as_deref().unwrap_or(folder_import::DEFAULT_GLOB))?;
+        let root = std::path::PathBuf::from(&path);
+        if !tokio::fs::metadata(&root).await.map(|m| m.is_dir()).unwrap_or(false) {
+            return Err(anyhow::anyhow!("Not a directory: {}", path));
+        }
+        let files = folder_import::collect_files(&root, &glob, MAX_IMPORT_FILES).await?;
+
+        let mut memory_ids = Vec::new();
+        let mut files_imported = 0u64;
+        let mut skipped = Vec::new();
+        for file in files {
+            let skip = |reason: String| SkippedFile { path: file.relative.clone(), reason };
+            if file.size > validation::MAX_CONTENT_BYTES as u64 {
+                skipped.push(skip(format!("larger than {} bytes", validation::MAX_CONTENT_BYTES)));
+                continue;
+            }
+            let bytes = tokio::fs::read(&file.path)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", file.path.display(), e))?;
+            if text_import::looks_binary(&bytes) {
+                skipped.push(skip("binary file".to_string()));
+                continue;
+            }
+            let text = text_import::decode_text(&bytes);
+
+            let is_markdown = file
+                .path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e.eq_ignore_ascii_case("md") || e.eq_ignore_ascii_case("markdown"));
+            let entries: Vec<(Option<String>, String, Vec<String>)> = if is_markdown {
+                let (frontmatter, body) = text_import::parse_frontmatter(&text);
+                let title = frontmatter
+                    .title
+                    .or_else(|| file.path.file_stem().map(|s| s.to_string_lossy().into_owned()));
+                vec![(title, body.trim().to_string(), frontmatter.tags)]
+            } else {
+                text_import::split_text(&text, &split_strategy)?
+                    .into_iter()
+                    .map(|segment| (segment.title, segment.content, Vec::new()))
+                    .collect()
+            };
+            if entries.iter().all(|(_, content, _)| content.trim().is_empty()) {
+                skipped.push(skip("no content".to_string()));
+                continue;
+            }
+
+            for (title, content, tags) in entries {
+                let memory_id = self
+                    .add_memory(MemoryEntry {
+                        id: None,
+                        title: title.map(|t| t.chars().take(validation::MAX_TITLE_CHARS).collect()),
+                        content,
+                        source: Some(file.relative.clone()),
+                        tags,
+                        is_pinned: false,
+                        created_at: None,
+                        updated_at: None,
+                    })
+                    .await?;
+                memory_ids.push(memory_id);
+            }
+            files_imported += 1;
+        }
+
+        Ok(DirectoryImportResult {
+            root: path,
+            files_imported,
+            memory_ids,
+            skipped,
+        })
+    }
+
+    // One memory per bookmark in a browser's bookmark export, with the URL as
+    // its source and the enclosing folders as a hierarchical tag, e.g.
+    // `Bookmarks bar/Rust`. Links that are not http(s), such as
+    // `javascript:` bookmarklets, are skipped and reported.
+    pub async fn import_bookmarks(&mut self, path: String) -> Result<BookmarkImportResult> {
+        let bytes = tokio::fs::read(&path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+        let bookmarks = bookmark_import::parse_bookmarks(&text_import::decode_text(&bytes))?;
+
+        let mut memory_ids = Vec::new();
+        let mut skipped = Vec::new();
+        for bookmark in bookmarks {
+            let is_web = url::Url::parse(&bookmark.url).is_ok_and(|u| matches!(u.scheme(), "http" | "https"));
+            if !is_web {
+                skipped.push(SkippedBookmark { url: bookmark.url, reason: "not an http(s) link".to_string() });
+                continue;
+            }
+
+            let title = if bookmark.title.is_empty() { bookmark.url.clone() } else { bookmark.title };
+            let mut content = format!("{}\n{}", title, bookmark.url);
+            if let Some(description) = &bookmark.description {
+                content.push_str("\n\n");
+                content.push_str(description);
+            }
+            // A `/` inside a folder name would otherwise add a level
+            let folder_tag = bookmark
+                .folders
+                .iter()
+                .map(|folder| folder.replace('/', "-"))
+                .collect::<Vec<_>>()
+                .join("/");
+            let folder_tag = validation::normalize_tag(&folder_tag);
+
+            let memory_id = self
+                .add_memory(MemoryEntry {
+                    id: None,
+                    title: Some(title.chars().take(validation::MAX_TITLE_CHARS).collect()),
+                    content,
+                    source: Some(bookmark.url),
+                    tags: if folder_tag.is_empty() { Vec::new() } else { vec![folder_tag] },
+                    is_pinned: false,
+                    created_at: None,
+                    updated_at: None,
+                })
+                .await?;
+            memory_ids.push(memory_id);
+        }
+
+        Ok(BookmarkImportResult {
+            path,
+            memory_ids,
+            skipped,
+        })
+    }
+
+    // One memory per note in an Evernote export. Titles, tags and source URLs
+    // carry over and the note's created/updated dates replace the import
+    // time. Attachments are listed in the content but not stored; notes with
+    // no text are skipped and reported.
+    pub async fn import_enex(&mut self, path: String) -> Result<EnexImportResult> {
+        let _write = maintenance::begin_write()?;
+        let bytes = tokio::fs::read(&path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+        let notes = enex_import::parse_enex(&text_import::decode_text(&bytes))?;
+
+        let mut memory_ids = Vec::new();
+        let mut skipped = Vec::new();
+        let mut attachment_count = 0u64;
+        for note in notes {
+            if note.content.is_empty() {
+                skipped.push(SkippedNote { title: note.title, reason: "note has no text".to_string() });
+                continue;
+            }
+            attachment_count += note.attachments.len() as u64;
+
+            // A malformed URL would fail validation for the whole note
+            let source = note.source_url.filter(|url| validation::validate_source(url).valid);
+            let memory_id = self
+                .add_memory(MemoryEntry {
+                    id: None,
+                    title: note.title.map(|t| t.chars().take(validation::MAX_TITLE_CHARS).collect()),
+                    content: note.content,
+                    source,
+                    tags: note.tags,
+                    is_pinned: false,
+                    created_at: None,
+                    updated_at: None,
+                })
+                .await?;
+
+            if let Some(created_at) = note.created_at {
+                let updated_at = note.updated_at.unwrap_or(created_at).max(created_at);
+                let pool = self.get_db().await?.get_pool().await;
+                sqlx::query("UPDATE memories SET created_at = ?, updated_at = ? WHERE id = ?")
+                    .bind(created_at)
+                    .bind(updated_at)
+                    .bind(&memory_id)
+                    .execute(pool)
+                    .await?;
+            }
+            memory_ids.push(memory_id);
+        }
+
+        Ok(EnexImportResult {
+            path,
+            memory_ids,
+            skipped,
+            attachment_count,
+        })
+    }
+
+    pub async fn import_data(&mut self, data: String, _format: String) -> Result<()> {
+        // Fails with `checksum::CorruptedFile` before anything is parsed
+        let payload = checksum::verify(&data)?;
+        // Simplified import - in real implementation, parse and import data
+        let _parsed: serde_json::Value = serde_json::from_str(payload)?;
+        Ok(())
+    }
+
+    pub async fn embed_text(&mut self, text: String) -> Result<EmbeddingResult> {
+        if text.trim().is_empty() {
+            return Err(anyhow::anyhow!("Cannot embed empty text"));
+        }
+        if text.chars().count() > MAX_EMBED_TEXT_CHARS {
+            return Err(anyhow::anyhow!(
+                "Text exceeds the embedding limit of {} characters",
+                MAX_EMBED_TEXT_CHARS
+            ));
+        }
+
+        let vector = self.embedder.embed_one(&text).await?;
+
+        Ok(EmbeddingResult {
+            dimension: vector.len(),
+            vector,
+            model: self.embedder.model_name().to_string(),
+        })
+    }
+
+    pub async fn test_provider(&mut self) -> Result<ProviderStatus> {
+        let started = std::time::Instant::now();
+        let result = self.embedder.embed_one("connection test").await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        let expected = self.embedder.dimensions();
+        let (vector, error) = match result {
+            Ok(vector) => (vector, None),
+            Err(e) => (Vec::new(), Some(e.to_string())),
+        };
+        let error = if error.is_some() {
+            error
+        } else if vector.len() != expected {
+            Some(format!(
+                "Provider returned {} dimensions but reports {}",
+                vector.len(),
+                expected
+            ))
+        } else if vector.iter().all(|v| *v == 0.0) {
+            Some("Provider returned an empty vector".to_string())
+        } else {
+            None
+        };
+
+        Ok(ProviderStatus {
+            ok: error.is_none(),
+            model: self.embedder.model_name().to_string(),
+            dimension: vector.len(),
+            latency_ms,
+            error,
+        })
+    }
+
+    // Embeds every chunk in the vault that has no vector for the current model
+    pub async fn sync_embeddings(&mut self) -> Result<u64> {
+        self.sync_embeddings_with_progress(&mut ProcessMemory::new(), |_| {}).await
+    }
+
+    // Embeds and commits in batches sized by `embedding_throttle`, reporting
+    // after each one. Batches already committed stay if a later one fails.
+    pub async fn sync_embeddings_with_progress(
+        &mut self,
+        memory: &mut impl MemorySignal,
+        mut on_progress: impl FnMut(&EmbeddingSyncProgress),
+    ) -> Result<u64> {
+        let _write = maintenance::begin_write()?;
+        let cipher = self.text_cipher();
+        let model = self.embedder.model_name().to_string();
+
+        let (settings, total) = {
+            let pool = self.get_db().await?.get_pool().await;
+            let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+            let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) {}", PENDING_EMBEDDINGS))
+                .bind(DEFAULT_VAULT_ID)
+                .bind(&model)
+                .fetch_one(pool)
+                .await?;
+            (settings, to_count(total, "pending chunk count")?)
+        };
+        let mut throttle = BatchThrottle::new(&settings.embedding_sync);
+
+        let mut embedded = 0u64;
+        let mut after: Option<String> = None;
+        loop {
+            let batch_size = throttle.batch_size();
+            let rows = {
+                let pool = self.get_db().await?.get_pool().await;
+                Self::pending_embedding_rows_static(pool, &model, after.as_deref(), Some(batch_size)).await?
+            };
+            let Some(last) = rows.last() else { break };
+            after = Some(last.get("id"));
+
+            let contents = rows
+                .iter()
+                .map(|row| Self::open_content_static(cipher.as_ref(), row.get("content"), row.get("encrypted")))
+                .collect::<Result<Vec<_>>>()?;
+            let mut vectors = Vec::with_capacity(rows.len());
+            for (row, vector) in rows.iter().zip(self.embedder.embed(&contents).await?) {
+                let chunk_id: String = row.get("id");
+                vectors.push((chunk_id, vector_codec::encode(&vector)?));
+            }
+            drop(contents);
+            drop(rows);
+
+            let pool = self.get_db().await?.get_pool().await;
+            let now = Utc::now();
+            let mut tx = pool.begin().await?;
+            for (chunk_id, blob) in &vectors {
+                sqlx::query(
+                    "INSERT INTO embeddings (id, chunk_id, vector, model_name, created_at) VALUES (?, ?, ?, ?, ?)"
+                )
+                .bind(Uuid::new_v4().to_string())
+                .bind(chunk_id)
+                .bind(blob)
+                .bind(&model)
+                .bind(now)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await?;
+            embedded += vectors.len() as u64;
+            drop(vectors);
+
+            let rss_bytes = memory.rss_bytes();
+            let pause = throttle.observe(rss_bytes);
+            on_progress(&EmbeddingSyncProgress {
+                embedded,
+                total,
+                batch_size: throttle.batch_size(),
+                rss_bytes,
+            });
+            if let Some(pause) = pause {
+                tokio::time::sleep(pause).await;
+            }
+        }
+
+        Ok(embedded)
+    }
+
+    // Chunks in the vault with no vector for `model` yet, in id order after
+    // `after`
+    async fn pending_embedding_rows_static(
+        pool: &sqlx::SqlitePool,
+        model: &str,
+        after: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<sqlx::sqlite::SqliteRow>> {
+        // SQLite treats a negative LIMIT as no limit
+        let limit = match limit {
+            Some(limit) => to_sql_int(limit, "batch size")?,
+            None => -1,
+        };
+        Ok(sqlx::query(&format!(
+            "SELECT c.id, c.content, c.encrypted {} AND (? IS NULL OR c.id > ?) ORDER BY c.id LIMIT ?",
+            PENDING_EMBEDDINGS
+        ))
+        .bind(DEFAULT_VAULT_ID)
+        .bind(model)
+        .bind(after)
+        .bind(after)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?)
+    }
+
+    // What sync_embeddings would send to the provider, without embedding
+    // anything
+    pub async fn estimate_embedding_sync(&mut self) -> Result<EmbeddingSyncEstimate> {
+        let cipher = self.text_cipher();
+        let model = self.embedder.model_name().to_string();
+        let pending = {
+            let pool = self.get_db().await?.get_pool().await;
+            Self::pending_embedding_rows_static(pool, &model, None, None).await?
+        };
+
+        let mut estimated_tokens = 0u64;
+        for row in &pending {
+            let content = Self::open_content_static(cipher.as_ref(), row.get("content"), row.get("encrypted"))?;
+            estimated_tokens += self.embedder.count_tokens(&content) as u64;
+        }
+
+        Ok(EmbeddingSyncEstimate {
+            model,
+            chunks_to_embed: pending.len() as u64,
+            estimated_tokens,
+            estimated_requests: pending.len().div_ceil(self.embedder.batch_size()) as u64,
+        })
+    }
+
+    // Mean of the stored chunk vectors per memory, for memories whose chunks
+    // have been embedded with `model`
+    async fn load_memory_vectors_static(pool: &sqlx::SqlitePool, model: &str) -> Result<HashMap<String, Vec<f32>>> {
+        let rows = sqlx::query(
+            "SELECT c.memory_id, e.vector
+             FROM embeddings e
+             JOIN chunks c ON e.chunk_id = COALESCE(c.embedding_chunk_id, c.id)
+             JOIN memories m ON c.memory_id = m.id
+             WHERE m.vault_id = ? AND e.model_name = ?"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(model)
+        .fetch_all(pool)
+        .await?;
+
+        let mut sums: HashMap<String, (Vec<f32>, usize)> = HashMap::new();
+        for row in rows {
+            let memory_id: String = row.get("memory_id");
+            let vector = vector_codec::decode(&row.get::<Vec<u8>, _>("vector"))
+                .map_err(|e| anyhow::anyhow!("Bad embedding for memory {}: {}", memory_id, e))?;
+            let (sum, count) = sums.entry(memory_id).or_insert_with(|| (vec![0.0; vector.len()], 0));
+            if sum.len() != vector.len() {
+                return Err(anyhow::anyhow!("Embeddings for model {} have mixed dimensions", model));
+            }
+            for (total, value) in sum.iter_mut().zip(&vector) {
+                *total += value;
+            }
+            *count += 1;
+        }
+
+        Ok(sums
+            .into_iter()
+            .map(|(memory_id, (sum, count))| (memory_id, sum.into_iter().map(|v| v / count as f32).collect()))
+            .collect())
+    }
+
+    pub async fn clear_embeddings(&mut self) -> Result<u64> {
+        let _write = maintenance::begin_write()?;
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        // Memories and chunks are left intact so sync_embeddings can rebuild
+        let mut tx = pool.begin().await?;
+
+        let removed = sqlx::query(
+            "DELETE FROM embeddings
+             WHERE chunk_id IN (
+                 SELECT c.id FROM chunks c
+                 JOIN memories m ON c.memory_id = m.id
+                 WHERE m.vault_id = ?
+             )"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+
+        Ok(removed)
+    }
+
+    // Writes the vault's vectors for the current model, keyed by chunk id and
+    // without any content, so another copy of the vault can skip
+    // sync_embeddings. Written next to `path` and renamed into place.
+    pub async fn export_embeddings(&mut self, path: String) -> Result<EmbeddingExportResult> {
+        let model = self.embedder.model_name().to_string();
+        let dimensions = self.embedder.dimensions();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let rows = sqlx::query(
+            "SELECT e.chunk_id, e.vector
+             FROM embeddings e
+             JOIN chunks c ON e.chunk_id = c.id
+             JOIN memories m ON c.memory_id = m.id
+             WHERE m.vault_id = ? AND e.model_name = ?
+             ORDER BY e.chunk_id, e.id"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(&model)
+        .fetch_all(pool)
+        .await?;
+
+        let mut entries: Vec<IndexEntry> = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let chunk_id: String = row.get("chunk_id");
+            // Only the first vector is kept if a chunk was embedded twice
+            if entries.last().is_some_and(|e| e.chunk_id == chunk_id) {
+                continue;
+            }
+            let vector = vector_codec::decode(&row.get::<Vec<u8>, _>("vector"))
+                .map_err(|e| anyhow::anyhow!("Bad embedding for chunk {}: {}", chunk_id, e))?;
+            entries.push(IndexEntry { chunk_id, vector });
+        }
+
+        let chunk_count = entries.len() as u64;
+        let bytes = embedding_index::encode(&EmbeddingIndex { model: model.clone(), dimensions, entries })?;
+
+        let target = std::path::PathBuf::from(&path);
+        let partial = target.with_extension("partial");
+        tokio::fs::write(&partial, &bytes)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", partial.display(), e))?;
+        tokio::fs::rename(&partial, &target)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to move embeddings into {}: {}", target.display(), e))?;
+
+        Ok(EmbeddingExportResult {
+            path,
+            bytes: bytes.len() as u64,
+            model,
+            dimensions,
+            chunk_count,
+        })
+    }
+
+    // Restores vectors from `export_embeddings` for chunks this vault has,
+    // replacing any it already holds for that model. The file must be for
+    // the current model and dimension; entries for unknown chunks are
+    // counted and skipped.
+    pub async fn import_embeddings(&mut self, path: String) -> Result<EmbeddingImportResult> {
+        let _write = maintenance::begin_write()?;
+        let bytes = tokio::fs::read(&path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+        let index = embedding_index::decode(&bytes)?;
+
+        let model = self.embedder.model_name().to_string();
+        if index.model != model {
+            return Err(anyhow::anyhow!("Embeddings are for model {}, but the vault uses {}", index.model, model));
+        }
+        if index.dimensions != self.embedder.dimensions() {
+            return Err(anyhow::anyhow!(
+                "Embeddings have {} dimensions, but {} produces {}",
+                index.dimensions,
+                model,
+                self.embedder.dimensions()
+            ));
+        }
+
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        // Checked before the transaction, which then only writes
+        let mut present = Vec::with_capacity(index.entries.len());
+        for entry in &index.entries {
+            let exists: bool = sqlx::query_scalar(
+                "SELECT EXISTS (
+                     SELECT 1 FROM chunks c JOIN memories m ON c.memory_id = m.id
+                     WHERE c.id = ? AND m.vault_id = ?
+                 )"
+            )
+            .bind(&entry.chunk_id)
+            .bind(DEFAULT_VAULT_ID)
+            .fetch_one(pool)
+            .await?;
+            if exists {
+                present.push(entry);
+            }
+        }
+        let missing_chunks = (index.entries.len() - present.len()) as u64;
+
+        let now = Utc::now();
+        let mut tx = pool.begin().await?;
+        for entry in &present {
+            sqlx::query("DELETE FROM embeddings WHERE chunk_id = ? AND model_name = ?")
+                .bind(&entry.chunk_id)
+                .bind(&model)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(
+                "INSERT INTO embeddings (id, chunk_id, vector, model_name, created_at) VALUES (?, ?, ?, ?, ?)"
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(&entry.chunk_id)
+            .bind(vector_codec::encode(&entry.vector)?)
+            .bind(&model)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(EmbeddingImportResult {
+            model,
+            imported: present.len() as u64,
+            missing_chunks,
+        })
+    }
+
+    // Cosine similarity across different dimensions is meaningless, so a
+    // stray vector quietly drops its chunk from semantic search. With
+    // `reembed`, offending vectors are deleted and their chunks embedded again.
+    // Chunks with identical text, compared by the SHA-256 of their
+    // plaintext, keep their own rows (positions and citations differ per
+    // memory) but share one set of embeddings: one chunk per group keeps the
+    // embeddings and the others point at it through `embedding_chunk_id`.
+    // Vectors a duplicate holds for models the kept chunk lacks are moved
+    // over; the rest are deleted.
+    pub async fn deduplicate_chunks(&mut self) -> Result<ChunkDedupResult> {
+        let _write = maintenance::begin_write()?;
+        let cipher = self.text_cipher();
+        let model = self.embedder.model_name().to_string();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let groups = Self::duplicate_chunk_groups_static(pool, cipher.as_ref(), &model).await?;
+
+        let mut result = ChunkDedupResult { duplicate_groups: 0, shared_chunks: 0, embeddings_removed: 0 };
+        let mut tx = pool.begin().await?;
+        for group in &groups {
+            let Some((kept, duplicates)) = group.split_first() else {
+                continue;
+            };
+            result.duplicate_groups += 1;
+
+            for duplicate in duplicates {
+                sqlx::query(
+                    "UPDATE embeddings SET chunk_id = ?
+                     WHERE chunk_id = ? AND model_name NOT IN (SELECT model_name FROM embeddings WHERE chunk_id = ?)"
+                )
+                .bind(kept)
+                .bind(duplicate)
+                .bind(kept)
+                .execute(&mut *tx)
+                .await?;
+                result.embeddings_removed += sqlx::query("DELETE FROM embeddings WHERE chunk_id = ?")
+                    .bind(duplicate)
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+                sqlx::query("UPDATE chunks SET embedding_chunk_id = ? WHERE id = ?")
+                    .bind(kept)
+                    .bind(duplicate)
+                    .execute(&mut *tx)
+                    .await?;
+                result.shared_chunks += 1;
+            }
+        }
+        tx.commit().await?;
+
+        Ok(result)
+    }
+
+    // Chunk ids with identical text, two or more per group; the first id in
+    // each group is the one deduplicate_chunks keeps
+    async fn duplicate_chunk_groups_static(
+        pool: &sqlx::SqlitePool,
+        cipher: Option<&TextCipher>,
+        model: &str,
+    ) -> Result<Vec<Vec<String>>> {
+        // Chunks embedded with the current model sort first within a group,
+        // so the one that is kept never needs re-embedding
+        let rows = sqlx::query(
+            "SELECT c.id, c.content, c.encrypted,
+                    EXISTS (SELECT 1 FROM embeddings e WHERE e.chunk_id = c.id AND e.model_name = ?) AS embedded
+             FROM chunks c
+             JOIN memories m ON c.memory_id = m.id
+             WHERE m.vault_id = ? AND c.embedding_chunk_id IS NULL
+             ORDER BY embedded DESC, c.created_at, c.id"
+        )
+        .bind(model)
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_all(pool)
+        .await?;
+
+        let mut groups: HashMap<[u8; 32], Vec<String>> = HashMap::new();
+        let mut order: Vec<[u8; 32]> = Vec::new();
+        for row in &rows {
+            let content = Self::open_content_static(cipher, row.get("content"), row.get("encrypted"))?;
+            let hash: [u8; 32] = Sha256::digest(content.as_bytes()).into();
+            let ids = groups.entry(hash).or_default();
+            if ids.is_empty() {
+                order.push(hash);
+            }
+            ids.push(row.get("id"));
+        }
+
+        Ok(order
+            .iter()
+            .filter_map(|hash| groups.remove(hash))
+            .filter(|ids| ids.len() > 1)
+            .collect())
+    }
+
+    // Bytes each optimization would free, computed without changing
+    // anything. f16 halves the values of every f32 vector in the vault;
+    // dedup frees the vectors deduplicate_chunks would delete (those for
+    // models the kept chunk already has); orphan cleanup frees chunks,
+    // vectors and versions whose memory or chunk no longer exists, in any
+    // vault. Figures are payload sizes; the file only shrinks once the freed
+    // pages are vacuumed.
+    pub async fn estimate_storage_savings(&mut self) -> Result<StorageSavingsEstimate> {
+        let cipher = self.text_cipher();
+        let model = self.embedder.model_name().to_string();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let header_len = to_sql_int(vector_codec::HEADER_LEN, "header length")?;
+        let f16_savings: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(SUM((length(e.vector) - ?) / 2), 0)
+             FROM embeddings e
+             JOIN chunks c ON e.chunk_id = c.id
+             JOIN memories m ON c.memory_id = m.id
+             WHERE m.vault_id = ? AND length(e.vector) > ? AND substr(e.vector, 4, 1) = X'00'"
+        )
+        .bind(header_len)
+        .bind(DEFAULT_VAULT_ID)
+        .bind(header_len)
+        .fetch_one(pool)
+        .await?;
+
+        let mut dedup_savings = 0i64;
+        for group in Self::duplicate_chunk_groups_static(pool, cipher.as_ref(), &model).await? {
+            let Some((kept, duplicates)) = group.split_first() else {
+                continue;
+            };
+            for duplicate in duplicates {
+                let freed: i64 = sqlx::query_scalar(
+                    "SELECT COALESCE(SUM(length(vector)), 0) FROM embeddings
+                     WHERE chunk_id = ? AND model_name IN (SELECT model_name FROM embeddings WHERE chunk_id = ?)"
+                )
+                .bind(duplicate)
+                .bind(kept)
+                .fetch_one(pool)
+                .await?;
+                dedup_savings += freed;
+            }
+        }
+
+        let orphan_savings: i64 = sqlx::query_scalar(
+            "SELECT
+                (SELECT COALESCE(SUM(length(e.vector)), 0) FROM embeddings e
+                 WHERE NOT EXISTS (SELECT 1 FROM chunks c JOIN memories m ON m.id = c.memory_id WHERE c.id = e.chunk_id))
+              + (SELECT COALESCE(SUM(length(CAST(c.content AS BLOB))), 0) FROM chunks c
+                 WHERE NOT EXISTS (SELECT 1 FROM memories m WHERE m.id = c.memory_id))
+              + (SELECT COALESCE(SUM(length(CAST(v.content AS BLOB))), 0) FROM memory_versions v
+                 WHERE NOT EXISTS (SELECT 1 FROM memories m WHERE m.id = v.memory_id))"
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(StorageSavingsEstimate {
+            f16_savings_bytes: to_count(f16_savings, "f16 savings")?,
+            chunk_dedup_savings_bytes: to_count(dedup_savings, "dedup savings")?,
+            orphan_cleanup_bytes: to_count(orphan_savings, "orphan savings")?,
+        })
+    }
+
+    pub async fn verify_embedding_dimensions(&mut self, reembed: bool) -> Result<DimensionReport> {
+        let _write = if reembed { Some(maintenance::begin_write()?) } else { None };
+        let model = self.embedder.model_name().to_string();
+        let expected = self.embedder.dimensions();
+        let pool = self.get_db().await?.get_pool().await;
+
+        let rows = sqlx::query(
+            "SELECT e.id, e.vector
+             FROM embeddings e
+             JOIN chunks c ON e.chunk_id = c.id
+             JOIN memories m ON c.memory_id = m.id
+             WHERE m.vault_id = ? AND e.model_name = ?
+             ORDER BY e.id"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(&model)
+        .fetch_all(pool)
+        .await?;
+
+        let mismatched_ids: Vec<String> = rows
+            .iter()
+            .filter(|row| {
+                vector_codec::decode(&row.get::<Vec<u8>, _>("vector"))
+                    .map_or(true, |vector| vector.len() != expected)
+            })
+            .map(|row| row.get("id"))
+            .collect();
+
+        let mut reembedded = 0;
+        if reembed && !mismatched_ids.is_empty() {
+            let mut tx = pool.begin().await?;
+            for id in &mismatched_ids {
+                sqlx::query("DELETE FROM embeddings WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await?;
+            reembedded = self.sync_embeddings().await?;
+        }
+
+        Ok(DimensionReport {
+            model,
+            expected,
+            mismatched_count: mismatched_ids.len() as u64,
+            mismatched_ids,
+            reembedded,
+        })
+    }
+
+    // Every chunk of a memory with the embeddings stored for it, in text
+    // order. Vectors are summarized by dimension and norm; the values
+    // themselves are only returned with `include_vectors`.
+    pub async fn memory_embedding_debug(&mut self, id: String, include_vectors: bool) -> Result<MemoryEmbeddingDebug> {
+        let model = self.embedder.model_name().to_string();
+        let expected_dimensions = self.embedder.dimensions();
+        let pool = self.get_db().await?.get_pool().await;
+
+        let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM memories WHERE id = ? AND vault_id = ?)")
+            .bind(&id)
+            .bind(DEFAULT_VAULT_ID)
+            .fetch_one(pool)
+            .await?;
+        if !exists {
+            return Err(anyhow::anyhow!("Memory not found: {}", id));
+        }
+
+        let chunk_rows = sqlx::query(
+            "SELECT id, kind, start_pos, end_pos FROM chunks WHERE memory_id = ? ORDER BY start_pos, id"
+        )
+        .bind(&id)
+        .fetch_all(pool)
+        .await?;
+        let embedding_rows = sqlx::query(
+            "SELECT c.id AS chunk_id, e.id, e.model_name, e.vector
+             FROM embeddings e
+             JOIN chunks c ON e.chunk_id = COALESCE(c.embedding_chunk_id, c.id)
+             WHERE c.memory_id = ?
+             ORDER BY e.model_name, e.id"
+        )
+        .bind(&id)
+        .fetch_all(pool)
+        .await?;
+
+        let mut embeddings_by_chunk: HashMap<String, Vec<EmbeddingDebug>> = HashMap::new();
+        for row in &embedding_rows {
+            let vector = vector_codec::decode(&row.get::<Vec<u8>, _>("vector")).ok();
+            let norm = vector.as_ref().map(|v| v.iter().map(|x| x * x).sum::<f32>().sqrt());
+            embeddings_by_chunk
+                .entry(row.get("chunk_id"))
+                .or_default()
+                .push(EmbeddingDebug {
+                    embedding_id: row.get("id"),
+                    model_name: row.get("model_name"),
+                    dimensions: vector.as_ref().map(|v| v.len()),
+                    norm,
+                    degenerate: norm.is_none_or(|n| n == 0.0 || !n.is_finite()),
+                    vector: vector.filter(|_| include_vectors),
+                });
+        }
+
+        let chunks = chunk_rows
+            .iter()
+            .map(|row| {
+                let chunk_id: String = row.get("id");
+                let embeddings = embeddings_by_chunk.remove(&chunk_id).unwrap_or_default();
+                ChunkEmbeddingDebug {
+                    missing_embedding: !embeddings.iter().any(|e| e.model_name == model),
+                    chunk_id,
+                    kind: row.get("kind"),
+                    start_pos: row.get("start_pos"),
+                    end_pos: row.get("end_pos"),
+                    embeddings,
+                }
+            })
+            .collect();
+
+        Ok(MemoryEmbeddingDebug {
+            memory_id: id,
+            model,
+            expected_dimensions,
+            chunks,
+        })
+    }
+
+    pub async fn list_large_embeddings(&mut self, limit: Option<usize>) -> Result<Vec<EmbeddingInfo>> {
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let limit = to_sql_int(limit.unwrap_or(20), "limit")?;
+
+        let rows = sqlx::query(
+            "SELECT e.id, e.chunk_id, c.memory_id, m.title, m.encrypted, e.model_name,
+                    LENGTH(e.vector) AS size_bytes, e.created_at
+             FROM embeddings e
+             JOIN chunks c ON e.chunk_id = c.id
+             JOIN memories m ON c.memory_id = m.id
+             WHERE m.vault_id = ?
+             ORDER BY size_bytes DESC, e.id
+             LIMIT ?"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(EmbeddingInfo {
+                    id: row.get("id"),
+                    chunk_id: row.get("chunk_id"),
+                    memory_id: row.get("memory_id"),
+                    memory_title: Self::open_title_static(cipher.as_ref(), row.get("title"), row.get("encrypted"))?,
+                    model_name: row.get("model_name"),
+                    size_bytes: to_count(row.get("size_bytes"), "embedding size")?,
+                    created_at: row.get::<chrono::DateTime<Utc>, _>("created_at").to_rfc3339(),
+                })
+            })
+            .collect()
+    }
+
+    // Deletes embeddings matching every given criterion; at least one is
+    // required so a missing argument cannot wipe all embeddings
+    pub async fn prune_embeddings(&mut self, older_than: Option<String>, model: Option<String>) -> Result<u64> {
+        if older_than.is_none() && model.is_none() {
+            return Err(anyhow::anyhow!("Specify older_than and/or model to prune embeddings"));
+        }
+        let older_than = older_than
+            .map(|ts| {
+                chrono::DateTime::parse_from_rfc3339(&ts)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .map_err(|e| anyhow::anyhow!("Invalid timestamp '{}': {}", ts, e))
+            })
+            .transpose()?;
+
+        let _write = maintenance::begin_write()?;
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let removed = sqlx::query(
+            "DELETE FROM embeddings
+             WHERE chunk_id IN (
+                 SELECT c.id FROM chunks c
+                 JOIN memories m ON c.memory_id = m.id
+                 WHERE m.vault_id = ?
+             )
+               AND (? IS NULL OR created_at < ?)
+               AND (? IS NULL OR model_name = ?)"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(older_than)
+        .bind(older_than)
+        .bind(&model)
+        .bind(&model)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(removed)
+    }
+
+    pub async fn rebuild_fts_index(&mut self) -> Result<u64> {
+        let _write = maintenance::begin_write()?;
+        let cipher = self.text_cipher();
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        // Repopulate the whole index from `memories` in one pass, which is much
+        // cheaper than the per-row triggers after a bulk load
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("INSERT INTO memories_fts (memories_fts) VALUES ('rebuild')")
+            .execute(&mut *tx)
+            .await?;
+
+        let indexed: i64 = sqlx::query("SELECT COUNT(*) FROM memories_fts_source")
+            .fetch_one(&mut *tx)
+            .await?
+            .get(0);
+
+        tx.commit().await?;
+
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+        Self::reindex_terms_static(pool, &settings, cipher.as_ref()).await?;
+
+        Ok(indexed as u64)
+    }
+
+    pub async fn check_fts_consistency(&mut self, repair: bool) -> Result<FtsConsistencyReport> {
+        // Checking alone is a read and stays allowed in read-only mode
+        let _write = if repair { Some(maintenance::begin_write()?) } else { None };
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let count = |sql: &'static str| async move {
+            let value: i64 = sqlx::query(sql).fetch_one(pool).await?.get(0);
+            to_count(value, "FTS row count")
+        };
+
+        // The docsize shadow table holds one row per indexed document; only
+        // plaintext memories are meant to be indexed
+        let memory_rows = count("SELECT COUNT(*) FROM memories_fts_source").await?;
+        let indexed_rows = count("SELECT COUNT(*) FROM memories_fts_docsize").await?;
+        let missing_rows = count(
+            "SELECT COUNT(*) FROM memories_fts_source s
+             WHERE NOT EXISTS (SELECT 1 FROM memories_fts_docsize d WHERE d.id = s.memory_rowid)"
+        )
+        .await?;
+        let stale_rows = count(
+            "SELECT COUNT(*) FROM memories_fts_docsize d
+             WHERE NOT EXISTS (SELECT 1 FROM memories_fts_source s WHERE s.memory_rowid = d.id)"
+        )
+        .await?;
+
+        // A rank of 1 also compares the index against the current plaintext
+        // rows of `memories`; FTS5 reports a difference as SQLITE_CORRUPT_VTAB, and
+        // any other error is a real failure
+        let content_mismatch = match sqlx::query("INSERT INTO memories_fts (memories_fts, rank) VALUES ('integrity-check', 1)")
+            .execute(pool)
+            .await
+        {
+            Ok(_) => false,
+            Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some(SQLITE_CORRUPT_VTAB) => true,
+            Err(e) => return Err(e.into()),
+        };
+
+        let drifted = missing_rows > 0 || stale_rows > 0 || content_mismatch;
+        let repaired = drifted && repair;
+        if repaired {
+            self.rebuild_fts_index().await?;
+        }
+
+        Ok(FtsConsistencyReport {
+            memory_rows,
+            indexed_rows,
+            missing_rows,
+            stale_rows,
+            content_mismatch,
+            repaired,
+        })
+    }
+
+    // Runs the enabled maintenance steps in an order where each benefits from
+    // the last: orphans are deleted first so the FTS rebuild and VACUUM don't
+    // carry them, and the recount sees the vacuumed file.
+    pub async fn optimize_vault(&mut self, steps: OptimizeSteps) -> Result<OptimizeReport> {
+        let _write = maintenance::begin_write()?;
+
+        let orphans = if steps.cleanup_orphans {
+            let pool = self.get_db().await?.get_pool().await;
+            Some(Self::cleanup_orphans_static(pool).await?)
+        } else {
+            None
+        };
+
+        let fts_rows_indexed = if steps.rebuild_fts {
+            Some(self.rebuild_fts_index().await?)
+        } else {
+            None
+        };
+
+        let vacuum = if steps.vacuum {
+            // VACUUM cannot run inside a transaction
+            let pool = self.get_db().await?.get_pool().await;
+            let size_before_bytes = Self::database_size_static(pool).await?;
+            sqlx::query("VACUUM").execute(pool).await?;
+            Some(VacuumResult {
+                size_before_bytes,
+                size_after_bytes: Self::database_size_static(pool).await?,
+            })
+        } else {
+            None
+        };
+
+        let statistics = if steps.recount {
+            Some(self.recompute_statistics().await?)
+        } else {
+            None
+        };
+
+        Ok(OptimizeReport {
+            orphans,
+            fts_rows_indexed,
+            vacuum,
+            statistics,
+        })
+    }
+
+    // Deletes, in one transaction, rows left behind by a memory or chunk that
+    // no longer exists. A chunk whose embedding another chunk still shares is
+    // kept along with its vectors.
+    async fn cleanup_orphans_static(pool: &sqlx::SqlitePool) -> Result<OrphanCleanup> {
+        const ORPHAN_CHUNKS: &str = "SELECT c.id FROM chunks c
+             WHERE NOT EXISTS (SELECT 1 FROM memories m WHERE m.id = c.memory_id)
+               AND NOT EXISTS (SELECT 1 FROM chunks d WHERE d.embedding_chunk_id = c.id)";
+
+        let mut tx = pool.begin().await?;
+
+        // Rows pointing at a chunk go before the chunk itself
+        let citations = sqlx::query(&format!(
+            "DELETE FROM citations
+             WHERE NOT EXISTS (SELECT 1 FROM memories m WHERE m.id = citations.memory_id)
+                OR NOT EXISTS (SELECT 1 FROM chunks c WHERE c.id = citations.chunk_id)
+                OR chunk_id IN ({})",
+            ORPHAN_CHUNKS
+        ))
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        let embeddings = sqlx::query(&format!(
+            "DELETE FROM embeddings
+             WHERE NOT EXISTS (SELECT 1 FROM chunks c WHERE c.id = embeddings.chunk_id)
+                OR chunk_id IN ({})",
+            ORPHAN_CHUNKS
+        ))
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        let chunks = sqlx::query(&format!("DELETE FROM chunks WHERE id IN ({})", ORPHAN_CHUNKS))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        let memory_tags = sqlx::query(
+            "DELETE FROM memory_tags WHERE NOT EXISTS (SELECT 1 FROM memories m WHERE m.id = memory_tags.memory_id)"
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        let versions = sqlx::query(
+            "DELETE FROM memory_versions WHERE NOT EXISTS (SELECT 1 FROM memories m WHERE m.id = memory_versions.memory_id)"
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        let relations = sqlx::query(
+            "DELETE FROM memory_relations
+             WHERE NOT EXISTS (SELECT 1 FROM memories m WHERE m.id = memory_relations.source_id)
+                OR NOT EXISTS (SELECT 1 FROM memories m WHERE m.id = memory_relations.target_id)"
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        let terms = sqlx::query("DELETE FROM memory_terms WHERE memory_id NOT IN (SELECT id FROM memories)")
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+        Ok(OrphanCleanup {
+            chunks,
+            embeddings,
+            citations,
+            memory_tags,
+            versions,
+            relations,
+            terms,
+        })
+    }
+
+    async fn database_size_static(pool: &sqlx::SqlitePool) -> Result<u64> {
+        let page_count: i64 = sqlx::query_scalar("PRAGMA page_count").fetch_one(pool).await?;
+        let page_size: i64 = sqlx::query_scalar("PRAGMA page_size").fetch_one(pool).await?;
+        Ok(to_count(page_count, "page count")?.saturating_mul(to_count(page_size, "page size")?))
+    }
+
+    // Encrypts every plaintext memory and chunk in the vault under a new vault
+    // key, wrapped with a key derived from the master password. Runs in one
+    // transaction so a failure leaves the vault untouched.
+    pub async fn enable_encryption(&mut self, master_password: String) -> Result<u64> {
+        let _write = maintenance::begin_write()?;
+        if master_password.is_empty() {
+            return Err(anyhow::anyhow!("Master password cannot be empty"));
+        }
+
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let row = sqlx::query("SELECT encrypted_key, password_hash FROM vaults WHERE id = ?")
+            .bind(DEFAULT_VAULT_ID)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Vault not found: {}", DEFAULT_VAULT_ID))?;
+        if row.get::<Option<Vec<u8>>, _>("encrypted_key").is_some() {
+            return Err(anyhow::anyhow!("Vault is already encrypted"));
+        }
+
+        let crypto = CryptoManager::new();
+        // unlock_vault checks the stored hash before unwrapping the key, so
+        // the key must be wrapped under that same password. A vault with no
+        // hash yet takes this password as its master password.
+        let password_hash = match row.get::<Option<String>, _>("password_hash") {
+            Some(_) => {
+                database::verify_master_password(pool, &master_password).await?;
+                None
+            }
+            None => Some(crypto.hash_password_async(&master_password).await?),
+        };
+        let salt = crypto.generate_salt();
+        // Key derivation is deliberately slow; keep it off the async worker
+        let kdf = CryptoManager::new();
+        let kdf_params = kdf.kdf_params().clone();
+        let wrapping_key = tokio::task::spawn_blocking(move || kdf.derive_key(&master_password, &salt)).await??;
+        let vault_key = crypto.generate_key();
+        let encrypted_key = crypto.encrypt_data(&vault_key, &wrapping_key)?;
+        let key_canary = crypto.encrypt_data(crypto::VAULT_CANARY, &vault_key)?;
+        let cipher = TextCipher::new(&vault_key);
+        let settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+
+        let mut tx = pool.begin().await?;
+
+        let memories = sqlx::query("SELECT id, title, content FROM memories WHERE vault_id = ? AND encrypted = 0")
+            .bind(DEFAULT_VAULT_ID)
+            .fetch_all(&mut *tx)
+            .await?;
+        for row in &memories {
+            let content: String = row.get("content");
+            let title: Option<String> = row.get("title");
+            sqlx::query("UPDATE memories SET title = ?, content = ?, encrypted = 1, crypto_version = ? WHERE id = ?")
+                .bind(Self::seal_title_static(Some(&cipher), true, title.as_deref())?)
+                .bind(cipher.encrypt_text(&content)?)
+                .bind(crypto::CRYPTO_VERSION)
+                .bind(row.get::<String, _>("id"))
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let chunks = sqlx::query(
+            "SELECT c.id, c.content FROM chunks c
+             JOIN memories m ON c.memory_id = m.id
+             WHERE m.vault_id = ? AND c.encrypted = 0"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in &chunks {
+            let content: String = row.get("content");
+            sqlx::query("UPDATE chunks SET content = ?, encrypted = 1, crypto_version = ? WHERE id = ?")
+                .bind(cipher.encrypt_text(&content)?)
+                .bind(crypto::CRYPTO_VERSION)
+                .bind(row.get::<String, _>("id"))
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let versions = sqlx::query(
+            "SELECT v.id, v.title, v.content FROM memory_versions v
+             JOIN memories m ON v.memory_id = m.id
+             WHERE m.vault_id = ? AND v.encrypted = 0"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in &versions {
+            let content: String = row.get("content");
+            let title: Option<String> = row.get("title");
+            sqlx::query("UPDATE memory_versions SET title = ?, content = ?, encrypted = 1, crypto_version = ? WHERE id = ?")
+                .bind(Self::seal_title_static(Some(&cipher), true, title.as_deref())?)
+                .bind(cipher.encrypt_text(&content)?)
+                .bind(crypto::CRYPTO_VERSION)
+                .bind(row.get::<String, _>("id"))
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        // Analyzed terms are derived from plaintext
+        if !settings.index_encrypted_vaults {
+            sqlx::query("DELETE FROM memory_terms WHERE memory_id IN (SELECT id FROM memories WHERE vault_id = ?)")
+                .bind(DEFAULT_VAULT_ID)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query(
+            "UPDATE vaults SET encryption_enabled = 1, key_salt = ?, encrypted_key = ?, key_canary = ?,
+                 password_hash = COALESCE(?, password_hash),
+                 kdf_memory_kib = ?, kdf_iterations = ?, kdf_parallelism = ?, updated_at = ?
+             WHERE id = ?"
+        )
+        .bind(&salt[..])
+        .bind(&encrypted_key)
+        .bind(&key_canary)
+        .bind(&password_hash)
+        .bind(kdf_params.m_cost())
+        .bind(kdf_params.t_cost())
+        .bind(kdf_params.p_cost())
+        .bind(Utc::now())
+        .bind(DEFAULT_VAULT_ID)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        self.vault_key = Some(vault_key);
+        Ok(memories.len() as u64)
+    }
+
+    // Checks a recovered vault key against the vault's canary without
+    // touching any data. Vaults encrypted before canaries existed are checked
+    // against one of their encrypted memories instead.
+    pub async fn verify_vault_key(&mut self, key: [u8; 32]) -> Result<bool> {
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let crypto = CryptoManager::new();
+
+        let canary: Option<Vec<u8>> = sqlx::query_scalar("SELECT key_canary FROM vaults WHERE id = ?")
+            .bind(DEFAULT_VAULT_ID)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Vault not found: {}", DEFAULT_VAULT_ID))?;
+
+        if let Some(canary) = canary {
+            return Ok(crypto.can_decrypt(&canary, &key)
+                && crypto.decrypt_data(&canary, &key)? == crypto::VAULT_CANARY);
+        }
+
+        let sample: Option<String> = sqlx::query_scalar(
+            "SELECT content FROM memories WHERE vault_id = ? AND encrypted = 1 ORDER BY id LIMIT 1"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_optional(pool)
+        .await?;
+
+        match sample {
+            Some(sample) => Ok(crypto.decrypt_text(&sample, &key).is_ok()),
+            None => Err(anyhow::anyhow!("Vault has no encrypted data to verify a key against")),
+        }
+    }
+
+    // Re-encrypts memories and chunks still stored in an older ciphertext
+    // format under `crypto::CRYPTO_VERSION`, and seals titles left in
+    // plaintext by versions that did not encrypt them. Needs the vault
+    // unlocked; runs in one transaction so a row that fails to decrypt leaves
+    // every row in its original format. Returns the number of rows upgraded.
+    pub async fn migrate_crypto_format(&mut self) -> Result<u64> {
+        let _write = maintenance::begin_write()?;
+        let key = self.vault_key.ok_or_else(|| anyhow::anyhow!("Vault is locked"))?;
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+        let cipher = TextCipher::new(&key);
+
+        let mut tx = pool.begin().await?;
+        let mut upgraded = 0u64;
+
+        let sealed_title = format!("{}*", crypto::V2_PREFIX);
+        let memories = sqlx::query(
+            "SELECT id, title, content FROM memories
+             WHERE vault_id = ? AND encrypted = 1
+               AND (crypto_version < ? OR (title IS NOT NULL AND NOT title GLOB ?))"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(crypto::CRYPTO_VERSION)
+        .bind(&sealed_title)
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in &memories {
+            let id: String = row.get("id");
+            let content = cipher
+                .decrypt_text(&row.get::<String, _>("content"))
+                .map_err(|e| anyhow::anyhow!("Failed to decrypt memory {}: {}", id, e))?;
+            let title = Self::open_title_static(Some(&cipher), row.get("title"), true)
+                .map_err(|e| anyhow::anyhow!("Failed to decrypt memory {}: {}", id, e))?;
+            sqlx::query("UPDATE memories SET title = ?, content = ?, crypto_version = ? WHERE id = ?")
+                .bind(Self::seal_title_static(Some(&cipher), true, title.as_deref())?)
+                .bind(cipher.encrypt_text(&content)?)
+                .bind(crypto::CRYPTO_VERSION)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?;
+            upgraded += 1;
+        }
+
+        let chunks = sqlx::query(
+            "SELECT c.id, c.content FROM chunks c
+             JOIN memories m ON c.memory_id = m.id
+             WHERE m.vault_id = ? AND c.encrypted = 1 AND c.crypto_version < ?"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(crypto::CRYPTO_VERSION)
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in &chunks {
+            let id: String = row.get("id");
+            let content = cipher
+                .decrypt_text(&row.get::<String, _>("content"))
+                .map_err(|e| anyhow::anyhow!("Failed to decrypt chunk {}: {}", id, e))?;
+            sqlx::query("UPDATE chunks SET content = ?, crypto_version = ? WHERE id = ?")
+                .bind(cipher.encrypt_text(&content)?)
+                .bind(crypto::CRYPTO_VERSION)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?;
+            upgraded += 1;
+        }
+
+        let versions = sqlx::query(
+            "SELECT v.id, v.title, v.content FROM memory_versions v
+             JOIN memories m ON v.memory_id = m.id
+             WHERE m.vault_id = ? AND v.encrypted = 1
+               AND (v.crypto_version < ? OR (v.title IS NOT NULL AND NOT v.title GLOB ?))"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(crypto::CRYPTO_VERSION)
+        .bind(&sealed_title)
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in &versions {
+            let id: String = row.get("id");
+            let content = cipher
+                .decrypt_text(&row.get::<String, _>("content"))
+                .map_err(|e| anyhow::anyhow!("Failed to decrypt memory version {}: {}", id, e))?;
+            let title = Self::open_title_static(Some(&cipher), row.get("title"), true)
+                .map_err(|e| anyhow::anyhow!("Failed to decrypt memory version {}: {}", id, e))?;
+            sqlx::query("UPDATE memory_versions SET title = ?, content = ?, crypto_version = ? WHERE id = ?")
+                .bind(Self::seal_title_static(Some(&cipher), true, title.as_deref())?)
+                .bind(cipher.encrypt_text(&content)?)
+                .bind(crypto::CRYPTO_VERSION)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?;
+            upgraded += 1;
+        }
+
+        tx.commit().await?;
+        Ok(upgraded)
+    }
+
+    // Compares the vault's key derivation costs and ciphertext formats with
+    // what this version writes. Needs no key, so it also works while locked.
+    pub async fn audit_crypto(&mut self) -> Result<CryptoAudit> {
+        let db = self.get_db().await?;
+        let pool = db.get_pool().await;
+
+        let row = sqlx::query(
+            "SELECT encrypted_key IS NOT NULL AS encrypted, kdf_memory_kib, kdf_iterations, kdf_parallelism
+             FROM vaults WHERE id = ?"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Vault not found: {}", DEFAULT_VAULT_ID))?;
+        let encrypted: bool = row.get("encrypted");
+
+        let legacy_rows: i64 = sqlx::query_scalar(
+            "SELECT
+                 (SELECT COUNT(*) FROM memories WHERE vault_id = ? AND encrypted = 1
+                    AND (crypto_version < ? OR (title IS NOT NULL AND NOT title GLOB ?)))
+               + (SELECT COUNT(*) FROM chunks c JOIN memories m ON c.memory_id = m.id
+                  WHERE m.vault_id = ? AND c.encrypted = 1 AND c.crypto_version < ?)"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .bind(crypto::CRYPTO_VERSION)
+        .bind(format!("{}*", crypto::V2_PREFIX))
+        .bind(DEFAULT_VAULT_ID)
+        .bind(crypto::CRYPTO_VERSION)
+        .fetch_one(pool)
+        .await?;
+        let legacy_rows = to_count(legacy_rows, "legacy row count")?;
+
+        let mut suggestions = Vec::new();
+        let kdf = if encrypted {
+            let kdf = KdfParams {
+                algorithm: "argon2id".to_string(),
+                memory_kib: row.get("kdf_memory_kib"),
+                iterations: row.get("kdf_iterations"),
+                parallelism: row.get("kdf_parallelism"),
+            };
+            if kdf.memory_kib < crypto::MIN_KDF_MEMORY_KIB
+                || kdf.iterations < crypto::MIN_KDF_ITERATIONS
+                || kdf.parallelism < crypto::MIN_KDF_PARALLELISM
+            {
+                suggestions.push(format!(
+                    "Key derivation uses m={} KiB, t={}, p={}; re-wrap the vault key with at least m={} KiB, t={}, p={}",
+                    kdf.memory_kib,
+                    kdf.iterations,
+                    kdf.parallelism,
+                    crypto::MIN_KDF_MEMORY_KIB,
+                    crypto::MIN_KDF_ITERATIONS,
+                    crypto::MIN_KDF_PARALLELISM
+                ));
+            }
+            Some(kdf)
+        } else {
+            suggestions.push("Enable encryption to protect memories at rest".to_string());
+            None
+        };
+        if legacy_rows > 0 {
+            suggestions.push(format!(
+                "Run migrate_crypto_format to upgrade {} values to ciphertext format v{}",
+                legacy_rows,
+                crypto::CRYPTO_VERSION
+            ));
+        }
+
+        Ok(CryptoAudit {
+            encrypted,
+            cipher: crypto::CIPHER.to_string(),
+            kdf,
+            crypto_version: crypto::CRYPTO_VERSION,
+            legacy_rows,
+            meets_recommendations: suggestions.is_empty(),
+            suggestions,
+        })
+    }
+
+    pub async fn get_recommendations(&mut self) -> Result<Vec<Recommendation>> {
+        let model = self.embedder.model_name().to_string();
+        let dimensions = self.embedder.dimensions();
+        let db = self.get_db().await?;
+        recommendations::collect(db.get_pool().await, DEFAULT_VAULT_ID, &model, dimensions, database::wal_size_bytes()).await
+    }
+
+    pub async fn get_system_info(&mut self) -> Result<SystemInfo> {
+        Ok(SystemInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            platform: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            memory_usage: 0, // Simplified
+            disk_usage: 0,   // Simplified
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, entry, TestVault};
+
+    #[test]
+    fn snippet_is_window_sized_and_centered_on_the_match() {
+        let content = format!("{} needle {}", "a".repeat(300), "b".repeat(300));
+        let snippet = MemoryManager::create_snippet_static(&content, "NEEDLE", 50);
+
+        assert_eq!(snippet.chars().count(), 50);
+        let at = snippet.find("needle").expect("match is inside the window");
+        let center = at + "needle".len() / 2;
+        assert!(center.abs_diff(25) <= 1, "match centered at {} in {:?}", center, snippet);
+    }
+
+    #[test]
+    fn snippet_is_clamped_to_the_content_bounds() {
+        let content = format!("needle {}", "x".repeat(100));
+        let start = MemoryManager::create_snippet_static(&content, "needle", 20);
+        assert_eq!(start.chars().count(), 20);
+        assert!(start.starts_with("needle"));
+
+        let content = format!("{} needle", "x".repeat(100));
+        let end = MemoryManager::create_snippet_static(&content, "needle", 20);
+        assert_eq!(end.chars().count(), 20);
+        assert!(end.ends_with("needle"));
+
+        // Short content and content without the term are never padded
+        assert_eq!(MemoryManager::create_snippet_static("short", "short", 20), "short");
+        let missing = MemoryManager::create_snippet_static(&"é".repeat(100), "needle", 20);
+        assert_eq!(missing, "é".repeat(20));
+    }
+
+    #[tokio::test]
+    async fn citations_follow_the_content_mode() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let content = format!("{} needle {}", "a ".repeat(200), "b ".repeat(200));
+        manager.add_memory(entry(&content, &[])).await.unwrap();
+
+        let request = |query: &str| QueryRequest {
+            query: query.to_string(),
+            limit: Some(50),
+            include_citations: true,
+            group_by_memory: false,
+            score_aggregate: ScoreAggregate::Max,
+            answer: false,
+            max_answer_chars: None,
+            answer_chunk_count: None,
+        };
+        let snippets = manager.query_memory(request("needle")).await.unwrap();
+        assert!(!snippets.citations.is_empty());
+        assert!(snippets.citations.iter().all(|c| c.content.chars().count() <= 200));
+
+        let mut settings = manager.get_settings().await.unwrap();
+        settings.citation_content = CitationContentMode::FullChunk;
+        manager.update_settings(settings).await.unwrap();
+        let full = manager.query_memory(request("needle")).await.unwrap();
+        assert!(full.citations.iter().any(|c| c.content.contains("needle")));
+    }
+
+    #[tokio::test]
+    async fn rebuild_fts_index_indexes_rows_loaded_without_triggers() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let pool = test_support::database().await.get_pool().await.clone();
+        // Opening a pool migrates the schema, which would restore the trigger
+        manager.get_settings().await.unwrap();
+
+        // Bulk load with the insert trigger out of the way, as an import would
+        sqlx::query("DROP TRIGGER memories_fts_insert").execute(&pool).await.unwrap();
+        for i in 0..20 {
+            manager.add_memory(entry(&format!("bulkloaded note {}", i), &[])).await.unwrap();
+        }
+        assert!(manager.prefix_search("bulkloaded".into(), Some(50)).await.unwrap().is_empty());
+
+        assert_eq!(manager.rebuild_fts_index().await.unwrap(), 20);
+        assert_eq!(manager.prefix_search("bulkloaded".into(), Some(50)).await.unwrap().len(), 20);
+    }
+
+    #[tokio::test]
+    async fn query_history_is_recorded_listed_newest_first_and_cleared() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("history of the roman empire", &[])).await.unwrap();
+
+        // Off by default
+        manager.query_memory(test_support::query("roman")).await.unwrap();
+        assert!(manager.list_query_history(None).await.unwrap().is_empty());
+
+        let mut settings = manager.get_settings().await.unwrap();
+        settings.record_query_history = true;
+        manager.update_settings(settings).await.unwrap();
+
+        manager.query_memory(test_support::query("roman")).await.unwrap();
+        manager.search_memories(test_support::search("empire"), None, false).await.unwrap();
+        manager.search_memories(test_support::search("carthage"), None, false).await.unwrap();
+
+        let history = manager.list_query_history(None).await.unwrap();
+        let queries: Vec<&str> = history.iter().map(|h| h.query.as_str()).collect();
+        assert_eq!(queries, ["carthage", "empire", "roman"]);
+        assert_eq!(history[0].result_count, 0);
+        assert_eq!(history[1].result_count, 1);
+        assert_eq!(manager.list_query_history(Some(1)).await.unwrap().len(), 1);
+
+        manager.clear_query_history().await.unwrap();
+        assert!(manager.list_query_history(None).await.unwrap().is_empty());
+    }
+
+    async fn cite(pool: &sqlx::SqlitePool, memory_id: &str) {
+        sqlx::query(
+            "INSERT INTO citations (id, memory_id, chunk_id, relevance_score)
+             SELECT ?, memory_id, id, 1.0 FROM chunks WHERE memory_id = ? LIMIT 1",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(memory_id)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn record_query(pool: &sqlx::SqlitePool, query: &str) {
+        MemoryManager::record_query_static(pool, query, 1).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn orphan_memories_skips_cited_and_queried_memories() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let pool = test_support::database().await.get_pool().await.clone();
+
+        let cited = manager.add_memory(entry("a note that was cited", &[])).await.unwrap();
+        let literal = manager.add_memory(entry("a discount of 100 percent", &[])).await.unwrap();
+        let secret = manager.add_memory(entry("the secret plan", &[])).await.unwrap();
+        let queried = manager.add_memory(entry("a note about gardening", &[])).await.unwrap();
+        cite(&pool, &cited).await;
+        let ids = |memories: Vec<MemoryEntry>| memories.into_iter().map(|m| m.id.unwrap()).collect::<Vec<_>>();
+
+        // `%` in a query is literal text
+        record_query(&pool, "100%").await;
+        record_query(&pool, "gardening").await;
+        assert_eq!(
+            ids(manager.orphan_memories(None, false).await.unwrap()),
+            [literal.clone(), secret.clone()]
+        );
+
+        // Encrypted content is compared after decryption
+        manager.enable_encryption("master password".into()).await.unwrap();
+        record_query(&pool, "SECRET").await;
+        assert_eq!(ids(manager.orphan_memories(None, false).await.unwrap()), std::slice::from_ref(&literal));
+        assert_eq!(
+            ids(manager.orphan_memories(None, true).await.unwrap()),
+            [literal.clone(), secret, queried]
+        );
+        assert_eq!(ids(manager.orphan_memories(Some(1), true).await.unwrap()), [literal]);
+    }
+
+    #[tokio::test]
+    async fn export_data_honours_pretty_and_field_selection() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let mut memory = entry("exported content", &["export"]);
+        memory.title = Some("Manifest".into());
+        manager.add_memory(memory).await.unwrap();
+
+        let compact = manager.export_data("json".into(), ExportOptions::default()).await.unwrap();
+        let compact = checksum::verify(&compact).unwrap();
+        assert!(!compact.contains('\n'));
+        let pretty = ExportOptions { pretty: true, fields: None };
+        let pretty = manager.export_data("json".into(), pretty).await.unwrap();
+        assert!(checksum::verify(&pretty).unwrap().contains("\n  "));
+
+        let manifest = ExportOptions {
+            pretty: false,
+            fields: Some(vec!["title".into(), "tags".into()]),
+        };
+        let manifest = manager.export_data("json".into(), manifest).await.unwrap();
+        let document: serde_json::Value = serde_json::from_str(checksum::verify(&manifest).unwrap()).unwrap();
+        let record = document["data"][0].as_object().unwrap();
+        let mut keys: Vec<&str> = record.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["tags", "title"]);
+        assert!(!manifest.contains("exported content"));
+
+        let unknown = ExportOptions {
+            pretty: false,
+            fields: Some(vec!["password".into()]),
+        };
+        assert!(manager.export_data("json".into(), unknown).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn get_citations_returns_the_cited_chunk_content() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let pool = test_support::database().await.get_pool().await.clone();
+
+        let id = manager.add_memory(entry("the memory text", &[])).await.unwrap();
+        sqlx::query("UPDATE chunks SET content = 'the chunk text' WHERE memory_id = ?")
+            .bind(&id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        cite(&pool, &id).await;
+
+        let citations = manager.get_citations(id, None, None).await.unwrap();
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].content, "the chunk text");
+    }
+
+    #[tokio::test]
+    async fn capture_memory_tags_links_and_code() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let pool = test_support::database().await.get_pool().await.clone();
+
+        let link = manager.capture_memory("  https://example.com/article \n".into(), None).await.unwrap();
+        assert_eq!(MemoryManager::get_memory_tags_static(&pool, &link).await.unwrap(), ["link"]);
+
+        let code = "Try this:\n```rust\nfn main() {}\n```".to_string();
+        let code = manager.capture_memory(code, Some("editor".into())).await.unwrap();
+        assert_eq!(MemoryManager::get_memory_tags_static(&pool, &code).await.unwrap(), ["code"]);
+
+        let prose = manager.capture_memory("Just a thought".into(), None).await.unwrap();
+        assert!(MemoryManager::get_memory_tags_static(&pool, &prose).await.unwrap().is_empty());
+
+        let sources: Vec<String> = sqlx::query_scalar("SELECT source FROM memories ORDER BY created_at")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(sources, ["https://example.com/article", "editor", "clipboard"]);
+    }
+
+    #[tokio::test]
+    async fn embed_text_is_deterministic_and_bounded() {
+        let provider = Arc::new(embeddings::HashingEmbedder::default());
+        let mut manager = MemoryManager::with_provider(provider.clone());
+
+        let first = manager.embed_text("the same input".into()).await.unwrap();
+        let second = manager.embed_text("the same input".into()).await.unwrap();
+        assert_eq!(first.dimension, provider.dimensions());
+        assert_eq!(first.vector.len(), first.dimension);
+        assert_eq!(first.model, provider.model_name());
+        assert_eq!(first.vector, second.vector);
+
+        assert!(manager.embed_text("  ".into()).await.is_err());
+        assert!(manager.embed_text("x".repeat(MAX_EMBED_TEXT_CHARS + 1)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn keyword_analysis_stems_queries_and_ignores_stopwords() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("I run every morning", &[])).await.unwrap();
+        manager.add_memory(entry("the cat sat on a mat", &[])).await.unwrap();
+
+        // Off by default: plain substring matching
+        assert!(manager.search_memories(test_support::search("running"), None, false).await.unwrap().is_empty());
+
+        let mut settings = manager.get_settings().await.unwrap();
+        settings.keyword_analysis.enabled = true;
+        manager.update_settings(settings).await.unwrap();
+
+        let runs = manager.search_memories(test_support::search("running"), None, false).await.unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].content, "I run every morning");
+
+        // Stopwords neither match on their own nor get in the way
+        assert!(manager.search_memories(test_support::search("the"), None, false).await.unwrap().is_empty());
+        let cats = manager.search_memories(test_support::search("the cats"), None, false).await.unwrap();
+        assert_eq!(cats.len(), 1);
+        assert_eq!(cats[0].content, "the cat sat on a mat");
+    }
+
+    #[tokio::test]
+    async fn memory_bundle_round_trips_through_import() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let id = manager.add_memory(entry("bundle me", &["shared", "travel"])).await.unwrap();
+        let related = manager.add_memory(entry("also travel", &["travel"])).await.unwrap();
+
+        let json = manager.export_memory(id.clone(), "json".into(), None).await.unwrap();
+        let bundle: MemoryBundle = serde_json::from_str(&json).unwrap();
+        assert_eq!(bundle.memory.content, "bundle me");
+        assert_eq!(bundle.memory.tags, ["shared", "travel"]);
+        assert_eq!(bundle.related_ids, [related]);
+        let markdown = manager.export_memory(id.clone(), "markdown".into(), None).await.unwrap();
+        assert!(markdown.contains("bundle me"));
+
+        let sealed = manager.export_memory(id.clone(), "json".into(), Some("share".into())).await.unwrap();
+        assert!(!sealed.contains("bundle me"));
+        assert!(manager.import_memory(sealed.clone(), Some("wrong".into())).await.is_err());
+
+        // The original id is kept once it is free again
+        manager.delete_memory(id.clone()).await.unwrap();
+        assert_eq!(manager.import_memory(sealed, Some("share".into())).await.unwrap(), id);
+        let pool = test_support::database().await.get_pool().await.clone();
+        let imported = MemoryManager::get_memory_static(&pool, &id, None).await.unwrap().unwrap();
+        assert_eq!(imported.content, "bundle me");
+        assert_eq!(imported.tags, ["shared", "travel"]);
+
+        // A colliding id is replaced
+        assert_ne!(manager.import_memory(json, None).await.unwrap(), id);
+    }
+
+    #[tokio::test]
+    async fn identical_timestamps_order_by_id() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let pool = test_support::database().await.get_pool().await.clone();
+
+        let mut ids = Vec::new();
+        for i in 0..6 {
+            ids.push(manager.add_memory(entry(&format!("tie {}", i), &[])).await.unwrap());
+        }
+        // As a bulk import would leave them
+        sqlx::query("UPDATE memories SET created_at = '2024-01-01T00:00:00Z', updated_at = '2024-01-01T00:00:00Z'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        ids.sort();
+
+        let order = |memories: Vec<MemoryEntry>| memories.into_iter().map(|m| m.id.unwrap()).collect::<Vec<_>>();
+        for _ in 0..3 {
+            let found = order(manager.search_memories(test_support::search("tie"), None, false).await.unwrap());
+            assert_eq!(found, ids);
+        }
+        let first_page = order(manager.search_memories(test_support::search("tie"), Some(3), false).await.unwrap());
+        assert_eq!(first_page, ids[..3]);
+        let oldest = order(manager.orphan_memories(None, true).await.unwrap());
+        assert_eq!(oldest, ids);
+    }
+
+    #[tokio::test]
+    async fn test_provider_reports_success_and_auth_failures() {
+        use crate::openai_embeddings::{tests::{embeddings_body, mock_server}, OpenAiEmbedder};
+
+        let mut manager = MemoryManager::with_provider(Arc::new(embeddings::HashingEmbedder::default()));
+        let status = manager.test_provider().await.unwrap();
+        assert!(status.ok);
+        assert_eq!(status.dimension, embeddings::HashingEmbedder::default().dimensions());
+
+        let url = mock_server("200 OK", embeddings_body(1536, 1)).await;
+        let openai = OpenAiEmbedder::new("key", "text-embedding-3-small").unwrap().with_url(&url);
+        let status = MemoryManager::with_provider(Arc::new(openai)).test_provider().await.unwrap();
+        assert!(status.ok, "{:?}", status.error);
+        assert_eq!(status.model, "openai/text-embedding-3-small");
+        assert_eq!(status.dimension, 1536);
+
+        let body = serde_json::json!({ "error": { "message": "Incorrect API key provided" } }).to_string();
+        let url = mock_server("401 Unauthorized", body).await;
+        let openai = OpenAiEmbedder::new("bad", "text-embedding-3-small").unwrap().with_url(&url);
+        let status = MemoryManager::with_provider(Arc::new(openai)).test_provider().await.unwrap();
+        assert!(!status.ok);
+        assert!(status.error.unwrap().contains("Incorrect API key provided"));
+    }
+
+    #[tokio::test]
+    async fn parent_tags_match_their_descendants() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let api = manager.add_memory(entry("api notes", &["project/human-api"])).await.unwrap();
+        let site = manager.add_memory(entry("site notes", &["project/site/blog"])).await.unwrap();
+        manager.add_memory(entry("health notes", &["area/health"])).await.unwrap();
+        // A sibling name sharing the prefix is not a child
+        manager.add_memory(entry("other notes", &["projects"])).await.unwrap();
+
+        let mut criteria = test_support::search("");
+        criteria.tags = Some(vec!["project".into()]);
+        let mut found: Vec<String> = manager
+            .search_memories(criteria, None, false)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.id.unwrap())
+            .collect();
+        found.sort();
+        let mut expected = vec![api, site];
+        expected.sort();
+        assert_eq!(found, expected);
+
+        let tree = manager.list_tags().await.unwrap();
+        let names: Vec<&str> = tree.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, ["area", "project", "projects"]);
+        let project = &tree[1];
+        assert_eq!(project.memory_count, 0);
+        let children: Vec<&str> = project.children.iter().map(|n| n.path.as_str()).collect();
+        assert_eq!(children, ["project/human-api", "project/site"]);
+        assert_eq!(project.children[1].children[0].path, "project/site/blog");
+        assert_eq!(project.children[1].children[0].memory_count, 1);
+    }
+
+    async fn count_rows(pool: &sqlx::SqlitePool, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn clear_embeddings_keeps_memories_and_chunks() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let pool = test_support::database().await.get_pool().await.clone();
+        manager.add_memory(entry("first memory", &[])).await.unwrap();
+        manager.add_memory(entry("second memory", &[])).await.unwrap();
+        manager.sync_embeddings().await.unwrap();
+
+        let embedded = count_rows(&pool, "embeddings").await;
+        assert!(embedded > 0);
+        let chunks = count_rows(&pool, "chunks").await;
+
+        assert_eq!(manager.clear_embeddings().await.unwrap(), embedded as u64);
+        assert_eq!(count_rows(&pool, "embeddings").await, 0);
+        assert_eq!(count_rows(&pool, "chunks").await, chunks);
+        assert_eq!(count_rows(&pool, "memories").await, 2);
+
+        // Everything can be embedded again
+        assert_eq!(manager.sync_embeddings().await.unwrap(), embedded as u64);
+    }
+
+    #[tokio::test]
+    async fn fts_drift_is_detected_and_repaired() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let pool = test_support::database().await.get_pool().await.clone();
+        manager.add_memory(entry("indexed before the drift", &[])).await.unwrap();
+
+        let clean = manager.check_fts_consistency(false).await.unwrap();
+        assert!(!clean.content_mismatch && clean.missing_rows == 0 && clean.stale_rows == 0);
+
+        // Writes that bypass the triggers, as after a crash mid-write
+        manager.add_memory(entry("unindexed", &[])).await.unwrap();
+        sqlx::query("DROP TRIGGER memories_fts_update").execute(&pool).await.unwrap();
+        sqlx::query("UPDATE memories SET content = 'rewritten behind the index' WHERE content = 'indexed before the drift'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM memories_fts_docsize WHERE id = (SELECT rowid FROM memories WHERE content = 'unindexed')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let drifted = manager.check_fts_consistency(false).await.unwrap();
+        assert!(drifted.content_mismatch);
+        assert_eq!(drifted.missing_rows, 1);
+        assert!(!drifted.repaired);
+
+        let repaired = manager.check_fts_consistency(true).await.unwrap();
+        assert!(repaired.repaired);
+        let after = manager.check_fts_consistency(false).await.unwrap();
+        assert!(!after.content_mismatch && after.missing_rows == 0 && after.stale_rows == 0);
+        assert_eq!(manager.prefix_search("rewritten".into(), None).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn add_memory_applies_entry_validation() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+
+        let error = manager.add_memory(entry("   ", &[])).await.unwrap_err().to_string();
+        assert!(error.contains("Content cannot be empty"), "{}", error);
+
+        let mut memory = entry("valid content", &[" a / b "]);
+        memory.source = Some("https://example.com/?utm_medium=mail".into());
+        let id = manager.add_memory(memory).await.unwrap();
+        let pool = test_support::database().await.get_pool().await.clone();
+        let stored = MemoryManager::get_memory_static(&pool, &id, None).await.unwrap().unwrap();
+        assert_eq!(stored.tags, ["a/b"]);
+        assert_eq!(stored.source.as_deref(), Some("https://example.com/"));
+    }
+
+    #[tokio::test]
+    async fn prefix_search_matches_word_starts_only() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let one = manager.add_memory(entry("notes on embedding models", &[])).await.unwrap();
+        let many = manager.add_memory(entry("storing embeddings on disk", &[])).await.unwrap();
+        manager.add_memory(entry("these two resemble each other", &[])).await.unwrap();
+
+        let ids: Vec<String> = manager
+            .prefix_search("emb".into(), None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        // Most recent first
+        assert_eq!(ids, [many, one.clone()]);
+
+        // Earlier words must match whole
+        let matches = manager.prefix_search("notes emb".into(), None).await.unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].id, one);
+        assert!(manager.prefix_search("note emb".into(), None).await.unwrap().is_empty());
+        assert!(manager.prefix_search(" -- ".into(), None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn enabling_encryption_seals_existing_titles_and_content() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let mut memory = entry("the launch codes are in the drawer", &["secret"]);
+        memory.title = Some("Launch plan".into());
+        let id = manager.add_memory(memory).await.unwrap();
+        manager.add_memory(entry("an untitled note about the drawer", &[])).await.unwrap();
+
+        assert_eq!(manager.enable_encryption("hunter2".into()).await.unwrap(), 2);
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        let (title, content): (Option<String>, String) =
+            sqlx::query_as("SELECT title, content FROM memories WHERE id = ?")
+                .bind(&id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert!(crypto::is_v2(title.as_deref().unwrap()));
+        assert!(crypto::is_v2(&content));
+        let leaked: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM memories WHERE title LIKE '%Launch%' OR content LIKE '%drawer%'"
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(leaked, 0);
+        let plaintext_chunks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM chunks WHERE content LIKE '%drawer%'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(plaintext_chunks, 0);
+
+        let cipher = manager.text_cipher();
+        let stored = MemoryManager::get_memory_static(&pool, &id, cipher.as_ref()).await.unwrap().unwrap();
+        assert_eq!(stored.title.as_deref(), Some("Launch plan"));
+        assert_eq!(stored.content, "the launch codes are in the drawer");
+
+        let mut criteria = test_support::search("drawer");
+        criteria.order = SearchOrder::TitleAsc;
+        let found = manager.search_memories(criteria, None, false).await.unwrap();
+        let titles: Vec<Option<String>> = found.into_iter().map(|m| m.title).collect();
+        // Ordered by the decrypted, case-folded titles
+        assert_eq!(titles, [Some("an untitled note about the drawer".into()), Some("Launch plan".into())]);
+    }
+
+    #[tokio::test]
+    async fn grouped_query_collapses_chunk_hits_per_memory() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let sentence = "The orchid on the windowsill needs watering twice a week during the warm summer months. ";
+        let long = manager.add_memory(entry(&sentence.repeat(6), &[])).await.unwrap();
+        manager.add_memory(entry("An orchid cutting went to the neighbours.", &[])).await.unwrap();
+
+        for aggregate in [ScoreAggregate::Max, ScoreAggregate::Sum] {
+            let mut request = test_support::query("orchid");
+            request.group_by_memory = true;
+            request.score_aggregate = aggregate;
+            let result = manager.query_memory(request).await.unwrap();
+
+            let scores: Vec<f32> = result
+                .citations
+                .iter()
+                .filter(|c| c.id == long)
+                .map(|c| c.relevance_score)
+                .collect();
+            assert!(scores.len() > 1);
+            let cited: HashSet<&str> = result.citations.iter().map(|c| c.id.as_str()).collect();
+            assert_eq!(result.memory_hits.len(), cited.len());
+
+            let hit = result.memory_hits.iter().find(|h| h.memory_id == long).unwrap();
+            assert_eq!(hit.matching_chunks, scores.len());
+            let expected = match aggregate {
+                ScoreAggregate::Max => scores.iter().copied().fold(f32::MIN, f32::max),
+                ScoreAggregate::Sum => scores.iter().sum(),
+            };
+            assert!((hit.relevance_score - expected).abs() < 1e-5, "{:?}", aggregate);
+        }
+    }
+
+    #[tokio::test]
+    async fn memories_by_tag_pages_members_and_counts_co_tags() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let mut members = HashSet::new();
+        members.insert(manager.add_memory(entry("parsing arguments", &["rust", "cli"])).await.unwrap());
+        members.insert(manager.add_memory(entry("serving requests", &["rust", "web"])).await.unwrap());
+        members.insert(manager.add_memory(entry("colored output", &["rust", "cli"])).await.unwrap());
+        manager.add_memory(entry("click commands", &["python", "cli"])).await.unwrap();
+
+        let first = manager.memories_by_tag("rust".into(), Some(2), None).await.unwrap();
+        assert_eq!(first.total, 3);
+        assert_eq!(first.memories.len(), 2);
+        let counts: Vec<(String, u64)> = first.co_tags.into_iter().map(|t| (t.name, t.memory_count)).collect();
+        assert_eq!(counts, [("cli".to_string(), 2), ("web".to_string(), 1)]);
+
+        let second = manager.memories_by_tag("rust".into(), Some(2), Some(2)).await.unwrap();
+        assert_eq!(second.memories.len(), 1);
+        let paged: HashSet<String> = first.memories.into_iter().chain(second.memories).filter_map(|m| m.id).collect();
+        assert_eq!(paged, members);
+    }
+
+    #[tokio::test]
+    async fn paraphrased_memories_cluster_as_duplicates() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let original = manager
+            .add_memory(entry("Remember to renew the car insurance before the end of March.", &[]))
+            .await
+            .unwrap();
+        let paraphrase = manager
+            .add_memory(entry("Remember to renew our car insurance before the end of March!", &[]))
+            .await
+            .unwrap();
+        manager.add_memory(entry("The sourdough starter needs feeding with rye flour.", &[])).await.unwrap();
+
+        let clusters = manager.find_semantic_duplicates(Some(0.8)).await.unwrap();
+        assert_eq!(clusters.len(), 1);
+        let ids: HashSet<String> = clusters[0].memories.iter().map(|m| m.id.clone()).collect();
+        assert_eq!(ids, HashSet::from([original, paraphrase]));
+        assert!(clusters[0].max_similarity >= 0.8);
+    }
+
+    #[tokio::test]
+    async fn similar_content_suggests_the_neighbours_tag() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("the borrow checker rejects two mutable borrows of a vector", &["rust"])).await.unwrap();
+        manager.add_memory(entry("cargo builds the crate and checks borrow lifetimes", &["rust"])).await.unwrap();
+        manager.add_memory(entry("simmer the tomato sauce with basil for an hour", &["cooking"])).await.unwrap();
+        let content = "why does the borrow checker reject this mutable borrow in my crate".to_string();
+
+        // Word overlap before any embeddings exist, vectors afterwards
+        for synced in [false, true] {
+            if synced {
+                manager.sync_embeddings().await.unwrap();
+            }
+            let suggestions = manager.suggest_tags_for_content(content.clone(), None).await.unwrap();
+            assert_eq!(suggestions.first().map(|s| s.tag.as_str()), Some("rust"), "synced: {}", synced);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_inserts_of_a_new_tag_share_one_row() {
+        let _vault = TestVault::new().await;
+        let pool = test_support::database().await.get_pool().await.clone();
+
+        let tasks: Vec<_> = (0..8)
+            .map(|i| {
+                let pool = pool.clone();
+                tokio::spawn(async move {
+                    let tag_id = MemoryManager::ensure_tag_static(&pool, "race").await.unwrap();
+                    let mut manager = MemoryManager::new();
+                    manager.add_memory(entry(&format!("racing memory {}", i), &["race", "fresh"])).await.unwrap();
+                    tag_id
+                })
+            })
+            .collect();
+        let mut tag_ids = HashSet::new();
+        for task in tasks {
+            tag_ids.insert(task.await.unwrap());
+        }
+        assert_eq!(tag_ids.len(), 1);
+
+        let tags: Vec<String> = sqlx::query_scalar("SELECT name FROM tags ORDER BY name").fetch_all(&pool).await.unwrap();
+        assert_eq!(tags, ["fresh", "race"]);
+        assert_eq!(count_rows(&pool, "memory_tags").await, 16);
+    }
+
+    #[tokio::test]
+    async fn export_to_file_round_trips_a_large_vault() {
+        let vault = TestVault::new().await;
+        let pool = test_support::database().await.get_pool().await.clone();
+        let count = EXPORT_BATCH_SIZE + 37;
+        let start = Utc::now();
+        let mut tx = pool.begin().await.unwrap();
+        for i in 0..count {
+            sqlx::query("INSERT INTO memories (id, vault_id, content, created_at, updated_at) VALUES (?, ?, ?, ?, ?)")
+                .bind(Uuid::new_v4().to_string())
+                .bind(DEFAULT_VAULT_ID)
+                .bind(format!("seeded memory {}", i))
+                // Pairs share a timestamp, so pages split between equal keys too
+                .bind(start + chrono::Duration::seconds(i as i64 / 2))
+                .bind(start)
+                .execute(&mut *tx)
+                .await
+                .unwrap();
+        }
+        tx.commit().await.unwrap();
+
+        let path = vault.dir().join("export.json");
+        let options = ExportOptions { pretty: false, fields: None };
+        let mut manager = MemoryManager::new();
+        let result = manager
+            .export_to_file(path.to_string_lossy().into_owned(), "json".into(), options)
+            .await
+            .unwrap();
+        assert_eq!(result.memory_count, count as u64);
+
+        let file = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(result.bytes, file.len() as u64);
+        assert!(!path.with_extension("partial").exists());
+        let document: serde_json::Value = serde_json::from_str(checksum::verify(&file).unwrap()).unwrap();
+        // Equal timestamps order by the random ids, so compare as a set
+        let contents: HashSet<&str> = document["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|record| record["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(contents.len(), count);
+        assert!((0..count).all(|i| contents.contains(format!("seeded memory {}", i).as_str())));
+    }
+
+    #[tokio::test]
+    async fn oversized_content_is_capped_and_flagged() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let mut settings = manager.get_settings().await.unwrap();
+        settings.max_chunks_per_memory = 0;
+        assert!(manager.update_settings(settings.clone()).await.is_err());
+        settings.max_chunks_per_memory = 3;
+        manager.update_settings(settings).await.unwrap();
+
+        let sentence = "Each sentence here is long enough that a handful of them fill a whole chunk of text. ";
+        let large = manager.add_memory(entry(&sentence.repeat(20), &[])).await.unwrap();
+        let small = manager.add_memory(entry("fits in one chunk", &[])).await.unwrap();
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        for (id, chunks, truncated) in [(large, 3, true), (small, 1, false)] {
+            let row = sqlx::query(
+                "SELECT (SELECT COUNT(*) FROM chunks WHERE memory_id = m.id) AS chunks, m.chunks_truncated
+                 FROM memories m WHERE m.id = ?"
+            )
+            .bind(&id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+            assert_eq!(row.get::<i64, _>("chunks"), chunks);
+            assert_eq!(row.get::<bool, _>("chunks_truncated"), truncated);
+        }
+        assert_eq!(manager.get_stats().await.unwrap().truncated_memories, 1);
+    }
+
+    #[tokio::test]
+    async fn recomputed_statistics_follow_the_source_tables() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("first memory about tides", &[])).await.unwrap();
+        manager.add_memory(entry("second memory about currents", &[])).await.unwrap();
+        manager.sync_embeddings().await.unwrap();
+
+        let report = manager.recompute_statistics().await.unwrap();
+        assert_eq!(report.vaults.len(), 1);
+        assert_eq!(report.vaults[0].vault_id, DEFAULT_VAULT_ID);
+        assert_eq!(report.vaults[0].memory_count, 2);
+        assert_eq!(report.total_chunks, 2);
+        assert_eq!(report.embedded_chunks, 2);
+        assert_eq!(report.embedding_coverage, 1.0);
+
+        // Nothing is cached: a vector removed behind the manager's back shows
+        // up in the next report
+        let pool = test_support::database().await.get_pool().await.clone();
+        sqlx::query("DELETE FROM embeddings WHERE id = (SELECT MIN(id) FROM embeddings)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let report = manager.recompute_statistics().await.unwrap();
+        assert_eq!(report.embedded_chunks, 1);
+        assert_eq!(report.embedding_coverage, 0.5);
+        assert_eq!(report.total_chunks, manager.get_stats().await.unwrap().total_chunks);
+    }
+
+    #[tokio::test]
+    async fn detected_languages_are_stored_and_tagged() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let mut settings = manager.get_settings().await.unwrap();
+        settings.language_detection.enabled = true;
+        settings.language_detection.auto_tag = true;
+        manager.update_settings(settings).await.unwrap();
+
+        let french = manager
+            .add_memory(entry("Nous avons acheté du pain frais et des croissants à la boulangerie ce matin.", &[]))
+            .await
+            .unwrap();
+        let short = manager.add_memory(entry("ok", &[])).await.unwrap();
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        for (id, language, tags) in [(french, "fra", vec!["lang/fra"]), (short, "unknown", vec![])] {
+            let stored: Option<String> = sqlx::query_scalar("SELECT language FROM memories WHERE id = ?")
+                .bind(&id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(stored.as_deref(), Some(language));
+            let memory = MemoryManager::get_memory_static(&pool, &id, None).await.unwrap().unwrap();
+            assert_eq!(memory.tags, tags);
+        }
+    }
+
+    #[tokio::test]
+    async fn candidate_keys_are_checked_against_the_canary() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("sealed away", &[])).await.unwrap();
+        manager.enable_encryption("hunter2".into()).await.unwrap();
+        let key = manager.vault_key.unwrap();
+        let mut wrong = key;
+        wrong[0] ^= 1;
+
+        assert!(manager.verify_vault_key(key).await.unwrap());
+        assert!(!manager.verify_vault_key(wrong).await.unwrap());
+
+        // Vaults from before canaries fall back to an encrypted memory
+        let pool = test_support::database().await.get_pool().await.clone();
+        sqlx::query("UPDATE vaults SET key_canary = NULL").execute(&pool).await.unwrap();
+        assert!(manager.verify_vault_key(key).await.unwrap());
+        assert!(!manager.verify_vault_key(wrong).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn citation_only_queries_skip_the_answer() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("the lighthouse keeper logs every passing ship", &[])).await.unwrap();
+
+        let mut request = test_support::query("lighthouse");
+        request.answer = false;
+        let result = manager.query_memory(request).await.unwrap();
+        assert!(result.answer.is_empty());
+        assert_eq!(result.citations.len(), 1);
+        assert!(result.citations[0].content.contains("lighthouse"));
+
+        let result = manager.query_memory(test_support::query("lighthouse")).await.unwrap();
+        assert!(result.answer.contains("lighthouse"));
+    }
+
+    #[tokio::test]
+    async fn pruning_embeddings_removes_only_the_matching_rows() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let old = manager.add_memory(entry("an old memory", &[])).await.unwrap();
+        manager.add_memory(entry("a new memory", &[])).await.unwrap();
+        manager.sync_embeddings().await.unwrap();
+        let mut small = MemoryManager::with_provider(Arc::new(embeddings::HashingEmbedder::new(16)));
+        small.sync_embeddings().await.unwrap();
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        sqlx::query(
+            "UPDATE embeddings SET created_at = ?
+             WHERE model_name = 'hashing-v1' AND chunk_id IN (SELECT id FROM chunks WHERE memory_id = ?)"
+        )
+        .bind(Utc::now() - chrono::Duration::days(30))
+        .bind(&old)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // Largest vectors first
+        let largest = manager.list_large_embeddings(Some(1)).await.unwrap();
+        assert_eq!(largest[0].model_name, "hashing-v1");
+
+        assert!(manager.prune_embeddings(None, None).await.is_err());
+        assert_eq!(manager.prune_embeddings(None, Some("hashing-v1-16".into())).await.unwrap(), 2);
+        let cutoff = (Utc::now() - chrono::Duration::days(7)).to_rfc3339();
+        assert_eq!(manager.prune_embeddings(Some(cutoff), None).await.unwrap(), 1);
+
+        let remaining: Vec<String> = sqlx::query_scalar(
+            "SELECT c.memory_id FROM embeddings e JOIN chunks c ON c.id = e.chunk_id"
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_ne!(remaining[0], old);
+    }
+
+    #[tokio::test]
+    async fn text_files_import_one_memory_per_segment() {
+        let vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let path = vault.dir().join("notes.md");
+        std::fs::write(&path, "# Groceries\nmilk\n\neggs\n\n# Errands\npost office\n").unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        let paragraphs = manager.import_text_file(path.clone(), SplitStrategy::BlankLines).await.unwrap();
+        assert_eq!(paragraphs.memory_ids.len(), 3);
+
+        let sections = manager.import_text_file(path, SplitStrategy::Headings).await.unwrap();
+        assert_eq!(sections.memory_ids.len(), 2);
+        let pool = test_support::database().await.get_pool().await.clone();
+        let first = MemoryManager::get_memory_static(&pool, &sections.memory_ids[0], None).await.unwrap().unwrap();
+        assert_eq!(first.title.as_deref(), Some("Groceries"));
+        assert_eq!(first.source.as_deref(), Some(sections.source.as_str()));
+    }
+
+    #[tokio::test]
+    async fn answer_cap_leaves_citations_complete() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        for place in ["harbour", "lighthouse", "boathouse"] {
+            manager
+                .add_memory(entry(&format!("the ferry schedule is posted at the {} every spring", place), &[]))
+                .await
+                .unwrap();
+        }
+
+        let full = manager.query_memory(test_support::query("ferry")).await.unwrap();
+        let mut request = test_support::query("ferry");
+        request.max_answer_chars = Some(30);
+        let capped = manager.query_memory(request).await.unwrap();
+
+        assert!(full.answer.chars().count() > 30);
+        assert!(capped.answer.chars().count() <= 30, "{}", capped.answer);
+        assert!(capped.answer.contains('…'));
+        assert_eq!(capped.citations.len(), 3);
+        let contents = |result: &QueryResult| result.citations.iter().map(|c| c.content.clone()).collect::<Vec<_>>();
+        assert_eq!(contents(&capped), contents(&full));
+    }
+
+    #[tokio::test]
+    async fn answer_markers_match_the_citations() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        for place in ["harbour", "lighthouse", "boathouse", "pier"] {
+            manager
+                .add_memory(entry(&format!("the ferry leaves from the {} at noon", place), &[]))
+                .await
+                .unwrap();
+        }
+
+        let result = manager.query_memory(test_support::query("ferry")).await.unwrap();
+        let parts: Vec<&str> = result.answer.split("\n\n").collect();
+        assert_eq!(result.citations.len(), 4);
+        assert_eq!(parts.len(), result.citations.len());
+        for (i, (part, citation)) in parts.iter().zip(&result.citations).enumerate() {
+            let marker = format!(" [{}]", i + 1);
+            assert!(part.ends_with(&marker), "{}", part);
+            assert_eq!(part.trim_end_matches(&marker), citation.content);
+            assert_eq!(result.answer.matches(&marker).count(), 1);
+        }
+        assert!(!result.answer.contains(&format!("[{}]", result.citations.len() + 1)));
+    }
+
+    #[tokio::test]
+    async fn legacy_ciphertexts_are_upgraded_and_still_decrypt() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.enable_encryption("hunter2".into()).await.unwrap();
+        let id = manager.add_memory(entry("the spare key is under the mat", &[])).await.unwrap();
+
+        // Rewrite the row as version 1 did: no prefix, no associated data
+        let key = manager.vault_key.unwrap();
+        let legacy = BASE64.encode(CryptoManager::new().encrypt_data(b"the spare key is under the mat", &key).unwrap());
+        let pool = test_support::database().await.get_pool().await.clone();
+        sqlx::query("UPDATE memories SET title = NULL, content = ?, crypto_version = 1 WHERE id = ?")
+            .bind(&legacy)
+            .bind(&id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE chunks SET content = ?, crypto_version = 1 WHERE memory_id = ?")
+            .bind(&legacy)
+            .bind(&id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let chunks = count_rows(&pool, "chunks").await as u64;
+
+        assert_eq!(manager.migrate_crypto_format().await.unwrap(), 1 + chunks);
+        assert_eq!(manager.migrate_crypto_format().await.unwrap(), 0);
+
+        let (content, version): (String, i64) = sqlx::query_as("SELECT content, crypto_version FROM memories WHERE id = ?")
+            .bind(&id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(crypto::is_v2(&content));
+        assert_eq!(version, crypto::CRYPTO_VERSION);
+        let cipher = manager.text_cipher();
+        let stored = MemoryManager::get_memory_static(&pool, &id, cipher.as_ref()).await.unwrap().unwrap();
+        assert_eq!(stored.content, "the spare key is under the mat");
+    }
+
+    #[tokio::test]
+    async fn neighbors_follow_creation_time() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let pool = test_support::database().await.get_pool().await.clone();
+
+        // Inserted out of order, with both timestamp formats SQLite writes
+        let mut seeded = Vec::new();
+        for created_at in ["2024-03-02T08:00:00Z", "2024-03-01 09:00:00", "2024-03-03T07:30:00Z", "2024-03-01T10:15:00Z"] {
+            let id = manager.add_memory(entry(&format!("written at {}", created_at), &[])).await.unwrap();
+            sqlx::query("UPDATE memories SET created_at = ? WHERE id = ?")
+                .bind(created_at)
+                .bind(&id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            seeded.push(id);
+        }
+        let chronological = [&seeded[1], &seeded[3], &seeded[0], &seeded[2]];
+
+        let ids = |neighbors: MemoryNeighbors| (neighbors.prev.and_then(|m| m.id), neighbors.next.and_then(|m| m.id));
+        for (i, id) in chronological.iter().enumerate() {
+            let (prev, next) = ids(manager.memory_neighbors(id.to_string()).await.unwrap());
+            assert_eq!(prev.as_ref(), i.checked_sub(1).map(|p| chronological[p]));
+            assert_eq!(next.as_ref(), chronological.get(i + 1).copied());
+        }
+        assert!(manager.memory_neighbors("missing".into()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn pinned_memories_sort_first_when_asked() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let mut ids = Vec::new();
+        for title in ["Alpha", "Bravo", "Charlie"] {
+            let mut memory = entry(&format!("{} notes on the garden", title), &[]);
+            memory.title = Some(title.into());
+            ids.push(manager.add_memory(memory).await.unwrap());
+        }
+
+        let titles = |memories: Vec<MemoryEntry>| memories.into_iter().map(|m| (m.title.unwrap(), m.is_pinned)).collect::<Vec<_>>();
+        let criteria = || SearchCriteria {
+            order: SearchOrder::TitleAsc,
+            ..test_support::search("garden")
+        };
+
+        manager.set_pinned(ids[2].clone(), true).await.unwrap();
+        let pinned_first = titles(manager.search_memories(criteria(), None, true).await.unwrap());
+        assert_eq!(
+            pinned_first,
+            [("Charlie".into(), true), ("Alpha".into(), false), ("Bravo".into(), false)]
+        );
+        let plain = titles(manager.search_memories(criteria(), None, false).await.unwrap());
+        assert_eq!(plain[0], ("Alpha".into(), false));
+        assert_eq!(plain[2], ("Charlie".into(), true));
+
+        manager.set_pinned(ids[2].clone(), false).await.unwrap();
+        let unpinned = titles(manager.search_memories(criteria(), None, true).await.unwrap());
+        assert_eq!(unpinned.iter().map(|(t, _)| t.as_str()).collect::<Vec<_>>(), ["Alpha", "Bravo", "Charlie"]);
+        assert!(unpinned.iter().all(|(_, pinned)| !pinned));
+
+        assert!(manager.set_pinned("missing".into(), true).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn bulk_tag_covers_exactly_the_matches_and_can_be_reversed() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let invoice = manager.add_memory(entry("invoice for the roof repair", &["house"])).await.unwrap();
+        let reminder = manager.add_memory(entry("pay the plumber invoice friday", &[])).await.unwrap();
+        let other = manager.add_memory(entry("plant tulips in october", &["garden"])).await.unwrap();
+        let pool = test_support::database().await.get_pool().await.clone();
+        let tags = |id: &String| {
+            let pool = pool.clone();
+            let id = id.clone();
+            async move {
+                let mut tags = MemoryManager::get_memory_static(&pool, &id, None).await.unwrap().unwrap().tags;
+                tags.sort();
+                tags
+            }
+        };
+
+        let added = manager.bulk_tag(test_support::search("invoice"), "finance".into(), BulkTagMode::Add).await.unwrap();
+        assert_eq!(added, 2);
+        assert_eq!(tags(&invoice).await, ["finance", "house"]);
+        assert_eq!(tags(&reminder).await, ["finance"]);
+        assert_eq!(tags(&other).await, ["garden"]);
+        // Already tagged, so nothing changes
+        assert_eq!(manager.bulk_tag(test_support::search("invoice"), "finance".into(), BulkTagMode::Add).await.unwrap(), 0);
+
+        let removed = manager.bulk_tag(test_support::search("invoice"), "finance".into(), BulkTagMode::Remove).await.unwrap();
+        assert_eq!(removed, 2);
+        assert_eq!(tags(&invoice).await, ["house"]);
+        assert!(tags(&reminder).await.is_empty());
+        assert_eq!(tags(&other).await, ["garden"]);
+    }
+
+    #[tokio::test]
+    async fn sync_estimate_counts_pending_tokens_and_batches() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        for i in 0..70 {
+            manager.add_memory(entry(&format!("note number {} about tides", i), &[])).await.unwrap();
+        }
+        let pool = test_support::database().await.get_pool().await.clone();
+        assert_eq!(count_rows(&pool, "chunks").await, 70);
+
+        let estimate = manager.estimate_embedding_sync().await.unwrap();
+        assert_eq!(estimate.model, "hashing-v1");
+        assert_eq!(estimate.chunks_to_embed, 70);
+        // Five words a chunk, 64 chunks a request
+        assert_eq!(estimate.estimated_tokens, 350);
+        assert_eq!(estimate.estimated_requests, 2);
+
+        manager.sync_embeddings().await.unwrap();
+        let estimate = manager.estimate_embedding_sync().await.unwrap();
+        assert_eq!((estimate.chunks_to_embed, estimate.estimated_tokens, estimate.estimated_requests), (0, 0, 0));
+    }
+
+    fn assert_read_only<T: std::fmt::Debug>(operation: &str, result: Result<T>) {
+        let error = result.expect_err(operation);
+        assert!(error.downcast_ref::<database::ReadOnly>().is_some(), "{}: {}", operation, error);
+    }
+
+    #[tokio::test]
+    async fn read_only_mode_serves_reads_and_refuses_writes() {
+        let _vault = TestVault::new().await;
+        let mut writer = MemoryManager::new();
+        let id = writer.add_memory(entry("the boiler was serviced in march", &["house"])).await.unwrap();
+        let other = writer.add_memory(entry("the roof was checked in april", &[])).await.unwrap();
+        writer.sync_embeddings().await.unwrap();
+        let saved = writer.save_search("house".into(), test_support::search("boiler")).await.unwrap();
+        drop(writer);
+
+        database::set_read_only(true);
+        let mut manager = MemoryManager::new();
+
+        assert_eq!(manager.search_memories(test_support::search("boiler"), None, false).await.unwrap().len(), 1);
+        assert_eq!(manager.query_memory(test_support::query("boiler")).await.unwrap().citations.len(), 1);
+        assert_eq!(manager.get_stats().await.unwrap().total_memories, 2);
+        assert!(!manager.list_tags().await.unwrap().is_empty());
+        assert!(manager.export_data("json".into(), ExportOptions::default()).await.unwrap().contains("boiler"));
+        assert_eq!(manager.list_saved_searches().await.unwrap().len(), 1);
+        assert!(manager.get_settings().await.is_ok());
+
+        assert_read_only("add_memory", manager.add_memory(entry("new", &[])).await);
+        assert_read_only("update_memory", manager.update_memory(id.clone(), entry("edited", &[])).await);
+        assert_read_only("delete_memory", manager.delete_memory(id.clone()).await);
+        assert_read_only("set_pinned", manager.set_pinned(id.clone(), true).await);
+        assert_read_only("set_memory_expiry", manager.set_memory_expiry(id.clone(), None).await);
+        assert_read_only("link_memories", manager.link_memories(id.clone(), other.clone(), "related".into()).await);
+        assert_read_only(
+            "bulk_tag",
+            manager.bulk_tag(test_support::search("boiler"), "x".into(), BulkTagMode::Add).await,
+        );
+        assert_read_only("update_settings", manager.update_settings(MemorySettings::default()).await);
+        assert_read_only("save_search", manager.save_search("more".into(), test_support::search("roof")).await);
+        assert_read_only("delete_saved_search", manager.delete_saved_search(saved.id.clone()).await);
+        assert_read_only("create_snapshot", manager.create_snapshot("before".into()).await);
+        assert_read_only("clear_query_history", manager.clear_query_history().await);
+        assert_read_only("sync_embeddings", manager.sync_embeddings().await);
+        assert_read_only("clear_embeddings", manager.clear_embeddings().await);
+        assert_read_only("prune_embeddings", manager.prune_embeddings(None, Some("hashing-v1".into())).await);
+        assert_read_only("rebuild_chunks", manager.rebuild_chunks().await);
+        assert_read_only("repair_missing_chunks", manager.repair_missing_chunks().await);
+        assert_read_only("backfill_titles", manager.backfill_titles().await);
+        assert_read_only("repair_tag_references", manager.repair_tag_references().await);
+        assert_read_only("deduplicate_chunks", manager.deduplicate_chunks().await);
+        assert_read_only("rebuild_fts_index", manager.rebuild_fts_index().await);
+        assert_read_only("sweep_expired_memories", manager.sweep_expired_memories().await);
+        assert_read_only("enable_encryption", manager.enable_encryption("hunter2".into()).await);
+
+        // Nothing reached the file
+        database::set_read_only(false);
+        let pool = test_support::database().await.get_pool().await.clone();
+        assert_eq!(count_rows(&pool, "memories").await, 2);
+        assert_eq!(count_rows(&pool, "saved_searches").await, 1);
+    }
+
+    #[tokio::test]
+    async fn dangling_tag_references_are_removed() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let id = manager.add_memory(entry("the shed needs a new lock", &["house"])).await.unwrap();
+
+        // As left by a delete that ran without foreign keys
+        let pool = test_support::database().await.get_pool().await.clone();
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query("PRAGMA foreign_keys = OFF").execute(&mut *conn).await.unwrap();
+        for (memory_id, tag_id) in [("gone-memory", "gone-tag"), ("gone-memory", "other-gone-tag"), (id.as_str(), "gone-tag")] {
+            sqlx::query("INSERT INTO memory_tags (memory_id, tag_id) VALUES (?, ?)")
+                .bind(memory_id)
+                .bind(tag_id)
+                .execute(&mut *conn)
+                .await
+                .unwrap();
+        }
+        sqlx::query("PRAGMA foreign_keys = ON").execute(&mut *conn).await.unwrap();
+        drop(conn);
+        assert_eq!(count_rows(&pool, "memory_tags").await, 4);
+
+        let repair = manager.repair_tag_references().await.unwrap();
+        assert_eq!((repair.missing_memory_rows, repair.missing_tag_rows), (2, 1));
+        assert_eq!(count_rows(&pool, "memory_tags").await, 1);
+        let stored = MemoryManager::get_memory_static(&pool, &id, None).await.unwrap().unwrap();
+        assert_eq!(stored.tags, ["house"]);
+
+        let again = manager.repair_tag_references().await.unwrap();
+        assert_eq!((again.missing_memory_rows, again.missing_tag_rows), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn default_tags_are_added_once_and_only_when_set() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let pool = test_support::database().await.get_pool().await.clone();
+        let tags = |id: String| {
+            let pool = pool.clone();
+            async move { MemoryManager::get_memory_static(&pool, &id, None).await.unwrap().unwrap().tags }
+        };
+
+        let untagged = manager.add_memory(entry("before any default tag", &[])).await.unwrap();
+        assert!(tags(untagged).await.is_empty());
+
+        let settings = MemorySettings {
+            default_tags: vec![" inbox ".into(), "inbox".into()],
+            ..manager.get_settings().await.unwrap()
+        };
+        manager.update_settings(settings).await.unwrap();
+        assert_eq!(manager.get_settings().await.unwrap().default_tags, ["inbox"]);
+
+        let added = manager.add_memory(entry("picked up on the way home", &["errand"])).await.unwrap();
+        assert_eq!(tags(added).await, ["errand", "inbox"]);
+        let present = manager.add_memory(entry("already filed", &["inbox "])).await.unwrap();
+        assert_eq!(tags(present).await, ["inbox"]);
+
+        let settings = MemorySettings {
+            default_tags: Vec::new(),
+            ..manager.get_settings().await.unwrap()
+        };
+        manager.update_settings(settings).await.unwrap();
+        let after = manager.add_memory(entry("after clearing the default", &[])).await.unwrap();
+        assert!(tags(after).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn wrong_dimension_vectors_are_flagged_and_reembedded() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("the kettle is descaled monthly", &[])).await.unwrap();
+        manager.add_memory(entry("the fridge filter lasts six months", &[])).await.unwrap();
+        manager.sync_embeddings().await.unwrap();
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        let bad_id: String = sqlx::query_scalar("SELECT id FROM embeddings ORDER BY id LIMIT 1")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE embeddings SET vector = ? WHERE id = ?")
+            .bind(vector_codec::encode(&[0.5; 16]).unwrap())
+            .bind(&bad_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let report = manager.verify_embedding_dimensions(false).await.unwrap();
+        assert_eq!(report.expected, embeddings::DEFAULT_DIMENSIONS);
+        assert_eq!(report.mismatched_ids, [bad_id]);
+        assert_eq!(report.reembedded, 0);
+
+        let repaired = manager.verify_embedding_dimensions(true).await.unwrap();
+        assert_eq!((repaired.mismatched_count, repaired.reembedded), (1, 1));
+        assert_eq!(manager.verify_embedding_dimensions(false).await.unwrap().mismatched_count, 0);
+    }
+
+    #[tokio::test]
+    async fn saved_search_export_matches_the_search() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("recipe: lentil soup with cumin", &["cooking"])).await.unwrap();
+        manager.add_memory(entry("recipe: lemon tart", &["cooking", "baking"])).await.unwrap();
+        manager.add_memory(entry("recipe: lemon drizzle cake", &["baking"])).await.unwrap();
+        manager.add_memory(entry("bike tyre pressure is 60 psi", &[])).await.unwrap();
+        let criteria = || SearchCriteria {
+            tags: Some(vec!["baking".into()]),
+            ..test_support::search("recipe")
+        };
+        manager.save_search("Baking".into(), criteria()).await.unwrap();
+
+        let export = manager.export_saved_search("Baking".into(), "json".into(), ExportOptions::default()).await.unwrap();
+        let document: serde_json::Value = serde_json::from_str(checksum::verify(&export).unwrap()).unwrap();
+        assert_eq!(document["saved_search"], "Baking");
+        let exported: Vec<&str> = document["data"].as_array().unwrap().iter().map(|m| m["content"].as_str().unwrap()).collect();
+
+        let searched: Vec<String> = manager
+            .search_memories(criteria(), None, false)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.content)
+            .collect();
+        assert_eq!(searched.len(), 2);
+        assert_eq!(exported, searched);
+
+        assert!(manager.export_saved_search("Missing".into(), "json".into(), ExportOptions::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn heatmap_zero_fills_quiet_days() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let pool = test_support::database().await.get_pool().await.clone();
+        let today = Utc::now().date_naive();
+
+        // Two today, three two days ago, one outside the window
+        for days_ago in [0, 0, 2, 2, 2, 9] {
+            let id = manager.add_memory(entry(&format!("{} days ago", days_ago), &[])).await.unwrap();
+            let created_at = (today - Duration::days(days_ago)).and_hms_opt(12, 0, 0).unwrap().and_utc().to_rfc3339();
+            sqlx::query("UPDATE memories SET created_at = ? WHERE id = ?")
+                .bind(created_at)
+                .bind(&id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let days = manager.activity_heatmap(5).await.unwrap();
+        let expected: Vec<(String, u64)> = [(4, 0), (3, 0), (2, 3), (1, 0), (0, 2)]
+            .into_iter()
+            .map(|(days_ago, count)| ((today - Duration::days(days_ago)).to_string(), count))
+            .collect();
+        assert_eq!(days.into_iter().map(|d| (d.date, d.memory_count)).collect::<Vec<_>>(), expected);
+
+        assert!(manager.activity_heatmap(0).await.is_err());
+        assert!(manager.activity_heatmap(MAX_HEATMAP_DAYS + 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn crypto_audit_flags_weak_parameters_and_legacy_rows() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let id = manager.add_memory(entry("the safe combination is written nowhere", &[])).await.unwrap();
+
+        let plaintext = manager.audit_crypto().await.unwrap();
+        assert!(!plaintext.encrypted && plaintext.kdf.is_none());
+        assert!(!plaintext.meets_recommendations);
+
+        manager.enable_encryption("hunter2".into()).await.unwrap();
+        let sound = manager.audit_crypto().await.unwrap();
+        assert!(sound.encrypted && sound.meets_recommendations, "{:?}", sound.suggestions);
+
+        // As a vault created by an early build would have been left
+        let pool = test_support::database().await.get_pool().await.clone();
+        sqlx::query("UPDATE vaults SET kdf_memory_kib = 4096, kdf_iterations = 1, kdf_parallelism = 1 WHERE id = ?")
+            .bind(DEFAULT_VAULT_ID)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE memories SET crypto_version = 1 WHERE id = ?")
+            .bind(&id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let weak = manager.audit_crypto().await.unwrap();
+        assert!(!weak.meets_recommendations);
+        let kdf = weak.kdf.unwrap();
+        assert_eq!((kdf.memory_kib, kdf.iterations, kdf.parallelism), (4096, 1, 1));
+        assert_eq!(weak.legacy_rows, 1);
+        assert_eq!(weak.suggestions.len(), 2);
+        assert!(weak.suggestions[0].contains("m=4096 KiB, t=1, p=1"));
+        assert!(weak.suggestions[1].contains("migrate_crypto_format"));
+    }
+
+    #[tokio::test]
+    async fn directory_import_walks_the_tree_with_relative_sources() {
+        let vault = TestVault::new().await;
+        let root = vault.dir().join("notes");
+        std::fs::create_dir_all(root.join("deep/deeper")).unwrap();
+        std::fs::create_dir_all(root.join(".obsidian")).unwrap();
+        std::fs::write(root.join("plan.md"), "---\ntitle: Garden plan\ntags: [garden, spring]\n---\nsow peas in march\n").unwrap();
+        std::fs::write(root.join("deep/log.txt"), "first entry\n\nsecond entry\n").unwrap();
+        std::fs::write(root.join("deep/deeper/untitled.md"), "a note with no frontmatter\n").unwrap();
+        std::fs::write(root.join("deep/empty.txt"), "\n\n").unwrap();
+        std::fs::write(root.join("deep/photo.jpg"), [0xFFu8, 0xD8, 0xFF, 0x00]).unwrap();
+        std::fs::write(root.join(".obsidian/config.md"), "hidden\n").unwrap();
+
+        let mut manager = MemoryManager::new();
+        let result = manager
+            .import_directory(root.display().to_string(), None, SplitStrategy::BlankLines)
+            .await
+            .unwrap();
+        assert_eq!(result.files_imported, 3);
+        assert_eq!(result.memory_ids.len(), 4);
+        assert_eq!(
+            result.skipped.iter().map(|s| (s.path.as_str(), s.reason.as_str())).collect::<Vec<_>>(),
+            [("deep/empty.txt", "no content")]
+        );
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        let mut imported = Vec::new();
+        for id in &result.memory_ids {
+            let memory = MemoryManager::get_memory_static(&pool, id, None).await.unwrap().unwrap();
+            imported.push((memory.source.unwrap(), memory.title, memory.content, memory.tags));
+        }
+        imported.sort();
+        assert_eq!(
+            imported,
+            [
+                ("deep/deeper/untitled.md".into(), Some("untitled".into()), "a note with no frontmatter".into(), vec![]),
+                ("deep/log.txt".into(), Some("first entry".into()), "first entry".into(), vec![]),
+                ("deep/log.txt".into(), Some("second entry".into()), "second entry".into(), vec![]),
+                (
+                    "plan.md".into(),
+                    Some("Garden plan".into()),
+                    "sow peas in march".into(),
+                    vec!["garden".to_string(), "spring".to_string()]
+                ),
+            ]
+        );
+
+        let only_markdown = manager
+            .import_directory(root.display().to_string(), Some("deep/**/*.md".into()), SplitStrategy::BlankLines)
+            .await
+            .unwrap();
+        assert_eq!(only_markdown.files_imported, 1);
+        assert!(manager.import_directory(root.join("plan.md").display().to_string(), None, SplitStrategy::BlankLines).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn embedding_debug_reports_stored_vectors_and_gaps() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let content: String = (0..80).map(|i| format!("Sentence {} about the canal walk. ", i)).collect();
+        let id = manager.add_memory(entry(&content, &[])).await.unwrap();
+        manager.sync_embeddings().await.unwrap();
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        let chunk_ids: Vec<String> = sqlx::query_scalar("SELECT id FROM chunks WHERE memory_id = ? ORDER BY start_pos, id")
+            .bind(&id)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert!(chunk_ids.len() >= 3);
+        sqlx::query("DELETE FROM embeddings WHERE chunk_id = ?")
+            .bind(&chunk_ids[0])
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE embeddings SET vector = ? WHERE chunk_id = ?")
+            .bind(vector_codec::encode(&vec![0.0; embeddings::DEFAULT_DIMENSIONS]).unwrap())
+            .bind(&chunk_ids[1])
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let debug = manager.memory_embedding_debug(id.clone(), false).await.unwrap();
+        assert_eq!(debug.model, "hashing-v1");
+        assert_eq!(debug.chunks.iter().map(|c| c.chunk_id.clone()).collect::<Vec<_>>(), chunk_ids);
+
+        let missing = &debug.chunks[0];
+        assert!(missing.missing_embedding && missing.embeddings.is_empty());
+        let zero = &debug.chunks[1].embeddings[0];
+        assert!(!debug.chunks[1].missing_embedding);
+        assert!(zero.degenerate);
+        for chunk in &debug.chunks[2..] {
+            assert!(!chunk.missing_embedding);
+            let embedding = &chunk.embeddings[0];
+            assert_eq!(embedding.dimensions, Some(embeddings::DEFAULT_DIMENSIONS));
+            assert!((embedding.norm.unwrap() - 1.0).abs() < 1e-3);
+            assert!(!embedding.degenerate && embedding.vector.is_none());
+        }
+
+        let with_vectors = manager.memory_embedding_debug(id, true).await.unwrap();
+        assert_eq!(with_vectors.chunks[2].embeddings[0].vector.as_ref().map(Vec::len), Some(embeddings::DEFAULT_DIMENSIONS));
+    }
+
+    #[tokio::test]
+    async fn tag_limit_applies_to_adds_and_updates_but_not_reads() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let crowded = manager.add_memory(entry("tagged before the limit", &["a", "b", "c", "d"])).await.unwrap();
+        let settings = MemorySettings {
+            max_tags_per_memory: 3,
+            ..manager.get_settings().await.unwrap()
+        };
+        manager.update_settings(settings).await.unwrap();
+
+        let error = manager.add_memory(entry("too many", &["a", "b", "c", "d"])).await.unwrap_err();
+        assert!(error.to_string().contains("max_tags_per_memory is 3"), "{}", error);
+        let id = manager.add_memory(entry("just enough", &["a", "b", "c"])).await.unwrap();
+
+        assert!(manager.update_memory(id.clone(), entry("just enough", &["a", "b", "c", "d"])).await.is_err());
+        manager.update_memory(id.clone(), entry("still enough", &["x", "y"])).await.unwrap();
+        assert!(manager.bulk_tag(test_support::search("enough"), "z".into(), BulkTagMode::Add).await.is_ok());
+        assert!(manager.bulk_tag(test_support::search("enough"), "w".into(), BulkTagMode::Add).await.is_err());
+
+        // Memories already over the limit still read and search normally
+        let pool = test_support::database().await.get_pool().await.clone();
+        let stored = MemoryManager::get_memory_static(&pool, &crowded, None).await.unwrap().unwrap();
+        assert_eq!(stored.tags.len(), 4);
+        let found = manager.search_memories(test_support::search("before the limit"), None, false).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(manager.export_data("json".into(), ExportOptions::default()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn word_cloud_counts_words_without_stopwords() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("The coffee in the morning and the coffee at noon", &[])).await.unwrap();
+        manager.add_memory(entry("Coffee beans from the market, 2024", &[])).await.unwrap();
+        manager.add_memory(entry("The market opens at nine", &[])).await.unwrap();
+
+        let words = manager.word_cloud(Some(3), None).await.unwrap();
+        let words: Vec<(&str, u64)> = words.iter().map(|w| (w.word.as_str(), w.count)).collect();
+        assert_eq!(words, [("coffee", 3), ("market", 2), ("beans", 1)]);
+
+        let all = manager.word_cloud(Some(100), Some(1)).await.unwrap();
+        for excluded in ["the", "and", "in", "at", "2024"] {
+            assert!(all.iter().all(|w| w.word != excluded), "{} was counted", excluded);
+        }
+        assert!(manager.word_cloud(None, Some(6)).await.unwrap().iter().all(|w| w.word.chars().count() >= 6));
+    }
+
+    #[tokio::test]
+    async fn memories_without_chunks_are_rechunked() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let broken = manager.add_memory(entry("the gate code changed to 4821", &[])).await.unwrap();
+        let intact = manager.add_memory(entry("the bins go out on tuesday", &[])).await.unwrap();
+
+        // As an add_memory that failed between the memory and its chunks
+        let pool = test_support::database().await.get_pool().await.clone();
+        sqlx::query("DELETE FROM chunks WHERE memory_id = ?")
+            .bind(&broken)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let intact_chunks: Vec<String> = sqlx::query_scalar("SELECT id FROM chunks WHERE memory_id = ?")
+            .bind(&intact)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(manager.repair_missing_chunks().await.unwrap(), 1);
+        assert_eq!(manager.repair_missing_chunks().await.unwrap(), 0);
+
+        let chunks: Vec<String> = sqlx::query_scalar("SELECT content FROM chunks WHERE memory_id = ?")
+            .bind(&broken)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(chunks, ["the gate code changed to 4821"]);
+        let unchanged: Vec<String> = sqlx::query_scalar("SELECT id FROM chunks WHERE memory_id = ?")
+            .bind(&intact)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(unchanged, intact_chunks);
+        let result = manager.query_memory(test_support::query("gate code")).await.unwrap();
+        assert_eq!(result.citations.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn answer_takes_the_most_relevant_chunks_in_order() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        for content in [
+            "tomato sauce",
+            "tomato sauce with basil and garlic",
+            "the tomato plants need staking before the sauce season",
+            "basil grows well beside tomato plants on a sunny windowsill in june",
+        ] {
+            manager.add_memory(entry(content, &[])).await.unwrap();
+        }
+        manager.sync_embeddings().await.unwrap();
+
+        let mut request = test_support::query("tomato sauce");
+        request.answer_chunk_count = Some(2);
+        let result = manager.query_memory(request).await.unwrap();
+        assert_eq!(result.citations.len(), 4);
+
+        let markers: Vec<usize> = result
+            .answer
+            .split("\n\n")
+            .map(|part| part.rsplit_once(" [").unwrap().1.trim_end_matches(']').parse().unwrap())
+            .collect();
+        assert_eq!(markers.len(), 2);
+        let scores: Vec<f32> = result.citations.iter().map(|c| c.relevance_score).collect();
+        let mut best = scores.clone();
+        best.sort_by(|a, b| b.total_cmp(a));
+        assert_eq!(markers.iter().map(|m| scores[m - 1]).collect::<Vec<_>>(), best[..2]);
+        assert!(result.answer.starts_with("tomato sauce ["));
+    }
+
+    #[tokio::test]
+    async fn untagged_memories_are_listed_newest_first() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let pool = test_support::database().await.get_pool().await.clone();
+        for (content, tags, created_at) in [
+            ("oldest untagged", &[][..], "2024-01-01T08:00:00Z"),
+            ("tagged", &["work"][..], "2024-01-02T08:00:00Z"),
+            ("newest untagged", &[][..], "2024-01-04T08:00:00Z"),
+            ("middle untagged", &[][..], "2024-01-03 08:00:00"),
+        ] {
+            let id = manager.add_memory(entry(content, tags)).await.unwrap();
+            sqlx::query("UPDATE memories SET created_at = ? WHERE id = ?")
+                .bind(created_at)
+                .bind(&id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let contents = |memories: Vec<MemoryEntry>| memories.into_iter().map(|m| m.content).collect::<Vec<_>>();
+        assert_eq!(
+            contents(manager.untagged_memories(None, None).await.unwrap()),
+            ["newest untagged", "middle untagged", "oldest untagged"]
+        );
+        assert_eq!(contents(manager.untagged_memories(Some(1), Some(1)).await.unwrap()), ["middle untagged"]);
+    }
+
+    #[tokio::test]
+    async fn backfilled_titles_follow_the_first_line() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let mut titled = entry("keeps its own title", &[]);
+        titled.title = Some("Chosen".into());
+        let titled = manager.add_memory(titled).await.unwrap();
+        let heading = manager.add_memory(entry("\n## Trip to Lisbon\nflights booked", &[])).await.unwrap();
+        let long = manager
+            .add_memory(entry("one two three four five six seven eight nine ten eleven twelve", &[]))
+            .await
+            .unwrap();
+
+        // As memories imported before titles were derived
+        let pool = test_support::database().await.get_pool().await.clone();
+        sqlx::query("UPDATE memories SET title = CASE WHEN id = ? THEN '  ' ELSE NULL END WHERE id != ?")
+            .bind(&heading)
+            .bind(&titled)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(manager.backfill_titles().await.unwrap(), 2);
+        assert_eq!(manager.backfill_titles().await.unwrap(), 0);
+
+        let title = |id: String| {
+            let pool = pool.clone();
+            async move { MemoryManager::get_memory_static(&pool, &id, None).await.unwrap().unwrap().title.unwrap() }
+        };
+        assert_eq!(title(titled).await, "Chosen");
+        assert_eq!(title(heading).await, "Trip to Lisbon");
+        assert_eq!(title(long).await, "one two three four five six seven eight nine ten…");
+    }
+
+    #[tokio::test]
+    async fn encrypted_memories_stay_out_of_the_full_text_index() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let mut memory = entry("the launch codes are in the drawer", &[]);
+        memory.title = Some("Launch plan".into());
+        manager.add_memory(memory).await.unwrap();
+        let pool = test_support::database().await.get_pool().await.clone();
+        assert_eq!(count_rows(&pool, "memories_fts_docsize").await, 1);
+
+        manager.enable_encryption("hunter2".into()).await.unwrap();
+        manager.add_memory(entry("the spare key hangs by the boiler", &[])).await.unwrap();
+        manager.rebuild_fts_index().await.unwrap();
+
+        // Every token the index holds, from its own storage
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query("CREATE VIRTUAL TABLE temp.fts_terms USING fts5vocab(main, memories_fts, 'row')")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        let terms: Vec<String> = sqlx::query_scalar("SELECT term FROM temp.fts_terms").fetch_all(&mut *conn).await.unwrap();
+        assert!(terms.is_empty(), "{:?}", terms);
+        drop(conn);
+        assert_eq!(count_rows(&pool, "memories_fts_docsize").await, 0);
+        assert_eq!(count_rows(&pool, "memory_terms").await, 0);
+
+        let report = manager.check_fts_consistency(false).await.unwrap();
+        assert_eq!((report.memory_rows, report.indexed_rows), (0, 0));
+        assert!(!report.content_mismatch && report.missing_rows == 0 && report.stale_rows == 0);
+        assert!(manager.prefix_search("draw".into(), None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn imports_check_the_export_checksum() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("exported and imported again", &[])).await.unwrap();
+        let export = manager.export_data("json".into(), ExportOptions::default()).await.unwrap();
+
+        manager.import_data(export.clone(), "json".into()).await.unwrap();
+
+        let damaged = export.replacen("imported", "imparted", 1);
+        let error = manager.import_data(damaged, "json".into()).await.unwrap_err();
+        assert!(error.downcast_ref::<checksum::CorruptedFile>().is_some(), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn citation_pages_do_not_overlap() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let pool = test_support::database().await.get_pool().await.clone();
+        let id = manager.add_memory(entry("a heavily cited memory", &[])).await.unwrap();
+
+        // Repeated scores so the id has to break ties
+        for i in 0..45 {
+            sqlx::query(
+                "INSERT INTO citations (id, memory_id, chunk_id, relevance_score)
+                 SELECT ?, memory_id, id, ? FROM chunks WHERE memory_id = ? LIMIT 1",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(f64::from(i % 5) / 5.0)
+            .bind(&id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        let mut paged = Vec::new();
+        for page in 0.. {
+            let citations = manager.get_citations(id.clone(), Some(10), Some(page * 10)).await.unwrap();
+            if citations.is_empty() {
+                break;
+            }
+            paged.extend(citations.into_iter().map(|c| (c.relevance_score, c.id)));
+        }
+        assert_eq!(paged.len(), 45);
+
+        let mut expected = paged.clone();
+        expected.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        assert_eq!(paged, expected);
+        let unique: HashSet<&String> = paged.iter().map(|(_, id)| id).collect();
+        assert_eq!(unique.len(), 45);
+    }
+
+    #[tokio::test]
+    async fn identical_memories_are_similar_and_unrelated_ones_are_not() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let original = manager.add_memory(entry("the ferry to the island leaves at seven", &[])).await.unwrap();
+        let copy = manager.add_memory(entry("the ferry to the island leaves at seven", &[])).await.unwrap();
+        let unrelated = manager.add_memory(entry("knead the dough and let it prove overnight", &[])).await.unwrap();
+
+        // Word overlap before any embeddings exist, vectors afterwards
+        for synced in [false, true] {
+            if synced {
+                manager.sync_embeddings().await.unwrap();
+            }
+            let same = manager.memory_similarity(original.clone(), copy.clone()).await.unwrap();
+            let different = manager.memory_similarity(original.clone(), unrelated.clone()).await.unwrap();
+            assert!((same - 1.0).abs() < 1e-4, "synced: {}, {}", synced, same);
+            assert!(different < 0.5, "synced: {}, {}", synced, different);
+        }
+        assert!(manager.memory_similarity(original, "missing".into()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn fingerprint_is_stable_until_the_vault_changes() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let empty = manager.vault_fingerprint().await.unwrap();
+        let id = manager.add_memory(entry("train times for the weekend", &["travel"])).await.unwrap();
+
+        let added = manager.vault_fingerprint().await.unwrap();
+        assert_ne!(added, empty);
+        assert_eq!(manager.vault_fingerprint().await.unwrap(), added);
+        assert_eq!(MemoryManager::new().vault_fingerprint().await.unwrap(), added);
+
+        manager.update_memory(id, entry("train times for the long weekend", &["travel"])).await.unwrap();
+        let edited = manager.vault_fingerprint().await.unwrap();
+        assert_ne!(edited, added);
+        assert_ne!(edited, empty);
+    }
+
+    #[tokio::test]
+    async fn bookmarks_import_with_titles_urls_and_folder_tags() {
+        let vault = TestVault::new().await;
+        let path = vault.dir().join("bookmarks.html");
+        std::fs::write(
+            &path,
+            r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><H3 ADD_DATE="1700000000">Bookmarks bar</H3>
+    <DL><p>
+        <DT><A HREF="https://example.com/news">Daily &amp; weekly news</A>
+        <DT><H3>Rust / Tools</H3>
+        <DL><p>
+            <DT><A HREF="https://crates.io/">crates.io</A>
+            <DD>The Rust package registry
+        </DL><p>
+        <DT><A HREF="javascript:alert(1)">Bookmarklet</A>
+    </DL><p>
+    <DT><A HREF="https://example.org/top">Top level</A>
+</DL><p>
+"#,
+        )
+        .unwrap();
+
+        let mut manager = MemoryManager::new();
+        let result = manager.import_bookmarks(path.display().to_string()).await.unwrap();
+        assert_eq!(result.memory_ids.len(), 3);
+        assert_eq!(
+            result.skipped.iter().map(|s| s.url.as_str()).collect::<Vec<_>>(),
+            ["javascript:alert(1)"]
+        );
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        let mut imported = Vec::new();
+        for id in &result.memory_ids {
+            let memory = MemoryManager::get_memory_static(&pool, id, None).await.unwrap().unwrap();
+            imported.push((memory.title.unwrap(), memory.source.unwrap(), memory.tags));
+        }
+        assert_eq!(
+            imported,
+            [
+                ("Daily & weekly news".into(), "https://example.com/news".into(), vec!["Bookmarks bar".to_string()]),
+                ("crates.io".into(), "https://crates.io/".into(), vec!["Bookmarks bar/Rust - Tools".to_string()]),
+                ("Top level".into(), "https://example.org/top".into(), vec![]),
+            ]
+        );
+        let registry = MemoryManager::get_memory_static(&pool, &result.memory_ids[1], None).await.unwrap().unwrap();
+        assert!(registry.content.ends_with("The Rust package registry"));
+    }
+
+    #[tokio::test]
+    async fn exported_embeddings_import_back_unchanged() {
+        let vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("the bakery opens at six", &[])).await.unwrap();
+        let removed = manager.add_memory(entry("library books are due friday", &[])).await.unwrap();
+        manager.sync_embeddings().await.unwrap();
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        let vectors = || async {
+            sqlx::query_as::<_, (String, Vec<u8>)>("SELECT chunk_id, vector FROM embeddings ORDER BY chunk_id")
+                .fetch_all(&pool)
+                .await
+                .unwrap()
+        };
+        let before = vectors().await;
+        let path = vault.dir().join("vectors.hvix").display().to_string();
+        let export = manager.export_embeddings(path.clone()).await.unwrap();
+        assert_eq!(export.chunk_count, before.len() as u64);
+        assert_eq!((export.model.as_str(), export.dimensions), ("hashing-v1", embeddings::DEFAULT_DIMENSIONS));
+
+        // Vectors for a memory deleted since the export have nowhere to go
+        let removed_chunks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM chunks WHERE memory_id = ?")
+            .bind(&removed)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        manager.delete_memory(removed).await.unwrap();
+        let kept = vectors().await;
+        sqlx::query("DELETE FROM embeddings").execute(&pool).await.unwrap();
+
+        let import = manager.import_embeddings(path.clone()).await.unwrap();
+        assert_eq!(import.imported, kept.len() as u64);
+        assert_eq!(import.missing_chunks, removed_chunks as u64);
+        assert_eq!(vectors().await, kept);
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[10] ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+        let error = manager.import_embeddings(path).await.unwrap_err();
+        assert!(error.downcast_ref::<checksum::CorruptedFile>().is_some(), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn timeline_buckets_split_at_utc_day_week_and_month_boundaries() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let pool = test_support::database().await.get_pool().await.clone();
+
+        // 2024-04-01 is a Monday; the +02:00 time is still 31 March in UTC
+        let mut ids = HashMap::new();
+        for (name, created_at) in [
+            ("sunday", "2024-03-31T23:30:00+00:00"),
+            ("sunday-offset", "2024-04-01T01:45:00+02:00"),
+            ("monday", "2024-04-01T00:15:00+00:00"),
+            ("end-of-week", "2024-04-07T23:59:59+00:00"),
+            ("next-week", "2024-04-08T00:00:00+00:00"),
+        ] {
+            let id = manager.add_memory(entry(name, &[])).await.unwrap();
+            sqlx::query("UPDATE memories SET created_at = ? WHERE id = ?")
+                .bind(created_at)
+                .bind(&id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            ids.insert(id, name);
+        }
+
+        let mut cases = Vec::new();
+        for granularity in [TimelineGranularity::Day, TimelineGranularity::Week, TimelineGranularity::Month] {
+            let buckets = manager.timeline(granularity).await.unwrap();
+            cases.push(
+                buckets
+                    .into_iter()
+                    .map(|b| {
+                        assert_eq!(b.memory_count, b.memories.len() as u64);
+                        (b.start, b.memories.iter().map(|m| ids[&m.id]).collect::<Vec<_>>())
+                    })
+                    .collect::<Vec<_>>(),
+            );
+        }
+        assert_eq!(
+            cases,
+            [
+                vec![
+                    ("2024-04-08".to_string(), vec!["next-week"]),
+                    ("2024-04-07".to_string(), vec!["end-of-week"]),
+                    ("2024-04-01".to_string(), vec!["monday"]),
+                    ("2024-03-31".to_string(), vec!["sunday-offset", "sunday"]),
+                ],
+                vec![
+                    ("2024-04-08".to_string(), vec!["next-week"]),
+                    ("2024-04-01".to_string(), vec!["end-of-week", "monday"]),
+                    ("2024-03-25".to_string(), vec!["sunday-offset", "sunday"]),
+                ],
+                vec![
+                    ("2024-04-01".to_string(), vec!["next-week", "end-of-week", "monday"]),
+                    ("2024-03-01".to_string(), vec!["sunday-offset", "sunday"]),
+                ],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_memories_are_hidden_then_swept_unless_the_expiry_is_cleared() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let past = (Utc::now() - Duration::hours(1)).to_rfc3339();
+        let future = (Utc::now() + Duration::days(1)).to_rfc3339();
+        let expired = manager.add_memory(entry("harbour tour tickets", &[])).await.unwrap();
+        let cleared = manager.add_memory(entry("harbour parking permit", &[])).await.unwrap();
+        let later = manager.add_memory(entry("harbour festival dates", &[])).await.unwrap();
+        manager.set_memory_expiry(expired.clone(), Some(past.clone())).await.unwrap();
+        manager.set_memory_expiry(cleared.clone(), Some(past)).await.unwrap();
+        manager.set_memory_expiry(cleared.clone(), None).await.unwrap();
+        manager.set_memory_expiry(later.clone(), Some(future)).await.unwrap();
+
+        let mut found: Vec<String> = manager
+            .search_memories(test_support::search("harbour"), None, false)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|m| m.id)
+            .collect();
+        found.sort();
+        let mut expected = vec![cleared.clone(), later.clone()];
+        expected.sort();
+        assert_eq!(found, expected);
+        assert_eq!(manager.query_memory(test_support::query("harbour")).await.unwrap().citations.len(), 2);
+
+        assert_eq!(manager.sweep_expired_memories().await.unwrap(), 1);
+        let pool = test_support::database().await.get_pool().await.clone();
+        let remaining: Vec<String> = sqlx::query_scalar("SELECT id FROM memories ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, expected);
+        assert_eq!(manager.sweep_expired_memories().await.unwrap(), 0);
+        assert!(manager.set_memory_expiry(expired, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn duplicate_chunks_share_one_embedding() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let first = manager.add_memory(entry("renew the passport before june", &["admin"])).await.unwrap();
+        let second = manager.add_memory(entry("renew the passport before june", &["travel"])).await.unwrap();
+        manager.add_memory(entry("water the ferns on sunday", &[])).await.unwrap();
+        manager.sync_embeddings().await.unwrap();
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        assert_eq!(count_rows(&pool, "embeddings").await, 3);
+        let result = manager.deduplicate_chunks().await.unwrap();
+        assert_eq!((result.duplicate_groups, result.shared_chunks, result.embeddings_removed), (1, 1, 1));
+        assert_eq!(count_rows(&pool, "embeddings").await, 2);
+
+        // Both memories' chunks resolve to the one remaining vector
+        let vectors: Vec<String> = sqlx::query_scalar(
+            "SELECT e.id FROM chunks c JOIN embeddings e ON e.chunk_id = COALESCE(c.embedding_chunk_id, c.id)
+             WHERE c.memory_id IN (?, ?)"
+        )
+        .bind(&first)
+        .bind(&second)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(vectors.len(), 2);
+        assert_eq!(vectors[0], vectors[1]);
+
+        // Nothing left to embed, and a second pass finds nothing new
+        manager.sync_embeddings().await.unwrap();
+        assert_eq!(count_rows(&pool, "embeddings").await, 2);
+        assert_eq!(manager.deduplicate_chunks().await.unwrap().shared_chunks, 0);
+        assert_eq!(manager.query_memory(test_support::query("passport")).await.unwrap().citations.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn repeated_queries_hit_the_cache_until_a_write() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("the boiler service is due in october", &[])).await.unwrap();
+        let lookups = || {
+            let stats = crate::metrics::query_cache_stats();
+            (stats.hits, stats.misses)
+        };
+
+        let (hits, misses) = lookups();
+        let first = manager.query_memory(test_support::query("boiler")).await.unwrap();
+        assert_eq!(lookups(), (hits, misses + 1));
+        let second = manager.query_memory(test_support::query("boiler")).await.unwrap();
+        assert_eq!(lookups(), (hits + 1, misses + 1));
+        assert_eq!(second.answer, first.answer);
+        assert_eq!(second.citations.len(), 1);
+
+        // A different request is a separate entry
+        let mut other = test_support::query("boiler");
+        other.limit = Some(5);
+        manager.query_memory(other).await.unwrap();
+        assert_eq!(lookups(), (hits + 1, misses + 2));
+
+        manager.add_memory(entry("boiler pressure should read 1.5 bar", &[])).await.unwrap();
+        let after_write = manager.query_memory(test_support::query("boiler")).await.unwrap();
+        assert_eq!(lookups(), (hits + 1, misses + 3));
+        assert_eq!(after_write.citations.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn refreshing_from_the_source_replaces_content_and_keeps_the_old_version() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let body = r#"{"note": "the gate code is now 4512"}"#.to_string();
+        let url = crate::openai_embeddings::tests::mock_server("200 OK", body.clone()).await;
+        let mut memory = entry("the gate code is 1234", &[]);
+        memory.source = Some(url.clone());
+        let id = manager.add_memory(memory).await.unwrap();
+
+        let result = manager.refresh_from_source(id.clone()).await.unwrap();
+        assert!(result.changed);
+        assert_eq!(result.chunk_count, 1);
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        let memory = MemoryManager::get_memory_static(&pool, &id, None).await.unwrap().unwrap();
+        assert_eq!(memory.content, body);
+        assert_eq!(memory.source.as_deref(), Some(url.as_str()));
+        let chunks: Vec<String> = sqlx::query_scalar("SELECT content FROM chunks WHERE memory_id = ?")
+            .bind(&id)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(chunks, [body]);
+
+        let versions = manager.list_memory_versions(id.clone()).await.unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(Some(&versions[0].id), result.version_id.as_ref());
+        assert_eq!(versions[0].content, "the gate code is 1234");
+
+        // The server has gone, and the memory is left as it was
+        assert!(manager.refresh_from_source(id.clone()).await.is_err());
+        assert_eq!(manager.list_memory_versions(id).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn drift_compares_each_querys_top_memories_under_both_models() {
+        let _vault = TestVault::new().await;
+        let mut baseline = MemoryManager::new();
+        let mut memories = Vec::new();
+        for text in ["apple", "boat", "cloud"] {
+            memories.push((text, baseline.add_memory(entry(text, &[])).await.unwrap()));
+        }
+        baseline.sync_embeddings().await.unwrap();
+
+        // "pie" pulls a query towards boats under the new model
+        let words: &[(&str, &[f32])] = &[
+            ("apple", &[1.0, 0.0, 0.0]),
+            ("boat", &[0.0, 1.0, 0.0]),
+            ("cloud", &[0.0, 0.0, 1.0]),
+            ("pie", &[0.0, 3.0, 0.0]),
+        ];
+        let mut manager = MemoryManager::with_provider(Arc::new(test_support::WordVectors::new("words-v1", words)));
+        let queries = || vec!["apple pie".to_string(), "boat trip".to_string()];
+        assert!(manager.embedding_drift(queries(), "hashing-v1".into(), Some(1)).await.is_err());
+        manager.sync_embeddings().await.unwrap();
+
+        // The baseline's top memory for each query, worked out directly
+        let hashing = embeddings::HashingEmbedder::default();
+        let mut baseline_top = Vec::new();
+        for query in queries() {
+            let query_vector = hashing.embed_one(&query).await.unwrap();
+            let mut best = ("", f32::MIN);
+            for (text, _) in &memories {
+                let score = embeddings::cosine_similarity(&query_vector, &hashing.embed_one(text).await.unwrap());
+                if score > best.1 {
+                    best = (text, score);
+                }
+            }
+            baseline_top.push(best.0);
+        }
+        assert_eq!(baseline_top, ["apple", "boat"]);
+
+        let drift = manager.embedding_drift(queries(), "hashing-v1".into(), Some(1)).await.unwrap();
+        assert_eq!(drift.iter().map(|d| (d.query.as_str(), d.jaccard_overlap)).collect::<Vec<_>>(), [("apple pie", 0.0), ("boat trip", 1.0)]);
+        // With every memory in the top k the models cannot disagree
+        let drift = manager.embedding_drift(queries(), "hashing-v1".into(), Some(3)).await.unwrap();
+        assert!(drift.iter().all(|d| d.jaccard_overlap == 1.0));
+
+        assert!(manager.embedding_drift(queries(), "hashing-v1".into(), Some(0)).await.is_err());
+        assert!(manager.embedding_drift(queries(), "unknown-model".into(), None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn chunk_versions_are_recorded_and_mixed_versions_reported() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let old = manager.add_memory(entry("first paragraph\n\nsecond paragraph", &[])).await.unwrap();
+        manager.add_memory(entry("a memory chunked by this version", &[])).await.unwrap();
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        let versions: Vec<i64> = sqlx::query_scalar("SELECT DISTINCT chunker_version FROM chunks")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(versions, [chunking::CHUNKER_VERSION]);
+        let report = manager.chunk_schema_report().await.unwrap();
+        assert!(!report.mixed && !report.needs_rebuild);
+        assert_eq!(report.versions.len(), 1);
+
+        // As left by the chunker before an upgrade
+        sqlx::query("UPDATE chunks SET chunker_version = ? WHERE memory_id = ?")
+            .bind(chunking::CHUNKER_VERSION - 1)
+            .bind(&old)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let report = manager.chunk_schema_report().await.unwrap();
+        assert!(report.mixed && report.needs_rebuild);
+        assert_eq!(report.outdated_memories, 1);
+        assert_eq!(
+            report.versions.iter().map(|v| (v.version, v.memory_count)).collect::<Vec<_>>(),
+            [(chunking::CHUNKER_VERSION - 1, 1), (chunking::CHUNKER_VERSION, 1)]
+        );
+
+        assert_eq!(manager.rebuild_chunks().await.unwrap(), 1);
+        let report = manager.chunk_schema_report().await.unwrap();
+        assert!(!report.mixed && !report.needs_rebuild);
+    }
+
+    #[tokio::test]
+    async fn in_memory_matches_point_into_the_original_content() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let content = "Café résumé:\n\nthe   Meeting moved to\nthursday.\n\nmeeting notes, then MEETING again";
+        let id = manager.add_memory(entry(content, &[])).await.unwrap();
+        let at = |offset: usize, length: usize| content.chars().skip(offset).take(length).collect::<String>();
+
+        let matches = manager.search_in_memory(id.clone(), "meeting".into()).await.unwrap();
+        assert_eq!(matches.iter().map(|m| at(m.offset, m.length)).collect::<Vec<_>>(), ["Meeting", "meeting", "MEETING"]);
+        assert!(matches.windows(2).all(|pair| pair[0].offset < pair[1].offset));
+        assert!(matches.iter().all(|m| m.chunk_id.is_some()));
+        assert_eq!(matches[0].snippet, "Café résumé: the **Meeting** moved to thursday. meeting notes, then…");
+        assert!(matches[2].snippet.ends_with("then **MEETING** again"));
+
+        // Whitespace in the query matches the line break in the content
+        let spanning = manager.search_in_memory(id.clone(), "moved  to thursday".into()).await.unwrap();
+        assert_eq!(spanning.len(), 1);
+        assert_eq!(at(spanning[0].offset, spanning[0].length), "moved to\nthursday");
+        assert!(spanning[0].snippet.contains("**moved to thursday**"));
+
+        assert!(manager.search_in_memory(id.clone(), "absent".into()).await.unwrap().is_empty());
+        assert!(manager.search_in_memory(id, "  ".into()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn distinctive_keywords_outrank_words_every_memory_shares() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        for topic in ["budget review", "hiring plan", "travel booking"] {
+            manager.add_memory(entry(&format!("project status for the {}", topic), &[])).await.unwrap();
+        }
+        let id = manager
+            .add_memory(entry("project status for the telescope: the telescope mirror is polished", &[]))
+            .await
+            .unwrap();
+
+        let keywords = manager.extract_keywords(id.clone(), None).await.unwrap();
+        let terms: Vec<&str> = keywords.iter().map(|k| k.term.as_str()).collect();
+        assert_eq!(terms[0], "telescope");
+        let rank = |term: &str| terms.iter().position(|t| *t == term).unwrap();
+        for distinctive in ["mirror", "polished"] {
+            for common in ["project", "status"] {
+                assert!(rank(distinctive) < rank(common), "{:?}", keywords);
+            }
+        }
+        assert!(!terms.contains(&"the") && !terms.contains(&"for"));
+        assert!(keywords.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+        // Served from the cache until the memory is edited
+        assert_eq!(manager.extract_keywords(id, Some(1)).await.unwrap()[0].term, "telescope");
+    }
+
+    // Replays fixed resident set sizes, one per batch
+    struct FakeMemory(std::collections::VecDeque<u64>);
+
+    impl MemorySignal for FakeMemory {
+        fn rss_bytes(&mut self) -> Option<u64> {
+            self.0.pop_front()
+        }
+    }
+
+    #[tokio::test]
+    async fn high_memory_use_shrinks_the_embedding_batch() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        for i in 0..20 {
+            manager.add_memory(entry(&format!("note number {}", i), &[])).await.unwrap();
+        }
+        let pool = test_support::database().await.get_pool().await.clone();
+        let mut settings = MemorySettings::load(&pool, DEFAULT_VAULT_ID).await.unwrap();
+        settings.embedding_sync = crate::settings::EmbeddingSyncSettings {
+            min_batch_size: 2,
+            max_batch_size: 8,
+            memory_limit_mb: 1,
+            pause_ms: 0,
+        };
+        settings.save(&pool, DEFAULT_VAULT_ID).await.unwrap();
+
+        // Near the 1 MB limit for three batches, then well under it
+        let high = 900 * 1024;
+        let mut memory = FakeMemory([high, high, high, 100, 100].into());
+        let mut progress = Vec::new();
+        let embedded = manager
+            .sync_embeddings_with_progress(&mut memory, |p| progress.push((p.embedded, p.batch_size)))
+            .await
+            .unwrap();
+        assert_eq!(embedded, 20);
+        assert_eq!(progress, [(8, 4), (12, 2), (14, 2), (16, 4), (20, 8)]);
+        assert_eq!(count_rows(&pool, "embeddings").await, 20);
+    }
+
+    #[tokio::test]
+    async fn graph_export_lists_linked_memories_and_their_relations() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let titled = |title: &str| {
+            let mut memory = entry(&format!("notes on {}", title), &[]);
+            memory.title = Some(title.to_string());
+            memory
+        };
+        let plan = manager.add_memory(titled("Trip \"plan\" & budget")).await.unwrap();
+        let route = manager.add_memory(titled("Route")).await.unwrap();
+        manager.add_memory(titled("Unlinked")).await.unwrap();
+        manager.link_memories(plan.clone(), route.clone(), "References".into()).await.unwrap();
+        manager.link_memories(route.clone(), plan.clone(), "follows".into()).await.unwrap();
+        manager.link_memories(plan.clone(), route.clone(), "references".into()).await.unwrap();
+
+        let mut ids = [(plan.as_str(), "Trip \\\"plan\\\" & budget"), (route.as_str(), "Route")];
+        ids.sort();
+        let mut expected = String::from("digraph memories {\n");
+        for (id, label) in ids {
+            expected.push_str(&format!("  \"{}\" [label=\"{}\"];\n", id, label));
+        }
+        let mut edges = [(plan.as_str(), route.as_str(), "references"), (route.as_str(), plan.as_str(), "follows")];
+        edges.sort();
+        for (source, target, kind) in edges {
+            expected.push_str(&format!("  \"{}\" -> \"{}\" [label=\"{}\"];\n", source, target, kind));
+        }
+        expected.push_str("}\n");
+        assert_eq!(manager.export_graph(GraphFormat::Dot).await.unwrap(), expected);
+
+        let graphml = manager.export_graph(GraphFormat::Graphml).await.unwrap();
+        assert_eq!(graphml.matches("<node ").count(), 2);
+        assert_eq!(graphml.matches("<edge ").count(), 2);
+        assert!(graphml.contains("<data key=\"label\">Trip &quot;plan&quot; &amp; budget</data>"));
+        assert!(graphml.contains(&format!("<edge source=\"{}\" target=\"{}\"><data key=\"kind\">follows</data></edge>", route, plan)));
+        assert!(!graphml.contains("Unlinked"));
+    }
+
+    #[tokio::test]
+    async fn storage_savings_are_estimated_without_changing_anything() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("the spare key is under the blue pot", &[])).await.unwrap();
+        manager.add_memory(entry("the spare key is under the blue pot", &[])).await.unwrap();
+        manager.sync_embeddings().await.unwrap();
+
+        // A vector left behind by a chunk deleted without foreign keys
+        let pool = test_support::database().await.get_pool().await.clone();
+        let orphan = vector_codec::encode(&[0.5; 4]).unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query("PRAGMA foreign_keys = OFF").execute(&mut *conn).await.unwrap();
+        sqlx::query("INSERT INTO embeddings (id, chunk_id, vector, model_name, created_at) VALUES ('orphan', 'gone', ?, 'hashing-v1', ?)")
+            .bind(&orphan)
+            .bind(Utc::now())
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query("PRAGMA foreign_keys = ON").execute(&mut *conn).await.unwrap();
+        drop(conn);
+
+        // f16 saves half the values of each of the two vectors
+        let values_bytes = (embeddings::DEFAULT_DIMENSIONS * 4) as u64;
+        let vector_bytes = vector_codec::HEADER_LEN as u64 + values_bytes;
+        let estimate = manager.estimate_storage_savings().await.unwrap();
+        assert_eq!(estimate.f16_savings_bytes, values_bytes);
+        assert_eq!(estimate.chunk_dedup_savings_bytes, vector_bytes);
+        assert_eq!(estimate.orphan_cleanup_bytes, orphan.len() as u64);
+        assert_eq!(count_rows(&pool, "embeddings").await, 3);
+
+        manager.deduplicate_chunks().await.unwrap();
+        let estimate = manager.estimate_storage_savings().await.unwrap();
+        assert_eq!(estimate.chunk_dedup_savings_bytes, 0);
+        assert_eq!(estimate.f16_savings_bytes, values_bytes / 2);
+    }
+
+    #[tokio::test]
+    async fn streamed_citations_arrive_in_relevance_order_then_complete() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        for content in [
+            "the garden fence needs paint",
+            "garden fence paint: white, two coats on the garden fence",
+            "bought paint for the shed",
+            "nothing relevant here",
+        ] {
+            manager.add_memory(entry(content, &[])).await.unwrap();
+        }
+        manager.sync_embeddings().await.unwrap();
+        let expected = manager.query_memory(test_support::query("garden fence paint")).await.unwrap();
+
+        // Twice: computed, then served from the cache
+        for _ in 0..2 {
+            let mut events = Vec::new();
+            manager
+                .query_memory_streaming(test_support::query("garden fence paint"), "stream-1".into(), |event| events.push(event))
+                .await
+                .unwrap();
+
+            let (last, citations) = events.split_last().unwrap();
+            let mut scores = Vec::new();
+            for (i, event) in citations.iter().enumerate() {
+                let QueryStreamEvent::Citation { stream_id, rank, citation } = event else {
+                    panic!("citation expected, got {:?}", event);
+                };
+                assert_eq!((stream_id.as_str(), *rank), ("stream-1", i + 1));
+                assert_eq!(citation.id, expected.citations[i].id);
+                scores.push(citation.relevance_score);
+            }
+            assert_eq!(citations.len(), expected.citations.len());
+            assert!(citations.len() >= 3);
+            assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]), "{:?}", scores);
+
+            let QueryStreamEvent::Complete { stream_id, answer, citation_count, .. } = last else {
+                panic!("completion expected, got {:?}", last);
+            };
+            assert_eq!((stream_id.as_str(), *citation_count), ("stream-1", citations.len()));
+            assert_eq!(answer, &expected.answer);
+        }
+    }
+
+    #[tokio::test]
+    async fn titles_sort_case_insensitively_by_default() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        for title in ["cherry", "Banana", "apple"] {
+            let mut memory = entry(&format!("{} pudding recipe", title), &[]);
+            memory.title = Some(title.to_string());
+            manager.add_memory(memory).await.unwrap();
+        }
+        let by_title = || SearchCriteria { order: SearchOrder::TitleAsc, ..test_support::search("pudding") };
+        let pool = test_support::database().await.get_pool().await.clone();
+
+        let mut orders = Vec::new();
+        for collation in [None, Some(crate::settings::TitleCollation::Binary)] {
+            if let Some(collation) = collation {
+                let mut settings = MemorySettings::load(&pool, DEFAULT_VAULT_ID).await.unwrap();
+                settings.title_collation = collation;
+                settings.save(&pool, DEFAULT_VAULT_ID).await.unwrap();
+            }
+            let found = manager.search_memories(by_title(), None, false).await.unwrap();
+            orders.push(found.into_iter().filter_map(|m| m.title).collect::<Vec<_>>());
+        }
+        assert_eq!(orders, [["apple", "Banana", "cherry"], ["Banana", "apple", "cherry"]]);
+
+        // The same order for titles sorted after decryption
+        let mut titles = ["cherry", "Banana", "apple"];
+        titles.sort_by(|a, b| crate::settings::TitleCollation::default().compare(a, b));
+        assert_eq!(titles, ["apple", "Banana", "cherry"]);
+    }
+
+    #[tokio::test]
+    async fn enex_import_keeps_titles_tags_and_dates() {
+        let vault = TestVault::new().await;
+        let path = vault.dir().join("notes.enex");
+        std::fs::write(
+            &path,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-export SYSTEM "http://xml.evernote.com/pub/evernote-export3.dtd">
+<en-export export-date="20240102T030405Z" application="Evernote">
+  <note>
+    <title>Sourdough   starter</title>
+    <content><![CDATA[<?xml version="1.0" encoding="UTF-8"?><en-note><div>Feed it twice a day</div><div><en-todo checked="true"/>buy rye flour</div><en-media hash="abc" type="image/png"/></en-note>]]></content>
+    <created>20130730T205204Z</created>
+    <updated>20130801T101500Z</updated>
+    <tag>baking</tag>
+    <tag>kitchen</tag>
+    <note-attributes>
+      <source-url>https://example.com/sourdough</source-url>
+    </note-attributes>
+    <resource>
+      <data encoding="base64">aGVsbG8=</data>
+      <mime>image/png</mime>
+      <resource-attributes><file-name>jar.png</file-name></resource-attributes>
+    </resource>
+  </note>
+  <note>
+    <title>Clipped page</title>
+    <content><![CDATA[<en-note><p>Tips &amp; tricks</p></en-note>]]></content>
+    <created>20200105T120000Z</created>
+    <note-attributes><source-url>https://exa mple.com/tips</source-url></note-attributes>
+  </note>
+  <note>
+    <title>Blank</title>
+    <content><![CDATA[<en-note></en-note>]]></content>
+  </note>
+</en-export>
+"#,
+        )
+        .unwrap();
+
+        let mut manager = MemoryManager::new();
+        let result = manager.import_enex(path.display().to_string()).await.unwrap();
+        assert_eq!(result.memory_ids.len(), 2);
+        assert_eq!(result.attachment_count, 1);
+        assert_eq!(result.skipped.len(), 1);
+        assert_eq!(result.skipped[0].title.as_deref(), Some("Blank"));
+        assert_eq!(result.skipped[0].reason, "note has no text");
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        let dates = |id: String| {
+            let pool = pool.clone();
+            async move {
+                sqlx::query_as::<_, (chrono::DateTime<Utc>, chrono::DateTime<Utc>)>(
+                    "SELECT created_at, updated_at FROM memories WHERE id = ?",
+                )
+                .bind(id)
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+            }
+        };
+        let timestamp = |s: &str| chrono::DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+
+        let sourdough = MemoryManager::get_memory_static(&pool, &result.memory_ids[0], None).await.unwrap().unwrap();
+        assert_eq!(sourdough.title.as_deref(), Some("Sourdough starter"));
+        assert_eq!(sourdough.source.as_deref(), Some("https://example.com/sourdough"));
+        let mut tags = sourdough.tags.clone();
+        tags.sort();
+        assert_eq!(tags, ["baking", "kitchen"]);
+        assert!(sourdough.content.contains("Feed it twice a day"));
+        assert!(sourdough.content.contains("[x] buy rye flour"));
+        assert!(sourdough.content.contains("- jar.png (image/png, 5 bytes)"));
+        assert_eq!(
+            dates(result.memory_ids[0].clone()).await,
+            (timestamp("2013-07-30T20:52:04Z"), timestamp("2013-08-01T10:15:00Z"))
+        );
+
+        // No `<updated>` falls back to the creation date; a bad URL is dropped
+        let clipped = MemoryManager::get_memory_static(&pool, &result.memory_ids[1], None).await.unwrap().unwrap();
+        assert_eq!(clipped.title.as_deref(), Some("Clipped page"));
+        assert_eq!(clipped.source, None);
+        assert!(clipped.tags.is_empty());
+        assert_eq!(clipped.content, "Tips & tricks");
+        assert_eq!(
+            dates(result.memory_ids[1].clone()).await,
+            (timestamp("2020-01-05T12:00:00Z"), timestamp("2020-01-05T12:00:00Z"))
+        );
+    }
+
+    #[tokio::test]
+    async fn optimize_reports_every_step_and_leaves_the_vault_consistent() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let kept = manager.add_memory(entry("the kettle descaler is under the sink", &["kitchen"])).await.unwrap();
+        let removed = manager.add_memory(entry("the bike chain needs oil every month", &["bike"])).await.unwrap();
+        manager.sync_embeddings().await.unwrap();
+
+        // As left by a delete that ran without foreign keys
+        let pool = test_support::database().await.get_pool().await.clone();
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query("PRAGMA foreign_keys = OFF").execute(&mut *conn).await.unwrap();
+        sqlx::query("DELETE FROM memories WHERE id = ?").bind(&removed).execute(&mut *conn).await.unwrap();
+        sqlx::query("PRAGMA foreign_keys = ON").execute(&mut *conn).await.unwrap();
+        drop(conn);
+
+        let report = manager.optimize_vault(OptimizeSteps::default()).await.unwrap();
+        let orphans = report.orphans.unwrap();
+        assert!(orphans.chunks >= 1);
+        assert_eq!(orphans.embeddings, orphans.chunks);
+        assert_eq!(orphans.memory_tags, 1);
+        assert_eq!(report.fts_rows_indexed, Some(1));
+        assert!(report.vacuum.is_some());
+        let statistics = report.statistics.unwrap();
+        assert_eq!(statistics.vaults.iter().map(|v| v.memory_count).sum::<u64>(), 1);
+        assert_eq!(statistics.total_chunks as i64, count_rows(&pool, "chunks").await);
+        assert_eq!(statistics.embedded_chunks, statistics.total_chunks);
+
+        let consistency = manager.check_fts_consistency(false).await.unwrap();
+        assert_eq!((consistency.missing_rows, consistency.stale_rows), (0, 0));
+        assert!(!consistency.content_mismatch);
+        let hits = manager.search_in_memory(kept.clone(), "descaler".into()).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        let stored = MemoryManager::get_memory_static(&pool, &kept, None).await.unwrap().unwrap();
+        assert_eq!(stored.tags, ["kitchen"]);
+
+        // Steps that are turned off are left out of the report
+        let steps = OptimizeSteps { cleanup_orphans: false, rebuild_fts: false, vacuum: false, recount: true };
+        let report = manager.optimize_vault(steps).await.unwrap();
+        assert!(report.orphans.is_none() && report.fts_rows_indexed.is_none() && report.vacuum.is_none());
+        assert!(report.statistics.is_some());
+    }
+
+    #[tokio::test]
+    async fn related_memories_rank_by_similarity_and_unsynced_ones_match_by_keyword() {
+        let _vault = TestVault::new().await;
+        let words: &[(&str, &[f32])] = &[
+            ("puppy", &[1.0, 0.0, 0.0]),
+            ("dog", &[0.9, 0.1, 0.0]),
+            ("tax", &[0.0, 0.0, 1.0]),
+            ("forms", &[0.0, 0.0, 1.0]),
+        ];
+        let mut manager = MemoryManager::with_provider(Arc::new(test_support::WordVectors::new("words-v1", words)));
+        let tax = manager.add_memory(entry("quarterly tax forms are due", &[])).await.unwrap();
+        let puppy = manager.add_memory(entry("the puppy chased a ball", &[])).await.unwrap();
+        manager.sync_embeddings().await.unwrap();
+
+        // No shared words with the puppy memory, and nothing like the tax one
+        let result = manager.query_memory(test_support::query("dog")).await.unwrap();
+        assert_eq!(result.citations.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), [puppy.as_str()]);
+        let expected = embeddings::cosine_similarity(&[0.9, 0.1, 0.0], &[1.0, 0.0, 0.0]);
+        assert!((result.citations[0].relevance_score - expected).abs() < 1e-5);
+        assert!((result.confidence - expected).abs() < 1e-5);
+        assert!(result.citations.iter().all(|c| c.id != tax));
+
+        // Added after the sync, so found through its text until embedded
+        let walk = manager.add_memory(entry("dog walking rota for the week", &[])).await.unwrap();
+        let result = manager.query_memory(test_support::query("dog")).await.unwrap();
+        assert_eq!(
+            result.citations.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(),
+            [puppy.as_str(), walk.as_str()]
+        );
+        assert_eq!(result.citations[1].relevance_score, UNEMBEDDED_MATCH_SCORE);
+        assert!((result.confidence - expected).abs() < 1e-5);
+
+        manager.sync_embeddings().await.unwrap();
+        let result = manager.query_memory(test_support::query("dog")).await.unwrap();
+        assert_eq!(result.citations.len(), 2);
+        assert!(result.citations[1].relevance_score > 0.99);
+    }
+
+    #[tokio::test]
+    async fn in_memory_matches_use_the_stored_chunk_offsets() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let content = "The ferry leaves at nine. Tickets are sold on board, cash only. \
+            Bring a jacket because the deck gets cold. The café closes early on Sundays. \
+            Return trips run hourly until dusk. Parking near the pier fills up by eight. \
+            Bicycles ride free but must be locked on the lower deck.";
+        let id = manager.add_memory(entry(content, &[])).await.unwrap();
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        let rows: Vec<(String, String, i64, i64)> =
+            sqlx::query_as("SELECT id, content, start_pos, end_pos FROM chunks WHERE memory_id = ? ORDER BY start_pos")
+                .bind(&id)
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert!(rows.len() > 1);
+        for (_, text, start, end) in &rows {
+            assert_eq!(&content[*start as usize..*end as usize], text);
+        }
+        assert!(rows.windows(2).all(|pair| pair[0].2 < pair[1].2 && pair[0].3 < pair[1].3));
+
+        // Chunking differently now would not move the stored chunks
+        let settings = MemorySettings {
+            chunking: crate::settings::ChunkingSettings { target_chars: 1000, overlap_chars: 0 },
+            ..manager.get_settings().await.unwrap()
+        };
+        manager.update_settings(settings).await.unwrap();
+        let bicycles = content.find("Bicycles").unwrap() as i64;
+        let expected = rows.iter().find(|(_, _, start, end)| (*start..*end).contains(&bicycles)).unwrap();
+        let matches = manager.search_in_memory(id.clone(), "bicycles".into()).await.unwrap();
+        assert_eq!(matches[0].chunk_id.as_deref(), Some(expected.0.as_str()));
+
+        // Older chunks are matched against a fresh chunking, which now differs
+        sqlx::query("UPDATE chunks SET chunker_version = 1 WHERE memory_id = ?")
+            .bind(&id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let matches = manager.search_in_memory(id, "bicycles".into()).await.unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].chunk_id, None);
+    }
+
+    #[tokio::test]
+    async fn blank_queries_do_not_hide_orphan_memories() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let pool = test_support::database().await.get_pool().await.clone();
+        let first = manager.add_memory(entry("the first loose note", &["loose"])).await.unwrap();
+        let second = manager.add_memory(entry("the second loose note", &[])).await.unwrap();
+        let ids = |memories: Vec<MemoryEntry>| memories.into_iter().map(|m| m.id.unwrap()).collect::<Vec<_>>();
+
+        // Neither a blank query nor a tag-only search is recorded
+        manager.query_memory(test_support::query("  ")).await.unwrap();
+        let tag_only = SearchCriteria { tags: Some(vec!["loose".into()]), ..test_support::search("") };
+        manager.search_memories(tag_only, None, false).await.unwrap();
+        assert_eq!(count_rows(&pool, "query_history").await, 0);
+
+        // As recorded by earlier versions
+        sqlx::query("INSERT INTO query_history (id, vault_id, query, result_count, created_at) VALUES ('blank', ?, ' ', 0, ?)")
+            .bind(DEFAULT_VAULT_ID)
+            .bind(Utc::now())
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(ids(manager.orphan_memories(None, false).await.unwrap()), [first.clone(), second.clone()]);
+        assert_eq!(ids(manager.orphan_memories(Some(1), false).await.unwrap()), std::slice::from_ref(&first));
+
+        manager.enable_encryption("hunter2".into()).await.unwrap();
+        record_query(&pool, "FIRST").await;
+        assert_eq!(ids(manager.orphan_memories(None, false).await.unwrap()), [second]);
+    }
+
+    #[tokio::test]
+    async fn keyword_matches_read_sealed_text_and_take_wildcards_literally() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        let serviced = manager.add_memory(entry("the boiler is 50% serviced", &[])).await.unwrap();
+        let room = manager.add_memory(entry("the boiler_room key hangs by the door", &[])).await.unwrap();
+        let moved = manager.add_memory(entry("the boiler manual went to the loft", &[])).await.unwrap();
+        manager.add_memory(entry("a pasta recipe", &[])).await.unwrap();
+        manager.enable_encryption("hunter2".into()).await.unwrap();
+
+        // Memories of another vault are never matched
+        let db = test_support::database().await;
+        let pool = db.get_pool().await;
+        sqlx::query("INSERT INTO vaults (id, name, encryption_enabled) VALUES ('elsewhere', 'elsewhere', 0)")
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE memories SET vault_id = 'elsewhere' WHERE id = ?")
+            .bind(&moved)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let ids = |result: &QueryResult| result.citations.iter().map(|c| c.id.clone()).collect::<HashSet<_>>();
+        let result = manager.query_memory(test_support::query("BOILER")).await.unwrap();
+        assert_eq!(ids(&result), HashSet::from([serviced.clone(), room.clone()]));
+        let result = manager.query_memory(test_support::query("50%")).await.unwrap();
+        assert_eq!(ids(&result), HashSet::from([serviced]));
+        let result = manager.query_memory(test_support::query("_")).await.unwrap();
+        assert_eq!(ids(&result), HashSet::from([room]));
+        let result = manager.query_memory(test_support::query("  ")).await.unwrap();
+        assert!(result.citations.is_empty());
+        assert_eq!(result.confidence, 0.0);
+    }
+
+    #[tokio::test]
+    async fn one_cipher_per_request_opens_every_sealed_row() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        for i in 0..20 {
+            manager.add_memory(entry(&format!("sealed memory number {}", i), &[])).await.unwrap();
+        }
+        manager.enable_encryption("hunter2".into()).await.unwrap();
+        let vault_key = manager.vault_key.unwrap();
+
+        // Built once and passed to every row, the way a request does
+        let cipher = manager.text_cipher();
+        let db = test_support::database().await;
+        let rows = sqlx::query("SELECT content FROM chunks WHERE encrypted = 1")
+            .fetch_all(db.get_pool().await)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 20);
+        let crypto = CryptoManager::new();
+        for row in rows {
+            let content: String = row.get("content");
+            let opened = MemoryManager::open_content_static(cipher.as_ref(), content.clone(), true).unwrap();
+            assert_eq!(opened, crypto.decrypt_text(&content, &vault_key).unwrap());
+            assert!(opened.starts_with("sealed memory number"));
+        }
+    }
 }
@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+// Upper bounds (in microseconds) of the latency histogram buckets; the last
+// bucket catches everything slower
+const BUCKET_BOUNDS_US: [u64; 8] = [1_000, 5_000, 10_000, 50_000, 100_000, 500_000, 1_000_000, 5_000_000];
+
+// Latencies kept per operation for percentiles; older samples are dropped
+const RECENT_SAMPLES: usize = 1024;
+
+// Operations whose latency `query_latency_stats` reports
+const QUERY_OPERATIONS: [&str; 2] = ["query_memory", "search_memories"];
+
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Default)]
+struct OperationStats {
+    count: u64,
+    total_us: u64,
+    min_us: u64,
+    max_us: u64,
+    buckets: [u64; BUCKET_BOUNDS_US.len() + 1],
+    recent_us: VecDeque<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LatencyStats {
+    // Samples the percentiles were computed from, at most the most recent
+    // RECENT_SAMPLES per operation
+    pub count: u64,
+    pub p50_us: u64,
+    pub p90_us: u64,
+    pub p99_us: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OperationMetrics {
+    pub operation: String,
+    pub count: u64,
+    pub total_us: u64,
+    pub mean_us: u64,
+    pub min_us: u64,
+    pub max_us: u64,
+    // Counts per bucket, aligned with `bucket_bounds_us` plus an overflow bucket
+    pub buckets: Vec<u64>,
+    pub bucket_bounds_us: Vec<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheMetrics {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+fn registry() -> &'static Mutex<HashMap<&'static str, OperationStats>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<&'static str, OperationStats>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn record(operation: &'static str, duration: Duration) {
+    // Never report zero so a recorded call is always visible
+    let micros = (duration.as_micros() as u64).max(1);
+    let bucket = BUCKET_BOUNDS_US
+        .iter()
+        .position(|bound| micros <= *bound)
+        .unwrap_or(BUCKET_BOUNDS_US.len());
+
+    let mut registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    let stats = registry.entry(operation).or_default();
+    stats.min_us = if stats.count == 0 { micros } else { stats.min_us.min(micros) };
+    stats.max_us = stats.max_us.max(micros);
+    stats.count += 1;
+    stats.total_us += micros;
+    stats.buckets[bucket] += 1;
+    if stats.recent_us.len() == RECENT_SAMPLES {
+        stats.recent_us.pop_front();
+    }
+    stats.recent_us.push_back(micros);
+}
+
+pub async fn timed<F: Future>(operation: &'static str, future: F) -> F::Output {
+    let started = Instant::now();
+    let output = future.await;
+    record(operation, started.elapsed());
+    output
+}
+
+pub fn snapshot() -> Vec<OperationMetrics> {
+    let registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    let mut metrics: Vec<OperationMetrics> = registry
+        .iter()
+        .map(|(operation, stats)| OperationMetrics {
+            operation: operation.to_string(),
+            count: stats.count,
+            total_us: stats.total_us,
+            mean_us: stats.total_us / stats.count.max(1),
+            min_us: stats.min_us,
+            max_us: stats.max_us,
+            buckets: stats.buckets.to_vec(),
+            bucket_bounds_us: BUCKET_BOUNDS_US.to_vec(),
+        })
+        .collect();
+
+    metrics.sort_by(|a, b| a.operation.cmp(&b.operation));
+    metrics
+}
+
+pub fn record_cache_lookup(hit: bool) {
+    let counter = if hit { &CACHE_HITS } else { &CACHE_MISSES };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+// Lookups in the query_memory result cache since startup
+pub fn query_cache_stats() -> CacheMetrics {
+    CacheMetrics {
+        hits: CACHE_HITS.load(Ordering::Relaxed),
+        misses: CACHE_MISSES.load(Ordering::Relaxed),
+    }
+}
+
+// Percentiles over recent searches and queries, or all zeros before any
+pub fn query_latency_stats() -> LatencyStats {
+    let mut samples: Vec<u64> = {
+        let registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+        QUERY_OPERATIONS
+            .iter()
+            .filter_map(|operation| registry.get(operation))
+            .flat_map(|stats| stats.recent_us.iter().copied())
+            .collect()
+    };
+    latency_stats(&mut samples)
+}
+
+fn latency_stats(samples: &mut [u64]) -> LatencyStats {
+    samples.sort_unstable();
+    LatencyStats {
+        count: samples.len() as u64,
+        p50_us: percentile(samples, 50),
+        p90_us: percentile(samples, 90),
+        p99_us: percentile(samples, 99),
+    }
+}
+
+// Nearest-rank percentile of sorted samples
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats_for(operation: &str) -> Option<OperationMetrics> {
+        snapshot().into_iter().find(|m| m.operation == operation)
+    }
+
+    #[tokio::test]
+    async fn timed_operations_are_counted_with_a_duration() {
+        assert!(stats_for("metrics_test_op").is_none());
+
+        timed("metrics_test_op", async {}).await;
+        let first = stats_for("metrics_test_op").unwrap();
+        assert_eq!(first.count, 1);
+        assert!(first.total_us > 0 && first.min_us > 0);
+
+        timed("metrics_test_op", tokio::time::sleep(Duration::from_millis(2))).await;
+        let second = stats_for("metrics_test_op").unwrap();
+        assert_eq!(second.count, 2);
+        assert!(second.max_us >= 2_000);
+        assert_eq!(second.buckets.iter().sum::<u64>(), 2);
+    }
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let mut samples: Vec<u64> = (1..=100).rev().collect();
+        let stats = latency_stats(&mut samples);
+        assert_eq!((stats.count, stats.p50_us, stats.p90_us, stats.p99_us), (100, 50, 90, 99));
+        assert_eq!(latency_stats(&mut []).p99_us, 0);
+    }
+
+    #[test]
+    fn percentiles_cover_only_the_recent_window() {
+        // Samples of 1..=RECENT_SAMPLES + 100 ms; the first 100 fall out
+        for ms in 1..=(RECENT_SAMPLES as u64 + 100) {
+            record("metrics_window_test", Duration::from_millis(ms));
+        }
+        let mut samples: Vec<u64> = registry()
+            .lock()
+            .unwrap()
+            .get("metrics_window_test")
+            .unwrap()
+            .recent_us
+            .iter()
+            .copied()
+            .collect();
+        assert_eq!(samples.len(), RECENT_SAMPLES);
+        assert_eq!(samples[0], 101_000);
+
+        let stats = latency_stats(&mut samples);
+        assert_eq!(stats.count, RECENT_SAMPLES as u64);
+        assert_eq!(stats.p50_us, 612_000);
+        assert_eq!(stats.p90_us, 1_022_000);
+        assert_eq!(stats.p99_us, 1_114_000);
+        assert_eq!(stats_for("metrics_window_test").unwrap().count, RECENT_SAMPLES as u64 + 100);
+    }
+}
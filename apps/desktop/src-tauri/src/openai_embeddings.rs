@@ -0,0 +1,221 @@
+// Embeddings from OpenAI's `/v1/embeddings` API. Chunk text leaves the
+// machine, so this backend is only used when chosen in the settings.
+use crate::embeddings::EmbeddingProvider;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::time::Duration;
+
+const EMBEDDINGS_URL: &str = "https://api.openai.com/v1/embeddings";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Inputs per request; the API accepts up to 2048
+const BATCH_SIZE: usize = 256;
+
+// Output size of each supported model
+const MODELS: &[(&str, usize)] = &[
+    ("text-embedding-3-small", 1536),
+    ("text-embedding-3-large", 3072),
+    ("text-embedding-ada-002", 1536),
+];
+
+pub struct OpenAiEmbedder {
+    client: reqwest::Client,
+    url: String,
+    api_key: String,
+    model: String,
+    // Prefixed, so OpenAI vectors never share a name with a local model's
+    model_name: String,
+    dimensions: usize,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+    message: String,
+}
+
+impl OpenAiEmbedder {
+    pub fn new(api_key: &str, model: &str) -> Result<Self> {
+        let dimensions = MODELS
+            .iter()
+            .find(|(name, _)| *name == model)
+            .map(|(_, dimensions)| *dimensions)
+            .ok_or_else(|| anyhow::anyhow!("Unsupported OpenAI embedding model: {}", model))?;
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("human-api/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        Ok(Self {
+            client,
+            url: EMBEDDINGS_URL.to_string(),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+            model_name: format!("openai/{}", model),
+            dimensions,
+        })
+    }
+
+    // Points the client at a stand-in server
+    #[cfg(test)]
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = url.to_string();
+        self
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAiEmbedder {
+    fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn batch_size(&self) -> usize {
+        BATCH_SIZE
+    }
+
+    // Rough estimate for sync planning: about four characters per token in
+    // English text
+    fn count_tokens(&self, text: &str) -> usize {
+        text.chars().count().div_ceil(4)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let body = serde_json::json!({ "model": self.model, "input": texts });
+        let response = self
+            .client
+            .post(&self.url)
+            .bearer_auth(&self.api_key)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            let message = serde_json::from_str::<ErrorResponse>(&text)
+                .map(|e| e.error.message)
+                .unwrap_or(text);
+            return Err(anyhow::anyhow!("OpenAI returned {}: {}", status, message));
+        }
+
+        // Results carry their input's index and are not guaranteed to be in
+        // order
+        let mut data = serde_json::from_str::<EmbeddingResponse>(&text)?.data;
+        data.sort_by_key(|d| d.index);
+        if data.len() != texts.len() || data.iter().enumerate().any(|(i, d)| d.index != i) {
+            return Err(anyhow::anyhow!(
+                "OpenAI returned {} embeddings for {} inputs",
+                data.len(),
+                texts.len()
+            ));
+        }
+        Ok(data.into_iter().map(|d| d.embedding).collect())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    // Serves one request with the given status and JSON body, returning the
+    // URL to send it to
+    pub async fn mock_server(status: &'static str, body: String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1/embeddings", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            // Read the headers and the body they announce before answering
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            loop {
+                let read = socket.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+                if read == 0 {
+                    break;
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        url
+    }
+
+    pub fn embeddings_body(dimensions: usize, count: usize) -> String {
+        // Listed out of order, as the API allows
+        let data: Vec<_> = (0..count)
+            .rev()
+            .map(|index| serde_json::json!({ "index": index, "embedding": vec![index as f32 + 1.0; dimensions] }))
+            .collect();
+        serde_json::json!({ "data": data }).to_string()
+    }
+
+    #[tokio::test]
+    async fn embeddings_are_returned_in_input_order() {
+        let url = mock_server("200 OK", embeddings_body(1536, 2)).await;
+        let embedder = OpenAiEmbedder::new("key", "text-embedding-3-small").unwrap().with_url(&url);
+        assert_eq!(embedder.model_name(), "openai/text-embedding-3-small");
+
+        let vectors = embedder.embed(&["a".to_string(), "b".to_string()]).await.unwrap();
+        assert_eq!(vectors.len(), 2);
+        assert_eq!(vectors[0][0], 1.0);
+        assert_eq!(vectors[1][0], 2.0);
+        assert_eq!(vectors[0].len(), 1536);
+    }
+
+    #[tokio::test]
+    async fn api_errors_carry_the_server_message() {
+        let body = serde_json::json!({ "error": { "message": "Incorrect API key provided" } }).to_string();
+        let url = mock_server("401 Unauthorized", body).await;
+        let embedder = OpenAiEmbedder::new("bad", "text-embedding-3-small").unwrap().with_url(&url);
+
+        let error = embedder.embed(&["a".to_string()]).await.unwrap_err().to_string();
+        assert!(error.contains("401"), "{}", error);
+        assert!(error.contains("Incorrect API key provided"), "{}", error);
+
+        assert!(OpenAiEmbedder::new("key", "text-embedding-4").is_err());
+    }
+}
@@ -0,0 +1,102 @@
+// Recent query_memory results, reused while nothing has been written to the
+// vault since they were computed. Every write moves
+// `maintenance::write_generation` on, which invalidates all entries at once.
+use crate::commands::{QueryRequest, QueryResult};
+use crate::metrics;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+struct Entry {
+    result: QueryResult,
+    // Rows the query matched, for the query history
+    result_count: usize,
+    generation: u64,
+    stored_at: Instant,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<String, Entry>,
+    // Bumped on every lookup and insert; the entry with the lowest
+    // `last_used` is evicted first
+    clock: u64,
+}
+
+fn cache() -> &'static Mutex<Cache> {
+    static CACHE: OnceLock<Mutex<Cache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(Cache::default()))
+}
+
+// The whole request takes part, so filters, limit and answer options all
+// separate entries. Whether the vault is unlocked does too: results
+// computed with the key must never be served once it is gone.
+pub fn key(vault_id: &str, unlocked: bool, request: &QueryRequest) -> Result<String> {
+    Ok(format!("{}\n{}\n{}", vault_id, unlocked, serde_json::to_string(request)?))
+}
+
+// A copy of the cached result and its row count, if still fresh
+pub fn get(key: &str, generation: u64, ttl: Duration) -> Option<(QueryResult, usize)> {
+    let mut cache = cache().lock().unwrap_or_else(|e| e.into_inner());
+    cache.clock += 1;
+    let clock = cache.clock;
+
+    let fresh = cache
+        .entries
+        .get(key)
+        .map(|entry| entry.generation == generation && entry.stored_at.elapsed() <= ttl);
+    let hit = match fresh {
+        Some(true) => {
+            let entry = cache.entries.get_mut(key)?;
+            entry.last_used = clock;
+            Some((entry.result.clone(), entry.result_count))
+        }
+        Some(false) => {
+            cache.entries.remove(key);
+            None
+        }
+        None => None,
+    };
+    metrics::record_cache_lookup(hit.is_some());
+    hit
+}
+
+// Drops every entry, e.g. when the vault locks: results hold decrypted text
+pub fn clear() {
+    cache().lock().unwrap_or_else(|e| e.into_inner()).entries.clear();
+}
+
+// `generation` is the one read before the query ran, so a result computed
+// while a write was in progress is already stale when stored
+pub fn insert(key: String, generation: u64, result: &QueryResult, result_count: usize, capacity: usize) {
+    let mut cache = cache().lock().unwrap_or_else(|e| e.into_inner());
+    cache.clock += 1;
+    let clock = cache.clock;
+
+    cache.entries.retain(|_, entry| entry.generation == generation);
+    while cache.entries.len() >= capacity && !cache.entries.contains_key(&key) {
+        let Some(oldest) = cache
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(key, _)| key.clone())
+        else {
+            break;
+        };
+        cache.entries.remove(&oldest);
+    }
+    if capacity > 0 {
+        cache.entries.insert(
+            key,
+            Entry {
+                result: result.clone(),
+                result_count,
+                generation,
+                stored_at: Instant::now(),
+                last_used: clock,
+            },
+        );
+    }
+}
@@ -0,0 +1,269 @@
+// Turns storage and index diagnostics into suggested actions. Each
+// recommendation names the command that addresses it, so the UI can offer a
+// one-click fix.
+use crate::chunking;
+use crate::commands::{Recommendation, Severity};
+use crate::database::{to_count, to_sql_int};
+use crate::settings::MemorySettings;
+use anyhow::Result;
+use sqlx::sqlite::SqlitePool;
+
+const WAL_WARN_BYTES: u64 = 64 * 1024 * 1024;
+// Free pages as a share of the file before compaction is worth suggesting
+const FRAGMENTATION_RATIO: f64 = 0.2;
+const MIN_FREE_PAGES: i64 = 256;
+// Below this share of embedded chunks semantic search misses most memories
+const LOW_COVERAGE: f64 = 0.5;
+
+pub async fn collect(
+    pool: &SqlitePool,
+    vault_id: &str,
+    model: &str,
+    dimensions: usize,
+    wal_bytes: u64,
+) -> Result<Vec<Recommendation>> {
+    let mut recommendations = Vec::new();
+    let settings = MemorySettings::load(pool, vault_id).await?;
+
+    // Embedding coverage for the active model
+    let (total_chunks, pending_chunks): (i64, i64) = sqlx::query_as(
+        "SELECT COUNT(*), COALESCE(SUM(NOT EXISTS (
+             SELECT 1 FROM embeddings e WHERE e.chunk_id = COALESCE(c.embedding_chunk_id, c.id) AND e.model_name = ?
+         )), 0)
+         FROM chunks c
+         JOIN memories m ON c.memory_id = m.id
+         WHERE m.vault_id = ?"
+    )
+    .bind(model)
+    .bind(vault_id)
+    .fetch_one(pool)
+    .await?;
+    if pending_chunks > 0 {
+        let coverage = 1.0 - pending_chunks as f64 / total_chunks as f64;
+        recommendations.push(recommendation(
+            "pending_embeddings",
+            if coverage < LOW_COVERAGE { Severity::Warning } else { Severity::Info },
+            format!("Embed {} chunks that have no {} vector yet", pending_chunks, model),
+            "sync_embeddings",
+        ));
+    }
+
+    let stale_embeddings: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM embeddings e
+         JOIN chunks c ON e.chunk_id = c.id
+         JOIN memories m ON c.memory_id = m.id
+         WHERE m.vault_id = ? AND e.model_name != ?"
+    )
+    .bind(vault_id)
+    .bind(model)
+    .fetch_one(pool)
+    .await?;
+    if stale_embeddings > 0 {
+        recommendations.push(recommendation(
+            "stale_embeddings",
+            Severity::Info,
+            format!("Prune {} embeddings from models other than {}", stale_embeddings, model),
+            "prune_embeddings",
+        ));
+    }
+
+    // Blob length is fixed by the dimension (8-byte header plus 4 bytes per
+    // value); verify_embedding_dimensions does the full decode
+    let wrong_dimension: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM embeddings e
+         JOIN chunks c ON e.chunk_id = c.id
+         JOIN memories m ON c.memory_id = m.id
+         WHERE m.vault_id = ? AND e.model_name = ? AND LENGTH(e.vector) != ?"
+    )
+    .bind(vault_id)
+    .bind(model)
+    .bind(to_sql_int(8 + dimensions * 4, "vector size")?)
+    .fetch_one(pool)
+    .await?;
+    if wrong_dimension > 0 {
+        recommendations.push(recommendation(
+            "embedding_dimension_mismatch",
+            Severity::Warning,
+            format!("Re-embed {} chunks whose vectors do not have {} dimensions", wrong_dimension, dimensions),
+            "verify_embedding_dimensions",
+        ));
+    }
+
+    // Junction rows and index entries left behind by deleted rows
+    let dangling_tags: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM memory_tags mt
+         WHERE NOT EXISTS (SELECT 1 FROM memories m WHERE m.id = mt.memory_id)
+            OR NOT EXISTS (SELECT 1 FROM tags t WHERE t.id = mt.tag_id)"
+    )
+    .fetch_one(pool)
+    .await?;
+    if dangling_tags > 0 {
+        recommendations.push(recommendation(
+            "dangling_tag_references",
+            Severity::Warning,
+            format!("Remove {} tag links to deleted memories or tags", dangling_tags),
+            "repair_tag_references",
+        ));
+    }
+
+    let outdated_chunk_memories: i64 = sqlx::query_scalar(
+        "SELECT COUNT(DISTINCT c.memory_id) FROM chunks c
+         JOIN memories m ON c.memory_id = m.id
+         WHERE m.vault_id = ? AND c.chunker_version != ?"
+    )
+    .bind(vault_id)
+    .bind(chunking::CHUNKER_VERSION)
+    .fetch_one(pool)
+    .await?;
+    if outdated_chunk_memories > 0 {
+        recommendations.push(recommendation(
+            "outdated_chunks",
+            Severity::Info,
+            format!(
+                "Re-chunk {} memories split by an older chunker so retrieval is consistent",
+                outdated_chunk_memories
+            ),
+            "rebuild_chunks",
+        ));
+    }
+
+    let fts_drift: i64 = sqlx::query_scalar(
+        "SELECT
+             (SELECT COUNT(*) FROM memories_fts_source s
+              WHERE NOT EXISTS (SELECT 1 FROM memories_fts_docsize d WHERE d.id = s.memory_rowid))
+           + (SELECT COUNT(*) FROM memories_fts_docsize d
+              WHERE NOT EXISTS (SELECT 1 FROM memories_fts_source s WHERE s.memory_rowid = d.id))"
+    )
+    .fetch_one(pool)
+    .await?;
+    if fts_drift > 0 {
+        recommendations.push(recommendation(
+            "fts_drift",
+            Severity::Warning,
+            format!("Repair the full-text index; {} entries are missing or stale", fts_drift),
+            "check_fts_consistency",
+        ));
+    }
+
+    // Storage
+    if wal_bytes > WAL_WARN_BYTES {
+        recommendations.push(recommendation(
+            "large_wal",
+            Severity::Warning,
+            format!("Checkpoint the {} MiB write-ahead log", wal_bytes / (1024 * 1024)),
+            "checkpoint_wal",
+        ));
+    }
+
+    let page_count: i64 = sqlx::query_scalar("PRAGMA page_count").fetch_one(pool).await?;
+    let free_pages: i64 = sqlx::query_scalar("PRAGMA freelist_count").fetch_one(pool).await?;
+    let fragmented = free_pages >= MIN_FREE_PAGES
+        && page_count > 0
+        && free_pages as f64 / page_count as f64 >= FRAGMENTATION_RATIO;
+    if fragmented && !settings.compaction.enabled {
+        recommendations.push(recommendation(
+            "fragmentation",
+            Severity::Info,
+            format!(
+                "Enable background compaction to reclaim {} free pages ({}% of the file)",
+                free_pages,
+                free_pages * 100 / page_count
+            ),
+            "update_memory_settings",
+        ));
+    }
+
+    // Security
+    let (memory_count, encrypted): (i64, bool) = sqlx::query_as(
+        "SELECT (SELECT COUNT(*) FROM memories WHERE vault_id = ?),
+                COALESCE((SELECT encrypted_key IS NOT NULL FROM vaults WHERE id = ?), 0)"
+    )
+    .bind(vault_id)
+    .bind(vault_id)
+    .fetch_one(pool)
+    .await?;
+    if memory_count > 0 && !encrypted {
+        recommendations.push(recommendation(
+            "unencrypted_vault",
+            Severity::Info,
+            format!("Enable encryption; {} memories are stored in plaintext", to_count(memory_count, "memory count")?),
+            "enable_encryption",
+        ));
+    }
+
+    // Most severe first; the order above is kept within a severity
+    recommendations.sort_by_key(|r| std::cmp::Reverse(r.severity));
+    Ok(recommendations)
+}
+
+fn recommendation(code: &str, severity: Severity, message: String, command: &str) -> Recommendation {
+    Recommendation {
+        code: code.to_string(),
+        severity,
+        message,
+        command: command.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::DEFAULT_DIMENSIONS;
+    use crate::memory::{MemoryManager, DEFAULT_VAULT_ID};
+    use crate::test_support::{self, entry, TestVault};
+
+    async fn codes(pool: &SqlitePool, dimensions: usize, wal_bytes: u64) -> Vec<(String, Severity)> {
+        collect(pool, DEFAULT_VAULT_ID, "hashing-v1", dimensions, wal_bytes)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.code, r.severity))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn conditions_produce_their_recommendations() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        for i in 0..3 {
+            manager.add_memory(entry(&format!("note {} about the allotment", i), &["garden"])).await.unwrap();
+        }
+        let pool = test_support::database().await.get_pool().await.clone();
+
+        assert_eq!(
+            codes(&pool, DEFAULT_DIMENSIONS, 0).await,
+            [
+                ("pending_embeddings".to_string(), Severity::Warning),
+                ("unencrypted_vault".to_string(), Severity::Info),
+            ]
+        );
+
+        manager.sync_embeddings().await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query("PRAGMA foreign_keys = OFF").execute(&mut *conn).await.unwrap();
+        sqlx::query("INSERT INTO memory_tags (memory_id, tag_id) VALUES ('gone-memory', 'gone-tag')")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query("PRAGMA foreign_keys = ON").execute(&mut *conn).await.unwrap();
+        drop(conn);
+
+        // Warnings ahead of advice
+        assert_eq!(
+            codes(&pool, DEFAULT_DIMENSIONS, WAL_WARN_BYTES + 1).await,
+            [
+                ("dangling_tag_references".to_string(), Severity::Warning),
+                ("large_wal".to_string(), Severity::Warning),
+                ("unencrypted_vault".to_string(), Severity::Info),
+            ]
+        );
+
+        manager.repair_tag_references().await.unwrap();
+        manager.enable_encryption("hunter2".into()).await.unwrap();
+        assert!(codes(&pool, DEFAULT_DIMENSIONS, 0).await.is_empty());
+        assert_eq!(
+            codes(&pool, 16, 0).await,
+            [("embedding_dimension_mismatch".to_string(), Severity::Warning)]
+        );
+    }
+}
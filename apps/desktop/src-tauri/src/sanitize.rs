@@ -0,0 +1,83 @@
+use crate::settings::ContentSanitization;
+use regex::Regex;
+use std::sync::OnceLock;
+
+// Invisible characters that survive copy/paste and split words for search
+const ZERO_WIDTH: &[char] = &['\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}', '\u{00AD}'];
+
+// Cleans pasted text before it is validated and stored: line endings become
+// `\n`, zero-width and control characters (other than newline and tab) are
+// dropped, and HTML is optionally reduced to its text
+pub fn sanitize(text: &str, config: &ContentSanitization) -> String {
+    if !config.enabled {
+        return text.to_string();
+    }
+
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let text = if config.html_to_text { html_to_text(&text) } else { text };
+
+    text.chars()
+        .filter(|c| *c == '\n' || *c == '\t' || !(c.is_control() || ZERO_WIDTH.contains(c)))
+        .collect()
+}
+
+pub fn html_to_text(html: &str) -> String {
+    static DROPPED: OnceLock<Regex> = OnceLock::new();
+    static BREAKS: OnceLock<Regex> = OnceLock::new();
+    static TAGS: OnceLock<Regex> = OnceLock::new();
+    static BLANK_RUNS: OnceLock<Regex> = OnceLock::new();
+
+    let dropped = DROPPED.get_or_init(|| Regex::new(r"(?is)<(script|style)\b.*?</(script|style)\s*>|<!--.*?-->").unwrap());
+    let breaks = BREAKS.get_or_init(|| {
+        Regex::new(r"(?i)<br\s*/?>|</(p|div|li|tr|h[1-6]|blockquote|pre)\s*>").unwrap()
+    });
+    let tags = TAGS.get_or_init(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+    let blank_runs = BLANK_RUNS.get_or_init(|| Regex::new(r"\n{3,}").unwrap());
+
+    let text = dropped.replace_all(html, "");
+    let text = breaks.replace_all(&text, "\n");
+    let text = tags.replace_all(&text, "");
+    let text = decode_entities(&text);
+    blank_runs.replace_all(text.trim(), "\n\n").into_owned()
+}
+
+// Only the entities common in pasted snippets; `&amp;` goes last so it
+// cannot create new entities
+pub fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_width_characters_and_carriage_returns_are_cleaned() {
+        let config = ContentSanitization::default();
+        assert_eq!(sanitize("zero\u{200B}width\u{FEFF} text", &config), "zerowidth text");
+        assert_eq!(sanitize("one\r\ntwo\rthree\n\tfour", &config), "one\ntwo\nthree\n\tfour");
+        assert_eq!(sanitize("bell\u{7}", &config), "bell");
+
+        let disabled = ContentSanitization {
+            enabled: false,
+            ..ContentSanitization::default()
+        };
+        assert_eq!(sanitize("a\u{200B}\r\nb", &disabled), "a\u{200B}\r\nb");
+    }
+
+    #[test]
+    fn html_is_reduced_to_its_text_when_asked() {
+        let config = ContentSanitization {
+            html_to_text: true,
+            ..ContentSanitization::default()
+        };
+        let html = "<p>Fish &amp; chips</p><script>alert(1)</script><br>done";
+        assert_eq!(sanitize(html, &config), "Fish & chips\n\ndone");
+    }
+}
@@ -0,0 +1,176 @@
+// Named search criteria kept per vault so a topical search can be re-run or
+// exported without retyping it
+use crate::commands::{SavedSearch, SearchCriteria};
+use crate::validation;
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::{sqlite::SqlitePool, Row};
+use uuid::Uuid;
+
+pub async fn save(pool: &SqlitePool, vault_id: &str, name: &str, criteria: SearchCriteria) -> Result<SavedSearch> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(anyhow::anyhow!("Saved search name cannot be empty"));
+    }
+    let criteria = validate_criteria(criteria)?;
+
+    let saved = SavedSearch {
+        id: Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        criteria,
+        created_at: Utc::now().to_rfc3339(),
+    };
+
+    let inserted = sqlx::query(
+        "INSERT INTO saved_searches (id, vault_id, name, criteria_json, created_at) VALUES (?, ?, ?, ?, ?)
+         ON CONFLICT(vault_id, name) DO NOTHING"
+    )
+    .bind(&saved.id)
+    .bind(vault_id)
+    .bind(&saved.name)
+    .bind(serde_json::to_string(&saved.criteria)?)
+    .bind(&saved.created_at)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    if inserted == 0 {
+        return Err(anyhow::anyhow!("A saved search named '{}' already exists", saved.name));
+    }
+
+    Ok(saved)
+}
+
+pub async fn list(pool: &SqlitePool, vault_id: &str) -> Result<Vec<SavedSearch>> {
+    let rows = sqlx::query(
+        "SELECT id, name, criteria_json, created_at FROM saved_searches WHERE vault_id = ? ORDER BY name, id"
+    )
+    .bind(vault_id)
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter().map(from_row).collect()
+}
+
+pub async fn find(pool: &SqlitePool, vault_id: &str, id: &str) -> Result<SavedSearch> {
+    let row = sqlx::query(
+        "SELECT id, name, criteria_json, created_at FROM saved_searches WHERE vault_id = ? AND id = ?"
+    )
+    .bind(vault_id)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| anyhow::anyhow!("Saved search not found: {}", id))?;
+
+    from_row(&row)
+}
+
+pub async fn find_by_name(pool: &SqlitePool, vault_id: &str, name: &str) -> Result<SavedSearch> {
+    let row = sqlx::query(
+        "SELECT id, name, criteria_json, created_at FROM saved_searches WHERE vault_id = ? AND name = ?"
+    )
+    .bind(vault_id)
+    .bind(name.trim())
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| anyhow::anyhow!("Saved search not found: {}", name))?;
+
+    from_row(&row)
+}
+
+pub async fn delete(pool: &SqlitePool, vault_id: &str, id: &str) -> Result<()> {
+    let deleted = sqlx::query("DELETE FROM saved_searches WHERE vault_id = ? AND id = ?")
+        .bind(vault_id)
+        .bind(id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(anyhow::anyhow!("Saved search not found: {}", id));
+    }
+    Ok(())
+}
+
+// Rejects criteria that would fail or match everything when run, and stores
+// tags normalized so the saved search reads the way it will be matched
+fn validate_criteria(criteria: SearchCriteria) -> Result<SearchCriteria> {
+    let tags = match criteria.tags {
+        Some(tags) => {
+            let mut normalized: Vec<String> = Vec::new();
+            for tag in &tags {
+                let tag = validation::normalize_tag(tag);
+                if tag.is_empty() {
+                    return Err(anyhow::anyhow!("Saved search tags cannot be empty"));
+                }
+                if !normalized.contains(&tag) {
+                    normalized.push(tag);
+                }
+            }
+            if normalized.is_empty() {
+                return Err(anyhow::anyhow!("Saved search tag list cannot be empty"));
+            }
+            Some(normalized)
+        }
+        None => None,
+    };
+
+    let language = criteria.language.map(|l| l.trim().to_lowercase());
+    if language.as_deref() == Some("") {
+        return Err(anyhow::anyhow!("Saved search language cannot be empty"));
+    }
+
+    if criteria.query.trim().is_empty() && tags.is_none() && language.is_none() {
+        return Err(anyhow::anyhow!("Saved search needs a query, tags or a language"));
+    }
+
+    Ok(SearchCriteria { query: criteria.query, tags, language, order: criteria.order })
+}
+
+fn from_row(row: &sqlx::sqlite::SqliteRow) -> Result<SavedSearch> {
+    Ok(SavedSearch {
+        id: row.get("id"),
+        name: row.get("name"),
+        criteria: serde_json::from_str(&row.get::<String, _>("criteria_json"))?,
+        created_at: row.get("created_at"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{MemoryEntry, SearchCriteria};
+    use crate::memory::MemoryManager;
+    use crate::test_support::{self, entry, TestVault};
+
+    #[tokio::test]
+    async fn saved_searches_are_created_listed_run_and_deleted() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("train to Leeds at 9:15", &["travel"])).await.unwrap();
+        manager.add_memory(entry("train pass renews in May", &["travel", "admin"])).await.unwrap();
+        manager.add_memory(entry("train the new starter on Monday", &["work"])).await.unwrap();
+        let criteria = || SearchCriteria {
+            tags: Some(vec![" travel ".into()]),
+            ..test_support::search("train")
+        };
+
+        let saved = manager.save_search(" Trips ".into(), criteria()).await.unwrap();
+        assert_eq!(saved.name, "Trips");
+        assert_eq!(saved.criteria.tags.as_deref(), Some(&["travel".to_string()][..]));
+        assert!(manager.save_search("Trips".into(), criteria()).await.is_err());
+        assert!(manager.save_search("Everything".into(), test_support::search(" ")).await.is_err());
+        manager.save_search("Admin".into(), test_support::search("renews")).await.unwrap();
+
+        let names: Vec<String> = manager.list_saved_searches().await.unwrap().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, ["Admin", "Trips"]);
+
+        let ids = |memories: Vec<MemoryEntry>| memories.into_iter().map(|m| m.id).collect::<Vec<_>>();
+        let run = ids(manager.run_saved_search(saved.id.clone(), None).await.unwrap());
+        let direct = ids(manager.search_memories(criteria(), None, false).await.unwrap());
+        assert_eq!(run.len(), 2);
+        assert_eq!(run, direct);
+
+        manager.delete_saved_search(saved.id.clone()).await.unwrap();
+        assert!(manager.run_saved_search(saved.id.clone(), None).await.is_err());
+        assert!(manager.delete_saved_search(saved.id).await.is_err());
+        assert_eq!(manager.list_saved_searches().await.unwrap().len(), 1);
+    }
+}
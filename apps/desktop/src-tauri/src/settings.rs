@@ -0,0 +1,316 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqlitePool, Row};
+
+// How much of a matched chunk is copied into a citation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum CitationContentMode {
+    // Window of `window` characters centered on the first match
+    Snippet { window: usize },
+    FullChunk,
+}
+
+impl Default for CitationContentMode {
+    fn default() -> Self {
+        CitationContentMode::Snippet { window: 200 }
+    }
+}
+
+// Stemming and stopword removal for keyword search
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeywordAnalysis {
+    pub enabled: bool,
+    pub language: String,
+    pub stemming: bool,
+    pub remove_stopwords: bool,
+}
+
+impl Default for KeywordAnalysis {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            language: "english".to_string(),
+            stemming: true,
+            remove_stopwords: true,
+        }
+    }
+}
+
+// Detects each new memory's language so searches can filter on it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanguageDetection {
+    pub enabled: bool,
+    // Also tag the memory `lang/<code>`, e.g. `lang/fra`
+    pub auto_tag: bool,
+}
+
+// Cleanup applied to titles and content on add and update
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentSanitization {
+    // Normalize line endings and strip zero-width and control characters
+    pub enabled: bool,
+    // Also reduce pasted HTML to its text
+    pub html_to_text: bool,
+}
+
+impl Default for ContentSanitization {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            html_to_text: false,
+        }
+    }
+}
+
+// What the background sweep does with memories past their `expires_at`.
+// Either way they are left out of search from the moment they expire.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryPolicy {
+    #[default]
+    Delete,
+    // Kept in the vault, only hidden from search
+    Keep,
+}
+
+// How titles compare when results are ordered by title
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TitleCollation {
+    // Byte order: uppercase before lowercase
+    Binary,
+    // SQLite's NOCASE; folds ASCII letters only
+    Nocase,
+    // Unicode case folding, so "Éclair" sorts with "éclair"
+    #[default]
+    Unicode,
+}
+
+impl TitleCollation {
+    // Name to use after COLLATE; `Unicode` is registered by `Database::new`
+    pub fn sql_name(&self) -> &'static str {
+        match self {
+            TitleCollation::Binary => "BINARY",
+            TitleCollation::Nocase => "NOCASE",
+            TitleCollation::Unicode => crate::database::UNICODE_NOCASE,
+        }
+    }
+    // The same order in Rust, for titles that are only readable decrypted
+    pub fn compare(&self, a: &str, b: &str) -> std::cmp::Ordering {
+        match self {
+            TitleCollation::Binary => a.cmp(b),
+            TitleCollation::Nocase => a.bytes().map(|c| c.to_ascii_lowercase()).cmp(b.bytes().map(|c| c.to_ascii_lowercase())),
+            TitleCollation::Unicode => a.to_lowercase().cmp(&b.to_lowercase()).then_with(|| a.cmp(b)),
+        }
+    }
+}
+
+// Reuse of recent query_memory results; any write to the vault clears them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueryCacheSettings {
+    pub enabled: bool,
+    // Results kept, least recently used dropped first
+    pub capacity: usize,
+    pub ttl_seconds: u64,
+}
+
+impl Default for QueryCacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            capacity: 64,
+            ttl_seconds: 30,
+        }
+    }
+}
+
+// Batching for sync_embeddings. The batch halves while the process is near
+// `memory_limit_mb` and grows back once usage drops.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmbeddingSyncSettings {
+    pub min_batch_size: usize,
+    pub max_batch_size: usize,
+    // Resident memory the process should stay under while syncing
+    pub memory_limit_mb: u64,
+    // Wait after a batch that left memory usage high
+    pub pause_ms: u64,
+}
+
+impl Default for EmbeddingSyncSettings {
+    fn default() -> Self {
+        Self {
+            min_batch_size: 8,
+            max_batch_size: 256,
+            memory_limit_mb: 1024,
+            pause_ms: 500,
+        }
+    }
+}
+
+// How prose is split into chunks for embedding. Changing it only affects
+// chunks written afterwards.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChunkingSettings {
+    // Chunks grow a sentence at a time while they stay within this many
+    // characters; a longer sentence is cut at word boundaries
+    pub target_chars: usize,
+    // Characters of the previous chunk repeated at the start of the next
+    pub overlap_chars: usize,
+}
+
+impl Default for ChunkingSettings {
+    fn default() -> Self {
+        Self {
+            target_chars: 200,
+            overlap_chars: 40,
+        }
+    }
+}
+
+// Where chunk and query vectors come from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingBackend {
+    // all-MiniLM-L6-v2 when built in and installed, otherwise hashing
+    #[default]
+    Local,
+    #[serde(rename = "openai")]
+    OpenAi,
+}
+
+// Vectors are stored per model, so switching backends keeps the old vectors
+// and sync_embeddings fills in the new model's
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmbeddingProviderSettings {
+    pub backend: EmbeddingBackend,
+    pub openai_model: String,
+    // Sealed with the vault key when the vault is encrypted, and returned
+    // sealed by get_settings
+    pub openai_api_key: Option<String>,
+}
+
+impl Default for EmbeddingProviderSettings {
+    fn default() -> Self {
+        Self {
+            backend: EmbeddingBackend::default(),
+            openai_model: "text-embedding-3-small".to_string(),
+            openai_api_key: None,
+        }
+    }
+}
+
+// Background incremental vacuum and FTS optimize
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompactionSettings {
+    pub enabled: bool,
+    pub interval_minutes: u64,
+    // Quiet period after the last write before compaction may start
+    pub idle_seconds: u64,
+}
+
+impl Default for CompactionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_minutes: 60,
+            idle_seconds: 120,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemorySettings {
+    pub citation_content: CitationContentMode,
+    // Query text is stored in plaintext, so history is off unless enabled
+    pub record_query_history: bool,
+    pub keyword_analysis: KeywordAnalysis,
+    pub compaction: CompactionSettings,
+    // Soft limit on chunks stored for one memory; extra chunks are dropped
+    // and the memory is marked `chunks_truncated`
+    pub max_chunks_per_memory: usize,
+    pub chunking: ChunkingSettings,
+    pub language_detection: LanguageDetection,
+    pub sanitization: ContentSanitization,
+    // Added to every new memory, e.g. `inbox` for triage; empty disables it
+    pub default_tags: Vec<String>,
+    // Enforced when tags are written; memories already over the limit stay
+    // readable and only fail when their tags are next changed
+    pub max_tags_per_memory: usize,
+    // Analyzed terms are plaintext, so encrypted vaults keep no term index
+    // and searches decrypt and match each memory instead, which slows down
+    // as the vault grows. Enabling this indexes encrypted vaults too: faster
+    // keyword search, but the terms are readable on disk.
+    pub index_encrypted_vaults: bool,
+    pub expired_memories: ExpiryPolicy,
+    pub query_cache: QueryCacheSettings,
+    pub embedding_sync: EmbeddingSyncSettings,
+    pub embedding_provider: EmbeddingProviderSettings,
+    pub title_collation: TitleCollation,
+    // Idle minutes before an unlocked vault locks itself; 0 never locks
+    pub auto_lock_minutes: u32,
+}
+
+impl Default for MemorySettings {
+    fn default() -> Self {
+        Self {
+            citation_content: CitationContentMode::default(),
+            record_query_history: false,
+            keyword_analysis: KeywordAnalysis::default(),
+            compaction: CompactionSettings::default(),
+            max_chunks_per_memory: 1000,
+            chunking: ChunkingSettings::default(),
+            language_detection: LanguageDetection::default(),
+            sanitization: ContentSanitization::default(),
+            default_tags: Vec::new(),
+            max_tags_per_memory: 50,
+            index_encrypted_vaults: false,
+            expired_memories: ExpiryPolicy::default(),
+            query_cache: QueryCacheSettings::default(),
+            embedding_sync: EmbeddingSyncSettings::default(),
+            embedding_provider: EmbeddingProviderSettings::default(),
+            title_collation: TitleCollation::default(),
+            auto_lock_minutes: 0,
+        }
+    }
+}
+
+impl MemorySettings {
+    pub async fn load(pool: &SqlitePool, vault_id: &str) -> Result<Self> {
+        let row = sqlx::query("SELECT data FROM settings WHERE vault_id = ?")
+            .bind(vault_id)
+            .fetch_optional(pool)
+            .await?;
+
+        match row {
+            Some(row) => {
+                let data: String = row.get("data");
+                Ok(serde_json::from_str(&data)?)
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub async fn save(&self, pool: &SqlitePool, vault_id: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO settings (vault_id, data, updated_at) VALUES (?, ?, ?)
+             ON CONFLICT(vault_id) DO UPDATE SET data = excluded.data, updated_at = excluded.updated_at"
+        )
+        .bind(vault_id)
+        .bind(serde_json::to_string(self)?)
+        .bind(chrono::Utc::now())
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
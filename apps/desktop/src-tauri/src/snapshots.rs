@@ -0,0 +1,220 @@
+// Point-in-time records of a vault's contents for auditing how it changed.
+// Hashes cover the stored values, so encrypted vaults can be snapshotted
+// while locked.
+use crate::commands::{Snapshot, SnapshotDiff};
+use crate::database::{to_count, to_sql_int};
+use anyhow::Result;
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use sqlx::{sqlite::SqlitePool, Row};
+use uuid::Uuid;
+
+pub async fn create(pool: &SqlitePool, vault_id: &str, label: &str) -> Result<Snapshot> {
+    let label = label.trim();
+    if label.is_empty() {
+        return Err(anyhow::anyhow!("Snapshot label cannot be empty"));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let rows = sqlx::query(
+        "SELECT m.id, m.title, m.content, m.source,
+                (SELECT GROUP_CONCAT(name, char(31)) FROM (
+                    SELECT t.name FROM memory_tags mt JOIN tags t ON t.id = mt.tag_id
+                    WHERE mt.memory_id = m.id ORDER BY t.name
+                )) AS tags
+         FROM memories m
+         WHERE m.vault_id = ?
+         ORDER BY m.id"
+    )
+    .bind(vault_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let chunk_count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM chunks c JOIN memories m ON c.memory_id = m.id WHERE m.vault_id = ?"
+    )
+    .bind(vault_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let tag_count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(DISTINCT mt.tag_id) FROM memory_tags mt
+         JOIN memories m ON mt.memory_id = m.id
+         WHERE m.vault_id = ?"
+    )
+    .bind(vault_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let snapshot_id = Uuid::new_v4().to_string();
+    let mut vault_hasher = Sha256::new();
+    let mut memory_hashes = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let memory_id: String = row.get("id");
+        let mut hasher = Sha256::new();
+        for field in [
+            row.get::<Option<String>, _>("title"),
+            Some(row.get::<String, _>("content")),
+            row.get::<Option<String>, _>("source"),
+            row.get::<Option<String>, _>("tags"),
+        ] {
+            // Length-prefix each field so adjacent values cannot run together
+            let field = field.unwrap_or_default();
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+        let hash = to_hex(&hasher.finalize());
+
+        vault_hasher.update(memory_id.as_bytes());
+        vault_hasher.update(hash.as_bytes());
+        memory_hashes.push((memory_id, hash));
+    }
+    let content_hash = to_hex(&vault_hasher.finalize());
+    let memory_count = to_sql_int(memory_hashes.len(), "memory count")?;
+    let created_at = Utc::now();
+
+    sqlx::query(
+        "INSERT INTO snapshots (id, vault_id, label, memory_count, chunk_count, tag_count, content_hash, created_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(&snapshot_id)
+    .bind(vault_id)
+    .bind(label)
+    .bind(memory_count)
+    .bind(chunk_count)
+    .bind(tag_count)
+    .bind(&content_hash)
+    .bind(created_at)
+    .execute(&mut *tx)
+    .await?;
+
+    for (memory_id, hash) in &memory_hashes {
+        sqlx::query("INSERT INTO snapshot_memories (snapshot_id, memory_id, content_hash) VALUES (?, ?, ?)")
+            .bind(&snapshot_id)
+            .bind(memory_id)
+            .bind(hash)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(Snapshot {
+        id: snapshot_id,
+        label: label.to_string(),
+        memory_count: to_count(memory_count, "memory count")?,
+        chunk_count: to_count(chunk_count, "chunk count")?,
+        tag_count: to_count(tag_count, "tag count")?,
+        content_hash,
+        created_at: created_at.to_rfc3339(),
+    })
+}
+
+pub async fn list(pool: &SqlitePool, vault_id: &str) -> Result<Vec<Snapshot>> {
+    let rows = sqlx::query(
+        "SELECT id, label, memory_count, chunk_count, tag_count, content_hash, created_at
+         FROM snapshots
+         WHERE vault_id = ?
+         ORDER BY created_at DESC, id"
+    )
+    .bind(vault_id)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(Snapshot {
+                id: row.get("id"),
+                label: row.get("label"),
+                memory_count: to_count(row.get("memory_count"), "memory count")?,
+                chunk_count: to_count(row.get("chunk_count"), "chunk count")?,
+                tag_count: to_count(row.get("tag_count"), "tag count")?,
+                content_hash: row.get("content_hash"),
+                created_at: row.get::<chrono::DateTime<Utc>, _>("created_at").to_rfc3339(),
+            })
+        })
+        .collect()
+}
+
+// Changes going from snapshot `from` to snapshot `to`
+pub async fn diff(pool: &SqlitePool, vault_id: &str, from: &str, to: &str) -> Result<SnapshotDiff> {
+    for id in [from, to] {
+        let exists: Option<String> = sqlx::query_scalar("SELECT id FROM snapshots WHERE id = ? AND vault_id = ?")
+            .bind(id)
+            .bind(vault_id)
+            .fetch_optional(pool)
+            .await?;
+        if exists.is_none() {
+            return Err(anyhow::anyhow!("Snapshot not found: {}", id));
+        }
+    }
+
+    let row = sqlx::query(
+        "SELECT
+             (SELECT COUNT(*) FROM snapshot_memories b
+              WHERE b.snapshot_id = ?2
+                AND NOT EXISTS (SELECT 1 FROM snapshot_memories a
+                                WHERE a.snapshot_id = ?1 AND a.memory_id = b.memory_id)) AS added,
+             (SELECT COUNT(*) FROM snapshot_memories a
+              WHERE a.snapshot_id = ?1
+                AND NOT EXISTS (SELECT 1 FROM snapshot_memories b
+                                WHERE b.snapshot_id = ?2 AND b.memory_id = a.memory_id)) AS removed,
+             (SELECT COUNT(*) FROM snapshot_memories a
+              JOIN snapshot_memories b ON b.memory_id = a.memory_id AND b.snapshot_id = ?2
+              WHERE a.snapshot_id = ?1 AND a.content_hash != b.content_hash) AS changed,
+             (SELECT COUNT(*) FROM snapshot_memories a
+              JOIN snapshot_memories b ON b.memory_id = a.memory_id AND b.snapshot_id = ?2
+              WHERE a.snapshot_id = ?1 AND a.content_hash = b.content_hash) AS unchanged"
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(SnapshotDiff {
+        from: from.to_string(),
+        to: to.to_string(),
+        added: to_count(row.get("added"), "added count")?,
+        removed: to_count(row.get("removed"), "removed count")?,
+        changed: to_count(row.get("changed"), "changed count")?,
+        unchanged: to_count(row.get("unchanged"), "unchanged count")?,
+    })
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{MemoryManager, DEFAULT_VAULT_ID};
+    use crate::test_support::{self, entry, TestVault};
+
+    #[tokio::test]
+    async fn diff_reflects_adds_edits_and_deletes() {
+        let _vault = TestVault::new().await;
+        let mut manager = MemoryManager::new();
+        manager.add_memory(entry("stays the same", &[])).await.unwrap();
+        let edited = manager.add_memory(entry("before the edit", &[])).await.unwrap();
+        let deleted = manager.add_memory(entry("about to go", &[])).await.unwrap();
+        let before = manager.create_snapshot("before".into()).await.unwrap();
+        assert_eq!((before.memory_count, before.chunk_count), (3, 3));
+
+        manager.update_memory(edited, entry("after the edit", &[])).await.unwrap();
+        manager.delete_memory(deleted).await.unwrap();
+        manager.add_memory(entry("brand new", &[])).await.unwrap();
+        manager.add_memory(entry("also new", &[])).await.unwrap();
+        let after = manager.create_snapshot("after".into()).await.unwrap();
+        assert_ne!(before.content_hash, after.content_hash);
+
+        let changes = manager.diff_snapshots(before.id.clone(), after.id.clone()).await.unwrap();
+        assert_eq!((changes.added, changes.removed, changes.changed, changes.unchanged), (2, 1, 1, 1));
+        assert_eq!(manager.list_snapshots().await.unwrap().len(), 2);
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        let same = diff(&pool, DEFAULT_VAULT_ID, &after.id, &after.id).await.unwrap();
+        assert_eq!((same.added, same.removed, same.changed, same.unchanged), (0, 0, 0, 4));
+        assert!(diff(&pool, DEFAULT_VAULT_ID, &before.id, "missing").await.is_err());
+    }
+}
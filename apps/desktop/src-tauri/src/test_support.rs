@@ -0,0 +1,155 @@
+// Fixtures for tests that touch the database. The database lives at
+// `data/memories.db` under the working directory, and read-only mode, the
+// file key and the query cache are process-wide, so a test holds its
+// `TestVault` for its whole run: such tests take turns, each in a fresh
+// directory.
+use crate::commands::{MemoryEntry, QueryRequest, ScoreAggregate, SearchCriteria, SearchOrder};
+use crate::database::{self, Database};
+use crate::embeddings::EmbeddingProvider;
+use crate::memory::DEFAULT_VAULT_ID;
+use crate::query_cache;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::sync::{Mutex, MutexGuard};
+
+static LOCK: Mutex<()> = Mutex::const_new(());
+
+pub struct TestVault {
+    dir: PathBuf,
+    previous_dir: PathBuf,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl TestVault {
+    // A fresh database with a plaintext vault row for memories to belong to
+    pub async fn new() -> Self {
+        let vault = Self::empty().await;
+        sqlx::query("INSERT INTO vaults (id, name, encryption_enabled) VALUES (?, 'test', 0)")
+            .bind(DEFAULT_VAULT_ID)
+            .execute(database().await.get_pool().await)
+            .await
+            .unwrap();
+        vault
+    }
+
+    // A fresh database with no vault, for tests that create one
+    pub async fn empty() -> Self {
+        let guard = LOCK.lock().await;
+        let dir = std::env::temp_dir().join(format!("human-api-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let previous_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+
+        database::set_read_only(false);
+        database::set_file_key(None);
+        query_cache::clear();
+
+        Self {
+            dir,
+            previous_dir,
+            _guard: guard,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for TestVault {
+    fn drop(&mut self) {
+        database::set_read_only(false);
+        database::set_file_key(None);
+        let _ = std::env::set_current_dir(&self.previous_dir);
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+// A separate connection to the test database, for inspecting raw rows
+pub async fn database() -> Database {
+    Database::new().await.unwrap()
+}
+
+pub fn entry(content: &str, tags: &[&str]) -> MemoryEntry {
+    MemoryEntry {
+        id: None,
+        content: content.to_string(),
+        title: None,
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        source: None,
+        is_pinned: false,
+        created_at: None,
+        updated_at: None,
+    }
+}
+
+pub fn query(text: &str) -> QueryRequest {
+    QueryRequest {
+        query: text.to_string(),
+        limit: Some(20),
+        include_citations: true,
+        group_by_memory: false,
+        score_aggregate: ScoreAggregate::Max,
+        answer: true,
+        max_answer_chars: None,
+        answer_chunk_count: None,
+    }
+}
+
+pub fn search(text: &str) -> SearchCriteria {
+    SearchCriteria {
+        query: text.to_string(),
+        tags: None,
+        language: None,
+        order: SearchOrder::Updated,
+    }
+}
+
+// An embedding provider whose vectors are chosen by the test: each known
+// word adds its vector and unknown words add nothing, so rankings can be
+// worked out by hand
+pub struct WordVectors {
+    model_name: String,
+    dimensions: usize,
+    words: HashMap<String, Vec<f32>>,
+}
+
+impl WordVectors {
+    pub fn new(model_name: &str, words: &[(&str, &[f32])]) -> Self {
+        Self {
+            model_name: model_name.to_string(),
+            dimensions: words.first().map_or(0, |(_, vector)| vector.len()),
+            words: words.iter().map(|(word, vector)| (word.to_string(), vector.to_vec())).collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for WordVectors {
+    fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn count_tokens(&self, text: &str) -> usize {
+        text.split_whitespace().count()
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts
+            .iter()
+            .map(|text| {
+                let mut vector = vec![0.0; self.dimensions];
+                for word in text.split_whitespace().filter_map(|word| self.words.get(&word.to_lowercase())) {
+                    vector.iter_mut().zip(word).for_each(|(total, value)| *total += value);
+                }
+                vector
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,245 @@
+// Splits a plain text file into memory-sized segments and reads the
+// frontmatter of markdown notes
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum SplitStrategy {
+    // Paragraphs separated by one or more blank lines
+    BlankLines,
+    // Markdown ATX headings (`#` to `######`); each heading starts a segment
+    // and becomes its title
+    Headings,
+    // Each match of `pattern` separates two segments
+    Regex { pattern: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub title: Option<String>,
+    pub content: String,
+}
+
+// Decodes UTF-8 and UTF-16 (with or without a byte order mark). Anything
+// else is read as UTF-8 with invalid sequences replaced.
+pub fn decode_text(bytes: &[u8]) -> String {
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return String::from_utf8_lossy(rest).into_owned();
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        return decode_utf16(rest, u16::from_le_bytes);
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        return decode_utf16(rest, u16::from_be_bytes);
+    }
+    // Zero bytes are valid UTF-8, so text holding them may still be UTF-16
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        if !text.contains('\0') {
+            return text.to_string();
+        }
+    }
+
+    // Without a BOM, mostly-ASCII UTF-16 shows up as zero bytes in every
+    // other position
+    let sample = &bytes[..bytes.len().min(1024)];
+    let zeros_at = |parity: usize| sample.iter().skip(parity).step_by(2).filter(|b| **b == 0).count();
+    let half = sample.len() / 2;
+    if half > 0 && zeros_at(1) * 2 > half {
+        decode_utf16(bytes, u16::from_le_bytes)
+    } else if half > 0 && zeros_at(0) * 2 > half {
+        decode_utf16(bytes, u16::from_be_bytes)
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+// Zero bytes show up in binary formats but never in UTF-8 text; UTF-16 text
+// has them too, but only in every other position
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) || bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF]) {
+        return false;
+    }
+    let sample = &bytes[..bytes.len().min(8192)];
+    if !sample.contains(&0) {
+        return false;
+    }
+    let zeros_at = |parity: usize| sample.iter().skip(parity).step_by(2).filter(|b| **b == 0).count();
+    let half = sample.len() / 2;
+    zeros_at(1) * 2 <= half && zeros_at(0) * 2 <= half
+}
+
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| to_unit([pair[0], pair[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+pub fn split_text(text: &str, strategy: &SplitStrategy) -> Result<Vec<Segment>> {
+    let text = text.replace("\r\n", "\n");
+
+    let segments = match strategy {
+        SplitStrategy::BlankLines => {
+            let blank = regex::Regex::new(r"\n[ \t]*\n")?;
+            blank.split(&text).map(untitled).collect()
+        }
+        SplitStrategy::Headings => split_headings(&text),
+        SplitStrategy::Regex { pattern } => {
+            let separator = regex::Regex::new(pattern)
+                .map_err(|e| anyhow::anyhow!("Invalid split pattern '{}': {}", pattern, e))?;
+            separator.split(&text).map(untitled).collect()
+        }
+    };
+
+    Ok(segments
+        .into_iter()
+        .filter(|segment: &Segment| !segment.content.trim().is_empty())
+        .collect())
+}
+
+fn untitled(content: &str) -> Segment {
+    Segment {
+        title: None,
+        content: content.trim().to_string(),
+    }
+}
+
+fn split_headings(text: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut title: Option<String> = None;
+    let mut lines: Vec<&str> = Vec::new();
+    let mut in_fence = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+
+        match heading_text(line).filter(|_| !in_fence) {
+            Some(heading) => {
+                segments.push(Segment {
+                    title: title.take(),
+                    content: lines.join("\n").trim().to_string(),
+                });
+                lines.clear();
+                title = Some(heading.to_string()).filter(|t| !t.is_empty());
+                lines.push(line);
+            }
+            None => lines.push(line),
+        }
+    }
+    segments.push(Segment {
+        title,
+        content: lines.join("\n").trim().to_string(),
+    });
+
+    segments
+}
+
+fn heading_text(line: &str) -> Option<&str> {
+    let hashes = line.chars().take_while(|c| *c == '#').count();
+    if !(1..=6).contains(&hashes) {
+        return None;
+    }
+    let rest = &line[hashes..];
+    if rest.is_empty() || rest.starts_with(' ') || rest.starts_with('\t') {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Frontmatter {
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+}
+
+// Reads `title` and `tags` from a leading `---` block and returns the rest
+// of the note. Only the flat YAML subset notes use is understood: `key:
+// value` pairs, inline `[a, b]` or comma separated lists, and `- item`
+// lists. Text without a closed block is returned unchanged.
+pub fn parse_frontmatter(text: &str) -> (Frontmatter, String) {
+    let text = text.replace("\r\n", "\n");
+    let Some(rest) = text.strip_prefix("---\n") else {
+        return (Frontmatter::default(), text);
+    };
+    let end = if rest.starts_with("---\n") {
+        Some((0, 4))
+    } else {
+        rest.find("\n---\n")
+            .map(|i| (i, i + 5))
+            .or_else(|| rest.strip_suffix("\n---").map(|block| (block.len(), rest.len())))
+    };
+    let Some(end) = end else {
+        return (Frontmatter::default(), text);
+    };
+    let (block, body) = (&rest[..end.0], &rest[end.1..]);
+
+    let mut frontmatter = Frontmatter::default();
+    let mut in_tags = false;
+    for line in block.lines() {
+        if in_tags {
+            if let Some(item) = line.trim_start().strip_prefix("- ") {
+                frontmatter.tags.push(unquote(item).to_string());
+                continue;
+            }
+            in_tags = false;
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "title" => frontmatter.title = Some(unquote(value).to_string()).filter(|t| !t.is_empty()),
+            "tags" if value.is_empty() => in_tags = true,
+            "tags" => {
+                let list = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')).unwrap_or(value);
+                frontmatter.tags.extend(list.split(',').map(|t| unquote(t.trim()).to_string()));
+            }
+            _ => {}
+        }
+    }
+    frontmatter.tags.retain(|t| !t.is_empty());
+
+    (frontmatter, body.to_string())
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    ['"', '\'']
+        .iter()
+        .find_map(|q| value.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blank_lines_separate_paragraphs() {
+        let segments = split_text("first\r\n\r\nsecond\nstill second\n  \n\n\nthird\n\n", &SplitStrategy::BlankLines).unwrap();
+        let contents: Vec<&str> = segments.iter().map(|s| s.content.as_str()).collect();
+        assert_eq!(contents, ["first", "second\nstill second", "third"]);
+    }
+
+    #[test]
+    fn headings_start_titled_segments_outside_code_fences() {
+        let text = "preamble\n# One\nbody one\n```\n# not a heading\n```\n## Two\nbody two\n#hashtag";
+        let segments = split_text(text, &SplitStrategy::Headings).unwrap();
+        let titles: Vec<Option<&str>> = segments.iter().map(|s| s.title.as_deref()).collect();
+        assert_eq!(titles, [None, Some("One"), Some("Two")]);
+        assert!(segments[1].content.contains("# not a heading"));
+        assert!(segments[2].content.ends_with("#hashtag"));
+    }
+
+    #[test]
+    fn utf16_without_a_byte_order_mark_is_decoded() {
+        let bytes: Vec<u8> = "plain text".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(decode_text(&bytes), "plain text");
+        assert!(!looks_binary(&bytes));
+        assert!(looks_binary(&[0x89, b'P', b'N', b'G', 0, 0, 0, 0x0D, 1, 2, 3]));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::commands::MemoryEntry;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_TITLE_CHARS: usize = 200;
+pub const MAX_CONTENT_BYTES: usize = 1024 * 1024;
+const LARGE_CONTENT_BYTES: usize = 100 * 1024;
+
+// Query parameters that only identify the campaign or click that led to a
+// page; `utm_*` is matched as a prefix
+const TRACKING_PARAMS: [&str; 10] = [
+    "fbclid", "gclid", "dclid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid", "_ga", "_hsenc",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub errors: Vec<ValidationIssue>,
+    pub warnings: Vec<ValidationIssue>,
+    // Tags as they would be stored: normalized and deduplicated
+    pub normalized_tags: Vec<String>,
+    // Source as it would be stored; see `normalize_source`
+    pub normalized_source: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceValidation {
+    pub valid: bool,
+    pub is_url: bool,
+    pub normalized: String,
+    pub error: Option<String>,
+}
+
+impl ValidationReport {
+    pub fn error_message(&self) -> String {
+        self.errors
+            .iter()
+            .map(|issue| format!("{}: {}", issue.field, issue.message))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+// Tags may be hierarchical paths like `project/human-api`; normalize the
+// separators so prefix matching on ancestors is reliable
+pub fn normalize_tag(tag_name: &str) -> String {
+    tag_name
+        .split('/')
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn looks_like_url(source: &str) -> bool {
+    let source = source.trim_start().as_bytes();
+    let has_prefix = |prefix: &str| source.len() >= prefix.len() && source[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes());
+    has_prefix("http://") || has_prefix("https://")
+}
+
+// Web sources are reduced to a canonical form so trivially different links
+// to the same page compare equal: scheme and host lowercased, default port
+// and tracking parameters dropped, empty query and fragment removed. Any
+// other source, or a URL that does not parse, is returned untouched.
+pub fn normalize_source(source: &str) -> String {
+    if !looks_like_url(source) {
+        return source.to_string();
+    }
+    let Ok(mut url) = url::Url::parse(source.trim()) else {
+        return source.to_string();
+    };
+
+    let kept: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| {
+            let key = key.to_ascii_lowercase();
+            !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_str())
+        })
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if kept.is_empty() {
+        url.set_query(None);
+    } else if url.query_pairs().count() != kept.len() {
+        url.query_pairs_mut().clear().extend_pairs(&kept);
+    }
+    if url.fragment() == Some("") {
+        url.set_fragment(None);
+    }
+
+    url.to_string()
+}
+
+pub fn validate_source(source: &str) -> SourceValidation {
+    let is_url = looks_like_url(source);
+    let error = match url::Url::parse(source.trim()) {
+        Err(e) if is_url => Some(format!("Invalid source URL '{}': {}", source, e)),
+        _ => None,
+    };
+    SourceValidation {
+        valid: error.is_none(),
+        is_url,
+        normalized: normalize_source(source),
+        error,
+    }
+}
+
+pub fn validate_entry(entry: &MemoryEntry) -> ValidationReport {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let issue = |list: &mut Vec<ValidationIssue>, field: &str, message: String| {
+        list.push(ValidationIssue {
+            field: field.to_string(),
+            message,
+        })
+    };
+
+    // Content
+    if entry.content.trim().is_empty() {
+        issue(&mut errors, "content", "Content cannot be empty".to_string());
+    } else if entry.content.len() > MAX_CONTENT_BYTES {
+        issue(
+            &mut errors,
+            "content",
+            format!("Content exceeds the {} byte limit", MAX_CONTENT_BYTES),
+        );
+    } else if entry.content.len() > LARGE_CONTENT_BYTES {
+        issue(
+            &mut warnings,
+            "content",
+            "Large content will produce many chunks".to_string(),
+        );
+    }
+
+    // Title
+    if let Some(title) = &entry.title {
+        if title.chars().count() > MAX_TITLE_CHARS {
+            issue(
+                &mut errors,
+                "title",
+                format!("Title exceeds {} characters", MAX_TITLE_CHARS),
+            );
+        }
+    }
+
+    // Tags
+    let mut normalized_tags: Vec<String> = Vec::new();
+    for tag in &entry.tags {
+        let normalized = normalize_tag(tag);
+        if normalized.is_empty() {
+            issue(&mut errors, "tags", format!("Tag '{}' is empty", tag));
+        } else if normalized_tags.contains(&normalized) {
+            issue(&mut warnings, "tags", format!("Duplicate tag '{}' ignored", normalized));
+        } else {
+            if normalized != *tag {
+                issue(&mut warnings, "tags", format!("Tag '{}' normalized to '{}'", tag, normalized));
+            }
+            normalized_tags.push(normalized);
+        }
+    }
+
+    // Source; non-URL sources such as "clipboard" are allowed as-is
+    let mut normalized_source = entry.source.clone();
+    if let Some(source) = &entry.source {
+        let report = validate_source(source);
+        if let Some(error) = report.error {
+            issue(&mut errors, "source", error);
+        } else if report.normalized != *source {
+            issue(&mut warnings, "source", format!("Source normalized to '{}'", report.normalized));
+            normalized_source = Some(report.normalized);
+        }
+    }
+
+    ValidationReport {
+        valid: errors.is_empty(),
+        errors,
+        warnings,
+        normalized_tags,
+        normalized_source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::entry;
+
+    fn fields(issues: &[ValidationIssue]) -> Vec<&str> {
+        issues.iter().map(|i| i.field.as_str()).collect()
+    }
+
+    #[test]
+    fn content_must_be_present_and_within_limits() {
+        assert_eq!(fields(&validate_entry(&entry("  \n", &[])).errors), ["content"]);
+        assert_eq!(fields(&validate_entry(&entry(&"x".repeat(MAX_CONTENT_BYTES + 1), &[])).errors), ["content"]);
+
+        let large = validate_entry(&entry(&"x".repeat(LARGE_CONTENT_BYTES + 1), &[]));
+        assert!(large.valid);
+        assert_eq!(fields(&large.warnings), ["content"]);
+        assert!(validate_entry(&entry("fine", &[])).valid);
+    }
+
+    #[test]
+    fn title_length_is_capped() {
+        let mut memory = entry("content", &[]);
+        memory.title = Some("t".repeat(MAX_TITLE_CHARS));
+        assert!(validate_entry(&memory).valid);
+        memory.title = Some("t".repeat(MAX_TITLE_CHARS + 1));
+        assert_eq!(fields(&validate_entry(&memory).errors), ["title"]);
+    }
+
+    #[test]
+    fn tags_are_normalized_and_deduplicated() {
+        let report = validate_entry(&entry("content", &[" project / api ", "project/api", "ok"]));
+        assert!(report.valid);
+        assert_eq!(report.normalized_tags, ["project/api", "ok"]);
+        assert_eq!(fields(&report.warnings), ["tags", "tags"]);
+
+        let report = validate_entry(&entry("content", &["//"]));
+        assert_eq!(fields(&report.errors), ["tags"]);
+    }
+
+    #[test]
+    fn sources_are_checked_and_normalized() {
+        let mut memory = entry("content", &[]);
+        memory.source = Some("clipboard".into());
+        let report = validate_entry(&memory);
+        assert!(report.valid && report.warnings.is_empty());
+
+        memory.source = Some("https://Example.com:443/page?utm_source=x&id=1#".into());
+        let report = validate_entry(&memory);
+        assert!(report.valid);
+        assert_eq!(report.normalized_source.as_deref(), Some("https://example.com/page?id=1"));
+        assert_eq!(fields(&report.warnings), ["source"]);
+
+        memory.source = Some("https://exa mple.com".into());
+        assert_eq!(fields(&validate_entry(&memory).errors), ["source"]);
+    }
+
+    #[test]
+    fn tracking_parameters_are_stripped_and_equivalent_urls_match() {
+        let canonical = "https://example.com/article?id=7";
+        for variant in [
+            "https://example.com/article?id=7",
+            "HTTPS://EXAMPLE.com:443/article?id=7",
+            "https://example.com/article?utm_source=news&id=7&utm_medium=email",
+            "https://example.com/article?id=7&fbclid=abc&GCLID=def&_ga=1.2",
+            "https://example.com/article?id=7#",
+            "  https://example.com/article?id=7&mc_cid=x ",
+        ] {
+            assert_eq!(normalize_source(variant), canonical, "{}", variant);
+        }
+
+        assert_eq!(normalize_source("https://example.com/a?utm_campaign=spring"), "https://example.com/a");
+        assert_eq!(normalize_source("http://example.com:80/"), "http://example.com/");
+        // What the page itself reads stays: paths, other parameters, fragments
+        assert_eq!(normalize_source("https://example.com/Article?ref=home#part-2"), "https://example.com/Article?ref=home#part-2");
+        assert_ne!(normalize_source("https://example.com/article?id=8"), canonical);
+        assert_eq!(normalize_source("notes.txt?utm_source=x"), "notes.txt?utm_source=x");
+    }
+}
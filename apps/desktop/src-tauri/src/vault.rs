@@ -1,11 +1,59 @@
-use crate::crypto::CryptoManager;
-use crate::database::Database;
+use crate::crypto::{self, CryptoManager};
+use crate::database::{self, to_count, Database};
 use crate::commands::{VaultConfig, VaultStatus};
+use crate::maintenance;
+use crate::memory::{MemoryManager, DEFAULT_VAULT_ID};
+use crate::settings::MemorySettings;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use uuid::Uuid;
 use sqlx::Row;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex as StdMutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use zeroize::Zeroize;
+
+// How often the auto-lock task compares idle time with the timeout
+const AUTO_LOCK_POLL: Duration = Duration::from_secs(15);
+
+// When a command last used the vault; the auto-lock timeout counts from here
+static LAST_ACTIVITY: StdMutex<Option<Instant>> = StdMutex::new(None);
+
+// Whether a vault is currently unlocked, for background tasks that hold no
+// `VaultManager`
+static UNLOCKED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_vault_unlocked() -> bool {
+    UNLOCKED.load(Ordering::SeqCst)
+}
+
+pub fn record_activity() {
+    *LAST_ACTIVITY.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+}
+
+fn idle_for() -> Duration {
+    LAST_ACTIVITY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .map_or(Duration::ZERO, |last| last.elapsed())
+}
+
+// Runs for the life of the app, locking both managers once the vault has
+// been idle for `auto_lock_minutes`
+pub async fn run_auto_lock(vault: &Mutex<VaultManager>, memory: &Mutex<MemoryManager>) {
+    loop {
+        tokio::time::sleep(AUTO_LOCK_POLL).await;
+        let mut vault_manager = vault.lock().await;
+        match vault_manager.auto_lock_remaining_seconds().await {
+            Ok(Some(0)) => {
+                vault_manager.lock();
+                memory.lock().await.lock();
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Auto-lock check failed: {}", e),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VaultData {
@@ -22,6 +70,14 @@ pub struct VaultManager {
     db: Option<Database>,
     current_vault: Option<VaultData>,
     is_unlocked: bool,
+    // Unwrapped with the master password on create and unlock
+    vault_key: Option<[u8; 32]>,
+}
+
+impl Default for VaultManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VaultManager {
@@ -31,37 +87,81 @@ impl VaultManager {
             db: None,
             current_vault: None,
             is_unlocked: false,
+            vault_key: None,
         }
     }
 
+    // A vault that fails to be created leaves the remembered file key as
+    // it was
     pub async fn create_vault(&mut self, config: VaultConfig, master_password: String) -> Result<VaultStatus> {
+        let _write = maintenance::begin_write()?;
+        let previous_key = database::file_key();
+        database::set_file_key(Some(master_password.clone()));
+        let status = self.initialize_vault(config, master_password).await;
+        if status.is_err() {
+            database::set_file_key(previous_key);
+        }
+        status
+    }
+
+    async fn initialize_vault(&mut self, config: VaultConfig, master_password: String) -> Result<VaultStatus> {
         // Initialize database
         let db = Database::new().await?;
         let pool = db.get_pool().await;
 
+        // The app keeps one vault per database, under the id every memory
+        // refers to
+        let existing: Option<String> = sqlx::query_scalar("SELECT id FROM vaults LIMIT 1")
+            .fetch_optional(pool)
+            .await?;
+        if existing.is_some() {
+            return Err(anyhow::anyhow!("A vault already exists; unlock it instead"));
+        }
+
+        // Hash master password
+        let password_hash = self.crypto.hash_password_async(&master_password).await?;
+
+        // The vault key is wrapped with a key derived from the master password
+        // and a per-vault salt, both of which unlock_vault can reproduce. A
+        // stored key is what marks a vault as encrypted, so a plaintext vault
+        // gets none; enable_encryption adds one later.
+        let kdf_params = self.crypto.kdf_params().clone();
+        let (vault_key, salt, encrypted_key, key_canary) = if config.encryption_enabled {
+            let salt = self.crypto.generate_salt();
+            let wrapping_key = Self::derive_wrapping_key_static(master_password, salt.to_vec()).await?;
+            let vault_key = self.crypto.generate_key();
+            let encrypted_key = self.crypto.encrypt_data(&vault_key, &wrapping_key)?;
+            let key_canary = self.crypto.encrypt_data(crypto::VAULT_CANARY, &vault_key)?;
+            (Some(vault_key), Some(salt.to_vec()), Some(encrypted_key), Some(key_canary))
+        } else {
+            (None, None, None, None)
+        };
+
         // Create vault record
-        let vault_id = Uuid::new_v4().to_string();
+        let vault_id = DEFAULT_VAULT_ID.to_string();
         let now = chrono::Utc::now();
 
         sqlx::query(
-            "INSERT INTO vaults (id, name, description, encryption_enabled, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?)"
+            "INSERT INTO vaults (id, name, description, encryption_enabled, key_salt, encrypted_key, key_canary,
+                 password_hash, kdf_memory_kib, kdf_iterations, kdf_parallelism, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(&vault_id)
         .bind(&config.name)
         .bind(&config.description)
         .bind(config.encryption_enabled)
+        .bind(&salt)
+        .bind(&encrypted_key)
+        .bind(&key_canary)
+        .bind(&password_hash)
+        .bind(encrypted_key.as_ref().map(|_| kdf_params.m_cost()))
+        .bind(encrypted_key.as_ref().map(|_| kdf_params.t_cost()))
+        .bind(encrypted_key.as_ref().map(|_| kdf_params.p_cost()))
         .bind(now)
         .bind(now)
         .execute(pool)
         .await?;
 
-        // Hash master password
-        let password_hash = self.crypto.hash_password(&master_password)?;
-
-        // Store encrypted vault key (in a real implementation, this would be more secure)
-        let vault_key = self.crypto.generate_key();
-        let encrypted_key = self.crypto.encrypt_data(&vault_key, &self.crypto.generate_key())?;
-
         // Store vault metadata
         let vault_data = VaultData {
             id: vault_id,
@@ -74,7 +174,10 @@ impl VaultManager {
 
         self.current_vault = Some(vault_data);
         self.is_unlocked = true;
+        UNLOCKED.store(true, Ordering::SeqCst);
+        self.vault_key = vault_key;
         self.db = Some(db);
+        record_activity();
 
         Ok(VaultStatus {
             is_initialized: true,
@@ -82,22 +185,82 @@ impl VaultManager {
             name: Some(config.name),
             memory_count: 0,
             last_sync: Some(now.to_rfc3339()),
+            read_only: database::is_read_only(),
+            auto_lock_remaining_seconds: self.auto_lock_remaining_seconds().await?,
         })
     }
 
     pub async fn unlock_vault(&mut self, master_password: String) -> Result<VaultStatus> {
-        // In a real implementation, you would verify the master password
-        // and decrypt the vault key
-        
+        self.open_vault_with_mode(master_password, false).await
+    }
+
+    // Searching, querying and exporting work as usual; anything that would
+    // write fails with `database::ReadOnly` until the vault is unlocked again
+    // normally
+    pub async fn unlock_vault_readonly(&mut self, master_password: String) -> Result<VaultStatus> {
+        self.open_vault_with_mode(master_password, true).await
+    }
+
+    // A failed unlock, e.g. a wrong password, leaves the previous mode and
+    // file key alone
+    async fn open_vault_with_mode(&mut self, master_password: String, read_only: bool) -> Result<VaultStatus> {
+        let previous = database::is_read_only();
+        let previous_key = database::file_key();
+        database::set_read_only(read_only);
+        database::set_file_key(Some(master_password.clone()));
+        let status = self.open_vault(master_password).await;
+        if status.is_err() {
+            database::set_read_only(previous);
+            database::set_file_key(previous_key);
+        }
+        status
+    }
+
+    async fn open_vault(&mut self, master_password: String) -> Result<VaultStatus> {
         let db = Database::new().await?;
         let pool = db.get_pool().await;
 
         // Get vault data
-        let row = sqlx::query("SELECT id, name, description, encryption_enabled, created_at, updated_at FROM vaults ORDER BY created_at DESC LIMIT 1")
-            .fetch_optional(pool)
-            .await?;
+        let row = sqlx::query(
+            "SELECT id, name, description, encryption_enabled, key_salt, encrypted_key, password_hash, created_at, updated_at
+             FROM vaults WHERE id = ?"
+        )
+        .bind(DEFAULT_VAULT_ID)
+        .fetch_optional(pool)
+        .await?;
 
         if let Some(row) = row {
+            // Vaults created before the hash was stored rely on the key
+            // unwrap below to reject a wrong password; one with neither
+            // would open with any password, so it is not opened at all
+            let salt: Option<Vec<u8>> = row.get("key_salt");
+            let encrypted_key: Option<Vec<u8>> = row.get("encrypted_key");
+            if let Some(password_hash) = row.get::<Option<String>, _>("password_hash") {
+                if !self.crypto.verify_password_async(&master_password, &password_hash).await? {
+                    return Err(anyhow::anyhow!("Incorrect master password"));
+                }
+            } else if salt.is_none() || encrypted_key.is_none() {
+                return Err(anyhow::anyhow!(
+                    "The vault has no stored password or key to check the master password against"
+                ));
+            }
+
+            // Vaults created before keys were wrapped have no key to unwrap
+            let vault_key = match (salt, encrypted_key) {
+                (Some(salt), Some(encrypted_key)) => {
+                    let wrapping_key = Self::derive_wrapping_key_static(master_password, salt).await?;
+                    let vault_key = self
+                        .crypto
+                        .decrypt_data(&encrypted_key, &wrapping_key)
+                        .map_err(|_| anyhow::anyhow!("Failed to unwrap the vault key"))?;
+                    let vault_key: [u8; 32] = vault_key
+                        .try_into()
+                        .map_err(|_| anyhow::anyhow!("Stored vault key has the wrong length"))?;
+                    Some(vault_key)
+                }
+                _ => None,
+            };
+
             let vault_data = VaultData {
                 id: row.get("id"),
                 name: row.get("name"),
@@ -116,14 +279,19 @@ impl VaultManager {
 
             self.current_vault = Some(vault_data.clone());
             self.is_unlocked = true;
+            UNLOCKED.store(true, Ordering::SeqCst);
+            self.vault_key = vault_key;
             self.db = Some(db);
+            record_activity();
 
             Ok(VaultStatus {
                 is_initialized: true,
                 is_unlocked: true,
                 name: Some(vault_data.name),
-                memory_count: memory_count as u64,
+                memory_count: to_count(memory_count, "memory count")?,
                 last_sync: Some(vault_data.updated_at.to_rfc3339()),
+                read_only: database::is_read_only(),
+                auto_lock_remaining_seconds: self.auto_lock_remaining_seconds().await?,
             })
         } else {
             Ok(VaultStatus {
@@ -132,10 +300,38 @@ impl VaultManager {
                 name: None,
                 memory_count: 0,
                 last_sync: None,
+                read_only: false,
+                auto_lock_remaining_seconds: None,
             })
         }
     }
 
+    // Encrypts (or decrypts) the whole database file with SQLCipher, FTS and
+    // indexes included. This sits underneath per-field encryption rather
+    // than replacing it: sealed fields stay sealed inside the file, and terms
+    // an encrypted vault keeps out of its indexes stay out, so turning file
+    // encryption off never exposes more than before it was turned on.
+    // Returns whether the file is now encrypted.
+    // The caller closes the MemoryManager's pool first; this one and the
+    // scheduler's are closed here and the vault's reopened on the new file.
+    pub async fn set_database_encryption(&mut self, master_password: String, enabled: bool) -> Result<bool> {
+        let _write = maintenance::begin_write()?;
+        let db = match (&self.db, self.is_unlocked) {
+            (Some(db), true) => db,
+            _ => return Err(anyhow::anyhow!("Vault is locked")),
+        };
+        database::verify_master_password(db.get_pool().await, &master_password).await?;
+
+        if let Some(pool) = self.take_pool() {
+            pool.close().await;
+        }
+        maintenance::close_scheduler_pool().await;
+        let converted = database::set_file_encryption(&master_password, enabled).await;
+        self.db = Some(Database::new().await?);
+        converted?;
+        Ok(database::is_file_encrypted())
+    }
+
     pub async fn get_status(&self) -> Result<VaultStatus> {
         if let Some(vault) = &self.current_vault {
             let memory_count = if let Some(db) = &self.db {
@@ -145,7 +341,7 @@ impl VaultManager {
                     .fetch_one(pool)
                     .await?
                     .get(0);
-                count as u64
+                to_count(count, "memory count")?
             } else {
                 0
             };
@@ -156,6 +352,8 @@ impl VaultManager {
                 name: Some(vault.name.clone()),
                 memory_count,
                 last_sync: Some(vault.updated_at.to_rfc3339()),
+                read_only: database::is_read_only(),
+                auto_lock_remaining_seconds: self.auto_lock_remaining_seconds().await?,
             })
         } else {
             Ok(VaultStatus {
@@ -164,11 +362,14 @@ impl VaultManager {
                 name: None,
                 memory_count: 0,
                 last_sync: None,
+                read_only: false,
+                auto_lock_remaining_seconds: None,
             })
         }
     }
 
     pub async fn update_settings(&mut self, name: Option<String>, description: Option<String>) -> Result<()> {
+        let _write = maintenance::begin_write()?;
         if let Some(vault) = &mut self.current_vault {
             if let Some(db) = &self.db {
                 let pool = db.get_pool().await;
@@ -196,11 +397,390 @@ impl VaultManager {
         Ok(())
     }
 
+    pub fn take_pool(&mut self) -> Option<sqlx::SqlitePool> {
+        self.db.take().map(Database::into_pool)
+    }
+
+    // Wipes the vault key and the database passphrase. The vault stays
+    // known, so get_status still reports its name.
+    pub fn lock(&mut self) {
+        self.vault_key.zeroize();
+        self.vault_key = None;
+        self.is_unlocked = false;
+        UNLOCKED.store(false, Ordering::SeqCst);
+        database::set_file_key(None);
+    }
+
+    pub async fn set_auto_lock_minutes(&mut self, minutes: u32) -> Result<()> {
+        let _write = maintenance::begin_write()?;
+        let db = match (&self.db, self.is_unlocked) {
+            (Some(db), true) => db,
+            _ => return Err(anyhow::anyhow!("Vault is locked")),
+        };
+        let pool = db.get_pool().await;
+        let mut settings = MemorySettings::load(pool, DEFAULT_VAULT_ID).await?;
+        settings.auto_lock_minutes = minutes;
+        settings.save(pool, DEFAULT_VAULT_ID).await?;
+        record_activity();
+        Ok(())
+    }
+
+    // None while locked or when auto-lock is off
+    async fn auto_lock_remaining_seconds(&self) -> Result<Option<u64>> {
+        let db = match (&self.db, self.is_unlocked) {
+            (Some(db), true) => db,
+            _ => return Ok(None),
+        };
+        let settings = MemorySettings::load(db.get_pool().await, DEFAULT_VAULT_ID).await?;
+        if settings.auto_lock_minutes == 0 {
+            return Ok(None);
+        }
+        let timeout = Duration::from_secs(u64::from(settings.auto_lock_minutes) * 60);
+        Ok(Some(timeout.saturating_sub(idle_for()).as_secs()))
+    }
+
     pub fn is_unlocked(&self) -> bool {
         self.is_unlocked
     }
 
+    pub fn vault_key(&self) -> Option<[u8; 32]> {
+        self.vault_key
+    }
+
+    // Key derivation is deliberately slow; keep it off the async worker
+    async fn derive_wrapping_key_static(master_password: String, salt: Vec<u8>) -> Result<[u8; 32]> {
+        let kdf = CryptoManager::new();
+        tokio::task::spawn_blocking(move || kdf.derive_key(&master_password, &salt)).await?
+    }
+
     pub fn get_vault_id(&self) -> Option<&String> {
         self.current_vault.as_ref().map(|v| &v.id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, entry, TestVault};
+
+    fn config() -> VaultConfig {
+        VaultConfig {
+            name: "Personal".into(),
+            description: None,
+            encryption_enabled: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn created_vault_holds_new_memories() {
+        let _vault = TestVault::empty().await;
+        let mut vaults = VaultManager::new();
+        vaults.create_vault(config(), "hunter2".into()).await.unwrap();
+        assert_eq!(vaults.get_vault_id().map(String::as_str), Some(DEFAULT_VAULT_ID));
+
+        let mut manager = MemoryManager::new();
+        manager.set_vault_key(vaults.vault_key());
+        let id = manager.add_memory(entry("kept in the new vault", &[])).await.unwrap();
+        let pool = test_support::database().await.get_pool().await.clone();
+        let vault_id: String = sqlx::query_scalar("SELECT vault_id FROM memories WHERE id = ?")
+            .bind(&id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(vault_id, DEFAULT_VAULT_ID);
+
+        let error = vaults.create_vault(config(), "other".into()).await.unwrap_err().to_string();
+        assert!(error.contains("already exists"), "{}", error);
+        assert_eq!(database::file_key().as_deref(), Some("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn file_encryption_checks_the_master_password_first() {
+        let _vault = TestVault::empty().await;
+        let mut vaults = VaultManager::new();
+        vaults.create_vault(config(), "hunter2".into()).await.unwrap();
+
+        let error = vaults.set_database_encryption("wrong".into(), true).await.unwrap_err().to_string();
+        assert!(error.contains("Incorrect master password"), "{}", error);
+        assert!(!database::is_file_encrypted());
+        assert!(vaults.get_status().await.unwrap().is_unlocked);
+
+        vaults.lock();
+        let error = vaults.set_database_encryption("hunter2".into(), true).await.unwrap_err().to_string();
+        assert!(error.contains("locked"), "{}", error);
+    }
+
+    #[cfg(not(feature = "sqlcipher"))]
+    #[tokio::test]
+    async fn file_encryption_is_refused_without_sqlcipher_and_the_vault_keeps_working() {
+        let _vault = TestVault::empty().await;
+        let mut vaults = VaultManager::new();
+        vaults.create_vault(config(), "hunter2".into()).await.unwrap();
+        let mut manager = MemoryManager::new();
+        manager.set_vault_key(vaults.vault_key());
+        manager.add_memory(entry("still reachable", &[])).await.unwrap();
+
+        let error = vaults.set_database_encryption("hunter2".into(), true).await.unwrap_err().to_string();
+        assert!(error.contains("SQLCipher"), "{}", error);
+        assert_eq!(vaults.get_status().await.unwrap().memory_count, 1);
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[tokio::test]
+    async fn file_encryption_swaps_the_file_under_fresh_pools() {
+        let _vault = TestVault::empty().await;
+        let mut vaults = VaultManager::new();
+        vaults.create_vault(config(), "hunter2".into()).await.unwrap();
+        let mut manager = MemoryManager::new();
+        manager.set_vault_key(vaults.vault_key());
+        manager.add_memory(entry("written before encryption", &[])).await.unwrap();
+
+        // A wrong passphrase changes nothing, even called directly
+        assert!(database::set_file_encryption("wrong", true).await.is_err());
+        assert!(!database::is_file_encrypted());
+
+        // As the command does: the memory pool is closed before the swap
+        for (enabled, count) in [(true, 2), (false, 3)] {
+            if let Some(pool) = manager.take_pool() {
+                pool.close().await;
+            }
+            assert_eq!(vaults.set_database_encryption("hunter2".into(), enabled).await.unwrap(), enabled);
+            assert_eq!(database::is_file_encrypted(), enabled);
+
+            // Both managers read and write the new file
+            manager.add_memory(entry(&format!("written with encryption {}", enabled), &[])).await.unwrap();
+            assert_eq!(vaults.get_status().await.unwrap().memory_count, count);
+        }
+    }
+
+    #[tokio::test]
+    async fn unlock_persists_in_the_shared_state() {
+        let _vault = TestVault::empty().await;
+        let mut vaults = VaultManager::new();
+        vaults.create_vault(config(), "hunter2".into()).await.unwrap();
+        drop(vaults);
+
+        // As managed by the app and handed to each command
+        let vault_state = Mutex::new(VaultManager::new());
+        let memory_state = Mutex::new(MemoryManager::new());
+        {
+            let mut vault_manager = vault_state.lock().await;
+            vault_manager.unlock_vault("hunter2".into()).await.unwrap();
+            memory_state.lock().await.set_vault_key(vault_manager.vault_key());
+        }
+
+        let status = vault_state.lock().await.get_status().await.unwrap();
+        assert!(status.is_unlocked);
+        assert!(vault_state.lock().await.vault_key().is_some());
+
+        // Memories added through the shared manager are sealed with the key
+        let id = memory_state.lock().await.add_memory(entry("behind the lock", &[])).await.unwrap();
+        let pool = test_support::database().await.get_pool().await.clone();
+        let encrypted: bool = sqlx::query_scalar("SELECT encrypted FROM memories WHERE id = ?")
+            .bind(&id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(encrypted);
+    }
+
+    #[tokio::test]
+    async fn memory_requests_are_refused_while_locked() {
+        let _vault = TestVault::empty().await;
+        let mut vaults = VaultManager::new();
+        vaults.create_vault(config(), "hunter2".into()).await.unwrap();
+        let mut manager = MemoryManager::new();
+        manager.set_vault_key(vaults.vault_key());
+        let id = manager.add_memory(entry("kept behind the lock", &[])).await.unwrap();
+
+        vaults.lock();
+        manager.lock();
+        assert!(manager.take_pool().is_none());
+        let error = manager.add_memory(entry("written while locked", &[])).await.unwrap_err();
+        assert!(error.downcast_ref::<database::Locked>().is_some(), "{}", error);
+        let error = manager.search_in_memory(id.clone(), "lock".into()).await.unwrap_err();
+        assert!(error.downcast_ref::<database::Locked>().is_some(), "{}", error);
+
+        vaults.unlock_vault("hunter2".into()).await.unwrap();
+        manager.set_vault_key(vaults.vault_key());
+        assert_eq!(manager.search_in_memory(id, "lock".into()).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn openai_key_is_sealed_and_loaded_after_unlock() {
+        let _vault = TestVault::empty().await;
+        let mut vaults = VaultManager::new();
+        vaults.create_vault(config(), "hunter2".into()).await.unwrap();
+        let mut manager = MemoryManager::new();
+        manager.set_vault_key(vaults.vault_key());
+
+        let mut settings = manager.get_settings().await.unwrap();
+        settings.embedding_provider.backend = crate::settings::EmbeddingBackend::OpenAi;
+        settings.embedding_provider.openai_api_key = Some("sk-secret".into());
+        manager.update_settings(settings).await.unwrap();
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        let stored = MemorySettings::load(&pool, DEFAULT_VAULT_ID).await.unwrap();
+        let sealed = stored.embedding_provider.openai_api_key.unwrap();
+        assert!(crypto::is_v2(&sealed), "{}", sealed);
+        let returned = manager.get_settings().await.unwrap();
+        assert_eq!(returned.embedding_provider.openai_api_key.as_deref(), Some(sealed.as_str()));
+        // Saving the settings as returned keeps the same key
+        manager.update_settings(returned).await.unwrap();
+        let model = manager.estimate_embedding_sync().await.unwrap().model;
+        assert_eq!(model, "openai/text-embedding-3-small");
+
+        // A fresh manager starts on the local provider and switches on unlock
+        vaults.lock();
+        vaults.unlock_vault("hunter2".into()).await.unwrap();
+        let mut unlocked = MemoryManager::new();
+        assert_ne!(unlocked.estimate_embedding_sync().await.unwrap().model, model);
+        unlocked.set_vault_key(vaults.vault_key());
+        unlocked.load_saved_provider().await.unwrap();
+        assert_eq!(unlocked.estimate_embedding_sync().await.unwrap().model, model);
+
+        // Without the key the sealed key cannot be read
+        assert!(MemoryManager::new().load_saved_provider().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn unlock_checks_the_master_password() {
+        let _vault = TestVault::empty().await;
+        let mut vaults = VaultManager::new();
+        vaults.create_vault(config(), "hunter2".into()).await.unwrap();
+        let vault_key = vaults.vault_key();
+        vaults.lock();
+
+        let error = vaults.unlock_vault("wrong".into()).await.unwrap_err().to_string();
+        assert!(error.contains("Incorrect master password"), "{}", error);
+        assert!(!vaults.get_status().await.unwrap().is_unlocked);
+        assert!(database::file_key().is_none());
+        assert!(vaults.vault_key().is_none());
+
+        let status = vaults.unlock_vault("hunter2".into()).await.unwrap();
+        assert!(status.is_unlocked);
+        assert_eq!(vaults.vault_key(), vault_key);
+    }
+
+    #[tokio::test]
+    async fn vaults_without_a_password_hash_fall_back_to_the_key_or_refuse() {
+        let _vault = TestVault::empty().await;
+        let mut vaults = VaultManager::new();
+        vaults.create_vault(config(), "hunter2".into()).await.unwrap();
+        vaults.lock();
+        let pool = test_support::database().await.get_pool().await.clone();
+
+        // As written before the hash was stored: the key unwrap decides
+        sqlx::query("UPDATE vaults SET password_hash = NULL").execute(&pool).await.unwrap();
+        assert!(vaults.unlock_vault("wrong".into()).await.is_err());
+        assert!(vaults.unlock_vault("hunter2".into()).await.unwrap().is_unlocked);
+        vaults.lock();
+
+        // Nothing left to check a password against
+        sqlx::query("UPDATE vaults SET key_salt = NULL, encrypted_key = NULL").execute(&pool).await.unwrap();
+        for password in ["wrong", "hunter2"] {
+            let error = vaults.unlock_vault(password.into()).await.unwrap_err().to_string();
+            assert!(error.contains("no stored password"), "{}", error);
+        }
+        assert!(!vaults.get_status().await.unwrap().is_unlocked);
+    }
+
+    #[tokio::test]
+    async fn vault_key_survives_a_restart() {
+        let _vault = TestVault::empty().await;
+        let mut vaults = VaultManager::new();
+        vaults.create_vault(config(), "hunter2".into()).await.unwrap();
+        let vault_key = vaults.vault_key();
+        assert!(vault_key.is_some());
+        let mut manager = MemoryManager::new();
+        manager.set_vault_key(vault_key);
+        let id = manager.add_memory(entry("sealed before the restart", &[])).await.unwrap();
+        drop(manager);
+        drop(vaults);
+
+        let mut vaults = VaultManager::new();
+        vaults.unlock_vault("hunter2".into()).await.unwrap();
+        assert_eq!(vaults.vault_key(), vault_key);
+        let mut manager = MemoryManager::new();
+        manager.set_vault_key(vaults.vault_key());
+        assert_eq!(manager.search_in_memory(id, "restart".into()).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn plaintext_vaults_store_no_key() {
+        let _vault = TestVault::empty().await;
+        let mut vaults = VaultManager::new();
+        let plaintext = VaultConfig { encryption_enabled: false, ..config() };
+        vaults.create_vault(plaintext, "hunter2".into()).await.unwrap();
+        assert!(vaults.vault_key().is_none());
+
+        let pool = test_support::database().await.get_pool().await.clone();
+        let keyless: bool =
+            sqlx::query_scalar("SELECT key_salt IS NULL AND encrypted_key IS NULL AND key_canary IS NULL FROM vaults")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert!(keyless);
+
+        let mut manager = MemoryManager::new();
+        manager.set_vault_key(vaults.vault_key());
+        let id = manager.add_memory(entry("stored in the clear", &[])).await.unwrap();
+        let encrypted: bool = sqlx::query_scalar("SELECT encrypted FROM memories WHERE id = ?")
+            .bind(&id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(!encrypted);
+        let audit = manager.audit_crypto().await.unwrap();
+        assert!(!audit.encrypted && audit.kdf.is_none());
+
+        vaults.lock();
+        assert!(vaults.unlock_vault("wrong".into()).await.is_err());
+        vaults.unlock_vault("hunter2".into()).await.unwrap();
+        assert!(vaults.vault_key().is_none());
+    }
+
+    #[tokio::test]
+    async fn enabling_encryption_checks_the_master_password() {
+        let _vault = TestVault::empty().await;
+        let mut vaults = VaultManager::new();
+        let plaintext = VaultConfig { encryption_enabled: false, ..config() };
+        vaults.create_vault(plaintext, "hunter2".into()).await.unwrap();
+        let mut manager = MemoryManager::new();
+        manager.set_vault_key(vaults.vault_key());
+        let id = manager.add_memory(entry("written in the clear", &[])).await.unwrap();
+
+        let error = manager.enable_encryption("wrong".into()).await.unwrap_err().to_string();
+        assert!(error.contains("Incorrect master password"), "{}", error);
+        vaults.lock();
+        vaults.unlock_vault("hunter2".into()).await.unwrap();
+        assert!(vaults.vault_key().is_none());
+
+        assert_eq!(manager.enable_encryption("hunter2".into()).await.unwrap(), 1);
+        vaults.lock();
+        assert!(vaults.unlock_vault("wrong".into()).await.is_err());
+        vaults.unlock_vault("hunter2".into()).await.unwrap();
+        let mut reopened = MemoryManager::new();
+        reopened.set_vault_key(vaults.vault_key());
+        assert_eq!(reopened.search_in_memory(id, "clear".into()).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn the_managed_memory_manager_waits_for_an_unlock() {
+        let _vault = TestVault::empty().await;
+        let mut vaults = VaultManager::new();
+        vaults.create_vault(config(), "hunter2".into()).await.unwrap();
+        vaults.lock();
+
+        // As managed by the app at startup
+        let memory_state = Mutex::new(MemoryManager::new_locked());
+        let error = memory_state.lock().await.add_memory(entry("too early", &[])).await.unwrap_err();
+        assert!(error.downcast_ref::<database::Locked>().is_some(), "{}", error);
+        let error = memory_state.lock().await.delete_memory("any".into()).await.unwrap_err();
+        assert!(error.downcast_ref::<database::Locked>().is_some(), "{}", error);
+
+        vaults.unlock_vault("hunter2".into()).await.unwrap();
+        crate::commands::share_vault_key(&vaults, &memory_state).await;
+        memory_state.lock().await.add_memory(entry("after the unlock", &[])).await.unwrap();
+    }
+}
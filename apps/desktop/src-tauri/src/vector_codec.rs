@@ -0,0 +1,103 @@
+// Layout of `embeddings.vector` blobs:
+//
+//   bytes 0..2   magic "hv"
+//   byte  2      format version (1)
+//   byte  3      element type (0 = f32)
+//   bytes 4..8   dimension as u32, little-endian
+//   bytes 8..    `dimension` little-endian f32 values
+//
+// Blobs are validated on read so a truncated or foreign blob is reported
+// instead of silently producing a wrong vector.
+use anyhow::Result;
+
+const MAGIC: &[u8; 2] = b"hv";
+const VERSION: u8 = 1;
+const DTYPE_F32: u8 = 0;
+pub const HEADER_LEN: usize = 8;
+
+pub fn encode(vector: &[f32]) -> Result<Vec<u8>> {
+    let dimension = u32::try_from(vector.len())
+        .map_err(|_| anyhow::anyhow!("Vector dimension {} is too large", vector.len()))?;
+
+    let mut blob = Vec::with_capacity(HEADER_LEN + vector.len() * 4);
+    blob.extend_from_slice(MAGIC);
+    blob.push(VERSION);
+    blob.push(DTYPE_F32);
+    blob.extend_from_slice(&dimension.to_le_bytes());
+    for value in vector {
+        blob.extend_from_slice(&value.to_le_bytes());
+    }
+    Ok(blob)
+}
+
+pub fn decode(blob: &[u8]) -> Result<Vec<f32>> {
+    if blob.len() < HEADER_LEN {
+        return Err(anyhow::anyhow!("Vector blob is truncated ({} bytes)", blob.len()));
+    }
+    if &blob[0..2] != MAGIC {
+        return Err(anyhow::anyhow!("Vector blob has an unknown format"));
+    }
+    if blob[2] != VERSION {
+        return Err(anyhow::anyhow!("Unsupported vector format version {}", blob[2]));
+    }
+    if blob[3] != DTYPE_F32 {
+        return Err(anyhow::anyhow!("Unsupported vector element type {}", blob[3]));
+    }
+
+    let dimension = u32::from_le_bytes([blob[4], blob[5], blob[6], blob[7]]) as usize;
+    let body = &blob[HEADER_LEN..];
+    if body.len() != dimension * 4 {
+        return Err(anyhow::anyhow!(
+            "Vector blob declares {} dimensions but holds {} bytes",
+            dimension,
+            body.len()
+        ));
+    }
+
+    let vector: Vec<f32> = body
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect();
+    if vector.iter().any(|v| !v.is_finite()) {
+        return Err(anyhow::anyhow!("Vector blob contains non-finite values"));
+    }
+    Ok(vector)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vectors_round_trip() {
+        let vector = vec![0.0, -1.5, 3.25, f32::MIN_POSITIVE, 1e30];
+        let blob = encode(&vector).unwrap();
+        assert_eq!(blob.len(), HEADER_LEN + vector.len() * 4);
+        assert_eq!(decode(&blob).unwrap(), vector);
+        assert!(decode(&encode(&[]).unwrap()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_blobs_are_rejected() {
+        let blob = encode(&[1.0, 2.0, 3.0]).unwrap();
+
+        assert!(decode(&blob[..HEADER_LEN - 1]).is_err());
+        assert!(decode(&blob[..blob.len() - 1]).is_err());
+
+        let mut foreign = blob.clone();
+        foreign[0] = b'x';
+        assert!(decode(&foreign).is_err());
+
+        let mut future = blob.clone();
+        future[2] = VERSION + 1;
+        assert!(decode(&future).is_err());
+
+        let mut wrong_type = blob.clone();
+        wrong_type[3] = 1;
+        assert!(decode(&wrong_type).is_err());
+
+        let mut nan = blob;
+        nan[HEADER_LEN..HEADER_LEN + 4].copy_from_slice(&f32::NAN.to_le_bytes());
+        assert!(decode(&nan).is_err());
+    }
+}